@@ -0,0 +1,89 @@
+//! The `lua!` macro. Use it through mab's `macros` feature, which re-exports it
+//! as `mab::lua`, rather than depending on this crate directly.
+
+extern crate proc_macro;
+
+use proc_macro::{Delimiter, Spacing, TokenStream, TokenTree};
+
+/// Builds a `mab::ast::Chunk` from Lua code written inline. `$name` or
+/// `$(rust expression)` splices in an `Expression` from the surrounding Rust
+/// code:
+///
+/// ```ignore
+/// let value = mab::builder::expr::num("2");
+/// let chunk = mab::lua! { local x = $value + 1 };
+/// ```
+///
+/// Every node except the spliced ones gets `NodeId::DUMMY`, like with the
+/// builder module.
+///
+/// The code goes through Rust's tokenizer first, so it can't contain anything
+/// Rust doesn't accept: comments, single-quoted strings longer than one
+/// character, and `//` are all out. The code is parsed when the macro runs,
+/// which panics if it isn't valid Lua.
+#[proc_macro]
+pub fn lua(input: TokenStream) -> TokenStream {
+    let mut source = String::new();
+    let mut splices = Vec::new();
+
+    let code = match write_lua(input, &mut source, &mut splices) {
+        Ok(()) => format!("::mab::quote::expand({:?}, vec![{}])", source, splices.join(", ")),
+        Err(message) => format!("compile_error!({:?})", message),
+    };
+
+    code.parse().unwrap()
+}
+
+// Turns the tokens back into Lua source, replacing each splice with a
+// placeholder name that mab::quote::expand knows to look for.
+fn write_lua(input: TokenStream, source: &mut String, splices: &mut Vec<String>) -> Result<(), String> {
+    let mut tokens = input.into_iter();
+
+    while let Some(token) = tokens.next() {
+        match token {
+            TokenTree::Punct(ref punct) if punct.as_char() == '$' => {
+                let splice = match tokens.next() {
+                    Some(TokenTree::Ident(ident)) => ident.to_string(),
+                    Some(TokenTree::Group(ref group)) if group.delimiter() == Delimiter::Parenthesis => {
+                        format!("({})", group.stream())
+                    },
+                    _ => return Err("`$` in lua! must be followed by a name or a parenthesized expression".to_string()),
+                };
+
+                source.push_str(&format!("__mab_splice_{} ", splices.len()));
+                splices.push(splice);
+            },
+            TokenTree::Punct(punct) => {
+                source.push(punct.as_char());
+
+                // Joint punctuation like the `~` in `~=` has to stay attached.
+                if punct.spacing() == Spacing::Alone {
+                    source.push(' ');
+                }
+            },
+            TokenTree::Group(group) => {
+                let (open, close) = match group.delimiter() {
+                    Delimiter::Parenthesis => ("(", ")"),
+                    Delimiter::Brace => ("{", "}"),
+                    Delimiter::Bracket => ("[", "]"),
+                    Delimiter::None => ("", ""),
+                };
+
+                source.push_str(open);
+                write_lua(group.stream(), source, splices)?;
+                source.push_str(close);
+                source.push(' ');
+            },
+            TokenTree::Ident(ident) => {
+                source.push_str(&ident.to_string());
+                source.push(' ');
+            },
+            TokenTree::Literal(literal) => {
+                source.push_str(&literal.to_string());
+                source.push(' ');
+            },
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1 @@
+t[1 = 2
\ No newline at end of file
@@ -0,0 +1 @@
+local x = a or
\ No newline at end of file
@@ -0,0 +1,4 @@
+while true do
+	break
+	print(x)
+end
\ No newline at end of file
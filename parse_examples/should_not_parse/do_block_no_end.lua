@@ -0,0 +1,2 @@
+do
+	print(x)
\ No newline at end of file
@@ -0,0 +1,2 @@
+for i, j = 1, 2 do
+end
\ No newline at end of file
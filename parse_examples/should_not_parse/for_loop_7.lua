@@ -0,0 +1,2 @@
+for i in do
+end
\ No newline at end of file
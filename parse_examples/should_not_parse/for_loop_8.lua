@@ -0,0 +1,2 @@
+for i, in pairs(t) do
+end
\ No newline at end of file
@@ -0,0 +1 @@
+f{
\ No newline at end of file
@@ -0,0 +1,2 @@
+function f(a,)
+end
\ No newline at end of file
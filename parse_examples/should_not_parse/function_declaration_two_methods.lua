@@ -0,0 +1,2 @@
+function a:b:c()
+end
\ No newline at end of file
@@ -0,0 +1 @@
+goto
\ No newline at end of file
@@ -0,0 +1,7 @@
+if a then
+	print(a)
+else
+	print(b)
+elseif c then
+	print(c)
+end
@@ -0,0 +1,3 @@
+if a
+	print(a)
+end
@@ -0,0 +1 @@
+local end = 1
\ No newline at end of file
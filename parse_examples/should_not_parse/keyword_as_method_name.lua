@@ -0,0 +1,2 @@
+function obj:repeat()
+end
\ No newline at end of file
@@ -0,0 +1,2 @@
+::top
+print(x)
\ No newline at end of file
@@ -0,0 +1,2 @@
+local function a.b()
+end
\ No newline at end of file
@@ -0,0 +1,2 @@
+return 1
+print(x)
\ No newline at end of file
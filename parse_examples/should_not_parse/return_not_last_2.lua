@@ -0,0 +1,4 @@
+function foo()
+	return
+	local x = 1
+end
\ No newline at end of file
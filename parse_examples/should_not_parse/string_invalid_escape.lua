@@ -0,0 +1 @@
+print("bad \q escape")
\ No newline at end of file
@@ -0,0 +1,3 @@
+local a = [==[
+never closed
+]=]
\ No newline at end of file
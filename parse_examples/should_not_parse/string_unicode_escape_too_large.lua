@@ -0,0 +1 @@
+local s = "\u{110000}"
\ No newline at end of file
@@ -0,0 +1 @@
+local t = { [1] 2 }
\ No newline at end of file
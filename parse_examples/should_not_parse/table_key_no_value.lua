@@ -0,0 +1 @@
+local t = { a = }
\ No newline at end of file
@@ -0,0 +1 @@
+local x = not
\ No newline at end of file
@@ -0,0 +1,2 @@
+function f(a, ..., b)
+end
\ No newline at end of file
@@ -0,0 +1,2 @@
+a, b = 1, 2
+b, a = a, b
\ No newline at end of file
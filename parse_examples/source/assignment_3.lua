@@ -0,0 +1,3 @@
+x = 5
+print(x)
+y = x
\ No newline at end of file
@@ -0,0 +1,3 @@
+t.x = 1
+t[k] = v
+a.b.c, a["d"][1] = 1, 2
\ No newline at end of file
@@ -0,0 +1,5 @@
+if a < b and c >= d then
+	return a == b
+elseif a ~= b or a <= b then
+	return a + 1 > b
+end
\ No newline at end of file
@@ -0,0 +1,3 @@
+local x = a or b and c
+local y = a and b or c and d
+local z = a .. b or c + d * e
\ No newline at end of file
@@ -0,0 +1,3 @@
+local a = x % 2
+local b = x // 2
+local c = a + b % c // d * e
\ No newline at end of file
@@ -0,0 +1,2 @@
+do
+end
\ No newline at end of file
@@ -0,0 +1,7 @@
+do
+	local x = 5
+	do
+		print(x)
+	end
+	return x
+end
\ No newline at end of file
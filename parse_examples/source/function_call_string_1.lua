@@ -0,0 +1,5 @@
+require "foo"
+print"hi"
+local x = require('bar')
+obj:method "baz"
+f "a" "b"
\ No newline at end of file
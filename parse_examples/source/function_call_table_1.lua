@@ -0,0 +1,3 @@
+setmetatable{}
+local point = Point{ x = 1, y = 2 }
+obj:configure{ 1, 2 }
\ No newline at end of file
@@ -0,0 +1,9 @@
+function a.b.c:d(x)
+	return x
+end
+
+function M.new()
+end
+
+function Class:method()
+end
\ No newline at end of file
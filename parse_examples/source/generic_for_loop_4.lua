@@ -0,0 +1,5 @@
+for key, value in pairs(t) do
+	for i, v in ipairs(value) do
+		print(key, i, v)
+	end
+end
\ No newline at end of file
@@ -0,0 +1,6 @@
+for i = 1, 10 do
+	for j = 1, 10 do
+		goto continue
+	end
+	::continue::
+end
\ No newline at end of file
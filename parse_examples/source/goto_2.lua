@@ -0,0 +1,3 @@
+::top::
+print(x)
+goto top
\ No newline at end of file
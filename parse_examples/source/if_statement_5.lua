@@ -0,0 +1,13 @@
+if a then
+	if b then
+		print(b)
+	end
+elseif c then
+	print(c)
+else
+	if d then
+		print(d)
+	else
+		print(e)
+	end
+end
@@ -0,0 +1,2 @@
+local x = t.a.b
+print(t[1], t.x[y])
\ No newline at end of file
@@ -0,0 +1,3 @@
+print(nil, true, false)
+local t = { true, [false] = nil, x = false }
+return nil == false, not true
\ No newline at end of file
@@ -0,0 +1,2 @@
+local mask = 0xFF
+local magic = 0XdeadBEEF + 0x10
\ No newline at end of file
@@ -0,0 +1,3 @@
+local eighth = 0x.2
+local sixteenth = 0x1p-4
+local big = 0xA.8p2 * 0X1P+10
\ No newline at end of file
@@ -0,0 +1,4 @@
+local big = 1e10
+local small = 3.5E-2
+local half = .5
+local huge = 2e+5
\ No newline at end of file
@@ -0,0 +1,3 @@
+a.b.c(x)[1]:m(y)(z)
+local v = (f)(1).field
+obj:method()
\ No newline at end of file
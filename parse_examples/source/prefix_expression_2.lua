@@ -0,0 +1,2 @@
+(f)(x).y = 1
+t[1][2].z = f()
\ No newline at end of file
@@ -0,0 +1 @@
+return 1, foo, "bar"
\ No newline at end of file
@@ -0,0 +1,7 @@
+function foo(a)
+	if a then
+		return a
+	end
+
+	return
+end
\ No newline at end of file
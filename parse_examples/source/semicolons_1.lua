@@ -0,0 +1,6 @@
+local x = 1;
+print(x);;
+;
+function f()
+	return x;
+end
\ No newline at end of file
@@ -0,0 +1,6 @@
+local tab = "a\tb"
+local bell = "\7\x07"
+local quoted = 'it\'s'
+local joined = "one \z
+    two"
+local raw = "\255"
\ No newline at end of file
@@ -0,0 +1,4 @@
+local s = "hello"
+local t = 'world'
+local u = s .. " " .. t
+local v = { "a", ["b"] = 'c' }
\ No newline at end of file
@@ -0,0 +1,7 @@
+local a = [[hello]]
+local b = [==[
+line one
+]]still going
+]==]
+print [[call]]
+local t = { [ [[key]] ] = [=[value]=] }
\ No newline at end of file
@@ -0,0 +1,2 @@
+local smile = "\u{1F600}"
+local accent = "caf\u{E9}"
\ No newline at end of file
@@ -0,0 +1 @@
+local t = { a, b.c, f(), x = y, [1] = z, ["literal key"] = v; a == b, [k] = { nested } }
\ No newline at end of file
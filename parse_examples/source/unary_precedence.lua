@@ -0,0 +1,6 @@
+local a = -2 ^ 2
+local b = -x ^ -y
+local c = not a == b
+local d = #t + 1
+local e = a-1
+local f = - - x
\ No newline at end of file
@@ -0,0 +1,9 @@
+function f(...)
+	print(...)
+	local a, b = ...
+	return select("#", ...)
+end
+
+local function g(a, b, ...)
+	return a, ...
+end
\ No newline at end of file
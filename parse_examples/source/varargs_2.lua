@@ -0,0 +1 @@
+print(...)
\ No newline at end of file
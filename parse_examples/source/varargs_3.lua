@@ -0,0 +1,7 @@
+function f(a, b, ...)
+	local c, d = ...
+	return a, b, c, d
+end
+
+function g(x, ...)
+end
\ No newline at end of file
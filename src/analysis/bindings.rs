@@ -0,0 +1,145 @@
+//! A side table from every name in a chunk to the local it refers to, for
+//! tools like renaming that need to know which names are the same variable.
+//!
+//! ```
+//! use mab::analysis::bindings::{Binding, Bindings};
+//! use mab::ast::{ExpressionKind, StatementKind};
+//!
+//! let tokens = mab::tokenize("local x = 1 print(x)").unwrap();
+//! let chunk = mab::parse_from_tokens(&tokens).unwrap();
+//! let bindings = Bindings::new(&chunk);
+//!
+//! let call = match chunk.statements[1].kind {
+//!     StatementKind::FunctionCall(ref call) => call,
+//!     _ => unreachable!(),
+//! };
+//!
+//! let argument = match call.arguments {
+//!     mab::ast::FunctionArguments::Parenthesized(ref arguments) => &arguments[0],
+//!     _ => unreachable!(),
+//! };
+//!
+//! assert_eq!(bindings.get(call.name_expression.id), Some(Binding::Global));
+//! assert_eq!(bindings.get(argument.id), Some(Binding::Local {
+//!     statement: chunk.statements[0].id,
+//!     position: 0,
+//! }));
+//! ```
+//!
+//! Nodes are looked up by [ID][::ast::NodeId], so nodes made with the
+//! [builder][::builder], which all share a dummy ID, can't be told apart.
+
+use std::collections::HashMap;
+
+use ast::{Chunk, NodeId};
+use analysis::scope::{Declaration, ScopeTree};
+
+/// What a name refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Binding {
+    /// A local, identified by the statement that declares it and which of the
+    /// statement's names it is, like [Declaration][Declaration] does.
+    Local {
+        statement: NodeId,
+        position: usize,
+    },
+
+    Global,
+}
+
+impl<'a, 'd> From<&'d Declaration<'a>> for Binding {
+    fn from(declaration: &'d Declaration<'a>) -> Binding {
+        Binding::Local {
+            statement: declaration.statement,
+            position: declaration.position,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Bindings {
+    bindings: HashMap<NodeId, Binding>,
+}
+
+impl Bindings {
+    pub fn new(chunk: &Chunk) -> Bindings {
+        Bindings::from_scopes(&ScopeTree::new(chunk))
+    }
+
+    /// The bindings from scopes that have already been worked out.
+    pub fn from_scopes(scopes: &ScopeTree) -> Bindings {
+        let bindings = scopes.references().iter()
+            .map(|reference| {
+                let binding = match reference.declaration {
+                    Some(id) => Binding::from(scopes.declaration(id)),
+                    None => Binding::Global,
+                };
+
+                (reference.node, binding)
+            })
+            .collect();
+
+        Bindings {
+            bindings,
+        }
+    }
+
+    /// What the [Name][::ast::ExpressionKind::Name] expression with this ID
+    /// refers to. The IDs of `function` statements that aren't `local` are
+    /// here too, for the first name in the function's name.
+    pub fn get(&self, id: NodeId) -> Option<Binding> {
+        self.bindings.get(&id).cloned()
+    }
+
+    /// The IDs of every name that refers to `binding`, in no particular order.
+    pub fn uses(&self, binding: Binding) -> impl Iterator<Item = NodeId> + '_ {
+        self.bindings.iter()
+            .filter(move |&(_, &found)| found == binding)
+            .map(|(&id, _)| id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.bindings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bindings.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ast::*;
+    use tokenizer::tokenize;
+    use parser::parse_from_tokens;
+
+    #[test]
+    fn uses() {
+        let tokens = tokenize("local a, b = 1, 2 a = b + a function g() end").unwrap();
+        let chunk = parse_from_tokens(&tokens).unwrap();
+        let bindings = Bindings::new(&chunk);
+
+        let local = chunk.statements[0].id;
+        let a = Binding::Local { statement: local, position: 0 };
+        let b = Binding::Local { statement: local, position: 1 };
+
+        let (target, value) = match chunk.statements[1].kind {
+            StatementKind::Assignment(ref assignment) => (&assignment.targets[0], &assignment.values[0]),
+            _ => unreachable!(),
+        };
+
+        let (left, right) = match value.kind {
+            ExpressionKind::BinaryOp(ref op) => (&op.left, &op.right),
+            _ => unreachable!(),
+        };
+
+        let mut uses = bindings.uses(a).collect::<Vec<_>>();
+        uses.sort();
+        assert_eq!(uses, [target.id, right.id]);
+        assert_eq!(bindings.get(left.id), Some(b));
+        assert_eq!(bindings.get(chunk.statements[2].id), Some(Binding::Global));
+        assert_eq!(bindings.get(value.id), None);
+        assert_eq!(bindings.len(), 4);
+    }
+}
@@ -0,0 +1,643 @@
+//! Control flow graphs, which show the orders a function's statements can run
+//! in.
+//!
+//! ```
+//! use mab::analysis::cfg::Cfg;
+//! use mab::analysis::scope::ScopeTree;
+//!
+//! let tokens = mab::tokenize("
+//!     local x = 1
+//!     if x > 0 then
+//!         x = 2
+//!     end
+//!     return x
+//! ").unwrap();
+//! let chunk = mab::parse_from_tokens(&tokens).unwrap();
+//! let scopes = ScopeTree::new(&chunk);
+//! let cfg = Cfg::for_chunk(&chunk, &scopes);
+//!
+//! // The `if` splits the chunk into the part before it and the condition, the
+//! // body, and the part after it.
+//! let entry = cfg.block(cfg.entry());
+//! assert_eq!(entry.steps.len(), 2);
+//! assert_eq!(entry.successors.len(), 2);
+//! ```
+//!
+//! Each graph covers one function, or the chunk itself, which Lua treats as a
+//! function too. The bodies of functions declared inside it aren't part of
+//! its graph, but the locals from outside that they use count as read where
+//! the function is declared.
+//!
+//! Blocks are made of [steps][Step], each of which is a statement or part of
+//! one, along with the locals it reads and writes, worked out with a
+//! [ScopeTree][::analysis::scope::ScopeTree].
+
+use std::collections::{HashMap, HashSet};
+
+use ast::*;
+use analysis::scope::{DeclarationId, DeclarationKind, Reference, ReferenceKind, ScopeTree};
+use visit::{self, Visitor};
+
+/// Identifies a [BasicBlock][BasicBlock] in a [Cfg][Cfg].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BlockId(usize);
+
+impl BlockId {
+    /// The block's position in [Cfg::blocks][Cfg::blocks].
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+/// Identifies one [Step][Step] in a [Cfg][Cfg].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StepId {
+    pub block: BlockId,
+
+    /// The step's position in the block.
+    pub index: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StepKind {
+    /// A function's parameters getting their values, at the start of its
+    /// entry block.
+    Parameters,
+
+    /// A statement that runs all at once, like an assignment, a call, or a
+    /// `return`.
+    Statement,
+
+    /// The condition of an `if`, `elseif`, `while`, or `repeat`, with the ID
+    /// of the condition expression.
+    Condition(NodeId),
+
+    /// A `for` loop working out its range or its iterator, before the first
+    /// time around.
+    LoopStart,
+
+    /// A `for` loop giving its variables their values for the next time
+    /// around, or finishing.
+    LoopNext,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Step {
+    pub kind: StepKind,
+
+    /// The statement the step is part of.
+    pub statement: NodeId,
+
+    /// The locals the step reads.
+    pub reads: Vec<DeclarationId>,
+
+    /// The locals the step gives values to, including ones it declares.
+    pub writes: Vec<DeclarationId>,
+}
+
+/// Steps that always run one after the other.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BasicBlock {
+    pub steps: Vec<Step>,
+
+    /// The blocks that can run next.
+    pub successors: Vec<BlockId>,
+
+    /// The blocks that can run just before this one.
+    pub predecessors: Vec<BlockId>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cfg {
+    blocks: Vec<BasicBlock>,
+}
+
+impl Cfg {
+    pub fn for_chunk(chunk: &Chunk, scopes: &ScopeTree) -> Cfg {
+        let mut builder = Builder::new(scopes);
+        builder.body(chunk);
+        builder.finish()
+    }
+
+    /// The graph for the body of a function declaration, or `None` if the
+    /// statement isn't one.
+    pub fn for_function(statement: &Statement, scopes: &ScopeTree) -> Option<Cfg> {
+        let function = match statement.kind {
+            StatementKind::FunctionDeclaration(ref function) => function,
+            _ => return None,
+        };
+
+        let mut builder = Builder::new(scopes);
+        let parameters = builder.declared_by(statement.id, |kind| {
+            kind == DeclarationKind::Parameter || kind == DeclarationKind::ImplicitSelf
+        });
+
+        builder.push(Step {
+            kind: StepKind::Parameters,
+            statement: statement.id,
+            reads: Vec::new(),
+            writes: parameters,
+        });
+
+        builder.body(&function.body);
+        Some(builder.finish())
+    }
+
+    /// The block that runs first.
+    pub fn entry(&self) -> BlockId {
+        BlockId(0)
+    }
+
+    /// An empty block that every `return`, and the end of the function, leads
+    /// to.
+    pub fn exit(&self) -> BlockId {
+        BlockId(1)
+    }
+
+    pub fn block(&self, id: BlockId) -> &BasicBlock {
+        &self.blocks[id.0]
+    }
+
+    pub fn step(&self, id: StepId) -> &Step {
+        &self.block(id.block).steps[id.index]
+    }
+
+    pub fn blocks(&self) -> &[BasicBlock] {
+        &self.blocks
+    }
+
+    pub fn block_ids(&self) -> impl Iterator<Item = BlockId> {
+        (0..self.blocks.len()).map(BlockId)
+    }
+
+    /// The blocks that can be reached from the entry block. Code after a
+    /// `return` in every branch of an `if`, for example, can't be.
+    pub fn reachable(&self) -> HashSet<BlockId> {
+        let mut reachable = HashSet::new();
+        let mut pending = vec![self.entry()];
+
+        while let Some(id) = pending.pop() {
+            if reachable.insert(id) {
+                pending.extend(self.block(id).successors.iter().cloned());
+            }
+        }
+
+        reachable
+    }
+}
+
+struct Builder<'s, 'a: 's> {
+    scopes: &'s ScopeTree<'a>,
+    references: HashMap<NodeId, &'s Reference<'a>>,
+    blocks: Vec<BasicBlock>,
+    current: BlockId,
+
+    // Where a `break` goes, innermost loop last.
+    loop_exits: Vec<BlockId>,
+
+    // The labels and gotos of each block being built, innermost last.
+    label_scopes: Vec<LabelScope>,
+}
+
+// The labels declared directly in a block, and the gotos in it or in blocks
+// inside it that haven't found their label yet.
+#[derive(Default)]
+struct LabelScope {
+    labels: HashMap<String, BlockId>,
+    gotos: Vec<(BlockId, String)>,
+}
+
+impl<'s, 'a> Builder<'s, 'a> {
+    fn new(scopes: &'s ScopeTree<'a>) -> Builder<'s, 'a> {
+        let references = scopes.references().iter()
+            .map(|reference| (reference.node, reference))
+            .collect();
+
+        Builder {
+            scopes,
+            references,
+            blocks: vec![BasicBlock::default(), BasicBlock::default()],
+            current: BlockId(0),
+            loop_exits: Vec::new(),
+            label_scopes: Vec::new(),
+        }
+    }
+
+    fn finish(mut self) -> Cfg {
+        let end = self.current;
+        self.edge(end, BlockId(1));
+
+        Cfg {
+            blocks: self.blocks,
+        }
+    }
+
+    fn new_block(&mut self) -> BlockId {
+        self.blocks.push(BasicBlock::default());
+        BlockId(self.blocks.len() - 1)
+    }
+
+    fn edge(&mut self, from: BlockId, to: BlockId) {
+        if !self.blocks[from.0].successors.contains(&to) {
+            self.blocks[from.0].successors.push(to);
+            self.blocks[to.0].predecessors.push(from);
+        }
+    }
+
+    // Starts a new block that `self.current` leads to.
+    fn fall_into(&mut self, block: BlockId) {
+        let current = self.current;
+        self.edge(current, block);
+        self.current = block;
+    }
+
+    fn push(&mut self, step: Step) {
+        let current = self.current;
+        self.blocks[current.0].steps.push(step);
+    }
+
+    fn declared_by<F: Fn(DeclarationKind) -> bool>(&self, statement: NodeId, filter: F) -> Vec<DeclarationId> {
+        self.scopes.declaration_ids()
+            .filter(|&id| {
+                let declaration = self.scopes.declaration(id);
+                declaration.statement == statement && filter(declaration.kind)
+            })
+            .collect()
+    }
+
+    // A step that reads and writes the locals the names in `expressions` refer
+    // to.
+    fn step<'e, I>(&self, kind: StepKind, statement: NodeId, expressions: I) -> Step
+        where I: IntoIterator<Item = &'e Expression<'e>>
+    {
+        let mut names = NameFinder::default();
+        for expression in expressions {
+            names.visit_expression(expression);
+        }
+
+        let mut step = Step {
+            kind,
+            statement,
+            reads: Vec::new(),
+            writes: Vec::new(),
+        };
+
+        for id in names.names {
+            self.add_reference(&mut step, id);
+        }
+
+        step
+    }
+
+    fn add_reference(&self, step: &mut Step, node: NodeId) {
+        let reference = match self.references.get(&node) {
+            Some(reference) => reference,
+            None => return,
+        };
+
+        if let Some(declaration) = reference.declaration {
+            match reference.kind {
+                ReferenceKind::Read => step.reads.push(declaration),
+                ReferenceKind::Write => step.writes.push(declaration),
+            }
+        }
+    }
+
+    fn body(&mut self, chunk: &Chunk) {
+        self.label_scopes.push(LabelScope::default());
+
+        for statement in &chunk.statements {
+            self.statement(statement);
+        }
+
+        // A goto can see the labels anywhere in its own block or the blocks
+        // around it, so the ones that aren't in this block are left for the
+        // block around it.
+        let scope = self.label_scopes.pop().expect("label scopes are pushed and popped in pairs");
+
+        for (from, label) in scope.gotos {
+            match scope.labels.get(&label) {
+                Some(&to) => self.edge(from, to),
+                None => if let Some(outer) = self.label_scopes.last_mut() {
+                    outer.gotos.push((from, label));
+                },
+            }
+        }
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        let id = statement.id;
+
+        match statement.kind {
+            StatementKind::Assignment(ref assignment) => {
+                let step = self.step(StepKind::Statement, id, assignment.targets.iter().chain(&assignment.values));
+                self.push(step);
+            },
+            StatementKind::LocalAssignment(ref local) => {
+                let mut step = self.step(StepKind::Statement, id, &local.values);
+                step.writes = self.declared_by(id, |_| true);
+                self.push(step);
+            },
+            StatementKind::FunctionCall(ref call) => {
+                let mut names = NameFinder::default();
+                names.visit_function_call(call);
+
+                let mut step = self.step(StepKind::Statement, id, Vec::new());
+                for name in names.names {
+                    self.add_reference(&mut step, name);
+                }
+
+                self.push(step);
+            },
+            StatementKind::FunctionDeclaration(ref function) => {
+                let mut step = self.step(StepKind::Statement, id, Vec::new());
+                self.add_reference(&mut step, id);
+
+                if function.local {
+                    step.writes = self.declared_by(id, |kind| kind == DeclarationKind::LocalFunction);
+                }
+
+                step.reads.extend(self.captured(id, &function.body));
+                self.push(step);
+            },
+            StatementKind::Return(ref return_statement) => {
+                let step = self.step(StepKind::Statement, id, &return_statement.values);
+                self.push(step);
+
+                let current = self.current;
+                self.edge(current, BlockId(1));
+                self.current = self.new_block();
+            },
+            StatementKind::Break => {
+                let step = self.step(StepKind::Statement, id, Vec::new());
+                self.push(step);
+
+                if let Some(&exit) = self.loop_exits.last() {
+                    let current = self.current;
+                    self.edge(current, exit);
+                }
+
+                self.current = self.new_block();
+            },
+            StatementKind::Goto(ref goto) => {
+                let step = self.step(StepKind::Statement, id, Vec::new());
+                self.push(step);
+
+                let current = self.current;
+                self.label_scope().gotos.push((current, goto.label.to_string()));
+                self.current = self.new_block();
+            },
+            StatementKind::Label(ref label) => {
+                let block = self.new_block();
+                self.fall_into(block);
+                self.label_scope().labels.entry(label.name.to_string()).or_insert(block);
+            },
+            StatementKind::DoBlock(ref do_block) => self.body(&do_block.body),
+            StatementKind::IfStatement(ref if_statement) => {
+                let after = self.new_block();
+                let branches = Some((&if_statement.condition, &if_statement.body)).into_iter()
+                    .chain(if_statement.else_if_branches.iter().map(|(condition, body)| (condition, body)));
+
+                for (condition, body) in branches {
+                    let step = self.step(StepKind::Condition(condition.id), id, Some(condition));
+                    self.push(step);
+                    let test = self.current;
+
+                    let block = self.new_block();
+                    self.fall_into(block);
+                    self.body(body);
+
+                    let end = self.current;
+                    self.edge(end, after);
+
+                    // The next condition runs when this one is false.
+                    let next = self.new_block();
+                    self.edge(test, next);
+                    self.current = next;
+                }
+
+                if let Some(ref else_branch) = if_statement.else_branch {
+                    self.body(else_branch);
+                }
+
+                self.fall_into(after);
+            },
+            StatementKind::WhileLoop(ref while_loop) => {
+                let header = self.new_block();
+                let after = self.new_block();
+                self.fall_into(header);
+
+                let step = self.step(StepKind::Condition(while_loop.condition.id), id, Some(&while_loop.condition));
+                self.push(step);
+                self.edge(header, after);
+
+                let body = self.new_block();
+                self.fall_into(body);
+                self.loop_body(&while_loop.body, after);
+
+                let end = self.current;
+                self.edge(end, header);
+                self.current = after;
+            },
+            StatementKind::RepeatLoop(ref repeat_loop) => {
+                let body = self.new_block();
+                let after = self.new_block();
+                self.fall_into(body);
+                self.loop_body(&repeat_loop.body, after);
+
+                let step = self.step(StepKind::Condition(repeat_loop.condition.id), id, Some(&repeat_loop.condition));
+                self.push(step);
+
+                let end = self.current;
+                self.edge(end, body);
+                self.fall_into(after);
+            },
+            StatementKind::NumericFor(ref numeric_for) => {
+                let range = Some(&numeric_for.start).into_iter()
+                    .chain(Some(&numeric_for.end))
+                    .chain(numeric_for.step.as_ref());
+
+                let step = self.step(StepKind::LoopStart, id, range);
+                self.push(step);
+                self.for_loop(id, &numeric_for.body);
+            },
+            StatementKind::GenericFor(ref generic_for) => {
+                let step = self.step(StepKind::LoopStart, id, &generic_for.item_source);
+                self.push(step);
+                self.for_loop(id, &generic_for.body);
+            },
+            StatementKind::Empty => {},
+        }
+    }
+
+    fn label_scope(&mut self) -> &mut LabelScope {
+        self.label_scopes.last_mut().expect("statements are always inside a block")
+    }
+
+    fn loop_body(&mut self, body: &Chunk, after: BlockId) {
+        self.loop_exits.push(after);
+        self.body(body);
+        self.loop_exits.pop();
+    }
+
+    fn for_loop(&mut self, id: NodeId, body: &Chunk) {
+        let header = self.new_block();
+        let after = self.new_block();
+        self.fall_into(header);
+
+        let mut step = self.step(StepKind::LoopNext, id, Vec::new());
+        step.writes = self.declared_by(id, |kind| kind == DeclarationKind::LoopVariable);
+        self.push(step);
+        self.edge(header, after);
+
+        let block = self.new_block();
+        self.fall_into(block);
+        self.loop_body(body, after);
+
+        let end = self.current;
+        self.edge(end, header);
+        self.current = after;
+    }
+
+    // The locals from outside a function that its body reads or writes.
+    // Either way, they need to have their values when the function is
+    // declared.
+    fn captured(&self, function: NodeId, body: &Chunk) -> Vec<DeclarationId> {
+        let (mut statements, names) = nodes_inside(body);
+        statements.insert(function);
+
+        let mut captured = Vec::new();
+
+        for node in statements.iter().chain(&names) {
+            if *node == function {
+                continue;
+            }
+
+            let declaration = match self.references.get(node).and_then(|reference| reference.declaration) {
+                Some(declaration) => declaration,
+                None => continue,
+            };
+
+            if !statements.contains(&self.scopes.declaration(declaration).statement) && !captured.contains(&declaration) {
+                captured.push(declaration);
+            }
+        }
+
+        captured.sort();
+        captured
+    }
+}
+
+// The IDs of the names in expressions, in the order they appear.
+#[derive(Default)]
+struct NameFinder {
+    names: Vec<NodeId>,
+}
+
+impl<'a> Visitor<'a> for NameFinder {
+    fn visit_expression(&mut self, expression: &'a Expression<'a>) {
+        if let ExpressionKind::Name(_) = expression.kind {
+            self.names.push(expression.id);
+        }
+
+        visit::walk_expression(self, expression);
+    }
+}
+
+// The IDs of every statement in a function's body, and of every name in it.
+fn nodes_inside(body: &Chunk) -> (HashSet<NodeId>, Vec<NodeId>) {
+    let mut statements = HashSet::new();
+    let mut names = Vec::new();
+
+    for node in body.descendants() {
+        match node {
+            NodeRef::Statement(statement) => {
+                statements.insert(statement.id);
+            },
+            NodeRef::Expression(expression) => {
+                if let ExpressionKind::Name(_) = expression.kind {
+                    names.push(expression.id);
+                }
+            },
+        }
+    }
+
+    (statements, names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokenizer::tokenize;
+    use parser::parse_from_tokens;
+
+    // The statements each goto jumps to, in the order of the gotos' IDs.
+    fn goto_targets(source: &str) -> Vec<Vec<NodeId>> {
+        let tokens = tokenize(source).unwrap();
+        let chunk = parse_from_tokens(&tokens).unwrap();
+        let scopes = ScopeTree::new(&chunk);
+        let cfg = Cfg::for_chunk(&chunk, &scopes);
+
+        let gotos = chunk.descendants()
+            .filter_map(|node| match node {
+                NodeRef::Statement(statement) if matches!(statement.kind, StatementKind::Goto(_)) => Some(statement.id),
+                _ => None,
+            })
+            .collect::<HashSet<_>>();
+
+        let mut targets = cfg.blocks().iter()
+            .filter(|block| block.steps.last().is_some_and(|step| gotos.contains(&step.statement)))
+            .map(|block| {
+                let goto = block.steps.last().unwrap().statement;
+                let next = block.successors.iter()
+                    .flat_map(|&successor| first_statement(&cfg, successor))
+                    .collect::<Vec<_>>();
+
+                (goto, next)
+            })
+            .collect::<Vec<_>>();
+
+        targets.sort();
+        targets.into_iter().map(|(_, next)| next).collect()
+    }
+
+    // The first statement that runs from `id` on, following blocks without
+    // steps, like the ones labels start.
+    fn first_statement(cfg: &Cfg, id: BlockId) -> Option<NodeId> {
+        let block = cfg.block(id);
+
+        match block.steps.first() {
+            Some(step) => Some(step.statement),
+            None => block.successors.first().and_then(|&next| first_statement(cfg, next)),
+        }
+    }
+
+    #[test]
+    fn sibling_blocks_reuse_labels() {
+        let source = "do goto l print(1) ::l:: print(2) end do goto l print(3) ::l:: print(4) end";
+        let tokens = tokenize(source).unwrap();
+        let chunk = parse_from_tokens(&tokens).unwrap();
+
+        let body = |index: usize| match chunk.statements[index].kind {
+            StatementKind::DoBlock(ref do_block) => do_block.body.statements.iter().map(|statement| statement.id).collect::<Vec<_>>(),
+            _ => unreachable!(),
+        };
+
+        // Each goto reaches the print after its own block's label.
+        assert_eq!(goto_targets(source), [vec![body(0)[3]], vec![body(1)[3]]]);
+    }
+
+    #[test]
+    fn gotos_see_enclosing_blocks() {
+        let source = "do do goto l end print(1) ::l:: print(2) end";
+        let tokens = tokenize(source).unwrap();
+        let chunk = parse_from_tokens(&tokens).unwrap();
+
+        let after = match chunk.statements[0].kind {
+            StatementKind::DoBlock(ref do_block) => do_block.body.statements[3].id,
+            _ => unreachable!(),
+        };
+
+        assert_eq!(goto_targets(source), [vec![after]]);
+    }
+}
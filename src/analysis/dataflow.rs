@@ -0,0 +1,372 @@
+//! A small dataflow engine that runs over a [Cfg][::analysis::cfg::Cfg], with
+//! the two analyses most lints need built in.
+//!
+//! ```
+//! use mab::analysis::cfg::{Cfg, StepId};
+//! use mab::analysis::dataflow::{solve, Liveness};
+//! use mab::analysis::scope::ScopeTree;
+//!
+//! let tokens = mab::tokenize("
+//!     local x = 1
+//!     x = 2
+//!     print(x)
+//! ").unwrap();
+//! let chunk = mab::parse_from_tokens(&tokens).unwrap();
+//! let scopes = ScopeTree::new(&chunk);
+//! let cfg = Cfg::for_chunk(&chunk, &scopes);
+//!
+//! // `x` is assigned again before it's read, so its first value is never used.
+//! let liveness = solve(&cfg, &Liveness);
+//! let x = scopes.declaration_ids().next().unwrap();
+//! let after = |index| liveness.after_step(&cfg, &Liveness, StepId { block: cfg.entry(), index });
+//!
+//! assert!(!after(0).contains(&x));
+//! assert!(after(1).contains(&x));
+//! ```
+//!
+//! New analyses implement [Analysis][Analysis], which says which way facts
+//! flow, how they change going through each [Step][::analysis::cfg::Step],
+//! and how they combine where paths meet.
+
+use std::collections::BTreeSet;
+
+use analysis::cfg::{BlockId, Cfg, Step, StepId};
+use analysis::scope::DeclarationId;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    /// Facts flow from the entry towards the exit, like which assignments
+    /// have happened.
+    Forward,
+
+    /// Facts flow from the exit towards the entry, like which locals will be
+    /// read later.
+    Backward,
+}
+
+pub trait Analysis {
+    type Fact: Clone + PartialEq;
+
+    fn direction(&self) -> Direction;
+
+    /// The fact where no paths have been seen yet, which joining with any
+    /// other fact leaves unchanged.
+    fn empty(&self) -> Self::Fact;
+
+    /// The fact at the start of the function, for forward analyses, or at the
+    /// end of it, for backward ones. Defaults to [empty][Analysis::empty].
+    fn boundary(&self) -> Self::Fact {
+        self.empty()
+    }
+
+    /// Combines `other` into `fact`, where two paths meet.
+    fn join(&self, fact: &mut Self::Fact, other: &Self::Fact);
+
+    /// Changes `fact` to what it is after `step`, going in the analysis's
+    /// direction.
+    fn transfer(&self, step: StepId, data: &Step, fact: &mut Self::Fact);
+}
+
+/// The facts at the start and end of every block, from [solve][solve].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Solution<F> {
+    starts: Vec<F>,
+    ends: Vec<F>,
+}
+
+impl<F: Clone> Solution<F> {
+    /// The fact just before the block runs.
+    pub fn block_start(&self, block: BlockId) -> &F {
+        &self.starts[block.index()]
+    }
+
+    /// The fact just after the block runs.
+    pub fn block_end(&self, block: BlockId) -> &F {
+        &self.ends[block.index()]
+    }
+
+    /// The fact just before a step runs.
+    pub fn before_step<A: Analysis<Fact = F>>(&self, cfg: &Cfg, analysis: &A, step: StepId) -> F {
+        match analysis.direction() {
+            Direction::Forward => self.replay(cfg, analysis, step.block, 0..step.index),
+            Direction::Backward => self.replay(cfg, analysis, step.block, step.index..cfg.block(step.block).steps.len()),
+        }
+    }
+
+    /// The fact just after a step runs.
+    pub fn after_step<A: Analysis<Fact = F>>(&self, cfg: &Cfg, analysis: &A, step: StepId) -> F {
+        match analysis.direction() {
+            Direction::Forward => self.replay(cfg, analysis, step.block, 0..step.index + 1),
+            Direction::Backward => self.replay(cfg, analysis, step.block, step.index + 1..cfg.block(step.block).steps.len()),
+        }
+    }
+
+    // Runs the steps in `range` over the fact at the block's edge.
+    fn replay<A: Analysis<Fact = F>>(&self, cfg: &Cfg, analysis: &A, block: BlockId, range: ::std::ops::Range<usize>) -> F {
+        let steps = &cfg.block(block).steps;
+
+        match analysis.direction() {
+            Direction::Forward => {
+                let mut fact = self.block_start(block).clone();
+                for index in range {
+                    analysis.transfer(StepId { block, index }, &steps[index], &mut fact);
+                }
+
+                fact
+            },
+            Direction::Backward => {
+                let mut fact = self.block_end(block).clone();
+                for index in range.rev() {
+                    analysis.transfer(StepId { block, index }, &steps[index], &mut fact);
+                }
+
+                fact
+            },
+        }
+    }
+}
+
+/// Runs an analysis until its facts stop changing.
+pub fn solve<A: Analysis>(cfg: &Cfg, analysis: &A) -> Solution<A::Fact> {
+    let count = cfg.blocks().len();
+    let mut solution = Solution {
+        starts: vec![analysis.empty(); count],
+        ends: vec![analysis.empty(); count],
+    };
+
+    let forward = analysis.direction() == Direction::Forward;
+    let mut changed = true;
+
+    while changed {
+        changed = false;
+
+        for block in cfg.block_ids() {
+            let data = cfg.block(block);
+
+            // The fact flowing into the block, from its predecessors going
+            // forward or its successors going backward.
+            let (neighbors, boundary) = if forward {
+                (&data.predecessors, block == cfg.entry())
+            } else {
+                (&data.successors, block == cfg.exit())
+            };
+
+            let mut fact = if boundary { analysis.boundary() } else { analysis.empty() };
+            for &neighbor in neighbors {
+                let other = if forward { &solution.ends[neighbor.index()] } else { &solution.starts[neighbor.index()] };
+                analysis.join(&mut fact, other);
+            }
+
+            let mut out = fact.clone();
+            let steps = data.steps.iter().enumerate();
+
+            if forward {
+                for (index, step) in steps {
+                    analysis.transfer(StepId { block, index }, step, &mut out);
+                }
+            } else {
+                for (index, step) in steps.rev() {
+                    analysis.transfer(StepId { block, index }, step, &mut out);
+                }
+            }
+
+            let (into, out_of) = if forward {
+                (&mut solution.starts, &mut solution.ends)
+            } else {
+                (&mut solution.ends, &mut solution.starts)
+            };
+
+            if into[block.index()] != fact || out_of[block.index()] != out {
+                into[block.index()] = fact;
+                out_of[block.index()] = out;
+                changed = true;
+            }
+        }
+    }
+
+    solution
+}
+
+/// One step giving a local a value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Definition {
+    pub step: StepId,
+    pub declaration: DeclarationId,
+}
+
+/// Which values each local could have, as the steps that gave it them. A
+/// local with no definitions hasn't been given a value on any path, and one
+/// with several was given different ones on different paths.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ReachingDefinitions;
+
+impl Analysis for ReachingDefinitions {
+    type Fact = BTreeSet<Definition>;
+
+    fn direction(&self) -> Direction {
+        Direction::Forward
+    }
+
+    fn empty(&self) -> BTreeSet<Definition> {
+        BTreeSet::new()
+    }
+
+    fn join(&self, fact: &mut BTreeSet<Definition>, other: &BTreeSet<Definition>) {
+        fact.extend(other.iter().cloned());
+    }
+
+    fn transfer(&self, step: StepId, data: &Step, fact: &mut BTreeSet<Definition>) {
+        fact.retain(|definition| !data.writes.contains(&definition.declaration));
+        fact.extend(data.writes.iter().map(|&declaration| Definition { step, declaration }));
+    }
+}
+
+/// Which locals might still be read, on some path, before they're given a
+/// new value.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Liveness;
+
+impl Analysis for Liveness {
+    type Fact = BTreeSet<DeclarationId>;
+
+    fn direction(&self) -> Direction {
+        Direction::Backward
+    }
+
+    fn empty(&self) -> BTreeSet<DeclarationId> {
+        BTreeSet::new()
+    }
+
+    fn join(&self, fact: &mut BTreeSet<DeclarationId>, other: &BTreeSet<DeclarationId>) {
+        fact.extend(other.iter().cloned());
+    }
+
+    // A step reads its locals before it writes any, like in `x = x + 1`.
+    fn transfer(&self, _step: StepId, data: &Step, fact: &mut BTreeSet<DeclarationId>) {
+        for declaration in &data.writes {
+            fact.remove(declaration);
+        }
+
+        fact.extend(data.reads.iter().cloned());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use analysis::cfg::StepKind;
+    use analysis::scope::ScopeTree;
+    use ast::*;
+    use tokenizer::tokenize;
+    use parser::{parse_from_tokens, parse_from_tokens_with_source_map};
+
+    // For each read of `name`, the statements whose values for it could be
+    // the one that's read.
+    fn reaching(source: &str, name: &str) -> Vec<Vec<String>> {
+        let tokens = tokenize(source).unwrap();
+        let (chunk, source_map) = parse_from_tokens_with_source_map(&tokens).unwrap();
+        let scopes = ScopeTree::new(&chunk);
+        let cfg = Cfg::for_chunk(&chunk, &scopes);
+        let solution = solve(&cfg, &ReachingDefinitions);
+
+        let mut reads = Vec::new();
+        for block in cfg.block_ids() {
+            for (index, step) in cfg.block(block).steps.iter().enumerate() {
+                let step_id = StepId { block, index };
+
+                for &declaration in &step.reads {
+                    if scopes.declaration(declaration).name != name {
+                        continue;
+                    }
+
+                    let mut definitions = solution.before_step(&cfg, &ReachingDefinitions, step_id).iter()
+                        .filter(|definition| definition.declaration == declaration)
+                        .map(|definition| source_map.source_text(cfg.step(definition.step).statement, source).unwrap().to_string())
+                        .collect::<Vec<_>>();
+
+                    definitions.sort();
+                    reads.push((source_map.span(step.statement).unwrap().start, definitions));
+                }
+            }
+        }
+
+        reads.sort();
+        reads.into_iter().map(|(_, definitions)| definitions).collect()
+    }
+
+    #[test]
+    fn branches() {
+        let source = "local x = 1 if c then x = 2 elseif d then x = 3 end print(x)";
+        assert_eq!(reaching(source, "x"), [["local x = 1", "x = 2", "x = 3"]]);
+
+        let source = "local x = 1 if c then x = 2 else x = 3 end print(x)";
+        assert_eq!(reaching(source, "x"), [["x = 2", "x = 3"]]);
+    }
+
+    #[test]
+    fn loops() {
+        // The condition and the body see the value from before the loop and
+        // the one from the last time around.
+        let source = "local x = 0 while x < 10 do x = x + 1 end";
+        assert_eq!(reaching(source, "x"), [["local x = 0", "x = x + 1"], ["local x = 0", "x = x + 1"]]);
+
+        // Reads are in the order of the statements they're in, and the
+        // condition is part of the `repeat`.
+        let source = "local x = 0 repeat local y = x x = y until x > 3 return x";
+        assert_eq!(reaching(source, "x"), [
+            vec!["x = y"],
+            vec!["local x = 0", "x = y"],
+            vec!["x = y"],
+        ]);
+
+        let source = "for i = 1, 3 do local x = i end local i = 0 print(i)";
+        assert_eq!(reaching(source, "i"), [["for i = 1, 3 do local x = i end"], ["local i = 0"]]);
+    }
+
+    #[test]
+    fn liveness_and_returns() {
+        let tokens = tokenize("local a, b = 1, 2 if a then return b end a = 3 print(a)").unwrap();
+        let chunk = parse_from_tokens(&tokens).unwrap();
+        let scopes = ScopeTree::new(&chunk);
+        let cfg = Cfg::for_chunk(&chunk, &scopes);
+        let solution = solve(&cfg, &Liveness);
+
+        let ids = scopes.declaration_ids().collect::<Vec<_>>();
+        let live = solution.after_step(&cfg, &Liveness, StepId { block: cfg.entry(), index: 0 });
+        assert_eq!(live.iter().cloned().collect::<Vec<_>>(), [ids[0], ids[1]]);
+
+        // Nothing is live where the function ends.
+        assert!(solution.block_start(cfg.exit()).is_empty());
+
+        let kinds = cfg.block(cfg.entry()).steps.iter().map(|step| step.kind).collect::<Vec<_>>();
+        assert_eq!(kinds, [StepKind::Statement, StepKind::Condition(match chunk.statements[1].kind {
+            StatementKind::IfStatement(ref if_statement) => if_statement.condition.id,
+            _ => unreachable!(),
+        })]);
+    }
+
+    #[test]
+    fn functions() {
+        let tokens = tokenize("
+            local count = 0
+            local function bump(by) count = count + by end
+            for i = 1, 3 do bump(i) end
+        ").unwrap();
+        let chunk = parse_from_tokens(&tokens).unwrap();
+        let scopes = ScopeTree::new(&chunk);
+
+        // `bump` reads `count` when it's declared, so `count` is live there.
+        let cfg = Cfg::for_chunk(&chunk, &scopes);
+        let solution = solve(&cfg, &Liveness);
+        let count = scopes.declaration_ids().next().unwrap();
+        assert!(solution.after_step(&cfg, &Liveness, StepId { block: cfg.entry(), index: 0 }).contains(&count));
+
+        // Inside, the parameter is live from the start.
+        let cfg = Cfg::for_function(&chunk.statements[1], &scopes).unwrap();
+        let solution = solve(&cfg, &Liveness);
+        let by = scopes.declaration_ids().nth(2).unwrap();
+        assert_eq!(scopes.declaration(by).name, "by");
+        assert!(solution.after_step(&cfg, &Liveness, StepId { block: cfg.entry(), index: 0 }).contains(&by));
+        assert!(Cfg::for_function(&chunk.statements[0], &scopes).is_none());
+    }
+}
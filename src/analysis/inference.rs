@@ -0,0 +1,381 @@
+//! Works out the types of expressions that obviously only have one, without
+//! needing any annotations.
+//!
+//! ```
+//! use mab::analysis::inference::{BasicType, TypeInference};
+//! use mab::ast::StatementKind;
+//!
+//! let tokens = mab::tokenize("
+//!     local count = 10
+//!     local label = 'count: ' .. count
+//!     return math.floor(count / 3), label, tostring(count), f(count)
+//! ").unwrap();
+//! let chunk = mab::parse_from_tokens(&tokens).unwrap();
+//! let inference = TypeInference::new(&chunk);
+//!
+//! let values = match chunk.statements[2].kind {
+//!     StatementKind::Return(ref return_statement) => &return_statement.values,
+//!     _ => unreachable!(),
+//! };
+//!
+//! let types = values.iter().map(|value| inference.type_of(value)).collect::<Vec<_>>();
+//! assert_eq!(types, [Some(BasicType::Number), Some(BasicType::String), Some(BasicType::String), None]);
+//! ```
+//!
+//! A type is only inferred when it can't be anything else:
+//!
+//! - literals, and functions declared with `local function`
+//! - the results of operators, when their operands have types that don't
+//!   need metatables, like `a + 1` when `a` is a number or a string
+//! - locals that are never assigned to after they're declared, which have
+//!   the type of their initial value, and numeric `for` loops' variables
+//! - calls to standard library functions that always return one type, like
+//!   `tostring` and `math.floor`, as long as the names aren't locals
+//!
+//! Tables can get metatables through any name they reach, so locals holding
+//! them don't get a type once they're passed to a function, called as a
+//! method's receiver, or assigned to another name.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use analysis::scope::{DeclarationId, DeclarationKind, ReferenceKind, ScopeTree};
+use ast::*;
+use index::NodeIndex;
+
+/// The types Lua values can have, apart from userdata and threads, which
+/// code can't make on its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BasicType {
+    Nil,
+    Boolean,
+    Number,
+    String,
+    Table,
+    Function,
+}
+
+/// Writes the type the way Lua's `type` function does, like `number`.
+impl fmt::Display for BasicType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            BasicType::Nil => "nil",
+            BasicType::Boolean => "boolean",
+            BasicType::Number => "number",
+            BasicType::String => "string",
+            BasicType::Table => "table",
+            BasicType::Function => "function",
+        };
+
+        f.write_str(name)
+    }
+}
+
+// Standard library functions that always return the same type, by the name
+// they're called with.
+const FUNCTION_RESULTS: &[(&str, BasicType)] = &[
+    ("tostring", BasicType::String),
+    ("type", BasicType::String),
+    ("rawequal", BasicType::Boolean),
+    ("rawlen", BasicType::Number),
+    ("math.abs", BasicType::Number),
+    ("math.ceil", BasicType::Number),
+    ("math.cos", BasicType::Number),
+    ("math.exp", BasicType::Number),
+    ("math.floor", BasicType::Number),
+    ("math.fmod", BasicType::Number),
+    ("math.log", BasicType::Number),
+    ("math.max", BasicType::Number),
+    ("math.min", BasicType::Number),
+    ("math.random", BasicType::Number),
+    ("math.sin", BasicType::Number),
+    ("math.sqrt", BasicType::Number),
+    ("math.tan", BasicType::Number),
+    ("os.clock", BasicType::Number),
+    ("os.time", BasicType::Number),
+    ("string.char", BasicType::String),
+    ("string.format", BasicType::String),
+    ("string.len", BasicType::Number),
+    ("string.lower", BasicType::String),
+    ("string.rep", BasicType::String),
+    ("string.reverse", BasicType::String),
+    ("string.sub", BasicType::String),
+    ("string.upper", BasicType::String),
+    ("table.concat", BasicType::String),
+    ("table.pack", BasicType::Table),
+];
+
+// Standard library values that aren't functions.
+const FIELD_TYPES: &[(&str, BasicType)] = &[
+    ("math.huge", BasicType::Number),
+    ("math.maxinteger", BasicType::Number),
+    ("math.mininteger", BasicType::Number),
+    ("math.pi", BasicType::Number),
+];
+
+/// Infers the types of a chunk's expressions.
+pub struct TypeInference<'a> {
+    scopes: ScopeTree<'a>,
+    index: NodeIndex<'a>,
+
+    /// The local each name refers to, by the name's ID. Globals aren't
+    /// included.
+    declarations: HashMap<NodeId, DeclarationId>,
+
+    /// Locals whose values can change after they're declared, or that hold
+    /// tables that could have metatables.
+    unstable: HashSet<DeclarationId>,
+}
+
+impl<'a> TypeInference<'a> {
+    pub fn new(chunk: &'a Chunk<'a>) -> TypeInference<'a> {
+        let scopes = ScopeTree::new(chunk);
+
+        let declarations = scopes.references().iter()
+            .filter_map(|reference| Some((reference.node, reference.declaration?)))
+            .collect::<HashMap<_, _>>();
+
+        let mut unstable = scopes.references().iter()
+            .filter(|reference| reference.kind == ReferenceKind::Write)
+            .filter_map(|reference| reference.declaration)
+            .collect::<HashSet<_>>();
+
+        let mut inference = TypeInference {
+            scopes,
+            index: NodeIndex::new(chunk),
+            declarations,
+            unstable: HashSet::new(),
+        };
+
+        // Values that other names or functions get hold of.
+        let mut escaped = Vec::new();
+
+        for node in chunk.descendants() {
+            let call = match node {
+                NodeRef::Statement(Statement { kind: StatementKind::FunctionCall(call), .. }) => call,
+                NodeRef::Expression(Expression { kind: ExpressionKind::FunctionCall(call), .. }) => call,
+                NodeRef::Statement(Statement { kind: StatementKind::LocalAssignment(local), .. }) => {
+                    escaped.extend(&local.values);
+                    continue;
+                },
+                NodeRef::Statement(Statement { kind: StatementKind::Assignment(assignment), .. }) => {
+                    escaped.extend(&assignment.values);
+                    continue;
+                },
+                _ => continue,
+            };
+
+            if call.method.is_some() {
+                escaped.push(&*call.name_expression);
+            }
+
+            if let FunctionArguments::Parenthesized(ref arguments) = call.arguments {
+                escaped.extend(arguments);
+            }
+
+            if inference.global_name(&call.name_expression).as_deref() != Some("setmetatable") {
+                continue;
+            }
+
+            if let FunctionArguments::Parenthesized(ref arguments) = call.arguments {
+                if let Some(&declaration) = arguments.first().and_then(|argument| inference.declarations.get(&argument.id)) {
+                    unstable.insert(declaration);
+                }
+            }
+        }
+
+        inference.unstable = unstable;
+
+        // Only tables are worth leaving out, since nothing another name does
+        // can change what the other types do.
+        let tables = escaped.iter()
+            .filter_map(|value| inference.declarations.get(&value.id).cloned())
+            .filter(|&declaration| inference.local_type(declaration) == Some(BasicType::Table))
+            .collect::<Vec<_>>();
+
+        inference.unstable.extend(tables);
+        inference
+    }
+
+    /// The type `expression` always has, or `None` if it could have more
+    /// than one or it isn't clear which.
+    pub fn type_of(&self, expression: &Expression) -> Option<BasicType> {
+        match expression.kind {
+            ExpressionKind::Nil => Some(BasicType::Nil),
+            ExpressionKind::Bool(_) => Some(BasicType::Boolean),
+            ExpressionKind::Number(_) => Some(BasicType::Number),
+            ExpressionKind::String(_) => Some(BasicType::String),
+            ExpressionKind::Table(_) => Some(BasicType::Table),
+            ExpressionKind::VarArg => None,
+            ExpressionKind::ParenExpression(ref inner) => self.type_of(inner),
+            ExpressionKind::Name(_) => match self.declarations.get(&expression.id) {
+                Some(&declaration) => self.local_type(declaration),
+                None => None,
+            },
+            ExpressionKind::FieldAccess(_) => {
+                let name = self.global_name(expression)?;
+                FIELD_TYPES.iter().find(|&&(field, _)| field == name).map(|&(_, result)| result)
+            },
+            ExpressionKind::Index(_) => None,
+            ExpressionKind::FunctionCall(ref call) => {
+                if call.method.is_some() {
+                    return None;
+                }
+
+                let name = self.global_name(&call.name_expression)?;
+                FUNCTION_RESULTS.iter().find(|&&(function, _)| function == name).map(|&(_, result)| result)
+            },
+            ExpressionKind::UnaryOp(ref unary_op) => {
+                let operand = self.type_of(&unary_op.argument);
+
+                match unary_op.operator {
+                    UnaryOpKind::BooleanNot => Some(BasicType::Boolean),
+                    UnaryOpKind::Negate if is_numeric(operand) => Some(BasicType::Number),
+                    UnaryOpKind::Length if operand == Some(BasicType::String) => Some(BasicType::Number),
+                    _ => None,
+                }
+            },
+            ExpressionKind::BinaryOp(ref binary_op) => {
+                let (left, right) = (self.type_of(&binary_op.left), self.type_of(&binary_op.right));
+
+                match binary_op.operator {
+                    // Metamethods for these always have their results
+                    // converted to booleans.
+                    BinaryOpKind::Equal | BinaryOpKind::NotEqual | BinaryOpKind::LessThan |
+                    BinaryOpKind::LessThanEqual | BinaryOpKind::GreaterThan | BinaryOpKind::GreaterThanEqual => Some(BasicType::Boolean),
+
+                    // These are one of their operands, which is only a known
+                    // type when both are the same.
+                    BinaryOpKind::And | BinaryOpKind::Or => if left == right { left } else { None },
+
+                    BinaryOpKind::Concat if is_numeric(left) && is_numeric(right) => Some(BasicType::String),
+                    BinaryOpKind::Concat => None,
+                    _ if is_numeric(left) && is_numeric(right) => Some(BasicType::Number),
+                    _ => None,
+                }
+            },
+        }
+    }
+
+    fn local_type(&self, id: DeclarationId) -> Option<BasicType> {
+        if self.unstable.contains(&id) {
+            return None;
+        }
+
+        let declaration = self.scopes.declaration(id);
+
+        let statement = match self.index.get(declaration.statement)? {
+            NodeRef::Statement(statement) => statement,
+            NodeRef::Expression(_) => return None,
+        };
+
+        match (declaration.kind, &statement.kind) {
+            (DeclarationKind::LocalFunction, _) => Some(BasicType::Function),
+            (DeclarationKind::LoopVariable, StatementKind::NumericFor(_)) => Some(BasicType::Number),
+            (DeclarationKind::Local, StatementKind::LocalAssignment(local)) => {
+                match local.values.get(declaration.position) {
+                    Some(value) => self.type_of(value),
+
+                    // Names past the end of the values are `nil`, unless the
+                    // last value can be more than one.
+                    None => match local.values.last().map(|value| &value.kind) {
+                        Some(ExpressionKind::FunctionCall(_)) | Some(ExpressionKind::VarArg) => None,
+                        _ => Some(BasicType::Nil),
+                    },
+                }
+            },
+            _ => None,
+        }
+    }
+
+    // The dotted name of a global, or a field of one, like `math.floor`.
+    fn global_name(&self, expression: &Expression) -> Option<String> {
+        match expression.kind {
+            ExpressionKind::Name(ref name) if !self.declarations.contains_key(&expression.id) => Some(name.to_string()),
+            ExpressionKind::FieldAccess(ref field_access) => {
+                let target = self.global_name(&field_access.target)?;
+                Some(format!("{}.{}", target, field_access.name))
+            },
+            _ => None,
+        }
+    }
+}
+
+// Whether arithmetic works on a value of this type without a metatable.
+// Strings are converted to numbers.
+fn is_numeric(found: Option<BasicType>) -> bool {
+    matches!(found, Some(BasicType::Number) | Some(BasicType::String))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokenizer::tokenize;
+    use parser::parse_from_tokens;
+
+    // The types of the values the chunk's last statement returns.
+    fn returned(source: &str) -> Vec<Option<BasicType>> {
+        let tokens = tokenize(source).unwrap();
+        let chunk = parse_from_tokens(&tokens).unwrap();
+        let inference = TypeInference::new(&chunk);
+
+        match chunk.statements.last().unwrap().kind {
+            StatementKind::Return(ref return_statement) => return_statement.values.iter()
+                .map(|value| inference.type_of(value))
+                .collect(),
+            _ => panic!("expected a return statement"),
+        }
+    }
+
+    #[test]
+    fn operators() {
+        use self::BasicType::*;
+
+        assert_eq!(
+            returned("return not x, -'1', #'abc', #t, 1 .. 2, x .. 1, 1 + '2', x * 2, x == 1, 1 and 2, x or 1"),
+            [Some(Boolean), Some(Number), Some(Number), None, Some(String), None, Some(Number), None, Some(Boolean), Some(Number), None],
+        );
+    }
+
+    #[test]
+    fn locals() {
+        use self::BasicType::*;
+
+        assert_eq!(returned("
+            local a, b, c = 1, {}
+            local d, e = f()
+            local changed = 1
+            changed = 'x'
+            local object = {}
+            setmetatable(object, meta)
+            local function g() end
+            return a, b, c, d, e, changed, object, g
+        "), [Some(Number), Some(Table), Some(Nil), None, None, None, None, Some(Function)]);
+    }
+
+    #[test]
+    fn escaped_tables() {
+        use self::BasicType::*;
+
+        assert_eq!(returned("
+            local passed, aliased, receiver, number = {}, {}, {}, 1
+            init(passed, number)
+            local alias = aliased
+            receiver:init()
+            local kept = {}
+            return passed, aliased, receiver, number, alias, kept
+        "), [None, None, None, Some(Number), None, Some(Table)]);
+    }
+
+    #[test]
+    fn standard_library() {
+        use self::BasicType::*;
+
+        assert_eq!(
+            returned("return tostring(x), math.floor(x), math.pi, string.format('%d', 1), s:upper(), unknown(x)"),
+            [Some(String), Some(Number), Some(Number), Some(String), None, None],
+        );
+
+        assert_eq!(returned("local tostring = f return tostring(x)"), [None]);
+    }
+}
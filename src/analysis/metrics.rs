@@ -0,0 +1,282 @@
+//! Measures how big and complicated each function is.
+//!
+//! ```
+//! use mab::analysis::metrics::function_metrics;
+//!
+//! let tokens = mab::tokenize("
+//!     local function clamp(x, low, high)
+//!         if x < low then
+//!             return low
+//!         elseif x > high then
+//!             return high
+//!         end
+//!         return x
+//!     end
+//! ").unwrap();
+//! let chunk = mab::parse_from_tokens(&tokens).unwrap();
+//!
+//! // The chunk comes first, then each function it declares.
+//! let metrics = function_metrics(&chunk);
+//! let clamp = &metrics[1];
+//!
+//! assert_eq!(clamp.name.as_ref().unwrap(), "clamp");
+//! assert_eq!(clamp.cyclomatic_complexity, 3);
+//! assert_eq!(clamp.nesting_depth, 1);
+//! assert_eq!(clamp.parameters, 3);
+//! assert_eq!(clamp.statements, 4);
+//! ```
+//!
+//! Each function is measured on its own, so the functions declared inside it
+//! don't add to its numbers, apart from their declarations counting as one
+//! statement.
+
+use std::fmt;
+
+use ast::*;
+use visit::{self, Visitor};
+
+/// Something [FunctionMetrics][FunctionMetrics] measures, for lints to say
+/// which one is too high.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Metric {
+    CyclomaticComplexity,
+    NestingDepth,
+    Parameters,
+    Statements,
+}
+
+impl fmt::Display for Metric {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            Metric::CyclomaticComplexity => "cyclomatic complexity",
+            Metric::NestingDepth => "nesting depth",
+            Metric::Parameters => "parameter count",
+            Metric::Statements => "statement count",
+        };
+
+        f.write_str(name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionMetrics {
+    /// The function's declaration, or `None` for the chunk.
+    pub statement: Option<NodeId>,
+
+    /// The function's name as it's declared, like `a.b:c`, or `None` for the
+    /// chunk.
+    pub name: Option<String>,
+
+    /// One more than the number of places the function can go more than one
+    /// way: each `if` and `elseif`, loop, `and`, and `or`.
+    pub cyclomatic_complexity: usize,
+
+    /// How many `if` statements and loops deep the most deeply nested
+    /// statement is.
+    pub nesting_depth: usize,
+
+    /// The number of named parameters, not counting `...` or a method's
+    /// implicit `self`.
+    pub parameters: usize,
+
+    /// The number of statements in the function, including nested ones but
+    /// not empty ones.
+    pub statements: usize,
+}
+
+impl FunctionMetrics {
+    pub fn get(&self, metric: Metric) -> usize {
+        match metric {
+            Metric::CyclomaticComplexity => self.cyclomatic_complexity,
+            Metric::NestingDepth => self.nesting_depth,
+            Metric::Parameters => self.parameters,
+            Metric::Statements => self.statements,
+        }
+    }
+}
+
+/// Measures the chunk and every function declared in it, in the order they
+/// appear.
+pub fn function_metrics(chunk: &Chunk) -> Vec<FunctionMetrics> {
+    let mut all = Vec::new();
+    measure(None, chunk, &mut all);
+    all
+}
+
+fn measure(function: Option<&Statement>, body: &Chunk, all: &mut Vec<FunctionMetrics>) {
+    let (name, parameters) = match function.map(|statement| &statement.kind) {
+        Some(StatementKind::FunctionDeclaration(function)) => (Some(function_name(&function.name)), function.parameters.len()),
+        _ => (None, 0),
+    };
+
+    let index = all.len();
+    all.push(FunctionMetrics {
+        statement: function.map(|statement| statement.id),
+        name,
+        cyclomatic_complexity: 1,
+        nesting_depth: 0,
+        parameters,
+        statements: 0,
+    });
+
+    let mut measurer = Measurer {
+        metrics: all[index].clone(),
+        nested_functions: Vec::new(),
+    };
+
+    measurer.block(body, 0);
+    all[index] = measurer.metrics;
+
+    for statement in measurer.nested_functions {
+        if let StatementKind::FunctionDeclaration(ref function) = statement.kind {
+            measure(Some(statement), &function.body, all);
+        }
+    }
+}
+
+fn function_name(name: &FunctionName) -> String {
+    let mut text = name.path.join(".");
+
+    if let Some(ref method) = name.method {
+        text.push(':');
+        text.push_str(method);
+    }
+
+    text
+}
+
+struct Measurer<'s, 'a: 's> {
+    metrics: FunctionMetrics,
+    nested_functions: Vec<&'s Statement<'a>>,
+}
+
+impl<'s, 'a> Measurer<'s, 'a> {
+    fn block(&mut self, chunk: &'s Chunk<'a>, depth: usize) {
+        self.metrics.nesting_depth = self.metrics.nesting_depth.max(depth);
+
+        for statement in &chunk.statements {
+            self.statement(statement, depth);
+        }
+    }
+
+    fn statement(&mut self, statement: &'s Statement<'a>, depth: usize) {
+        if statement.kind == StatementKind::Empty {
+            return;
+        }
+
+        self.metrics.statements += 1;
+
+        match statement.kind {
+            StatementKind::FunctionDeclaration(_) => {
+                self.nested_functions.push(statement);
+                return;
+            },
+            StatementKind::IfStatement(ref if_statement) => {
+                self.decisions(1 + if_statement.else_if_branches.len());
+                self.block(&if_statement.body, depth + 1);
+
+                for (_, body) in &if_statement.else_if_branches {
+                    self.block(body, depth + 1);
+                }
+
+                if let Some(ref body) = if_statement.else_branch {
+                    self.block(body, depth + 1);
+                }
+            },
+            StatementKind::WhileLoop(ref while_loop) => {
+                self.decisions(1);
+                self.block(&while_loop.body, depth + 1);
+            },
+            StatementKind::RepeatLoop(ref repeat_loop) => {
+                self.decisions(1);
+                self.block(&repeat_loop.body, depth + 1);
+            },
+            StatementKind::NumericFor(ref numeric_for) => {
+                self.decisions(1);
+                self.block(&numeric_for.body, depth + 1);
+            },
+            StatementKind::GenericFor(ref generic_for) => {
+                self.decisions(1);
+                self.block(&generic_for.body, depth + 1);
+            },
+            StatementKind::DoBlock(ref do_block) => self.block(&do_block.body, depth),
+            _ => {},
+        }
+
+        let mut logic = LogicCounter::default();
+        logic.visit_statement(statement);
+        self.decisions(logic.count);
+    }
+
+    fn decisions(&mut self, count: usize) {
+        self.metrics.cyclomatic_complexity += count;
+    }
+}
+
+// Counts the `and`s and `or`s in a statement, but not in the statements
+// nested in it.
+#[derive(Default)]
+struct LogicCounter {
+    count: usize,
+}
+
+impl<'a> Visitor<'a> for LogicCounter {
+    // Nested statements are measured on their own.
+    fn visit_chunk(&mut self, _chunk: &'a Chunk<'a>) {}
+
+    fn visit_expression(&mut self, expression: &'a Expression<'a>) {
+        if let ExpressionKind::BinaryOp(BinaryOp { operator: BinaryOpKind::And, .. })
+            | ExpressionKind::BinaryOp(BinaryOp { operator: BinaryOpKind::Or, .. }) = expression.kind
+        {
+            self.count += 1;
+        }
+
+        visit::walk_expression(self, expression);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokenizer::tokenize;
+    use parser::parse_from_tokens;
+
+    fn measured(source: &str) -> Vec<(Option<String>, usize, usize, usize, usize)> {
+        let tokens = tokenize(source).unwrap();
+        let chunk = parse_from_tokens(&tokens).unwrap();
+
+        function_metrics(&chunk).into_iter()
+            .map(|metrics| (metrics.name, metrics.cyclomatic_complexity, metrics.nesting_depth, metrics.parameters, metrics.statements))
+            .collect()
+    }
+
+    #[test]
+    fn chunk_and_functions() {
+        let source = "
+            local x = a and b or c
+            function t.m:run(first, ...)
+                for i = 1, 10 do
+                    while x do
+                        if i > 5 then break end
+                    end
+                end
+
+                local function inner()
+                    repeat x = x - 1 until x < 0
+                end
+            end
+            do print(x) ; end
+        ";
+
+        assert_eq!(measured(source), [
+            (None, 3, 0, 0, 4),
+            (Some("t.m:run".to_string()), 4, 3, 1, 5),
+            (Some("inner".to_string()), 2, 1, 0, 2),
+        ]);
+    }
+
+    #[test]
+    fn empty_chunk() {
+        assert_eq!(measured(""), [(None, 1, 0, 0, 0)]);
+    }
+}
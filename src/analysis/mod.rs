@@ -0,0 +1,10 @@
+//! Analyses that work out what code means, rather than how it's written.
+
+pub mod scope;
+pub mod bindings;
+pub mod cfg;
+pub mod dataflow;
+pub mod inference;
+pub mod metrics;
+pub mod requires;
+pub mod types;
@@ -0,0 +1,393 @@
+//! Finds the modules code loads with `require`, and puts together which
+//! modules in a project need which.
+//!
+//! ```
+//! use std::path::PathBuf;
+//! use mab::analysis::requires::{find_requires, ModuleGraph};
+//!
+//! let tokens = mab::tokenize("
+//!     local json = require('json')
+//!     local util = require 'game.util'
+//! ").unwrap();
+//! let chunk = mab::parse_from_tokens(&tokens).unwrap();
+//!
+//! let modules = find_requires(&chunk).into_iter()
+//!     .map(|require| require.module)
+//!     .collect::<Vec<_>>();
+//! assert_eq!(modules, ["json", "game.util"]);
+//!
+//! let mut graph = ModuleGraph::new();
+//! graph.add_module("game", PathBuf::from("game/init.lua"), modules);
+//! graph.add_module("game.util", PathBuf::from("game/util.lua"), vec!["game".to_string()]);
+//!
+//! assert_eq!(graph.cycles(), [["game", "game.util"]]);
+//! assert!(graph.external().contains("json"));
+//! ```
+//!
+//! Only calls to the global `require` with one string literal count. Module
+//! names work the way Lua's default `package.path` of `?.lua;?/init.lua`
+//! finds them, so `game/util.lua` is `game.util` and `game/init.lua` is
+//! `game`.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use analysis::scope::ScopeTree;
+use ast::*;
+use parser::{parse_from_tokens, ParseError};
+use tokenizer::{tokenize, TokenizeError};
+
+/// A call to `require` with a module name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Require {
+    pub module: String,
+
+    /// The ID of the call, which is a statement or an expression.
+    pub call: NodeId,
+}
+
+/// Finds the calls to `require` in a chunk, in the order they appear.
+pub fn find_requires(chunk: &Chunk) -> Vec<Require> {
+    let scopes = ScopeTree::new(chunk);
+
+    // `require` could be shadowed by a local, in which case the call could
+    // be anything.
+    let global_names = scopes.references().iter()
+        .filter(|reference| reference.declaration.is_none())
+        .map(|reference| reference.node)
+        .collect::<BTreeSet<_>>();
+
+    chunk.descendants()
+        .filter_map(|node| {
+            let (id, call) = match node {
+                NodeRef::Statement(statement) => match statement.kind {
+                    StatementKind::FunctionCall(ref call) => (statement.id, call),
+                    _ => return None,
+                },
+                NodeRef::Expression(expression) => match expression.kind {
+                    ExpressionKind::FunctionCall(ref call) => (expression.id, call),
+                    _ => return None,
+                },
+            };
+
+            let is_require = call.method.is_none()
+                && call.name_expression.kind == ExpressionKind::Name("require".into())
+                && global_names.contains(&call.name_expression.id);
+
+            if !is_require {
+                return None;
+            }
+
+            let literal = match call.arguments {
+                FunctionArguments::String(ref literal) => literal,
+                FunctionArguments::Parenthesized(ref arguments) => match arguments[..] {
+                    [Expression { kind: ExpressionKind::String(ref literal), .. }] => literal,
+                    _ => return None,
+                },
+                _ => return None,
+            };
+
+            Some(Require {
+                module: String::from_utf8_lossy(literal.value()).into_owned(),
+                call: id,
+            })
+        })
+        .collect()
+}
+
+/// The name `require` finds a file by, given its path from the project's
+/// root, or `None` if it isn't a `.lua` file.
+pub fn module_name(path: &Path) -> Option<String> {
+    if path.extension()? != "lua" {
+        return None;
+    }
+
+    let mut names = path.with_extension("").iter()
+        .map(|name| name.to_str().map(|name| name.to_string()))
+        .collect::<Option<Vec<_>>>()?;
+
+    if names.len() > 1 && names.last().map(String::as_str) == Some("init") {
+        names.pop();
+    }
+
+    Some(names.join("."))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Module {
+    pub path: PathBuf,
+
+    /// The modules this one requires, each once, in the order they first
+    /// appear.
+    pub requires: Vec<String>,
+}
+
+/// Which modules require which.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ModuleGraph {
+    modules: BTreeMap<String, Module>,
+}
+
+impl ModuleGraph {
+    pub fn new() -> ModuleGraph {
+        ModuleGraph::default()
+    }
+
+    /// Reads every `.lua` file under `root`, naming each module by its path
+    /// from `root`.
+    #[allow(clippy::result_large_err)]
+    pub fn from_directory(root: &Path) -> Result<ModuleGraph, ModuleGraphError> {
+        let mut graph = ModuleGraph::new();
+        let mut pending = vec![root.to_path_buf()];
+
+        while let Some(directory) = pending.pop() {
+            let entries = fs::read_dir(&directory).map_err(|error| ModuleGraphError::Io {
+                path: directory.clone(),
+                error,
+            })?;
+
+            for entry in entries {
+                let path = entry.map_err(|error| ModuleGraphError::Io { path: directory.clone(), error })?.path();
+
+                if path.is_dir() {
+                    pending.push(path);
+                    continue;
+                }
+
+                let name = match path.strip_prefix(root).ok().and_then(module_name) {
+                    Some(name) => name,
+                    None => continue,
+                };
+
+                let source = fs::read_to_string(&path).map_err(|error| ModuleGraphError::Io { path: path.clone(), error })?;
+                let tokens = tokenize(&source).map_err(|error| ModuleGraphError::Tokenize { path: path.clone(), error })?;
+                let chunk = parse_from_tokens(&tokens).map_err(|error| ModuleGraphError::Parse { path: path.clone(), error })?;
+
+                let requires = find_requires(&chunk).into_iter().map(|require| require.module).collect();
+                graph.add_module(name, path, requires);
+            }
+        }
+
+        Ok(graph)
+    }
+
+    /// Adds a module, replacing any other one with the same name.
+    pub fn add_module<S: Into<String>>(&mut self, name: S, path: PathBuf, requires: Vec<String>) {
+        let mut unique = Vec::new();
+        for module in requires {
+            if !unique.contains(&module) {
+                unique.push(module);
+            }
+        }
+
+        self.modules.insert(name.into(), Module {
+            path,
+            requires: unique,
+        });
+    }
+
+    pub fn module(&self, name: &str) -> Option<&Module> {
+        self.modules.get(name)
+    }
+
+    /// Every module, in order of their names.
+    pub fn modules(&self) -> impl Iterator<Item = (&str, &Module)> {
+        self.modules.iter().map(|(name, module)| (name.as_str(), module))
+    }
+
+    /// The modules that are required but aren't in the graph, like libraries
+    /// from outside the project.
+    pub fn external(&self) -> BTreeSet<&str> {
+        self.modules.values()
+            .flat_map(|module| &module.requires)
+            .filter(|name| !self.modules.contains_key(*name))
+            .map(String::as_str)
+            .collect()
+    }
+
+    /// Groups of modules that require each other, directly or through other
+    /// modules, each in order of their names. A module that requires itself
+    /// is a group of one.
+    pub fn cycles(&self) -> Vec<Vec<&str>> {
+        let mut finder = CycleFinder {
+            graph: self,
+            next_index: 0,
+            indexes: HashMap::new(),
+            lowest: HashMap::new(),
+            stack: Vec::new(),
+            cycles: Vec::new(),
+        };
+
+        for name in self.modules.keys() {
+            if !finder.indexes.contains_key(name.as_str()) {
+                finder.visit(name);
+            }
+        }
+
+        let mut cycles = finder.cycles;
+        for cycle in &mut cycles {
+            cycle.sort();
+        }
+
+        cycles.sort();
+        cycles
+    }
+}
+
+// Tarjan's algorithm for strongly connected components.
+struct CycleFinder<'g> {
+    graph: &'g ModuleGraph,
+    next_index: usize,
+    indexes: HashMap<&'g str, usize>,
+    lowest: HashMap<&'g str, usize>,
+    stack: Vec<&'g str>,
+    cycles: Vec<Vec<&'g str>>,
+}
+
+impl<'g> CycleFinder<'g> {
+    fn visit(&mut self, name: &'g str) {
+        let index = self.next_index;
+        self.next_index += 1;
+        self.indexes.insert(name, index);
+        self.lowest.insert(name, index);
+        self.stack.push(name);
+
+        let graph = self.graph;
+        let requires = &graph.modules[name].requires;
+        for required in requires.iter().filter(|required| graph.modules.contains_key(*required)) {
+            let required = required.as_str();
+
+            if !self.indexes.contains_key(required) {
+                self.visit(required);
+                let lowest = self.lowest[name].min(self.lowest[required]);
+                self.lowest.insert(name, lowest);
+            } else if self.stack.contains(&required) {
+                let lowest = self.lowest[name].min(self.indexes[required]);
+                self.lowest.insert(name, lowest);
+            }
+        }
+
+        if self.lowest[name] != index {
+            return;
+        }
+
+        let start = self.stack.iter().rposition(|&member| member == name).unwrap();
+        let component = self.stack.split_off(start);
+
+        if component.len() > 1 || requires.iter().any(|required| required == name) {
+            self.cycles.push(component);
+        }
+    }
+}
+
+/// Why a [ModuleGraph][ModuleGraph] couldn't be read from a directory.
+#[derive(Debug)]
+pub enum ModuleGraphError {
+    Io {
+        path: PathBuf,
+        error: io::Error,
+    },
+
+    Tokenize {
+        path: PathBuf,
+        error: TokenizeError,
+    },
+
+    Parse {
+        path: PathBuf,
+        error: ParseError,
+    },
+}
+
+impl fmt::Display for ModuleGraphError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ModuleGraphError::Io { ref path, ref error } => write!(f, "couldn't read {}: {}", path.display(), error),
+            ModuleGraphError::Tokenize { ref path, ref error } => write!(f, "{} didn't tokenize: {}", path.display(), error),
+            ModuleGraphError::Parse { ref path, ref error } => write!(f, "{} didn't parse: {}", path.display(), error),
+        }
+    }
+}
+
+impl Error for ModuleGraphError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn requires(source: &str) -> Vec<String> {
+        let tokens = tokenize(source).unwrap();
+        let chunk = parse_from_tokens(&tokens).unwrap();
+        find_requires(&chunk).into_iter().map(|require| require.module).collect()
+    }
+
+    #[test]
+    fn finding_requires() {
+        assert_eq!(
+            requires("require 'a' local b = require(\"b\") f(require [[c]]) require(d) require('e', 1) x.require('f')"),
+            ["a", "b", "c"],
+        );
+
+        assert!(requires("local require = load require 'a'").is_empty());
+    }
+
+    #[test]
+    fn module_names() {
+        let name = |path: &str| module_name(Path::new(path));
+
+        assert_eq!(name("main.lua").unwrap(), "main");
+        assert_eq!(name("game/util.lua").unwrap(), "game.util");
+        assert_eq!(name("game/init.lua").unwrap(), "game");
+        assert_eq!(name("init.lua").unwrap(), "init");
+        assert_eq!(name("README.md"), None);
+    }
+
+    #[test]
+    fn cycles() {
+        let mut graph = ModuleGraph::new();
+        let mut add = |name: &str, requires: &[&str]| {
+            let requires = requires.iter().map(|name| name.to_string()).collect();
+            graph.add_module(name, PathBuf::from(name), requires);
+        };
+
+        add("a", &["b", "socket"]);
+        add("b", &["c", "d"]);
+        add("c", &["a"]);
+        add("d", &["d", "e"]);
+        add("e", &[]);
+
+        assert_eq!(graph.cycles(), [vec!["a", "b", "c"], vec!["d"]]);
+        assert_eq!(graph.external().into_iter().collect::<Vec<_>>(), ["socket"]);
+    }
+
+    #[test]
+    fn from_directory() {
+        let root = ::std::env::temp_dir().join(format!("mab-requires-test-{}", ::std::process::id()));
+        fs::create_dir_all(root.join("game")).unwrap();
+
+        fs::write(root.join("main.lua"), "local game = require('game') game.run()").unwrap();
+        fs::write(root.join("game").join("init.lua"), "return { util = require 'game.util' }").unwrap();
+        fs::write(root.join("game").join("util.lua"), "return {}").unwrap();
+        fs::write(root.join("notes.txt"), "not lua").unwrap();
+
+        let graph = ModuleGraph::from_directory(&root);
+        let _ = fs::remove_dir_all(&root);
+        let graph = graph.unwrap();
+
+        let modules = graph.modules()
+            .map(|(name, module)| (name, module.requires.clone()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(modules, [
+            ("game", vec!["game.util".to_string()]),
+            ("game.util", Vec::new()),
+            ("main", vec!["game".to_string()]),
+        ]);
+
+        assert!(graph.cycles().is_empty());
+    }
+}
@@ -0,0 +1,471 @@
+//! Works out which names refer to which locals.
+//!
+//! ```
+//! use mab::analysis::scope::ScopeTree;
+//!
+//! let tokens = mab::tokenize("
+//!     local x = 1
+//!     local function f(y)
+//!         return x + y + z
+//!     end
+//! ").unwrap();
+//! let chunk = mab::parse_from_tokens(&tokens).unwrap();
+//! let scopes = ScopeTree::new(&chunk);
+//!
+//! let names = scopes.declarations().iter().map(|declaration| declaration.name).collect::<Vec<_>>();
+//! assert_eq!(names, ["x", "f", "y"]);
+//!
+//! let globals = scopes.references().iter()
+//!     .filter(|reference| reference.declaration.is_none())
+//!     .map(|reference| reference.name)
+//!     .collect::<Vec<_>>();
+//! assert_eq!(globals, ["z"]);
+//! ```
+//!
+//! Locals are visible from the statement after the one that declares them to
+//! the end of their block, which means the values in `local x = x` see the
+//! `x` from outside. A `local function` can see itself, a `repeat` loop's
+//! condition can see the locals in its body, and a `for` loop's variables are
+//! only visible inside it.
+
+use ast::*;
+use visit::{self, Visitor};
+
+/// Identifies a [Scope][Scope] in a [ScopeTree][ScopeTree].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ScopeId(usize);
+
+/// Identifies a [Declaration][Declaration] in a [ScopeTree][ScopeTree].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DeclarationId(usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ScopeKind {
+    /// The whole chunk.
+    Chunk,
+
+    /// A function's parameters and body.
+    Function,
+
+    /// A `for` loop's variables and body.
+    Loop,
+
+    /// Any other block, like a `do` block, the body of a `while` or `repeat`
+    /// loop, or one branch of an `if`.
+    Block,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Scope {
+    pub kind: ScopeKind,
+
+    /// The statement the scope belongs to. Every branch of an `if` has the
+    /// `if` statement. The chunk's scope doesn't have one.
+    pub statement: Option<NodeId>,
+
+    pub parent: Option<ScopeId>,
+    pub children: Vec<ScopeId>,
+
+    /// The locals declared directly in this scope, in order.
+    pub declarations: Vec<DeclarationId>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DeclarationKind {
+    Local,
+    LocalFunction,
+    Parameter,
+
+    /// The `self` that methods declared with `:` get.
+    ImplicitSelf,
+
+    LoopVariable,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Declaration<'a> {
+    pub name: &'a str,
+    pub kind: DeclarationKind,
+
+    /// The statement that declares the local: the `local` statement, the
+    /// function for parameters, or the loop for loop variables.
+    pub statement: NodeId,
+
+    /// Which of the names the statement declares this is, counting from 0.
+    /// A `local function`'s name and an implicit `self` come before the
+    /// parameters.
+    pub position: usize,
+
+    pub scope: ScopeId,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ReferenceKind {
+    Read,
+    Write,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reference<'a> {
+    pub name: &'a str,
+    pub kind: ReferenceKind,
+
+    /// The [Name][ExpressionKind::Name] expression, or for the first name in
+    /// a `function a.b()` declaration, the declaration.
+    pub node: NodeId,
+
+    pub scope: ScopeId,
+
+    /// The local the name refers to, or `None` for a global.
+    pub declaration: Option<DeclarationId>,
+}
+
+/// The scopes in a chunk, along with every local declared in them and every
+/// name that's used.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScopeTree<'a> {
+    scopes: Vec<Scope>,
+    declarations: Vec<Declaration<'a>>,
+    references: Vec<Reference<'a>>,
+}
+
+impl<'a> ScopeTree<'a> {
+    pub fn new(chunk: &'a Chunk<'a>) -> ScopeTree<'a> {
+        let mut builder = Builder {
+            tree: ScopeTree {
+                scopes: Vec::new(),
+                declarations: Vec::new(),
+                references: Vec::new(),
+            },
+            current: ScopeId(0),
+        };
+
+        builder.enter(ScopeKind::Chunk, None);
+        visit::walk_chunk(&mut builder, chunk);
+        builder.tree
+    }
+
+    /// The chunk's scope, which all the others are inside.
+    pub fn root(&self) -> ScopeId {
+        ScopeId(0)
+    }
+
+    pub fn scope(&self, id: ScopeId) -> &Scope {
+        &self.scopes[id.0]
+    }
+
+    pub fn declaration(&self, id: DeclarationId) -> &Declaration<'a> {
+        &self.declarations[id.0]
+    }
+
+    /// Every local, in the order they're declared.
+    pub fn declarations(&self) -> &[Declaration<'a>] {
+        &self.declarations
+    }
+
+    /// The IDs of every local, in the order they're declared.
+    pub fn declaration_ids(&self) -> impl Iterator<Item = DeclarationId> {
+        (0..self.declarations.len()).map(DeclarationId)
+    }
+
+    /// Every use of a name, in the order they appear.
+    pub fn references(&self) -> &[Reference<'a>] {
+        &self.references
+    }
+
+    /// The uses of one local.
+    pub fn references_to(&self, id: DeclarationId) -> impl Iterator<Item = &Reference<'a>> {
+        self.references.iter().filter(move |reference| reference.declaration == Some(id))
+    }
+
+    /// The local a name would refer to if it was used at the end of `scope`,
+    /// or `None` if it would be a global.
+    pub fn lookup(&self, scope: ScopeId, name: &str) -> Option<DeclarationId> {
+        let mut scope = Some(scope);
+
+        while let Some(id) = scope {
+            let scope_data = self.scope(id);
+            let found = scope_data.declarations.iter()
+                .rev()
+                .find(|&&declaration| self.declaration(declaration).name == name);
+
+            if let Some(&declaration) = found {
+                return Some(declaration);
+            }
+
+            scope = scope_data.parent;
+        }
+
+        None
+    }
+}
+
+struct Builder<'a> {
+    tree: ScopeTree<'a>,
+    current: ScopeId,
+}
+
+impl<'a> Builder<'a> {
+    fn enter(&mut self, kind: ScopeKind, statement: Option<NodeId>) {
+        let id = ScopeId(self.tree.scopes.len());
+        let parent = if self.tree.scopes.is_empty() { None } else { Some(self.current) };
+
+        if let Some(parent) = parent {
+            self.tree.scopes[parent.0].children.push(id);
+        }
+
+        self.tree.scopes.push(Scope {
+            kind,
+            statement,
+            parent,
+            children: Vec::new(),
+            declarations: Vec::new(),
+        });
+
+        self.current = id;
+    }
+
+    fn exit(&mut self) {
+        self.current = self.tree.scope(self.current).parent.expect("the chunk's scope is never exited");
+    }
+
+    fn block(&mut self, statement: NodeId, body: &'a Chunk<'a>) {
+        self.enter(ScopeKind::Block, Some(statement));
+        visit::walk_chunk(self, body);
+        self.exit();
+    }
+
+    fn declare(&mut self, name: &'a str, kind: DeclarationKind, statement: NodeId, position: usize) {
+        let id = DeclarationId(self.tree.declarations.len());
+
+        self.tree.declarations.push(Declaration {
+            name,
+            kind,
+            statement,
+            position,
+            scope: self.current,
+        });
+
+        self.tree.scopes[self.current.0].declarations.push(id);
+    }
+
+    fn reference(&mut self, name: &'a str, kind: ReferenceKind, node: NodeId) {
+        let declaration = self.tree.lookup(self.current, name);
+
+        self.tree.references.push(Reference {
+            name,
+            kind,
+            node,
+            scope: self.current,
+            declaration,
+        });
+    }
+}
+
+impl<'a> Visitor<'a> for Builder<'a> {
+    fn visit_statement(&mut self, statement: &'a Statement<'a>) {
+        let id = statement.id;
+
+        match statement.kind {
+            StatementKind::Assignment(ref assignment) => {
+                for target in &assignment.targets {
+                    match target.kind {
+                        ExpressionKind::Name(ref name) => self.reference(name, ReferenceKind::Write, target.id),
+                        _ => self.visit_expression(target),
+                    }
+                }
+
+                for value in &assignment.values {
+                    self.visit_expression(value);
+                }
+            },
+            StatementKind::LocalAssignment(ref local) => {
+                visit::walk_local_assignment(self, local);
+
+                for (position, name) in local.names.iter().enumerate() {
+                    self.declare(name, DeclarationKind::Local, id, position);
+                }
+            },
+            StatementKind::NumericFor(ref numeric_for) => {
+                self.visit_expression(&numeric_for.start);
+                self.visit_expression(&numeric_for.end);
+
+                if let Some(ref step) = numeric_for.step {
+                    self.visit_expression(step);
+                }
+
+                self.enter(ScopeKind::Loop, Some(id));
+                self.declare(&numeric_for.var, DeclarationKind::LoopVariable, id, 0);
+                visit::walk_chunk(self, &numeric_for.body);
+                self.exit();
+            },
+            StatementKind::GenericFor(ref generic_for) => {
+                for item_source in &generic_for.item_source {
+                    self.visit_expression(item_source);
+                }
+
+                self.enter(ScopeKind::Loop, Some(id));
+                for (position, var) in generic_for.vars.iter().enumerate() {
+                    self.declare(var, DeclarationKind::LoopVariable, id, position);
+                }
+
+                visit::walk_chunk(self, &generic_for.body);
+                self.exit();
+            },
+            StatementKind::IfStatement(ref if_statement) => {
+                self.visit_expression(&if_statement.condition);
+                self.block(id, &if_statement.body);
+
+                for (condition, body) in &if_statement.else_if_branches {
+                    self.visit_expression(condition);
+                    self.block(id, body);
+                }
+
+                if let Some(ref else_branch) = if_statement.else_branch {
+                    self.block(id, else_branch);
+                }
+            },
+            StatementKind::DoBlock(ref do_block) => self.block(id, &do_block.body),
+            StatementKind::WhileLoop(ref while_loop) => {
+                self.visit_expression(&while_loop.condition);
+                self.block(id, &while_loop.body);
+            },
+            StatementKind::RepeatLoop(ref repeat_loop) => {
+                self.enter(ScopeKind::Block, Some(id));
+                visit::walk_repeat_loop(self, repeat_loop);
+                self.exit();
+            },
+            StatementKind::FunctionDeclaration(ref declaration) => {
+                let name = &declaration.name;
+
+                if declaration.local {
+                    self.declare(&name.path[0], DeclarationKind::LocalFunction, id, 0);
+                } else {
+                    // `function f()` assigns to `f`, but `function a.f()`
+                    // only reads `a`.
+                    let kind = if name.path.len() == 1 && name.method.is_none() {
+                        ReferenceKind::Write
+                    } else {
+                        ReferenceKind::Read
+                    };
+
+                    self.reference(&name.path[0], kind, id);
+                }
+
+                self.enter(ScopeKind::Function, Some(id));
+
+                // A local function's name is its first name, so its
+                // parameters come after it.
+                let mut position = if declaration.local { 1 } else { 0 };
+                if declaration.is_method {
+                    self.declare("self", DeclarationKind::ImplicitSelf, id, position);
+                    position += 1;
+                }
+
+                for parameter in &declaration.parameters {
+                    self.declare(parameter, DeclarationKind::Parameter, id, position);
+                    position += 1;
+                }
+
+                visit::walk_chunk(self, &declaration.body);
+                self.exit();
+            },
+            _ => visit::walk_statement(self, statement),
+        }
+    }
+
+    fn visit_expression(&mut self, expression: &'a Expression<'a>) {
+        match expression.kind {
+            ExpressionKind::Name(ref name) => self.reference(name, ReferenceKind::Read, expression.id),
+            _ => visit::walk_expression(self, expression),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokenizer::tokenize;
+    use parser::parse_from_tokens;
+
+    // Each reference's name, with the kind and statement position of the
+    // local it refers to, or "global".
+    fn resolved(source: &str) -> Vec<String> {
+        let tokens = tokenize(source).unwrap();
+        let chunk = parse_from_tokens(&tokens).unwrap();
+        let scopes = ScopeTree::new(&chunk);
+
+        scopes.references().iter()
+            .map(|reference| match reference.declaration {
+                Some(id) => {
+                    let declaration = scopes.declaration(id);
+                    format!("{} {:?} {}", reference.name, declaration.kind, declaration.position)
+                },
+                None => format!("{} global", reference.name),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn visibility() {
+        assert_eq!(resolved("
+            local x = x
+            local a, b = 1, 2
+            do local x = b end
+            print(x)
+        "), ["x global", "b Local 1", "print global", "x Local 0"]);
+    }
+
+    #[test]
+    fn loops() {
+        assert_eq!(resolved("
+            local i = 0
+            for i = i, 10 do print(i) end
+            for k, v in pairs(t) do print(k, v) end
+            print(i, k)
+            repeat local done = true until done
+            while done do end
+        "), [
+            "i Local 0", "print global", "i LoopVariable 0",
+            "pairs global", "t global", "print global", "k LoopVariable 0", "v LoopVariable 1",
+            "print global", "i Local 0", "k global",
+            "done Local 0",
+            "done global",
+        ]);
+    }
+
+    #[test]
+    fn functions() {
+        assert_eq!(resolved("
+            local function f(n) return f(n) end
+            function g() return g end
+            function t.m:method(x) return self, x, method end
+        "), [
+            "f LocalFunction 0", "n Parameter 1",
+            "g global", "g global",
+            "t global", "self ImplicitSelf 0", "x Parameter 1", "method global",
+        ]);
+    }
+
+    #[test]
+    fn tree() {
+        let tokens = tokenize("local a if a then local b elseif a then else local c end function f(p) end").unwrap();
+        let chunk = parse_from_tokens(&tokens).unwrap();
+        let scopes = ScopeTree::new(&chunk);
+
+        let root = scopes.scope(scopes.root());
+        assert_eq!(root.kind, ScopeKind::Chunk);
+        assert_eq!(root.children.len(), 4);
+
+        let kinds = root.children.iter().map(|&child| scopes.scope(child).kind).collect::<Vec<_>>();
+        assert_eq!(kinds, [ScopeKind::Block, ScopeKind::Block, ScopeKind::Block, ScopeKind::Function]);
+
+        let function = scopes.scope(root.children[3]);
+        assert_eq!(function.statement, Some(chunk.statements[2].id));
+        assert_eq!(scopes.lookup(root.children[3], "p").map(|id| scopes.declaration(id).name), Some("p"));
+        assert_eq!(scopes.lookup(scopes.root(), "p"), None);
+
+        let kinds = scopes.references().iter().map(|reference| reference.kind).collect::<Vec<_>>();
+        assert_eq!(kinds, [ReferenceKind::Read, ReferenceKind::Read, ReferenceKind::Write]);
+    }
+}
@@ -0,0 +1,513 @@
+//! Checks that code agrees with its Luau type annotations, as parsed by
+//! [parse_from_tokens_with_types][::parser::parse_from_tokens_with_types].
+//!
+//! ```
+//! use mab::analysis::types::check_types;
+//! use mab::tokenizer::{Dialect, Tokenizer};
+//!
+//! let tokens = Tokenizer::new("
+//!     local function greet(name: string, times: number?)
+//!     end
+//!
+//!     local count: number = 'three'
+//!     greet(count)
+//! ").dialect(Dialect::Luau).collect::<Result<Vec<_>, _>>().unwrap();
+//! let chunk = mab::parser::parse_from_tokens_with_types(&tokens).unwrap();
+//!
+//! let messages = check_types(&chunk).iter()
+//!     .map(|error| error.kind.to_string())
+//!     .collect::<Vec<_>>();
+//!
+//! assert_eq!(messages, [
+//!     "expected `number`, found `string`",
+//!     "expected `string`, found `number`",
+//! ]);
+//! ```
+//!
+//! This is a long way from Luau's own checker. Only `nil`, `boolean`,
+//! `number`, `string`, `table`, and `function` are known types, along with
+//! `?`, unions, `{T}` for tables, and function types for functions. Any other
+//! name, like `any` or a type from a module, matches everything.
+//!
+//! The type of an expression is only known when it's a literal, a local with
+//! an annotation, the result of an operator, or a call to a local function
+//! with annotated returns. Operators are assumed to work the way they do
+//! without metatables, so `a + b` is always a number. Calls are only checked
+//! when they're to a local function with annotations.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use analysis::scope::{DeclarationId, DeclarationKind, ScopeTree};
+use ast::*;
+use index::NodeIndex;
+use visit::{self, Visitor};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TypeErrorKind {
+    /// A value doesn't match the annotation of the local, parameter, or
+    /// return value it's going to.
+    Mismatch {
+        /// The annotation, as it's written.
+        expected: String,
+        found: String,
+    },
+
+    /// A call to an annotated function with too many arguments, or without
+    /// some that can't be `nil`.
+    WrongArgumentCount {
+        function: String,
+
+        /// How many arguments the call would need, which is the number of
+        /// parameters when there are too many arguments, or the number up to
+        /// the last one that can't be `nil` when there are too few.
+        expected: usize,
+        found: usize,
+    },
+}
+
+impl fmt::Display for TypeErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TypeErrorKind::Mismatch { ref expected, ref found } => write!(f, "expected `{}`, found `{}`", expected, found),
+            TypeErrorKind::WrongArgumentCount { ref function, expected, found } => {
+                write!(f, "`{}` needs {} argument{}, but was given {}", function, expected, if expected == 1 { "" } else { "s" }, found)
+            },
+        }
+    }
+}
+
+/// A place where code disagrees with its annotations, and the ID of the
+/// expression that's wrong, or the call for the wrong number of arguments.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeError {
+    pub kind: TypeErrorKind,
+    pub id: NodeId,
+}
+
+impl fmt::Display for TypeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} (node {})", self.kind, self.id)
+    }
+}
+
+/// Checks the values of annotated locals, the arguments of calls to annotated
+/// local functions, and the values annotated functions return. Errors are
+/// listed in the order the checker finds them, which is roughly the order the
+/// code is in.
+pub fn check_types(chunk: &Chunk) -> Vec<TypeError> {
+    let scopes = ScopeTree::new(chunk);
+
+    let mut checker = Checker {
+        index: NodeIndex::new(chunk),
+        declarations: scopes.references().iter()
+            .filter_map(|reference| Some((reference.node, reference.declaration?)))
+            .collect(),
+        scopes: &scopes,
+        returns: Vec::new(),
+        errors: Vec::new(),
+    };
+
+    checker.visit_chunk(chunk);
+    checker.errors
+}
+
+// The types the checker knows about. `Any` is anything it doesn't.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Type {
+    Any,
+    Nil,
+    Boolean,
+    Number,
+    String,
+    Table,
+    Function,
+    Union(Vec<Type>),
+}
+
+impl Type {
+    fn from_annotation(annotation: &TypeAnnotation) -> Type {
+        match *annotation {
+            TypeAnnotation::Nil => Type::Nil,
+            TypeAnnotation::Named(ref path) => match path[..] {
+                [ref name] => match name.as_ref() {
+                    "boolean" => Type::Boolean,
+                    "number" => Type::Number,
+                    "string" => Type::String,
+                    "table" => Type::Table,
+                    "function" => Type::Function,
+                    _ => Type::Any,
+                },
+                _ => Type::Any,
+            },
+            TypeAnnotation::Optional(ref inner) => Type::Union(vec![Type::from_annotation(inner), Type::Nil]),
+            TypeAnnotation::Union(ref types) => Type::Union(types.iter().map(Type::from_annotation).collect()),
+            TypeAnnotation::Array(_) => Type::Table,
+            TypeAnnotation::Function { .. } => Type::Function,
+        }
+    }
+
+    // Whether every value of type `found` is also of this type.
+    fn accepts(&self, found: &Type) -> bool {
+        match (self, found) {
+            (Type::Any, _) | (_, Type::Any) => true,
+            (_, Type::Union(types)) => types.iter().all(|found| self.accepts(found)),
+            (Type::Union(types), _) => types.iter().any(|expected| expected.accepts(found)),
+            _ => self == found,
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Type::Any => f.write_str("any"),
+            Type::Nil => f.write_str("nil"),
+            Type::Boolean => f.write_str("boolean"),
+            Type::Number => f.write_str("number"),
+            Type::String => f.write_str("string"),
+            Type::Table => f.write_str("table"),
+            Type::Function => f.write_str("function"),
+            Type::Union(ref types) => {
+                let names = types.iter().map(|member| member.to_string()).collect::<Vec<_>>();
+                f.write_str(&names.join(" | "))
+            },
+        }
+    }
+}
+
+struct Checker<'s, 'a: 's> {
+    index: NodeIndex<'a>,
+    scopes: &'s ScopeTree<'a>,
+
+    /// The local each name that refers to one refers to, by the name's ID.
+    declarations: HashMap<NodeId, DeclarationId>,
+
+    /// The annotated return types of the functions we're inside of,
+    /// innermost last.
+    returns: Vec<Option<&'a [TypeAnnotation<'a>]>>,
+
+    errors: Vec<TypeError>,
+}
+
+impl<'s, 'a> Checker<'s, 'a> {
+    fn check(&mut self, annotation: &TypeAnnotation, value: &Expression) {
+        let found = match self.type_of(value) {
+            Type::Any => return,
+            found => found,
+        };
+
+        if !Type::from_annotation(annotation).accepts(&found) {
+            self.errors.push(TypeError {
+                kind: TypeErrorKind::Mismatch {
+                    expected: annotation.to_string(),
+                    found: found.to_string(),
+                },
+                id: value.id,
+            });
+        }
+    }
+
+    fn type_of(&self, expression: &Expression) -> Type {
+        match expression.kind {
+            ExpressionKind::Nil => Type::Nil,
+            ExpressionKind::Bool(_) => Type::Boolean,
+            ExpressionKind::Number(_) => Type::Number,
+            ExpressionKind::String(_) => Type::String,
+            ExpressionKind::Table(_) => Type::Table,
+            ExpressionKind::ParenExpression(ref inner) => self.type_of(inner),
+            ExpressionKind::Name(_) => self.annotation_of(expression.id).map_or(Type::Any, Type::from_annotation),
+            ExpressionKind::UnaryOp(ref unary_op) => match unary_op.operator {
+                UnaryOpKind::BooleanNot => Type::Boolean,
+                UnaryOpKind::Negate | UnaryOpKind::Length => Type::Number,
+            },
+            ExpressionKind::BinaryOp(ref binary_op) => match binary_op.operator {
+                BinaryOpKind::And | BinaryOpKind::Or => Type::Any,
+                BinaryOpKind::Concat => Type::String,
+                BinaryOpKind::LessThan | BinaryOpKind::LessThanEqual | BinaryOpKind::GreaterThan |
+                BinaryOpKind::GreaterThanEqual | BinaryOpKind::Equal | BinaryOpKind::NotEqual => Type::Boolean,
+                _ => Type::Number,
+            },
+            ExpressionKind::FunctionCall(ref call) => {
+                let returns = self.called_function(call).and_then(|function| function.return_types.as_ref());
+
+                match returns.and_then(|returns| returns.first()) {
+                    Some(annotation) => Type::from_annotation(annotation),
+                    None => Type::Any,
+                }
+            },
+            _ => Type::Any,
+        }
+    }
+
+    // The annotation on the local or parameter a name refers to.
+    fn annotation_of(&self, name: NodeId) -> Option<&'a TypeAnnotation<'a>> {
+        let declaration = self.scopes.declaration(*self.declarations.get(&name)?);
+
+        let statement = match self.index.get(declaration.statement)? {
+            NodeRef::Statement(statement) => statement,
+            NodeRef::Expression(_) => return None,
+        };
+
+        match (declaration.kind, &statement.kind) {
+            (DeclarationKind::Local, StatementKind::LocalAssignment(local)) => local.types.get(declaration.position)?.as_ref(),
+            (DeclarationKind::Parameter, StatementKind::FunctionDeclaration(function)) => {
+                // Local functions' names and methods' `self` come first.
+                let position = declaration.position - function.local as usize - function.is_method as usize;
+                function.parameter_types.get(position)?.as_ref()
+            },
+            _ => None,
+        }
+    }
+
+    // The local function a call is to, if it's to one.
+    fn called_function(&self, call: &FunctionCall) -> Option<&'a FunctionDeclaration<'a>> {
+        if call.method.is_some() {
+            return None;
+        }
+
+        let declaration = self.scopes.declaration(*self.declarations.get(&call.name_expression.id)?);
+        if declaration.kind != DeclarationKind::LocalFunction {
+            return None;
+        }
+
+        match self.index.get(declaration.statement)? {
+            NodeRef::Statement(Statement { kind: StatementKind::FunctionDeclaration(function), .. }) => Some(function),
+            _ => None,
+        }
+    }
+
+    fn check_call(&mut self, id: NodeId, call: &'a FunctionCall<'a>) {
+        let function = match self.called_function(call) {
+            Some(function) if !function.parameter_types.is_empty() => function,
+            _ => return,
+        };
+
+        let arguments = match call.arguments {
+            FunctionArguments::Parenthesized(ref arguments) => &arguments[..],
+            FunctionArguments::String(_) | FunctionArguments::Table(_) => {
+                // These are always exactly one argument.
+                if let Some(Some(annotation)) = function.parameter_types.first() {
+                    let found = match call.arguments {
+                        FunctionArguments::String(_) => Type::String,
+                        _ => Type::Table,
+                    };
+
+                    if !Type::from_annotation(annotation).accepts(&found) {
+                        self.errors.push(TypeError {
+                            kind: TypeErrorKind::Mismatch {
+                                expected: annotation.to_string(),
+                                found: found.to_string(),
+                            },
+                            id,
+                        });
+                    }
+                }
+
+                &[]
+            },
+        };
+
+        for (annotation, argument) in function.parameter_types.iter().zip(arguments) {
+            if let Some(ref annotation) = *annotation {
+                self.check(annotation, argument);
+            }
+        }
+
+        if let FunctionArguments::Parenthesized(_) = call.arguments {
+            self.check_argument_count(id, call, function, arguments);
+        }
+    }
+
+    fn check_argument_count(&mut self, id: NodeId, call: &FunctionCall, function: &FunctionDeclaration, arguments: &[Expression]) {
+        // A call or `...` at the end can stand for any number of values.
+        let open_ended = matches!(arguments.last().map(|argument| &argument.kind),
+            Some(ExpressionKind::FunctionCall(_)) | Some(ExpressionKind::VarArg));
+
+        let required = function.parameter_types.iter()
+            .rposition(|annotation| match *annotation {
+                Some(ref annotation) => !Type::from_annotation(annotation).accepts(&Type::Nil),
+                None => false,
+            })
+            .map_or(0, |position| position + 1);
+
+        let expected = if arguments.len() > function.parameters.len() && !function.is_variadic {
+            function.parameters.len()
+        } else if arguments.len() < required && !open_ended {
+            required
+        } else {
+            return;
+        };
+
+        self.errors.push(TypeError {
+            kind: TypeErrorKind::WrongArgumentCount {
+                function: match call.name_expression.kind {
+                    ExpressionKind::Name(ref name) => name.to_string(),
+                    _ => String::new(),
+                },
+                expected,
+                found: arguments.len(),
+            },
+            id,
+        });
+    }
+}
+
+impl<'s, 'a> Visitor<'a> for Checker<'s, 'a> {
+    fn visit_statement(&mut self, statement: &'a Statement<'a>) {
+        if let StatementKind::FunctionCall(ref call) = statement.kind {
+            self.check_call(statement.id, call);
+        }
+
+        visit::walk_statement(self, statement);
+    }
+
+    fn visit_expression(&mut self, expression: &'a Expression<'a>) {
+        if let ExpressionKind::FunctionCall(ref call) = expression.kind {
+            self.check_call(expression.id, call);
+        }
+
+        visit::walk_expression(self, expression);
+    }
+
+    fn visit_local_assignment(&mut self, local_assignment: &'a LocalAssignment<'a>) {
+        for (annotation, value) in local_assignment.types.iter().zip(&local_assignment.values) {
+            if let Some(ref annotation) = *annotation {
+                self.check(annotation, value);
+            }
+        }
+
+        visit::walk_local_assignment(self, local_assignment);
+    }
+
+    fn visit_assignment(&mut self, assignment: &'a Assignment<'a>) {
+        for (target, value) in assignment.targets.iter().zip(&assignment.values) {
+            if let Some(annotation) = self.annotation_of(target.id) {
+                self.check(annotation, value);
+            }
+        }
+
+        visit::walk_assignment(self, assignment);
+    }
+
+    fn visit_function_declaration(&mut self, function_declaration: &'a FunctionDeclaration<'a>) {
+        self.returns.push(function_declaration.return_types.as_deref());
+        visit::walk_function_declaration(self, function_declaration);
+        self.returns.pop();
+    }
+
+    fn visit_return(&mut self, return_statement: &'a Return<'a>) {
+        if let Some(&Some(returns)) = self.returns.last() {
+            for (annotation, value) in returns.iter().zip(&return_statement.values) {
+                self.check(annotation, value);
+            }
+        }
+
+        visit::walk_return(self, return_statement);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parser::parse_from_tokens_with_types;
+    use tokenizer::tokenize_luau;
+
+    fn errors(source: &str) -> Vec<String> {
+        let tokens = tokenize_luau(source);
+        let chunk = parse_from_tokens_with_types(&tokens).unwrap();
+
+        check_types(&chunk).iter().map(|error| error.kind.to_string()).collect()
+    }
+
+    #[test]
+    fn locals() {
+        assert_eq!(errors("
+            local a: number, b: string?, c: boolean | string = 1, nil, 'c'
+            local d: number = a + 1
+            local e: {number} = {}
+            local f: any = 'f'
+            local g: Vector3 = 1
+        "), Vec::<String>::new());
+
+        assert_eq!(errors("
+            local a: number = 'a'
+            local b: string = a
+            local c: string? = true
+            local d: number = 'd' .. 1
+            local e: string = #'e'
+            local f: number = (nil)
+        "), [
+            "expected `number`, found `string`",
+            "expected `string`, found `number`",
+            "expected `string?`, found `boolean`",
+            "expected `number`, found `string`",
+            "expected `string`, found `number`",
+            "expected `number`, found `nil`",
+        ]);
+    }
+
+    #[test]
+    fn assignments() {
+        assert_eq!(errors("
+            local a: number? = 1
+            local b: string = 'b'
+            a = nil
+            b = a
+            a, b = 'a', 'b'
+            local a = 'shadowed'
+            a = true
+        "), [
+            "expected `string`, found `number | nil`",
+            "expected `number?`, found `string`",
+        ]);
+    }
+
+    #[test]
+    fn calls() {
+        assert_eq!(errors("
+            local function f(a: number, b: string?, c)
+            end
+
+            f(1)
+            f(1, 'b', 3)
+            f('a', 2)
+            f()
+            f(1, nil, nil, 4)
+            f(g())
+            f 'a'
+            print(f{}, f(1))
+        "), [
+            "expected `number`, found `string`",
+            "expected `string?`, found `number`",
+            "`f` needs 1 argument, but was given 0",
+            "`f` needs 3 arguments, but was given 4",
+            "expected `number`, found `string`",
+            "expected `number`, found `table`",
+        ]);
+    }
+
+    #[test]
+    fn returns() {
+        assert_eq!(errors("
+            local function f(x: number): (number, string)
+                if x > 0 then
+                    return x, 'positive'
+                end
+
+                local function g()
+                    return 'anything'
+                end
+
+                return 'x', x
+            end
+
+            local y: string = f(1)
+            local z: number = f(2)
+        "), [
+            "expected `number`, found `string`",
+            "expected `string`, found `number`",
+            "expected `string`, found `number`",
+        ]);
+    }
+}
@@ -1,7 +1,45 @@
 use std::borrow::Cow;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::num::NonZeroU32;
+
 use tokenizer::StringLiteral;
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub use validate::{validate, ValidationError, ValidationErrorKind};
+pub use owned::Owned;
+pub use debug_tree::DebugTree;
+
+/// Identifies a statement or expression within the chunk it was parsed from.
+/// Every parse numbers its nodes starting from 1, so IDs from different parses
+/// can collide.
+///
+/// IDs serialize as plain numbers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct NodeId(NonZeroU32);
+
+impl NodeId {
+    /// The ID of nodes that didn't come from the parser, like the ones made
+    /// with the [builder][::builder] module. It's the largest possible ID, so
+    /// the parser never gets to it.
+    pub const DUMMY: NodeId = NodeId(NonZeroU32::MAX);
+
+    /// The ID with the given number, or `None` for 0.
+    pub fn new(id: u32) -> Option<NodeId> {
+        NonZeroU32::new(id).map(NodeId)
+    }
+
+    pub fn get(self) -> u32 {
+        self.0.get()
+    }
+}
+
+impl fmt::Display for NodeId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum UnaryOpKind {
     Negate, // -
     BooleanNot, // not
@@ -12,16 +50,35 @@ impl UnaryOpKind {
     pub fn precedence(&self) -> u8 {
         11
     }
+
+    /// The operator as it's written in Lua.
+    pub fn to_str(&self) -> &'static str {
+        match *self {
+            UnaryOpKind::Negate => "-",
+            UnaryOpKind::BooleanNot => "not",
+            UnaryOpKind::Length => "#",
+        }
+    }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum BinaryOpKind {
     Add, // +
     Subtract, // -
     Multiply, // *
     Divide, // /
+    FloorDivide, // //
+    Modulo, // %
     Exponent, // ^
     Concat, // ..
+    And, // and
+    Or, // or
+    LessThan, // <
+    LessThanEqual, // <=
+    GreaterThan, // >
+    GreaterThanEqual, // >=
+    Equal, // ==
+    NotEqual, // ~=
 }
 
 impl BinaryOpKind {
@@ -40,29 +97,54 @@ impl BinaryOpKind {
     // 12 ^
     pub fn precedence(&self) -> u8 {
         match *self {
+            BinaryOpKind::Or => 1,
+            BinaryOpKind::And => 2,
+            BinaryOpKind::LessThan | BinaryOpKind::LessThanEqual |
+            BinaryOpKind::GreaterThan | BinaryOpKind::GreaterThanEqual |
+            BinaryOpKind::Equal | BinaryOpKind::NotEqual => 3,
             BinaryOpKind::Concat => 8,
             BinaryOpKind::Add | BinaryOpKind::Subtract => 9,
-            BinaryOpKind::Multiply | BinaryOpKind::Divide => 10,
+            BinaryOpKind::Multiply | BinaryOpKind::Divide |
+            BinaryOpKind::FloorDivide | BinaryOpKind::Modulo => 10,
             BinaryOpKind::Exponent => 12,
         }
     }
 
     pub fn is_right_associative(&self) -> bool {
+        matches!(*self, BinaryOpKind::Exponent | BinaryOpKind::Concat)
+    }
+
+    /// The operator as it's written in Lua.
+    pub fn to_str(&self) -> &'static str {
         match *self {
-            BinaryOpKind::Exponent | BinaryOpKind::Concat => true,
-            _ => false,
+            BinaryOpKind::Add => "+",
+            BinaryOpKind::Subtract => "-",
+            BinaryOpKind::Multiply => "*",
+            BinaryOpKind::Divide => "/",
+            BinaryOpKind::FloorDivide => "//",
+            BinaryOpKind::Modulo => "%",
+            BinaryOpKind::Exponent => "^",
+            BinaryOpKind::Concat => "..",
+            BinaryOpKind::And => "and",
+            BinaryOpKind::Or => "or",
+            BinaryOpKind::LessThan => "<",
+            BinaryOpKind::LessThanEqual => "<=",
+            BinaryOpKind::GreaterThan => ">",
+            BinaryOpKind::GreaterThanEqual => ">=",
+            BinaryOpKind::Equal => "==",
+            BinaryOpKind::NotEqual => "~=",
         }
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct UnaryOp<'a> {
     pub operator: UnaryOpKind,
     #[serde(borrow)]
     pub argument: Box<Expression<'a>>,
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct BinaryOp<'a> {
     pub operator: BinaryOpKind,
     #[serde(borrow)]
@@ -70,28 +152,72 @@ pub struct BinaryOp<'a> {
     pub right: Box<Expression<'a>>,
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct FunctionCall<'a> {
+    /// The expression being called, or the receiver for method calls.
     #[serde(borrow)]
     pub name_expression: Box<Expression<'a>>,
-    pub arguments: Vec<Expression<'a>>,
+
+    /// The name after the `:` for method calls like `a:b()`.
+    pub method: Option<Cow<'a, str>>,
+
+    pub arguments: FunctionArguments<'a>,
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+// args ::= `(´ [explist] `)´ | tableconstructor | String
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum FunctionArguments<'a> {
+    /// A normal argument list, like `f(a, b)`.
+    #[serde(borrow)]
+    Parenthesized(Vec<Expression<'a>>),
+
+    /// A single string literal argument, like `f "a"`.
+    String(StringLiteral<'a>),
+
+    /// A single table constructor argument, like `f { a = 1 }`.
+    Table(TableLiteral<'a>),
+}
+
+// a.b
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct FieldAccess<'a> {
+    #[serde(borrow)]
+    pub target: Box<Expression<'a>>,
+    pub name: Cow<'a, str>,
+}
+
+// a[b]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Index<'a> {
+    #[serde(borrow)]
+    pub target: Box<Expression<'a>>,
+    pub key: Box<Expression<'a>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Assignment<'a> {
+    /// The assigned-to expressions. The parser only produces
+    /// [Name][ExpressionKind::Name], [FieldAccess][ExpressionKind::FieldAccess], and
+    /// [Index][ExpressionKind::Index] expressions here.
     #[serde(borrow)]
-    pub names: Vec<Cow<'a, str>>,
+    pub targets: Vec<Expression<'a>>,
     pub values: Vec<Expression<'a>>,
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct LocalAssignment<'a> {
     #[serde(borrow)]
     pub names: Vec<Cow<'a, str>>,
     pub values: Vec<Expression<'a>>,
+
+    /// The type annotation on each name, when the chunk was parsed with
+    /// [type annotations][::parser::ParserOptions::type_annotations]. This
+    /// is either empty or has one entry for each name.
+    #[serde(default)]
+    pub types: Vec<Option<TypeAnnotation<'a>>>,
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct NumericFor<'a> {
     #[serde(borrow)]
     pub var: Cow<'a, str>,
@@ -101,7 +227,7 @@ pub struct NumericFor<'a> {
     pub body: Chunk<'a>,
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct GenericFor<'a> {
     #[serde(borrow)]
     pub vars: Vec<Cow<'a, str>>,
@@ -109,7 +235,7 @@ pub struct GenericFor<'a> {
     pub body: Chunk<'a>
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct IfStatement<'a> {
     #[serde(borrow)]
     pub condition: Expression<'a>,
@@ -118,31 +244,189 @@ pub struct IfStatement<'a> {
     pub else_branch: Option<Chunk<'a>>,
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct DoBlock<'a> {
+    #[serde(borrow)]
+    pub body: Chunk<'a>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct WhileLoop<'a> {
     #[serde(borrow)]
     pub condition: Expression<'a>,
     pub body: Chunk<'a>,
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct RepeatLoop<'a> {
     #[serde(borrow)]
     pub condition: Expression<'a>,
     pub body: Chunk<'a>,
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+// funcname ::= Name {`.´ Name} [`:´ Name]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct FunctionName<'a> {
+    /// The dot-separated names leading up to the function, like `a`, `b` in
+    /// `a.b:c`. This always contains at least one name.
+    #[serde(borrow)]
+    pub path: Vec<Cow<'a, str>>,
+
+    /// The name after the `:`, like `c` in `a.b:c`.
+    pub method: Option<Cow<'a, str>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct FunctionDeclaration<'a> {
     #[serde(borrow)]
-    pub name: Cow<'a, str>,
+    pub name: FunctionName<'a>,
     pub body: Chunk<'a>,
     pub parameters: Vec<Cow<'a, str>>,
+
+    /// Whether the parameter list ends with `...`.
+    pub is_variadic: bool,
+
+    /// Whether this was declared with a method name like `a:b`, which gives
+    /// the function an implicit `self` parameter that isn't listed in
+    /// `parameters`.
+    pub is_method: bool,
     pub local: bool,
+
+    /// The type annotation on each parameter, which is either empty or has
+    /// one entry for each of `parameters`.
+    #[serde(default)]
+    pub parameter_types: Vec<Option<TypeAnnotation<'a>>>,
+
+    /// The annotated return types, like `number, string` in
+    /// `function f(): (number, string)`, or `None` without an annotation.
+    #[serde(default)]
+    pub return_types: Option<Vec<TypeAnnotation<'a>>>,
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
-pub enum Expression<'a> {
+/// A Luau type, as written after the `:` in an annotation like
+/// `local x: number?`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TypeAnnotation<'a> {
+    /// `nil`, which is a keyword rather than a name.
+    Nil,
+
+    /// A type by its name, like `number` or `Module.Type`. This always
+    /// contains at least one name.
+    #[serde(borrow)]
+    Named(Vec<Cow<'a, str>>),
+
+    /// `T?`, which is short for `T | nil`.
+    Optional(Box<TypeAnnotation<'a>>),
+
+    /// `A | B`, with at least two types.
+    Union(Vec<TypeAnnotation<'a>>),
+
+    /// `{T}`, a table used as an array of `T`.
+    Array(Box<TypeAnnotation<'a>>),
+
+    /// `(A, B) -> R`, with the returns in parentheses when there's more or
+    /// less than one.
+    Function {
+        parameters: Vec<TypeAnnotation<'a>>,
+        returns: Vec<TypeAnnotation<'a>>,
+    },
+}
+
+impl<'a> TypeAnnotation<'a> {
+    pub fn into_owned(self) -> TypeAnnotation<'static> {
+        match self {
+            TypeAnnotation::Nil => TypeAnnotation::Nil,
+            TypeAnnotation::Named(path) => TypeAnnotation::Named(owned_strs(path)),
+            TypeAnnotation::Optional(inner) => TypeAnnotation::Optional(Box::new(inner.into_owned())),
+            TypeAnnotation::Union(types) => TypeAnnotation::Union(owned_types(types)),
+            TypeAnnotation::Array(item) => TypeAnnotation::Array(Box::new(item.into_owned())),
+            TypeAnnotation::Function { parameters, returns } => TypeAnnotation::Function {
+                parameters: owned_types(parameters),
+                returns: owned_types(returns),
+            },
+        }
+    }
+}
+
+fn owned_types(types: Vec<TypeAnnotation>) -> Vec<TypeAnnotation<'static>> {
+    types.into_iter().map(TypeAnnotation::into_owned).collect()
+}
+
+fn owned_optional_types(types: Vec<Option<TypeAnnotation>>) -> Vec<Option<TypeAnnotation<'static>>> {
+    types.into_iter().map(|annotation| annotation.map(TypeAnnotation::into_owned)).collect()
+}
+
+/// Writes the type the way it's written in an annotation.
+impl<'a> fmt::Display for TypeAnnotation<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TypeAnnotation::Nil => f.write_str("nil"),
+            TypeAnnotation::Named(ref path) => f.write_str(&path.join(".")),
+
+            // `(A | B)?` needs its parentheses, since `A | B?` means
+            // something else.
+            TypeAnnotation::Optional(ref inner) => match **inner {
+                TypeAnnotation::Union(_) | TypeAnnotation::Function { .. } => write!(f, "({})?", inner),
+                _ => write!(f, "{}?", inner),
+            },
+            TypeAnnotation::Union(ref types) => {
+                for (index, annotation) in types.iter().enumerate() {
+                    if index > 0 {
+                        f.write_str(" | ")?;
+                    }
+
+                    match *annotation {
+                        TypeAnnotation::Function { .. } => write!(f, "({})", annotation)?,
+                        _ => write!(f, "{}", annotation)?,
+                    }
+                }
+
+                Ok(())
+            },
+            TypeAnnotation::Array(ref item) => write!(f, "{{{}}}", item),
+            TypeAnnotation::Function { ref parameters, ref returns } => {
+                write!(f, "({}) -> ", join_types(parameters))?;
+
+                match returns[..] {
+                    [ref only] if !matches!(*only, TypeAnnotation::Union(_) | TypeAnnotation::Function { .. }) => write!(f, "{}", only),
+                    _ => write!(f, "({})", join_types(returns)),
+                }
+            },
+        }
+    }
+}
+
+fn join_types(types: &[TypeAnnotation]) -> String {
+    types.iter().map(|annotation| annotation.to_string()).collect::<Vec<_>>().join(", ")
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Goto<'a> {
+    #[serde(borrow)]
+    pub label: Cow<'a, str>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Label<'a> {
+    #[serde(borrow)]
+    pub name: Cow<'a, str>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Return<'a> {
+    #[serde(borrow)]
+    pub values: Vec<Expression<'a>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Expression<'a> {
+    pub id: NodeId,
+    #[serde(borrow)]
+    pub kind: ExpressionKind<'a>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ExpressionKind<'a> {
     Nil,
     Bool(bool),
     #[serde(borrow)]
@@ -152,12 +436,14 @@ pub enum Expression<'a> {
     Table(TableLiteral<'a>),
     FunctionCall(FunctionCall<'a>),
     Name(Cow<'a, str>),
+    FieldAccess(FieldAccess<'a>),
+    Index(Index<'a>),
     ParenExpression(Box<Expression<'a>>),
     UnaryOp(UnaryOp<'a>),
     BinaryOp(BinaryOp<'a>),
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum TableKey<'a> {
     #[serde(borrow)]
     // '[' expression ']'
@@ -167,7 +453,7 @@ pub enum TableKey<'a> {
     Name(Cow<'a, str>),
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct TableLiteral<'a> {
     #[serde(borrow)]
     pub items: Vec<(Option<TableKey<'a>>, Expression<'a>)>,
@@ -188,8 +474,17 @@ pub struct TableLiteral<'a> {
 //     function funcname funcbody |
 //     local function Name funcbody |
 //     local namelist [‘=’ explist]
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
-pub enum Statement<'a> {
+//
+// laststat ::= return [explist] | break
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Statement<'a> {
+    pub id: NodeId,
+    #[serde(borrow)]
+    pub kind: StatementKind<'a>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum StatementKind<'a> {
     #[serde(borrow)]
     Assignment(Assignment<'a>),
     LocalAssignment(LocalAssignment<'a>),
@@ -197,15 +492,698 @@ pub enum Statement<'a> {
     NumericFor(NumericFor<'a>),
     GenericFor(GenericFor<'a>),
     IfStatement(IfStatement<'a>),
+    DoBlock(DoBlock<'a>),
     WhileLoop(WhileLoop<'a>),
     RepeatLoop(RepeatLoop<'a>),
     FunctionDeclaration(FunctionDeclaration<'a>),
+    Goto(Goto<'a>),
+    Label(Label<'a>),
+    Return(Return<'a>),
+    Break,
+
+    /// A lone `;`, which Lua allows between or after any statements.
+    Empty,
 }
 
 // chunk ::= block
 // block ::= {stat} [retstat]
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Chunk<'a> {
     #[serde(borrow)]
     pub statements: Vec<Statement<'a>>,
-}
\ No newline at end of file
+}
+
+/// Anything with a [NodeId][NodeId], for code that works the same on
+/// statements and expressions.
+pub trait HasId {
+    fn id(&self) -> NodeId;
+}
+
+impl<'a> HasId for Statement<'a> {
+    fn id(&self) -> NodeId {
+        self.id
+    }
+}
+
+impl<'a> HasId for Expression<'a> {
+    fn id(&self) -> NodeId {
+        self.id
+    }
+}
+
+/// A borrowed statement or expression, for code that handles any node with
+/// an ID.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NodeRef<'a> {
+    Statement(&'a Statement<'a>),
+    Expression(&'a Expression<'a>),
+}
+
+impl<'a> NodeRef<'a> {
+    pub fn as_statement(&self) -> Option<&'a Statement<'a>> {
+        match *self {
+            NodeRef::Statement(statement) => Some(statement),
+            NodeRef::Expression(_) => None,
+        }
+    }
+
+    pub fn as_expression(&self) -> Option<&'a Expression<'a>> {
+        match *self {
+            NodeRef::Statement(_) => None,
+            NodeRef::Expression(expression) => Some(expression),
+        }
+    }
+}
+
+impl<'a> HasId for NodeRef<'a> {
+    fn id(&self) -> NodeId {
+        match *self {
+            NodeRef::Statement(statement) => statement.id,
+            NodeRef::Expression(expression) => expression.id,
+        }
+    }
+}
+
+/// Like [NodeRef][NodeRef], but mutable. The node is borrowed for `'n`, which
+/// is usually shorter than the `'a` its text is borrowed for.
+#[derive(Debug)]
+pub enum NodeRefMut<'n, 'a: 'n> {
+    Statement(&'n mut Statement<'a>),
+    Expression(&'n mut Expression<'a>),
+}
+
+impl<'n, 'a> NodeRefMut<'n, 'a> {
+    pub fn as_statement(self) -> Option<&'n mut Statement<'a>> {
+        match self {
+            NodeRefMut::Statement(statement) => Some(statement),
+            NodeRefMut::Expression(_) => None,
+        }
+    }
+
+    pub fn as_expression(self) -> Option<&'n mut Expression<'a>> {
+        match self {
+            NodeRefMut::Statement(_) => None,
+            NodeRefMut::Expression(expression) => Some(expression),
+        }
+    }
+}
+
+impl<'n, 'a> HasId for NodeRefMut<'n, 'a> {
+    fn id(&self) -> NodeId {
+        match *self {
+            NodeRefMut::Statement(ref statement) => statement.id,
+            NodeRefMut::Expression(ref expression) => expression.id,
+        }
+    }
+}
+
+// Everything below copies borrowed text out of the source, so that ASTs can
+// outlive it, like when they're cached.
+
+fn owned_str(value: Cow<str>) -> Cow<'static, str> {
+    Cow::Owned(value.into_owned())
+}
+
+fn owned_strs(values: Vec<Cow<str>>) -> Vec<Cow<'static, str>> {
+    values.into_iter().map(owned_str).collect()
+}
+
+fn owned_expressions(expressions: Vec<Expression>) -> Vec<Expression<'static>> {
+    expressions.into_iter().map(Expression::into_owned).collect()
+}
+
+fn owned_expression(expression: Expression) -> Box<Expression<'static>> {
+    Box::new(expression.into_owned())
+}
+
+impl<'a> UnaryOp<'a> {
+    pub fn into_owned(self) -> UnaryOp<'static> {
+        UnaryOp {
+            operator: self.operator,
+            argument: owned_expression(*self.argument),
+        }
+    }
+}
+
+impl<'a> BinaryOp<'a> {
+    pub fn into_owned(self) -> BinaryOp<'static> {
+        BinaryOp {
+            operator: self.operator,
+            left: owned_expression(*self.left),
+            right: owned_expression(*self.right),
+        }
+    }
+}
+
+impl<'a> FunctionCall<'a> {
+    pub fn into_owned(self) -> FunctionCall<'static> {
+        FunctionCall {
+            name_expression: owned_expression(*self.name_expression),
+            method: self.method.map(owned_str),
+            arguments: self.arguments.into_owned(),
+        }
+    }
+}
+
+impl<'a> FunctionArguments<'a> {
+    pub fn into_owned(self) -> FunctionArguments<'static> {
+        match self {
+            FunctionArguments::Parenthesized(values) => FunctionArguments::Parenthesized(owned_expressions(values)),
+            FunctionArguments::String(literal) => FunctionArguments::String(literal.into_owned()),
+            FunctionArguments::Table(table) => FunctionArguments::Table(table.into_owned()),
+        }
+    }
+}
+
+impl<'a> FieldAccess<'a> {
+    pub fn into_owned(self) -> FieldAccess<'static> {
+        FieldAccess {
+            target: owned_expression(*self.target),
+            name: owned_str(self.name),
+        }
+    }
+}
+
+impl<'a> Index<'a> {
+    pub fn into_owned(self) -> Index<'static> {
+        Index {
+            target: owned_expression(*self.target),
+            key: owned_expression(*self.key),
+        }
+    }
+}
+
+impl<'a> Assignment<'a> {
+    pub fn into_owned(self) -> Assignment<'static> {
+        Assignment {
+            targets: owned_expressions(self.targets),
+            values: owned_expressions(self.values),
+        }
+    }
+}
+
+impl<'a> LocalAssignment<'a> {
+    pub fn into_owned(self) -> LocalAssignment<'static> {
+        LocalAssignment {
+            names: owned_strs(self.names),
+            values: owned_expressions(self.values),
+            types: owned_optional_types(self.types),
+        }
+    }
+}
+
+impl<'a> NumericFor<'a> {
+    pub fn into_owned(self) -> NumericFor<'static> {
+        NumericFor {
+            var: owned_str(self.var),
+            start: self.start.into_owned(),
+            end: self.end.into_owned(),
+            step: self.step.map(Expression::into_owned),
+            body: self.body.into_owned(),
+        }
+    }
+}
+
+impl<'a> GenericFor<'a> {
+    pub fn into_owned(self) -> GenericFor<'static> {
+        GenericFor {
+            vars: owned_strs(self.vars),
+            item_source: owned_expressions(self.item_source),
+            body: self.body.into_owned(),
+        }
+    }
+}
+
+impl<'a> IfStatement<'a> {
+    pub fn into_owned(self) -> IfStatement<'static> {
+        IfStatement {
+            condition: self.condition.into_owned(),
+            body: self.body.into_owned(),
+            else_if_branches: self.else_if_branches.into_iter()
+                .map(|(condition, body)| (condition.into_owned(), body.into_owned()))
+                .collect(),
+            else_branch: self.else_branch.map(Chunk::into_owned),
+        }
+    }
+}
+
+impl<'a> DoBlock<'a> {
+    pub fn into_owned(self) -> DoBlock<'static> {
+        DoBlock {
+            body: self.body.into_owned(),
+        }
+    }
+}
+
+impl<'a> WhileLoop<'a> {
+    pub fn into_owned(self) -> WhileLoop<'static> {
+        WhileLoop {
+            condition: self.condition.into_owned(),
+            body: self.body.into_owned(),
+        }
+    }
+}
+
+impl<'a> RepeatLoop<'a> {
+    pub fn into_owned(self) -> RepeatLoop<'static> {
+        RepeatLoop {
+            condition: self.condition.into_owned(),
+            body: self.body.into_owned(),
+        }
+    }
+}
+
+impl<'a> FunctionName<'a> {
+    pub fn into_owned(self) -> FunctionName<'static> {
+        FunctionName {
+            path: owned_strs(self.path),
+            method: self.method.map(owned_str),
+        }
+    }
+}
+
+impl<'a> FunctionDeclaration<'a> {
+    pub fn into_owned(self) -> FunctionDeclaration<'static> {
+        FunctionDeclaration {
+            name: self.name.into_owned(),
+            body: self.body.into_owned(),
+            parameters: owned_strs(self.parameters),
+            is_variadic: self.is_variadic,
+            is_method: self.is_method,
+            local: self.local,
+            parameter_types: owned_optional_types(self.parameter_types),
+            return_types: self.return_types.map(owned_types),
+        }
+    }
+}
+
+impl<'a> Goto<'a> {
+    pub fn into_owned(self) -> Goto<'static> {
+        Goto {
+            label: owned_str(self.label),
+        }
+    }
+}
+
+impl<'a> Label<'a> {
+    pub fn into_owned(self) -> Label<'static> {
+        Label {
+            name: owned_str(self.name),
+        }
+    }
+}
+
+impl<'a> Return<'a> {
+    pub fn into_owned(self) -> Return<'static> {
+        Return {
+            values: owned_expressions(self.values),
+        }
+    }
+}
+
+impl<'a> Expression<'a> {
+    pub fn into_owned(self) -> Expression<'static> {
+        Expression {
+            id: self.id,
+            kind: self.kind.into_owned(),
+        }
+    }
+}
+
+impl<'a> ExpressionKind<'a> {
+    pub fn into_owned(self) -> ExpressionKind<'static> {
+        match self {
+            ExpressionKind::Nil => ExpressionKind::Nil,
+            ExpressionKind::Bool(value) => ExpressionKind::Bool(value),
+            ExpressionKind::Number(value) => ExpressionKind::Number(owned_str(value)),
+            ExpressionKind::String(literal) => ExpressionKind::String(literal.into_owned()),
+            ExpressionKind::VarArg => ExpressionKind::VarArg,
+            ExpressionKind::Table(table) => ExpressionKind::Table(table.into_owned()),
+            ExpressionKind::FunctionCall(call) => ExpressionKind::FunctionCall(call.into_owned()),
+            ExpressionKind::Name(name) => ExpressionKind::Name(owned_str(name)),
+            ExpressionKind::FieldAccess(field_access) => ExpressionKind::FieldAccess(field_access.into_owned()),
+            ExpressionKind::Index(index) => ExpressionKind::Index(index.into_owned()),
+            ExpressionKind::ParenExpression(expression) => ExpressionKind::ParenExpression(owned_expression(*expression)),
+            ExpressionKind::UnaryOp(unary_op) => ExpressionKind::UnaryOp(unary_op.into_owned()),
+            ExpressionKind::BinaryOp(binary_op) => ExpressionKind::BinaryOp(binary_op.into_owned()),
+        }
+    }
+}
+
+impl<'a> TableKey<'a> {
+    pub fn into_owned(self) -> TableKey<'static> {
+        match self {
+            TableKey::Expression(expression) => TableKey::Expression(expression.into_owned()),
+            TableKey::Name(name) => TableKey::Name(owned_str(name)),
+        }
+    }
+}
+
+impl<'a> TableLiteral<'a> {
+    pub fn into_owned(self) -> TableLiteral<'static> {
+        TableLiteral {
+            items: self.items.into_iter()
+                .map(|(key, value)| (key.map(TableKey::into_owned), value.into_owned()))
+                .collect(),
+        }
+    }
+}
+
+impl<'a> Statement<'a> {
+    pub fn into_owned(self) -> Statement<'static> {
+        Statement {
+            id: self.id,
+            kind: self.kind.into_owned(),
+        }
+    }
+}
+
+impl<'a> StatementKind<'a> {
+    pub fn into_owned(self) -> StatementKind<'static> {
+        match self {
+            StatementKind::Assignment(value) => StatementKind::Assignment(value.into_owned()),
+            StatementKind::LocalAssignment(value) => StatementKind::LocalAssignment(value.into_owned()),
+            StatementKind::FunctionCall(value) => StatementKind::FunctionCall(value.into_owned()),
+            StatementKind::NumericFor(value) => StatementKind::NumericFor(value.into_owned()),
+            StatementKind::GenericFor(value) => StatementKind::GenericFor(value.into_owned()),
+            StatementKind::IfStatement(value) => StatementKind::IfStatement(value.into_owned()),
+            StatementKind::DoBlock(value) => StatementKind::DoBlock(value.into_owned()),
+            StatementKind::WhileLoop(value) => StatementKind::WhileLoop(value.into_owned()),
+            StatementKind::RepeatLoop(value) => StatementKind::RepeatLoop(value.into_owned()),
+            StatementKind::FunctionDeclaration(value) => StatementKind::FunctionDeclaration(value.into_owned()),
+            StatementKind::Goto(value) => StatementKind::Goto(value.into_owned()),
+            StatementKind::Label(value) => StatementKind::Label(value.into_owned()),
+            StatementKind::Return(value) => StatementKind::Return(value.into_owned()),
+            StatementKind::Break => StatementKind::Break,
+            StatementKind::Empty => StatementKind::Empty,
+        }
+    }
+}
+
+impl<'a> Chunk<'a> {
+    /// Copies everything borrowed from the source, so the chunk can outlive
+    /// it. The other nodes have the same method.
+    pub fn into_owned(self) -> Chunk<'static> {
+        Chunk {
+            statements: self.statements.into_iter().map(Statement::into_owned).collect(),
+        }
+    }
+}
+
+/// Compares nodes by their shape and values only, ignoring IDs. Unlike `==`,
+/// this can tell whether two separately parsed trees are the same code.
+pub trait AstEq {
+    fn structural_eq(&self, other: &Self) -> bool;
+}
+
+// Leaves that don't contain any nodes compare as usual.
+macro_rules! ast_eq_by_value {
+    ($($type: ty),*) => {
+        $(
+            impl<'a> AstEq for $type {
+                fn structural_eq(&self, other: &Self) -> bool {
+                    self == other
+                }
+            }
+        )*
+    }
+}
+
+ast_eq_by_value!(bool, Cow<'a, str>, StringLiteral<'a>, UnaryOpKind, BinaryOpKind, TypeAnnotation<'a>);
+
+// Structs are the same when all of their fields are.
+macro_rules! ast_eq_fields {
+    ($type: ident { $($field: ident),* }) => {
+        impl<'a> AstEq for $type<'a> {
+            fn structural_eq(&self, other: &Self) -> bool {
+                true $(&& self.$field.structural_eq(&other.$field))*
+            }
+        }
+    }
+}
+
+ast_eq_fields!(UnaryOp { operator, argument });
+ast_eq_fields!(BinaryOp { operator, left, right });
+ast_eq_fields!(FunctionCall { name_expression, method, arguments });
+ast_eq_fields!(FieldAccess { target, name });
+ast_eq_fields!(Index { target, key });
+ast_eq_fields!(Assignment { targets, values });
+ast_eq_fields!(LocalAssignment { names, values, types });
+ast_eq_fields!(NumericFor { var, start, end, step, body });
+ast_eq_fields!(GenericFor { vars, item_source, body });
+ast_eq_fields!(IfStatement { condition, body, else_if_branches, else_branch });
+ast_eq_fields!(DoBlock { body });
+ast_eq_fields!(WhileLoop { condition, body });
+ast_eq_fields!(RepeatLoop { condition, body });
+ast_eq_fields!(FunctionName { path, method });
+ast_eq_fields!(FunctionDeclaration { name, body, parameters, is_variadic, is_method, local, parameter_types, return_types });
+ast_eq_fields!(Goto { label });
+ast_eq_fields!(Label { name });
+ast_eq_fields!(Return { values });
+ast_eq_fields!(TableLiteral { items });
+ast_eq_fields!(Chunk { statements });
+
+// Leaving out `id` here is what makes this structural.
+ast_eq_fields!(Expression { kind });
+ast_eq_fields!(Statement { kind });
+
+impl<T: AstEq> AstEq for &T {
+    fn structural_eq(&self, other: &Self) -> bool {
+        (**self).structural_eq(*other)
+    }
+}
+
+impl<T: AstEq> AstEq for Box<T> {
+    fn structural_eq(&self, other: &Self) -> bool {
+        (**self).structural_eq(other)
+    }
+}
+
+impl<T: AstEq> AstEq for Option<T> {
+    fn structural_eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Some(a), Some(b)) => a.structural_eq(b),
+            (None, None) => true,
+            _ => false,
+        }
+    }
+}
+
+impl<T: AstEq> AstEq for Vec<T> {
+    fn structural_eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().zip(other).all(|(a, b)| a.structural_eq(b))
+    }
+}
+
+impl<A: AstEq, B: AstEq> AstEq for (A, B) {
+    fn structural_eq(&self, other: &Self) -> bool {
+        self.0.structural_eq(&other.0) && self.1.structural_eq(&other.1)
+    }
+}
+
+impl<'a> AstEq for FunctionArguments<'a> {
+    fn structural_eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (FunctionArguments::Parenthesized(a), FunctionArguments::Parenthesized(b)) => a.structural_eq(b),
+            (FunctionArguments::String(a), FunctionArguments::String(b)) => a.structural_eq(b),
+            (FunctionArguments::Table(a), FunctionArguments::Table(b)) => a.structural_eq(b),
+            _ => false,
+        }
+    }
+}
+
+impl<'a> AstEq for TableKey<'a> {
+    fn structural_eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (TableKey::Expression(a), TableKey::Expression(b)) => a.structural_eq(b),
+            (TableKey::Name(a), TableKey::Name(b)) => a.structural_eq(b),
+            _ => false,
+        }
+    }
+}
+
+impl<'a> AstEq for ExpressionKind<'a> {
+    fn structural_eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (ExpressionKind::Nil, ExpressionKind::Nil) => true,
+            (ExpressionKind::Bool(a), ExpressionKind::Bool(b)) => a.structural_eq(b),
+            (ExpressionKind::Number(a), ExpressionKind::Number(b)) => a.structural_eq(b),
+            (ExpressionKind::String(a), ExpressionKind::String(b)) => a.structural_eq(b),
+            (ExpressionKind::VarArg, ExpressionKind::VarArg) => true,
+            (ExpressionKind::Table(a), ExpressionKind::Table(b)) => a.structural_eq(b),
+            (ExpressionKind::FunctionCall(a), ExpressionKind::FunctionCall(b)) => a.structural_eq(b),
+            (ExpressionKind::Name(a), ExpressionKind::Name(b)) => a.structural_eq(b),
+            (ExpressionKind::FieldAccess(a), ExpressionKind::FieldAccess(b)) => a.structural_eq(b),
+            (ExpressionKind::Index(a), ExpressionKind::Index(b)) => a.structural_eq(b),
+            (ExpressionKind::ParenExpression(a), ExpressionKind::ParenExpression(b)) => a.structural_eq(b),
+            (ExpressionKind::UnaryOp(a), ExpressionKind::UnaryOp(b)) => a.structural_eq(b),
+            (ExpressionKind::BinaryOp(a), ExpressionKind::BinaryOp(b)) => a.structural_eq(b),
+            _ => false,
+        }
+    }
+}
+
+impl<'a> AstEq for StatementKind<'a> {
+    fn structural_eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (StatementKind::Assignment(a), StatementKind::Assignment(b)) => a.structural_eq(b),
+            (StatementKind::LocalAssignment(a), StatementKind::LocalAssignment(b)) => a.structural_eq(b),
+            (StatementKind::FunctionCall(a), StatementKind::FunctionCall(b)) => a.structural_eq(b),
+            (StatementKind::NumericFor(a), StatementKind::NumericFor(b)) => a.structural_eq(b),
+            (StatementKind::GenericFor(a), StatementKind::GenericFor(b)) => a.structural_eq(b),
+            (StatementKind::IfStatement(a), StatementKind::IfStatement(b)) => a.structural_eq(b),
+            (StatementKind::DoBlock(a), StatementKind::DoBlock(b)) => a.structural_eq(b),
+            (StatementKind::WhileLoop(a), StatementKind::WhileLoop(b)) => a.structural_eq(b),
+            (StatementKind::RepeatLoop(a), StatementKind::RepeatLoop(b)) => a.structural_eq(b),
+            (StatementKind::FunctionDeclaration(a), StatementKind::FunctionDeclaration(b)) => a.structural_eq(b),
+            (StatementKind::Goto(a), StatementKind::Goto(b)) => a.structural_eq(b),
+            (StatementKind::Label(a), StatementKind::Label(b)) => a.structural_eq(b),
+            (StatementKind::Return(a), StatementKind::Return(b)) => a.structural_eq(b),
+            (StatementKind::Break, StatementKind::Break) => true,
+            (StatementKind::Empty, StatementKind::Empty) => true,
+            _ => false,
+        }
+    }
+}
+
+
+// Hashes leave out IDs too, so nodes that are structurally equal hash the
+// same. That also keeps them consistent with `==`, which is stricter.
+
+impl<'a> Hash for Expression<'a> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.kind.hash(state);
+    }
+}
+
+impl<'a> Hash for Statement<'a> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.kind.hash(state);
+    }
+}
+
+/// Wraps a node so that `==` ignores IDs, for using nodes as keys by their
+/// structure, like when looking for duplicated code:
+///
+/// ```
+/// use std::collections::HashSet;
+/// use mab::ast::{StatementKind, Structural};
+///
+/// let tokens = mab::tokenize("f(x + 1) g(x + 1) h(x - 1)").unwrap();
+/// let chunk = mab::parse_from_tokens(&tokens).unwrap();
+///
+/// let mut arguments = HashSet::new();
+/// for statement in &chunk.statements {
+///     if let StatementKind::FunctionCall(ref call) = statement.kind {
+///         arguments.insert(Structural(&call.arguments));
+///     }
+/// }
+///
+/// assert_eq!(arguments.len(), 2);
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Structural<T>(pub T);
+
+impl<T: AstEq> PartialEq for Structural<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.structural_eq(&other.0)
+    }
+}
+
+impl<T: AstEq> Eq for Structural<T> {}
+
+impl<T: Hash> Hash for Structural<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokenizer::tokenize;
+    use parser::parse_from_tokens;
+
+    // The source and tokens are dropped before this returns.
+    fn parse_owned(source: String) -> Chunk<'static> {
+        let tokens = tokenize(&source).unwrap();
+        parse_from_tokens(&tokens).unwrap().into_owned()
+    }
+
+    #[test]
+    fn into_owned_outlives_source() {
+        let chunk = parse_owned("local x = f('a', b.c)".to_string());
+
+        match chunk.statements[0].kind {
+            StatementKind::LocalAssignment(LocalAssignment { ref names, ref values, .. }) => {
+                assert!(matches!(names[0], Cow::Owned(ref name) if name == "x"));
+                assert!(matches!(values[0].kind, ExpressionKind::FunctionCall(_)));
+            },
+            _ => panic!("expected a local assignment"),
+        }
+    }
+
+    #[test]
+    fn structural_eq_ignores_ids() {
+        let parse = |source| {
+            let tokens = tokenize(source).unwrap();
+            parse_from_tokens(&tokens).unwrap().into_owned()
+        };
+
+        // The `while` condition uses up IDs, so `f(x)` gets different ones.
+        let mut first = parse("while a do end f(x)");
+        let second = parse("f(x)");
+        first.statements.remove(0);
+
+        assert_ne!(first, second);
+        assert!(first.structural_eq(&second));
+        assert!(!first.structural_eq(&parse("f(y)")));
+        assert!(!first.structural_eq(&parse("f(x, y)")));
+        assert!(!first.structural_eq(&parse("f(x) g()")));
+    }
+
+    #[test]
+    fn hash_ignores_ids() {
+        use std::collections::hash_map::DefaultHasher;
+
+        fn hash<T: Hash>(value: &T) -> u64 {
+            let mut hasher = DefaultHasher::new();
+            value.hash(&mut hasher);
+            hasher.finish()
+        }
+
+        let tokens = tokenize("f(x) f(x) f(y)").unwrap();
+        let chunk = parse_from_tokens(&tokens).unwrap();
+        let statements = &chunk.statements;
+
+        assert_ne!(statements[0].id, statements[1].id);
+        assert_eq!(hash(&statements[0]), hash(&statements[1]));
+        assert_ne!(hash(&statements[0]), hash(&statements[2]));
+
+        assert_eq!(Structural(&statements[0]), Structural(&statements[1]));
+        assert_ne!(Structural(&statements[0]), Structural(&statements[2]));
+    }
+
+    #[test]
+    fn node_refs() {
+        fn ids<T: HasId>(nodes: &[T]) -> Vec<NodeId> {
+            nodes.iter().map(HasId::id).collect()
+        }
+
+        let tokens = tokenize("f(x)").unwrap();
+        let mut chunk = parse_from_tokens(&tokens).unwrap();
+        let statement_id = chunk.statements[0].id;
+
+        let nodes = chunk.descendants().collect::<Vec<_>>();
+        assert_eq!(ids(&nodes).len(), 3);
+        assert_eq!(ids(&nodes)[0], statement_id);
+        assert_eq!(ids(&chunk.statements), vec![statement_id]);
+        assert!(nodes[0].as_statement().is_some());
+        assert!(nodes[1].as_expression().is_some());
+
+        let node = NodeRefMut::Statement(&mut chunk.statements[0]);
+        assert_eq!(node.id(), statement_id);
+        node.as_statement().unwrap().id = NodeId::new(10).unwrap();
+        assert_eq!(chunk.statements[0].id.get(), 10);
+    }
+}
@@ -0,0 +1,99 @@
+//! Compact binary encodings of chunks, for caching parsed code. Each encoding
+//! is behind a feature named after its crate: `bincode` or `rmp-serde`.
+//!
+//! Decoding borrows strings from the bytes where it can. The `_owned`
+//! functions copy them instead, for when the bytes won't stay around.
+//!
+//! The encodings follow the AST's Rust definitions, so bytes written by one
+//! version of mab can't necessarily be read by another.
+
+/// Bincode, from the `bincode` crate.
+#[cfg(feature = "bincode")]
+pub mod bincode {
+    use ast::{Chunk, Owned};
+
+    pub use ::bincode::Error;
+
+    pub fn to_vec(chunk: &Chunk) -> Result<Vec<u8>, Error> {
+        ::bincode::serialize(chunk)
+    }
+
+    pub fn from_slice<'a>(bytes: &'a [u8]) -> Result<Chunk<'a>, Error> {
+        ::bincode::deserialize(bytes)
+    }
+
+    pub fn from_slice_owned(bytes: &[u8]) -> Result<Chunk<'static>, Error> {
+        ::bincode::deserialize(bytes).map(Owned::into_inner)
+    }
+}
+
+/// MessagePack, from the `rmp-serde` crate.
+#[cfg(feature = "rmp-serde")]
+pub mod msgpack {
+    use ast::{Chunk, Owned};
+
+    pub use rmp_serde::encode::Error as EncodeError;
+    pub use rmp_serde::decode::Error as DecodeError;
+
+    pub fn to_vec(chunk: &Chunk) -> Result<Vec<u8>, EncodeError> {
+        rmp_serde::to_vec(chunk)
+    }
+
+    pub fn from_slice<'a>(bytes: &'a [u8]) -> Result<Chunk<'a>, DecodeError> {
+        rmp_serde::from_slice(bytes)
+    }
+
+    pub fn from_slice_owned(bytes: &[u8]) -> Result<Chunk<'static>, DecodeError> {
+        rmp_serde::from_slice(bytes).map(Owned::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ast::Chunk;
+    use tokenizer::tokenize;
+    use parser::parse_from_tokens;
+
+    const SOURCE: &str = r#"
+        local function f(a, ...)
+            return { a, b = "\255", [1] = [==[long]==] }
+        end
+
+        for i = 1, #x do
+            if not y then print'hi' elseif z then break end
+        end
+    "#;
+
+    fn round_trip<E, D, O, Error: ::std::fmt::Debug>(encode: E, decode: D, decode_owned: O)
+    where
+        E: Fn(&Chunk) -> Vec<u8>,
+        D: for<'b> Fn(&'b [u8]) -> Result<Chunk<'b>, Error>,
+        O: Fn(&[u8]) -> Result<Chunk<'static>, Error>,
+    {
+        let tokens = tokenize(SOURCE).unwrap();
+        let chunk = parse_from_tokens(&tokens).unwrap();
+        let bytes = encode(&chunk);
+
+        assert_eq!(decode(&bytes).unwrap(), chunk);
+
+        let owned = decode_owned(&bytes).unwrap();
+        drop(bytes);
+        assert_eq!(owned, chunk);
+    }
+
+    #[cfg(feature = "bincode")]
+    #[test]
+    fn bincode() {
+        use super::bincode::*;
+
+        round_trip(|chunk| to_vec(chunk).unwrap(), from_slice, from_slice_owned);
+    }
+
+    #[cfg(feature = "rmp-serde")]
+    #[test]
+    fn msgpack() {
+        use super::msgpack::*;
+
+        round_trip(|chunk| to_vec(chunk).unwrap(), from_slice, from_slice_owned);
+    }
+}
@@ -0,0 +1,403 @@
+//! Shorthand for constructing AST nodes by hand, for code generators and
+//! tests. The functions take care of boxing, converting names to `Cow`s, and
+//! giving every node [NodeId::DUMMY][::ast::NodeId::DUMMY]:
+//!
+//! ```
+//! use mab::ast::{AstEq, BinaryOpKind};
+//! use mab::builder::{chunk, expr, stmt};
+//!
+//! let built = chunk(vec![
+//!     stmt::local(["x"], [expr::binary(BinaryOpKind::Add, expr::name("y"), expr::num("1"))]),
+//!     stmt::call(expr::name("print"), [expr::name("x")]),
+//! ]);
+//!
+//! let tokens = mab::tokenize("local x = y + 1 print(x)").unwrap();
+//! assert!(built.structural_eq(&mab::parse_from_tokens(&tokens).unwrap()));
+//! ```
+//!
+//! Lists of names, expressions, and statements can be anything iterable, like
+//! arrays or `Vec`s.
+
+use std::borrow::Cow;
+
+use ast::*;
+
+fn names<'a, N>(names: N) -> Vec<Cow<'a, str>>
+where
+    N: IntoIterator,
+    N::Item: Into<Cow<'a, str>>,
+{
+    names.into_iter().map(Into::into).collect()
+}
+
+pub fn chunk<'a, S: IntoIterator<Item = Statement<'a>>>(statements: S) -> Chunk<'a> {
+    Chunk {
+        statements: statements.into_iter().collect(),
+    }
+}
+
+pub mod expr {
+    use std::borrow::Cow;
+
+    use ast::*;
+    use tokenizer::StringLiteral;
+
+    pub fn new(kind: ExpressionKind) -> Expression {
+        Expression {
+            id: NodeId::DUMMY,
+            kind,
+        }
+    }
+
+    pub fn nil<'a>() -> Expression<'a> {
+        new(ExpressionKind::Nil)
+    }
+
+    pub fn bool<'a>(value: bool) -> Expression<'a> {
+        new(ExpressionKind::Bool(value))
+    }
+
+    /// A number, written the way it should appear in the source, like `"0x10"`.
+    pub fn num<'a, T: Into<Cow<'a, str>>>(text: T) -> Expression<'a> {
+        new(ExpressionKind::Number(text.into()))
+    }
+
+    /// A double-quoted string with the given contents, escaped as needed.
+    pub fn string<'a>(value: &str) -> Expression<'a> {
+        let mut raw_content = String::new();
+
+        for character in value.chars() {
+            match character {
+                '"' => raw_content.push_str("\\\""),
+                '\\' => raw_content.push_str("\\\\"),
+                '\n' => raw_content.push_str("\\n"),
+                '\r' => raw_content.push_str("\\r"),
+                '\t' => raw_content.push_str("\\t"),
+                // Always three digits, so a digit after it isn't swallowed.
+                character if character.is_control() && (character as u32) < 0x80 => {
+                    raw_content.push_str(&format!("\\{:03}", character as u32))
+                },
+                character => raw_content.push(character),
+            }
+        }
+
+        new(ExpressionKind::String(StringLiteral::DoubleQuote {
+            raw_content: Cow::Owned(raw_content),
+            value: Cow::Owned(value.as_bytes().to_vec()),
+        }))
+    }
+
+    pub fn vararg<'a>() -> Expression<'a> {
+        new(ExpressionKind::VarArg)
+    }
+
+    pub fn name<'a, T: Into<Cow<'a, str>>>(name: T) -> Expression<'a> {
+        new(ExpressionKind::Name(name.into()))
+    }
+
+    /// A table constructor with keys like `[k] = v`, `k = v`, or none at all.
+    pub fn table<'a, I: IntoIterator<Item = (Option<TableKey<'a>>, Expression<'a>)>>(items: I) -> Expression<'a> {
+        new(ExpressionKind::Table(TableLiteral {
+            items: items.into_iter().collect(),
+        }))
+    }
+
+    /// A table constructor with no keys, like `{ 1, 2, 3 }`.
+    pub fn list<'a, V: IntoIterator<Item = Expression<'a>>>(values: V) -> Expression<'a> {
+        table(values.into_iter().map(|value| (None, value)))
+    }
+
+    pub fn call<'a, A: IntoIterator<Item = Expression<'a>>>(function: Expression<'a>, arguments: A) -> Expression<'a> {
+        new(ExpressionKind::FunctionCall(super::function_call(function, None, arguments)))
+    }
+
+    /// A call like `receiver:method(arguments)`.
+    pub fn method_call<'a, T, A>(receiver: Expression<'a>, method: T, arguments: A) -> Expression<'a>
+    where
+        T: Into<Cow<'a, str>>,
+        A: IntoIterator<Item = Expression<'a>>,
+    {
+        new(ExpressionKind::FunctionCall(super::function_call(receiver, Some(method.into()), arguments)))
+    }
+
+    pub fn field<'a, T: Into<Cow<'a, str>>>(target: Expression<'a>, name: T) -> Expression<'a> {
+        new(ExpressionKind::FieldAccess(FieldAccess {
+            target: Box::new(target),
+            name: name.into(),
+        }))
+    }
+
+    pub fn index<'a>(target: Expression<'a>, key: Expression<'a>) -> Expression<'a> {
+        new(ExpressionKind::Index(Index {
+            target: Box::new(target),
+            key: Box::new(key),
+        }))
+    }
+
+    pub fn paren(expression: Expression) -> Expression {
+        new(ExpressionKind::ParenExpression(Box::new(expression)))
+    }
+
+    pub fn unary(operator: UnaryOpKind, argument: Expression) -> Expression {
+        new(ExpressionKind::UnaryOp(UnaryOp {
+            operator,
+            argument: Box::new(argument),
+        }))
+    }
+
+    /// Nothing is parenthesized automatically, so use [paren][paren] where
+    /// precedence calls for it.
+    pub fn binary<'a>(operator: BinaryOpKind, left: Expression<'a>, right: Expression<'a>) -> Expression<'a> {
+        new(ExpressionKind::BinaryOp(BinaryOp {
+            operator,
+            left: Box::new(left),
+            right: Box::new(right),
+        }))
+    }
+}
+
+pub mod stmt {
+    use std::borrow::Cow;
+
+    use ast::*;
+    use super::{chunk, names};
+
+    pub fn new(kind: StatementKind) -> Statement {
+        Statement {
+            id: NodeId::DUMMY,
+            kind,
+        }
+    }
+
+    pub fn assign<'a, T, V>(targets: T, values: V) -> Statement<'a>
+    where
+        T: IntoIterator<Item = Expression<'a>>,
+        V: IntoIterator<Item = Expression<'a>>,
+    {
+        new(StatementKind::Assignment(Assignment {
+            targets: targets.into_iter().collect(),
+            values: values.into_iter().collect(),
+        }))
+    }
+
+    pub fn local<'a, N, V>(names: N, values: V) -> Statement<'a>
+    where
+        N: IntoIterator,
+        N::Item: Into<Cow<'a, str>>,
+        V: IntoIterator<Item = Expression<'a>>,
+    {
+        new(StatementKind::LocalAssignment(LocalAssignment {
+            names: super::names(names),
+            values: values.into_iter().collect(),
+            types: Vec::new(),
+        }))
+    }
+
+    pub fn call<'a, A: IntoIterator<Item = Expression<'a>>>(function: Expression<'a>, arguments: A) -> Statement<'a> {
+        new(StatementKind::FunctionCall(super::function_call(function, None, arguments)))
+    }
+
+    pub fn method_call<'a, T, A>(receiver: Expression<'a>, method: T, arguments: A) -> Statement<'a>
+    where
+        T: Into<Cow<'a, str>>,
+        A: IntoIterator<Item = Expression<'a>>,
+    {
+        new(StatementKind::FunctionCall(super::function_call(receiver, Some(method.into()), arguments)))
+    }
+
+    pub fn numeric_for<'a, T, B>(var: T, start: Expression<'a>, end: Expression<'a>, step: Option<Expression<'a>>, body: B) -> Statement<'a>
+    where
+        T: Into<Cow<'a, str>>,
+        B: IntoIterator<Item = Statement<'a>>,
+    {
+        new(StatementKind::NumericFor(NumericFor {
+            var: var.into(),
+            start,
+            end,
+            step,
+            body: chunk(body),
+        }))
+    }
+
+    pub fn generic_for<'a, N, E, B>(vars: N, item_source: E, body: B) -> Statement<'a>
+    where
+        N: IntoIterator,
+        N::Item: Into<Cow<'a, str>>,
+        E: IntoIterator<Item = Expression<'a>>,
+        B: IntoIterator<Item = Statement<'a>>,
+    {
+        new(StatementKind::GenericFor(GenericFor {
+            vars: names(vars),
+            item_source: item_source.into_iter().collect(),
+            body: chunk(body),
+        }))
+    }
+
+    /// An `if` without any `elseif` or `else` branches. Add those to the
+    /// [IfStatement][::ast::IfStatement] directly.
+    pub fn if_then<'a, B: IntoIterator<Item = Statement<'a>>>(condition: Expression<'a>, body: B) -> Statement<'a> {
+        new(StatementKind::IfStatement(IfStatement {
+            condition,
+            body: chunk(body),
+            else_if_branches: Vec::new(),
+            else_branch: None,
+        }))
+    }
+
+    pub fn do_block<'a, B: IntoIterator<Item = Statement<'a>>>(body: B) -> Statement<'a> {
+        new(StatementKind::DoBlock(DoBlock {
+            body: chunk(body),
+        }))
+    }
+
+    pub fn while_loop<'a, B: IntoIterator<Item = Statement<'a>>>(condition: Expression<'a>, body: B) -> Statement<'a> {
+        new(StatementKind::WhileLoop(WhileLoop {
+            condition,
+            body: chunk(body),
+        }))
+    }
+
+    pub fn repeat_loop<'a, B: IntoIterator<Item = Statement<'a>>>(body: B, condition: Expression<'a>) -> Statement<'a> {
+        new(StatementKind::RepeatLoop(RepeatLoop {
+            condition,
+            body: chunk(body),
+        }))
+    }
+
+    /// A global function like `function a.b(x) end`. `path` is the
+    /// dot-separated name, which needs at least one part.
+    pub fn function<'a, N, P, B>(path: N, parameters: P, body: B) -> Statement<'a>
+    where
+        N: IntoIterator,
+        N::Item: Into<Cow<'a, str>>,
+        P: IntoIterator,
+        P::Item: Into<Cow<'a, str>>,
+        B: IntoIterator<Item = Statement<'a>>,
+    {
+        new(StatementKind::FunctionDeclaration(FunctionDeclaration {
+            name: FunctionName {
+                path: names(path),
+                method: None,
+            },
+            body: chunk(body),
+            parameters: names(parameters),
+            is_variadic: false,
+            is_method: false,
+            local: false,
+            parameter_types: Vec::new(),
+            return_types: None,
+        }))
+    }
+
+    pub fn local_function<'a, T, P, B>(name: T, parameters: P, body: B) -> Statement<'a>
+    where
+        T: Into<Cow<'a, str>>,
+        P: IntoIterator,
+        P::Item: Into<Cow<'a, str>>,
+        B: IntoIterator<Item = Statement<'a>>,
+    {
+        new(StatementKind::FunctionDeclaration(FunctionDeclaration {
+            name: FunctionName {
+                path: vec![name.into()],
+                method: None,
+            },
+            body: chunk(body),
+            parameters: names(parameters),
+            is_variadic: false,
+            is_method: false,
+            local: true,
+            parameter_types: Vec::new(),
+            return_types: None,
+        }))
+    }
+
+    pub fn goto<'a, T: Into<Cow<'a, str>>>(label: T) -> Statement<'a> {
+        new(StatementKind::Goto(Goto {
+            label: label.into(),
+        }))
+    }
+
+    pub fn label<'a, T: Into<Cow<'a, str>>>(name: T) -> Statement<'a> {
+        new(StatementKind::Label(Label {
+            name: name.into(),
+        }))
+    }
+
+    pub fn ret<'a, V: IntoIterator<Item = Expression<'a>>>(values: V) -> Statement<'a> {
+        new(StatementKind::Return(Return {
+            values: values.into_iter().collect(),
+        }))
+    }
+
+    pub fn brk<'a>() -> Statement<'a> {
+        new(StatementKind::Break)
+    }
+
+    pub fn empty<'a>() -> Statement<'a> {
+        new(StatementKind::Empty)
+    }
+}
+
+fn function_call<'a, A>(function: Expression<'a>, method: Option<Cow<'a, str>>, arguments: A) -> FunctionCall<'a>
+where
+    A: IntoIterator<Item = Expression<'a>>,
+{
+    FunctionCall {
+        name_expression: Box::new(function),
+        method,
+        arguments: FunctionArguments::Parenthesized(arguments.into_iter().collect()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokenizer::tokenize;
+    use parser::parse_from_tokens;
+
+    fn assert_builds(built: Chunk, source: &str) {
+        let tokens = tokenize(source).unwrap();
+        let parsed = parse_from_tokens(&tokens).unwrap();
+        assert!(built.structural_eq(&parsed), "{:#?}\ndoesn't match {:?}", built, source);
+    }
+
+    #[test]
+    fn statements() {
+        assert_builds(chunk(vec![
+            stmt::assign([expr::field(expr::name("a"), "b"), expr::index(expr::name("c"), expr::num("1"))], [expr::nil(), expr::vararg()]),
+            stmt::method_call(expr::name("obj"), "m", [expr::bool(true)]),
+            stmt::numeric_for("i", expr::num("1"), expr::num("10"), None, [stmt::brk()]),
+            stmt::generic_for(["k", "v"], [expr::call(expr::name("pairs"), [expr::name("t")])], vec![]),
+            stmt::if_then(expr::unary(UnaryOpKind::BooleanNot, expr::name("x")), [stmt::goto("done")]),
+            stmt::while_loop(expr::name("x"), [stmt::do_block([stmt::empty()])]),
+            stmt::repeat_loop(vec![], expr::paren(expr::name("y"))),
+            stmt::function(["a", "b"], ["x"], [stmt::ret([expr::list([expr::name("x")])])]),
+            stmt::local_function("f", Vec::<&str>::new(), vec![]),
+            stmt::label("done"),
+        ]), "
+            a.b, c[1] = nil, ...
+            obj:m(true)
+            for i = 1, 10 do break end
+            for k, v in pairs(t) do end
+            if not x then goto done end
+            while x do do ; end end
+            repeat until (y)
+            function a.b(x) return { x } end
+            local function f() end
+            ::done::
+        ");
+    }
+
+    #[test]
+    fn strings_are_escaped() {
+        assert_builds(chunk(vec![
+            stmt::call(expr::name("print"), [expr::string("say \"hi\"\\\n\t\u{1}2")]),
+        ]), r#"print("say \"hi\"\\\n\t\0012")"#);
+    }
+
+    #[test]
+    fn ids_are_synthetic() {
+        let statement = stmt::local(["x"], [expr::num("1")]);
+        assert_eq!(statement.id, NodeId::DUMMY);
+    }
+}
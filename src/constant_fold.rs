@@ -0,0 +1,343 @@
+//! Works out expressions whose operands are all constants ahead of time, for
+//! shipping code that does less at runtime.
+//!
+//! ```
+//! use mab::emitter::print_chunk;
+//!
+//! let tokens = mab::tokenize("
+//!     local day = 24 * 60 * 60
+//!     local greeting = 'hello, ' .. 'world'
+//!     local debug = not true and print('debugging')
+//! ").unwrap();
+//! let chunk = mab::constant_fold::fold_constants(mab::parse_from_tokens(&tokens).unwrap());
+//!
+//! assert_eq!(print_chunk(&chunk), "\
+//! local day = 86400
+//! local greeting = \"hello, world\"
+//! local debug = false
+//! ");
+//! ```
+//!
+//! Folding follows Lua 5.3's rules, so integers wrap around and stay integers,
+//! `/` and `^` always make floats, and `//` and `%` round towards negative
+//! infinity. Anything that would raise an error, like dividing an integer by
+//! zero or adding a string to a number, is left for runtime, and so are
+//! results that can't be written as a literal, like NaN and infinity.
+//!
+//! Operators between strings other than `..`, `==`, and `~=` are left alone,
+//! since how they behave depends on the locale. So are `..` with floats,
+//! since Lua's formatting of them doesn't round-trip.
+
+use std::borrow::Cow;
+
+use ast::*;
+use fold::{self, Fold};
+use tokenizer::StringLiteral;
+
+pub fn fold_constants(chunk: Chunk) -> Chunk {
+    ConstantFolder.fold_chunk(chunk)
+}
+
+struct ConstantFolder;
+
+impl<'a> Fold<'a> for ConstantFolder {
+    fn fold_expression(&mut self, expression: Expression<'a>) -> Expression<'a> {
+        let expression = fold::walk_expression(self, expression);
+        let id = expression.id;
+
+        let value = match expression.kind {
+            ExpressionKind::BinaryOp(BinaryOp { operator: BinaryOpKind::And, left, right }) => {
+                return short_circuit(id, BinaryOpKind::And, *left, *right);
+            },
+            ExpressionKind::BinaryOp(BinaryOp { operator: BinaryOpKind::Or, left, right }) => {
+                return short_circuit(id, BinaryOpKind::Or, *left, *right);
+            },
+            ExpressionKind::BinaryOp(ref op) => match (constant(&op.left), constant(&op.right)) {
+                (Some(left), Some(right)) => binary(op.operator, &left, &right),
+                _ => None,
+            },
+
+            // `-1` is already as folded as it gets.
+            ExpressionKind::UnaryOp(UnaryOp { operator: UnaryOpKind::Negate, ref argument })
+                if matches!(argument.kind, ExpressionKind::Number(_)) => None,
+
+            ExpressionKind::UnaryOp(ref op) => constant(&op.argument).and_then(|argument| unary(&op.operator, &argument)),
+            _ => None,
+        };
+
+        value.and_then(|value| value.into_expression(id)).unwrap_or(expression)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Nil,
+    Bool(bool),
+    Integer(i64),
+    Float(f64),
+    String(Vec<u8>),
+}
+
+impl Value {
+    fn is_truthy(&self) -> bool {
+        !matches!(*self, Value::Nil | Value::Bool(false))
+    }
+
+    fn as_float(&self) -> Option<f64> {
+        match *self {
+            Value::Integer(integer) => Some(integer as f64),
+            Value::Float(float) => Some(float),
+            _ => None,
+        }
+    }
+
+    fn into_expression<'a>(self, id: NodeId) -> Option<Expression<'a>> {
+        let kind = match self {
+            Value::Nil => ExpressionKind::Nil,
+            Value::Bool(value) => ExpressionKind::Bool(value),
+            Value::String(value) => ExpressionKind::String(StringLiteral::double_quoted(&value)),
+
+            // The most negative integer's digits would be read as a float.
+            Value::Integer(i64::MIN) => return None,
+            Value::Integer(integer) if integer < 0 => negative(integer.unsigned_abs().to_string()),
+            Value::Integer(integer) => ExpressionKind::Number(Cow::Owned(integer.to_string())),
+
+            // Debug formatting always includes a `.` or an exponent, so the
+            // literal stays a float.
+            Value::Float(float) if !float.is_finite() => return None,
+            Value::Float(float) if float.is_sign_negative() => negative(format!("{:?}", -float)),
+            Value::Float(float) => ExpressionKind::Number(Cow::Owned(format!("{:?}", float))),
+        };
+
+        Some(Expression {
+            id,
+            kind,
+        })
+    }
+}
+
+fn negative<'a>(digits: String) -> ExpressionKind<'a> {
+    ExpressionKind::UnaryOp(UnaryOp {
+        operator: UnaryOpKind::Negate,
+        argument: Box::new(Expression {
+            id: NodeId::DUMMY,
+            kind: ExpressionKind::Number(Cow::Owned(digits)),
+        }),
+    })
+}
+
+// The value of an expression that's already a constant.
+fn constant(expression: &Expression) -> Option<Value> {
+    match expression.kind {
+        ExpressionKind::Nil => Some(Value::Nil),
+        ExpressionKind::Bool(value) => Some(Value::Bool(value)),
+        ExpressionKind::Number(ref number) => parse_number(number),
+        ExpressionKind::String(ref literal) => Some(Value::String(literal.value().to_vec())),
+        ExpressionKind::ParenExpression(ref inner) => constant(inner),
+        ExpressionKind::UnaryOp(UnaryOp { operator: UnaryOpKind::Negate, ref argument }) => {
+            constant(argument).and_then(|argument| unary(&UnaryOpKind::Negate, &argument))
+        },
+        _ => None,
+    }
+}
+
+// Decimal integers too big for 64 bits are floats, but hex ones wrap around.
+// Hex floats are left alone.
+fn parse_number(number: &str) -> Option<Value> {
+    if number.starts_with("0x") || number.starts_with("0X") {
+        let digits = &number[2..];
+        if !digits.chars().all(|character| character.is_ascii_hexdigit()) {
+            return None;
+        }
+
+        let value = digits.chars().fold(0u64, |value, digit| {
+            value.wrapping_mul(16).wrapping_add(digit.to_digit(16).unwrap() as u64)
+        });
+
+        return Some(Value::Integer(value as i64));
+    }
+
+    if number.contains(['.', 'e', 'E']) {
+        return number.parse().ok().map(Value::Float);
+    }
+
+    match number.parse() {
+        Ok(integer) => Some(Value::Integer(integer)),
+        Err(_) => number.parse().ok().map(Value::Float),
+    }
+}
+
+fn unary(operator: &UnaryOpKind, argument: &Value) -> Option<Value> {
+    match (operator, argument) {
+        (&UnaryOpKind::Negate, &Value::Integer(integer)) => Some(Value::Integer(integer.wrapping_neg())),
+        (&UnaryOpKind::Negate, &Value::Float(float)) => Some(Value::Float(-float)),
+        (&UnaryOpKind::BooleanNot, argument) => Some(Value::Bool(!argument.is_truthy())),
+        (UnaryOpKind::Length, Value::String(string)) => Some(Value::Integer(string.len() as i64)),
+        _ => None,
+    }
+}
+
+fn binary(operator: BinaryOpKind, left: &Value, right: &Value) -> Option<Value> {
+    use self::Value::{Float, Integer};
+
+    match operator {
+        BinaryOpKind::Add | BinaryOpKind::Subtract | BinaryOpKind::Multiply => {
+            if let (&Integer(left), &Integer(right)) = (left, right) {
+                return Some(Integer(match operator {
+                    BinaryOpKind::Add => left.wrapping_add(right),
+                    BinaryOpKind::Subtract => left.wrapping_sub(right),
+                    _ => left.wrapping_mul(right),
+                }));
+            }
+
+            let (left, right) = (left.as_float()?, right.as_float()?);
+            Some(Float(match operator {
+                BinaryOpKind::Add => left + right,
+                BinaryOpKind::Subtract => left - right,
+                _ => left * right,
+            }))
+        },
+        BinaryOpKind::Divide => Some(Float(left.as_float()? / right.as_float()?)),
+        BinaryOpKind::Exponent => Some(Float(left.as_float()?.powf(right.as_float()?))),
+        BinaryOpKind::FloorDivide => match (left, right) {
+            (&Integer(_), &Integer(0)) => None,
+            (&Integer(left), &Integer(right)) => {
+                let quotient = left.wrapping_div(right);
+                let inexact = left.wrapping_rem(right) != 0;
+                Some(Integer(if inexact && (left < 0) != (right < 0) { quotient - 1 } else { quotient }))
+            },
+            _ => Some(Float((left.as_float()? / right.as_float()?).floor())),
+        },
+        BinaryOpKind::Modulo => match (left, right) {
+            (&Integer(_), &Integer(0)) => None,
+            (&Integer(left), &Integer(right)) => {
+                let remainder = left.wrapping_rem(right);
+                Some(Integer(if remainder != 0 && (remainder < 0) != (right < 0) { remainder + right } else { remainder }))
+            },
+            _ => {
+                let (left, right) = (left.as_float()?, right.as_float()?);
+                let remainder = left % right;
+                Some(Float(if remainder != 0.0 && (remainder < 0.0) != (right < 0.0) { remainder + right } else { remainder }))
+            },
+        },
+        BinaryOpKind::Concat => {
+            let mut string = concat_piece(left)?;
+            string.extend(concat_piece(right)?);
+            Some(Value::String(string))
+        },
+        BinaryOpKind::Equal => equal(left, right).map(Value::Bool),
+        BinaryOpKind::NotEqual => equal(left, right).map(|equal| Value::Bool(!equal)),
+        BinaryOpKind::LessThan => compare(left, right).map(|ordering| Value::Bool(ordering.is_lt())),
+        BinaryOpKind::LessThanEqual => compare(left, right).map(|ordering| Value::Bool(ordering.is_le())),
+        BinaryOpKind::GreaterThan => compare(left, right).map(|ordering| Value::Bool(ordering.is_gt())),
+        BinaryOpKind::GreaterThanEqual => compare(left, right).map(|ordering| Value::Bool(ordering.is_ge())),
+        BinaryOpKind::And | BinaryOpKind::Or => None,
+    }
+}
+
+fn concat_piece(value: &Value) -> Option<Vec<u8>> {
+    match *value {
+        Value::String(ref string) => Some(string.clone()),
+        Value::Integer(integer) => Some(integer.to_string().into_bytes()),
+        _ => None,
+    }
+}
+
+// Integers past 2^53 can't all be turned into floats exactly, so comparing
+// them with floats is left for runtime.
+fn exact_float(value: &Value) -> Option<f64> {
+    match *value {
+        Value::Integer(integer) if integer.unsigned_abs() <= 1 << 53 => Some(integer as f64),
+        Value::Integer(_) => None,
+        Value::Float(float) => Some(float),
+        _ => None,
+    }
+}
+
+fn equal(left: &Value, right: &Value) -> Option<bool> {
+    match (left, right) {
+        (&Value::Integer(left), &Value::Integer(right)) => Some(left == right),
+        (&Value::Integer(_), &Value::Float(_)) | (&Value::Float(_), &Value::Integer(_)) | (&Value::Float(_), &Value::Float(_)) => {
+            Some(exact_float(left)? == exact_float(right)?)
+        },
+        _ => Some(left == right),
+    }
+}
+
+fn compare(left: &Value, right: &Value) -> Option<::std::cmp::Ordering> {
+    match (left, right) {
+        (&Value::Integer(left), &Value::Integer(right)) => Some(left.cmp(&right)),
+        _ => exact_float(left)?.partial_cmp(&exact_float(right)?),
+    }
+}
+
+// `and` and `or` only need their left side to be constant. When the right
+// side is the result and it's a call or `...`, it keeps the parentheses
+// the operator gave it, so it still only makes one value.
+fn short_circuit<'a>(id: NodeId, operator: BinaryOpKind, left: Expression<'a>, right: Expression<'a>) -> Expression<'a> {
+    let truthy = match constant(&left) {
+        Some(value) => value.is_truthy(),
+        None => {
+            return Expression {
+                id,
+                kind: ExpressionKind::BinaryOp(BinaryOp {
+                    operator,
+                    left: Box::new(left),
+                    right: Box::new(right),
+                }),
+            };
+        },
+    };
+
+    let right_wins = (operator == BinaryOpKind::And) == truthy;
+    if !right_wins {
+        return left;
+    }
+
+    match right.kind {
+        ExpressionKind::FunctionCall(_) | ExpressionKind::VarArg => Expression {
+            id,
+            kind: ExpressionKind::ParenExpression(Box::new(right)),
+        },
+        _ => right,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use emitter::print_chunk;
+    use tokenizer::tokenize;
+    use parser::parse_from_tokens;
+
+    fn folded(source: &str) -> String {
+        let source = format!("return {}", source);
+        let tokens = tokenize(&source).unwrap();
+        let printed = print_chunk(&fold_constants(parse_from_tokens(&tokens).unwrap()));
+        printed["return ".len()..printed.len() - 1].to_string()
+    }
+
+    #[test]
+    fn arithmetic() {
+        assert_eq!(folded("2 * 60 * 60, 1 + 2.5, 7 / 2, 2 ^ 10, -(3 - 5)"), "7200, 3.5, 3.5, 1024.0, 2");
+        assert_eq!(folded("7 // 2, -7 // 2, 7 // -2.0, 7 % 3, -7 % 3, 7 % -3, 5.5 % -2"), "3, -4, -4.0, 1, 2, -2, -0.5");
+        assert_eq!(folded("0x7FFFFFFFFFFFFFFF + 1, 0xFFFFFFFFFFFFFFFF, 9223372036854775808"), "0x7FFFFFFFFFFFFFFF + 1, 0xFFFFFFFFFFFFFFFF, 9223372036854775808");
+        assert_eq!(folded("0x10 * 2, 1e2 + 1, 0x7FFFFFFFFFFFFFFF * 2"), "32, 101.0, -2");
+    }
+
+    #[test]
+    fn left_for_runtime() {
+        assert_eq!(folded("1 // 0, 1 % 0, 1 / 0, 0 / 0, '10' + 1, 1.5 .. 'x', 'a' < 'b', x + 1, #t"), "1 // 0, 1 % 0, 1 / 0, 0 / 0, '10' + 1, 1.5 .. 'x', 'a' < 'b', x + 1, #t");
+    }
+
+    #[test]
+    fn strings_and_comparisons() {
+        assert_eq!(folded("'a' .. 'b' .. 1, #'hello', 'x' == 'x', 1 == 1.0, 1 ~= '1', nil == false, 3 < 2.5"), "\"ab1\", 5, true, true, true, false, false");
+    }
+
+    #[test]
+    fn logic() {
+        assert_eq!(folded("not true, not nil, not 0, true and x, false and x, nil or f(), 1 or x, x and false"), "false, true, false, x, false, (f()), 1, x and false");
+        assert_eq!(folded("(1 + 2) * 3, true and ..."), "9, (...)");
+    }
+}
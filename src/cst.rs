@@ -0,0 +1,262 @@
+//! A syntax tree that remembers the tokens it came from, so that code can be
+//! written back out with its whitespace, comments, and literal spellings
+//! intact.
+//!
+//! ```
+//! let source = "-- Greets people.\nlocal name = 'world'  -- TODO: ask\nprint(\"hi, \" .. name)\n";
+//! let tokens = mab::tokenize(source).unwrap();
+//! let mut cst = mab::cst::Cst::parse(&tokens).unwrap();
+//!
+//! assert_eq!(cst.print_exact(), source);
+//!
+//! cst.chunk_mut().statements.pop();
+//! assert_eq!(cst.print_exact(), "-- Greets people.\nlocal name = 'world'  -- TODO: ask\n");
+//! ```
+//!
+//! Changes are tracked one top-level statement at a time. Statements that are
+//! unchanged keep their original text, along with the comments and whitespace
+//! in front of them. Changed statements keep the comments in front of them,
+//! but are written by the [emitter][::emitter], and new statements each go
+//! on a new line.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::ops::Range;
+
+use ast::*;
+use emitter::print_statement;
+use parser::{parse_from_tokens_with_source_map, ParseError, SourceMap};
+use tokenizer::{Token, TokenKind, TokenPrefix};
+
+/// A parsed chunk, along with the tokens it was parsed from.
+#[derive(Debug, Clone)]
+pub struct Cst<'a> {
+    tokens: &'a [Token<'a>],
+    source_map: SourceMap,
+    chunk: Chunk<'a>,
+
+    // The top-level statements as they were parsed.
+    original: HashMap<NodeId, Statement<'a>>,
+
+    // For each top-level statement, the tokens from its first up to the first
+    // of the statement after it.
+    extents: HashMap<NodeId, Range<usize>>,
+}
+
+impl<'a> Cst<'a> {
+    #[allow(clippy::result_large_err)]
+    pub fn parse(tokens: &'a [Token<'a>]) -> Result<Cst<'a>, ParseError> {
+        let (chunk, source_map) = parse_from_tokens_with_source_map(tokens)?;
+
+        let starts = chunk.statements.iter()
+            .map(|statement| token_at(tokens, source_map.span(statement.id).map_or(0, |span| span.start)))
+            .collect::<Vec<_>>();
+
+        // Everything up to the end of file token, if there is one, belongs to
+        // the last statement.
+        let end_of_file = match tokens.last() {
+            Some(&Token { kind: TokenKind::EndOfFile, .. }) => tokens.len() - 1,
+            _ => tokens.len(),
+        };
+
+        let extents = chunk.statements.iter()
+            .enumerate()
+            .map(|(index, statement)| {
+                let end = starts.get(index + 1).cloned().unwrap_or(end_of_file);
+                (statement.id, starts[index]..end)
+            })
+            .collect();
+
+        let original = chunk.statements.iter()
+            .map(|statement| (statement.id, statement.clone()))
+            .collect();
+
+        Ok(Cst {
+            tokens,
+            source_map,
+            chunk,
+            original,
+            extents,
+        })
+    }
+
+    pub fn chunk(&self) -> &Chunk<'a> {
+        &self.chunk
+    }
+
+    pub fn chunk_mut(&mut self) -> &mut Chunk<'a> {
+        &mut self.chunk
+    }
+
+    pub fn into_chunk(self) -> Chunk<'a> {
+        self.chunk
+    }
+
+    /// Every token the chunk was parsed from, including the comments after
+    /// its last statement.
+    pub fn all_tokens(&self) -> &'a [Token<'a>] {
+        self.tokens
+    }
+
+    /// The tokens a node was parsed from. Whitespace and comments in front of
+    /// the node are in the first token's prefix.
+    ///
+    /// Nodes that weren't parsed from the tokens don't have any, and nodes
+    /// that have been changed still have the tokens they started with.
+    pub fn tokens(&self, id: NodeId) -> Option<&'a [Token<'a>]> {
+        let span = self.source_map.span(id)?;
+        let start = token_at(self.tokens, span.start);
+        let end = token_at(self.tokens, span.end.max(span.start + 1));
+
+        Some(&self.tokens[start..end])
+    }
+
+    /// Writes the chunk out as source. If nothing has changed, this is
+    /// exactly the source it was parsed from.
+    pub fn print_exact(&self) -> String {
+        let mut output = String::new();
+
+        for statement in &self.chunk.statements {
+            let extent = match self.extents.get(&statement.id) {
+                Some(extent) => extent.clone(),
+                None => {
+                    if !output.is_empty() {
+                        output.push('\n');
+                    }
+
+                    output.push_str(&print_statement(statement));
+                    continue;
+                },
+            };
+
+            output.push_str(&self.leading_trivia(extent.start));
+
+            if self.original[&statement.id] == *statement {
+                output.push_str(&self.tokens[extent.start].kind.source_text());
+
+                for token in &self.tokens[extent.start + 1..extent.end] {
+                    for prefix in &token.prefix {
+                        let _ = write!(output, "{}", prefix);
+                    }
+
+                    output.push_str(&token.kind.source_text());
+                }
+            } else {
+                output.push_str(&print_statement(statement));
+            }
+
+            output.push_str(&self.trailing_trivia(extent.end));
+        }
+
+        // Whatever's after the last statement, like a comment at the end of
+        // the file, is in front of the end of file token.
+        if let Some(&Token { kind: TokenKind::EndOfFile, .. }) = self.tokens.last() {
+            output.push_str(&self.leading_trivia(self.tokens.len() - 1));
+        }
+
+        output
+    }
+
+    // A token's prefix is split at its first line break. What's before it,
+    // like a comment at the end of a line, belongs with the token before.
+    fn split_trivia(&self, index: usize) -> (String, String) {
+        let mut trailing = String::new();
+        let mut leading = String::new();
+        let mut past_line_break = index == 0;
+
+        // Past the last token, there's nothing.
+        let prefixes = self.tokens.get(index).map_or(&[][..], |token| &token.prefix[..]);
+
+        for prefix in prefixes {
+            match *prefix {
+                TokenPrefix::Whitespace(ref whitespace) if !past_line_break => {
+                    match whitespace.find('\n') {
+                        Some(line_break) => {
+                            trailing.push_str(&whitespace[..line_break]);
+                            leading.push_str(&whitespace[line_break..]);
+                            past_line_break = true;
+                        },
+                        None => trailing.push_str(whitespace),
+                    }
+                },
+                _ if !past_line_break => {
+                    let _ = write!(trailing, "{}", prefix);
+                },
+                _ => {
+                    let _ = write!(leading, "{}", prefix);
+                },
+            }
+        }
+
+        (trailing, leading)
+    }
+
+    fn trailing_trivia(&self, index: usize) -> String {
+        self.split_trivia(index).0
+    }
+
+    fn leading_trivia(&self, index: usize) -> String {
+        self.split_trivia(index).1
+    }
+}
+
+// The index of the first token that starts at or after `bytes`.
+fn token_at(tokens: &[Token], bytes: usize) -> usize {
+    tokens.partition_point(|token| token.start_position.bytes < bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use builder::{expr, stmt};
+    use tokenizer::tokenize;
+
+    const SOURCE: &str = "\u{feff}--[==[ Header ]==]
+local   t = { 0x10, 'a', [[b]]; } -- trailing
+
+if t  then
+\treturn t [1] ;
+end
+-- end of file";
+
+    #[test]
+    fn print_unchanged() {
+        let tokens = tokenize(SOURCE).unwrap();
+        let cst = Cst::parse(&tokens).unwrap();
+
+        assert_eq!(cst.print_exact(), SOURCE);
+    }
+
+    #[test]
+    fn print_changed() {
+        let tokens = tokenize("-- a\nlocal a = 1\n\n-- b\nlocal b = 2 -- two\n").unwrap();
+        let mut cst = Cst::parse(&tokens).unwrap();
+
+        {
+            let chunk = cst.chunk_mut();
+            let id = chunk.statements[0].id;
+            chunk.statements[0] = stmt::local(["a"], [expr::num("3")]);
+            chunk.statements[0].id = id;
+            chunk.statements.push(stmt::call(expr::name("print"), [expr::name("a")]));
+        }
+
+        assert_eq!(cst.print_exact(), "-- a\nlocal a = 3\n\n-- b\nlocal b = 2 -- two\nprint(a)\n");
+    }
+
+    #[test]
+    fn node_tokens() {
+        let tokens = tokenize("local x = a  +  b").unwrap();
+        let cst = Cst::parse(&tokens).unwrap();
+
+        let value = match cst.chunk().statements[0].kind {
+            StatementKind::LocalAssignment(ref local) => &local.values[0],
+            _ => unreachable!(),
+        };
+
+        let text = cst.tokens(value.id).unwrap().iter()
+            .map(|token| token.kind.source_text())
+            .collect::<Vec<_>>();
+
+        assert_eq!(text, ["a", "+", "b"]);
+    }
+}
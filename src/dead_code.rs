@@ -0,0 +1,333 @@
+//! Removes code that can never run, or whose only effect is declaring a local
+//! function nothing calls.
+//!
+//! ```
+//! use mab::emitter::print_chunk;
+//!
+//! let tokens = mab::tokenize("
+//!     local function unused() end
+//!     if false then
+//!         print('debugging')
+//!     end
+//!     do return end
+//!     print('unreachable')
+//! ").unwrap();
+//! let chunk = mab::dead_code::eliminate_dead_code(mab::parse_from_tokens(&tokens).unwrap());
+//!
+//! assert_eq!(print_chunk(&chunk), "do\n\treturn\nend\n");
+//! ```
+//!
+//! This removes:
+//!
+//! - statements that a [control flow graph][::analysis::cfg::Cfg] shows can't
+//!   be reached, like the ones after a `return`, `break`, or `goto`
+//! - branches of `if` statements whose conditions are always false, along
+//!   with the ones after a condition that's always true, and `while` loops
+//!   whose conditions are always false
+//! - `local function` statements for functions that are only used in their
+//!   own bodies, if at all
+//! - `do` blocks that end up empty
+//!
+//! Conditions only count as always true or false when they're literals, so
+//! running [fold_constants][::constant_fold::fold_constants] first finds
+//! more of them. Labels are always kept, since a `goto` could still jump to
+//! them.
+//!
+//! An `if` that loses every branch but its `else` becomes a `do` block with
+//! the `if`'s ID. Otherwise, the statements that are left keep their IDs,
+//! and the chunk needs IDs to begin with, like the ones the parser gives it.
+
+use std::collections::HashSet;
+
+use ast::*;
+use analysis::cfg::{Cfg, StepKind};
+use analysis::scope::{DeclarationKind, ScopeId, ScopeKind, ScopeTree};
+use fold::{self, Fold};
+
+pub fn eliminate_dead_code(chunk: Chunk) -> Chunk {
+    let mut chunk = BranchPruner.fold_chunk(chunk);
+
+    // Removing a function can leave the ones it called unused, so this
+    // repeats until there's nothing left to remove.
+    loop {
+        let dead = dead_statements(&chunk);
+        if dead.is_empty() {
+            return chunk;
+        }
+
+        chunk = Remover { dead }.fold_chunk(chunk);
+    }
+}
+
+struct BranchPruner;
+
+impl<'a> Fold<'a> for BranchPruner {
+    fn fold_chunk(&mut self, chunk: Chunk<'a>) -> Chunk<'a> {
+        let chunk = fold::walk_chunk(self, chunk);
+
+        Chunk {
+            statements: chunk.statements.into_iter().filter_map(prune_branches).collect(),
+        }
+    }
+}
+
+fn prune_branches(statement: Statement) -> Option<Statement> {
+    let id = statement.id;
+
+    match statement.kind {
+        StatementKind::WhileLoop(ref while_loop) if is_truthy(&while_loop.condition) == Some(false) => None,
+        StatementKind::IfStatement(if_statement) => {
+            let mut branches = Vec::new();
+            let mut else_branch = if_statement.else_branch;
+
+            let all_branches = Some((if_statement.condition, if_statement.body)).into_iter()
+                .chain(if_statement.else_if_branches);
+
+            for (condition, body) in all_branches {
+                match is_truthy(&condition) {
+                    Some(false) => {},
+                    Some(true) => {
+                        else_branch = Some(body);
+                        break;
+                    },
+                    None => branches.push((condition, body)),
+                }
+            }
+
+            if branches.is_empty() {
+                return else_branch.map(|body| Statement {
+                    id,
+                    kind: StatementKind::DoBlock(DoBlock { body }),
+                });
+            }
+
+            let (condition, body) = branches.remove(0);
+            Some(Statement {
+                id,
+                kind: StatementKind::IfStatement(IfStatement {
+                    condition,
+                    body,
+                    else_if_branches: branches,
+                    else_branch,
+                }),
+            })
+        },
+        _ => Some(statement),
+    }
+}
+
+// Whether a condition is always true or always false.
+fn is_truthy(condition: &Expression) -> Option<bool> {
+    match condition.kind {
+        ExpressionKind::Nil => Some(false),
+        ExpressionKind::Bool(value) => Some(value),
+        ExpressionKind::Number(_) | ExpressionKind::String(_) => Some(true),
+        ExpressionKind::ParenExpression(ref inner) => is_truthy(inner),
+        _ => None,
+    }
+}
+
+struct Remover {
+    dead: HashSet<NodeId>,
+}
+
+impl<'a> Fold<'a> for Remover {
+    fn fold_chunk(&mut self, chunk: Chunk<'a>) -> Chunk<'a> {
+        let mut chunk = fold::walk_chunk(self, chunk);
+        chunk.statements.retain(|statement| !self.dead.contains(&statement.id));
+        chunk
+    }
+}
+
+fn dead_statements(chunk: &Chunk) -> HashSet<NodeId> {
+    let scopes = ScopeTree::new(chunk);
+
+    let mut cfgs = vec![Cfg::for_chunk(chunk, &scopes)];
+    for node in chunk.descendants() {
+        if let NodeRef::Statement(statement) = node {
+            cfgs.extend(Cfg::for_function(statement, &scopes));
+        }
+    }
+
+    // Every function's graph starts with a step for its parameters, which
+    // belongs to its declaration. Leaving those out means each statement's
+    // steps all come from the graph of the function it's in.
+    let mut reached = HashSet::new();
+    for cfg in &cfgs {
+        for id in cfg.reachable() {
+            let steps = cfg.block(id).steps.iter().filter(|step| step.kind != StepKind::Parameters);
+            reached.extend(steps.map(|step| step.statement));
+        }
+    }
+
+    let mut dead = HashSet::new();
+    unreachable_statements(chunk, &reached, &mut dead);
+
+    for id in scopes.declaration_ids() {
+        let declaration = scopes.declaration(id);
+        if declaration.kind != DeclarationKind::LocalFunction {
+            continue;
+        }
+
+        let used = scopes.references_to(id)
+            .any(|reference| !is_inside_function(&scopes, reference.scope, declaration.statement));
+
+        if !used {
+            dead.insert(declaration.statement);
+        }
+    }
+
+    dead.remove(&NodeId::DUMMY);
+    dead
+}
+
+// Adds the statements in `chunk` that can't run to `dead`, and returns
+// whether any of them can.
+fn unreachable_statements(chunk: &Chunk, reached: &HashSet<NodeId>, dead: &mut HashSet<NodeId>) -> bool {
+    let mut any_live = false;
+
+    for statement in &chunk.statements {
+        let live = match statement.kind {
+            StatementKind::Label(_) | StatementKind::Empty => true,
+
+            // The body has its own graph, so whether it runs doesn't say
+            // anything about the declaration.
+            StatementKind::FunctionDeclaration(ref function) => {
+                unreachable_statements(&function.body, reached, dead);
+                reached.contains(&statement.id)
+            },
+
+            // A statement with a reachable part is kept whole, like a
+            // `repeat` loop whose condition can't be reached because its
+            // body always breaks.
+            _ => {
+                let mut inner_live = false;
+                for body in bodies(statement) {
+                    inner_live |= unreachable_statements(body, reached, dead);
+                }
+
+                inner_live || reached.contains(&statement.id)
+            },
+        };
+
+        if !live {
+            dead.insert(statement.id);
+        }
+
+        any_live |= live;
+    }
+
+    any_live
+}
+
+fn bodies<'s, 'a>(statement: &'s Statement<'a>) -> Vec<&'s Chunk<'a>> {
+    match statement.kind {
+        StatementKind::IfStatement(ref if_statement) => Some(&if_statement.body).into_iter()
+            .chain(if_statement.else_if_branches.iter().map(|(_, body)| body))
+            .chain(if_statement.else_branch.as_ref())
+            .collect(),
+        StatementKind::DoBlock(ref do_block) => vec![&do_block.body],
+        StatementKind::WhileLoop(ref while_loop) => vec![&while_loop.body],
+        StatementKind::RepeatLoop(ref repeat_loop) => vec![&repeat_loop.body],
+        StatementKind::NumericFor(ref numeric_for) => vec![&numeric_for.body],
+        StatementKind::GenericFor(ref generic_for) => vec![&generic_for.body],
+        _ => Vec::new(),
+    }
+}
+
+fn is_inside_function(scopes: &ScopeTree, scope: ScopeId, function: NodeId) -> bool {
+    let mut scope = Some(scope);
+
+    while let Some(id) = scope {
+        let current = scopes.scope(id);
+        if current.kind == ScopeKind::Function && current.statement == Some(function) {
+            return true;
+        }
+
+        scope = current.parent;
+    }
+
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use emitter::print_chunk;
+    use tokenizer::tokenize;
+    use parser::parse_from_tokens;
+
+    fn eliminated(source: &str) -> String {
+        let tokens = tokenize(source).unwrap();
+        print_chunk(&eliminate_dead_code(parse_from_tokens(&tokens).unwrap()))
+    }
+
+    fn printed(source: &str) -> String {
+        let tokens = tokenize(source).unwrap();
+        print_chunk(&parse_from_tokens(&tokens).unwrap())
+    }
+
+    #[test]
+    fn unreachable() {
+        assert_eq!(
+            eliminated("while x do if y then do break end print(1) end print(2) end do return end print(3)"),
+            printed("while x do if y then do break end end print(2) end do return end"),
+        );
+
+        assert_eq!(
+            eliminated("goto skip print(1) do print(2) end ::skip:: print(3)"),
+            printed("goto skip ::skip:: print(3)"),
+        );
+
+        // Each block's goto jumps to its own label, not the first one with
+        // that name.
+        assert_eq!(
+            eliminated("do goto l print(1) ::l:: end do goto l print(2) ::l:: print(3) end"),
+            printed("do goto l ::l:: end do goto l ::l:: print(3) end"),
+        );
+
+        // The condition can't be reached, but the loop still runs once.
+        let source = "repeat print(1) do break end until x";
+        assert_eq!(eliminated(source), printed(source));
+    }
+
+    #[test]
+    fn constant_branches() {
+        assert_eq!(
+            eliminated("if false then a() elseif x then b() elseif nil then c() else d() end while (false) do e() end"),
+            printed("if x then b() else d() end"),
+        );
+
+        assert_eq!(eliminated("if x then a() elseif 1 then b() else c() end"), printed("if x then a() else b() end"));
+        assert_eq!(eliminated("if false then a() else local x = 1 b(x) end"), printed("do local x = 1 b(x) end"));
+        assert_eq!(eliminated("if nil then a() end if true then end"), "");
+    }
+
+    #[test]
+    fn unused_local_functions() {
+        assert_eq!(
+            eliminated("
+                local function helper() end
+                local function unused(n) helper() return unused(n - 1) end
+                local function used() end
+                local function reassigned() end
+                used()
+                reassigned = nil
+            "),
+            printed("local function used() end local function reassigned() end used() reassigned = nil"),
+        );
+
+        assert_eq!(
+            eliminated("function outer() local function inner() end return 1 end"),
+            printed("function outer() return 1 end"),
+        );
+    }
+
+    #[test]
+    fn function_bodies() {
+        assert_eq!(
+            eliminated("local function f() return 1 end local t = {} function t.m() do return f() end print(1) end"),
+            printed("local function f() return 1 end local t = {} function t.m() do return f() end end"),
+        );
+    }
+}
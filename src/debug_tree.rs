@@ -0,0 +1,331 @@
+use std::fmt::{self, Write};
+
+use ast::*;
+use tokenizer::StringLiteral;
+
+/// An indented outline of a chunk, without the IDs, `Box`es, and `Cow`s that
+/// make the derived `Debug` output so long. Made by
+/// [Chunk::debug_tree][Chunk::debug_tree].
+///
+/// ```
+/// let tokens = mab::tokenize("if x then print('hi', y + 1) end").unwrap();
+/// let chunk = mab::parse_from_tokens(&tokens).unwrap();
+///
+/// assert_eq!(chunk.debug_tree().to_string(), "\
+/// If
+///   condition: Name x
+///   then
+///     Call
+///       function: Name print
+///       argument: String \"hi\"
+///       argument: Binary +
+///         Name y
+///         Number 1
+/// ");
+/// ```
+pub struct DebugTree<'c, 'a: 'c> {
+    chunk: &'c Chunk<'a>,
+}
+
+impl<'a> Chunk<'a> {
+    pub fn debug_tree(&self) -> DebugTree<'_, 'a> {
+        DebugTree {
+            chunk: self,
+        }
+    }
+}
+
+impl<'c, 'a> fmt::Display for DebugTree<'c, 'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut writer = TreeWriter {
+            out: String::new(),
+            depth: 0,
+        };
+
+        writer.statements(self.chunk);
+        f.write_str(&writer.out)
+    }
+}
+
+impl<'c, 'a> fmt::Debug for DebugTree<'c, 'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+struct TreeWriter {
+    out: String,
+    depth: usize,
+}
+
+impl TreeWriter {
+    // Writes one node. `prefix` says what the node is to its parent, like
+    // "condition: ", and can be empty.
+    fn line(&mut self, prefix: &str, text: &str) {
+        for _ in 0..self.depth {
+            self.out.push_str("  ");
+        }
+
+        self.out.push_str(prefix);
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    fn nested<F: FnOnce(&mut TreeWriter)>(&mut self, write_children: F) {
+        self.depth += 1;
+        write_children(self);
+        self.depth -= 1;
+    }
+
+    fn statements(&mut self, chunk: &Chunk) {
+        for statement in &chunk.statements {
+            self.statement(statement);
+        }
+    }
+
+    fn block(&mut self, label: &str, chunk: &Chunk) {
+        self.line("", label);
+        self.nested(|this| this.statements(chunk));
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        match statement.kind {
+            StatementKind::Assignment(ref assignment) => {
+                self.line("", "Assignment");
+                self.nested(|this| {
+                    for target in &assignment.targets {
+                        this.expression("target: ", target);
+                    }
+
+                    for value in &assignment.values {
+                        this.expression("value: ", value);
+                    }
+                });
+            },
+            StatementKind::LocalAssignment(ref local) => {
+                self.line("", &format!("Local {}", local.names.join(", ")));
+                self.nested(|this| {
+                    for value in &local.values {
+                        this.expression("", value);
+                    }
+                });
+            },
+            StatementKind::FunctionCall(ref call) => self.call("", call),
+            StatementKind::NumericFor(ref numeric_for) => {
+                self.line("", &format!("NumericFor {}", numeric_for.var));
+                self.nested(|this| {
+                    this.expression("start: ", &numeric_for.start);
+                    this.expression("end: ", &numeric_for.end);
+
+                    if let Some(ref step) = numeric_for.step {
+                        this.expression("step: ", step);
+                    }
+
+                    this.block("do", &numeric_for.body);
+                });
+            },
+            StatementKind::GenericFor(ref generic_for) => {
+                self.line("", &format!("GenericFor {}", generic_for.vars.join(", ")));
+                self.nested(|this| {
+                    for value in &generic_for.item_source {
+                        this.expression("in: ", value);
+                    }
+
+                    this.block("do", &generic_for.body);
+                });
+            },
+            StatementKind::IfStatement(ref if_statement) => {
+                self.line("", "If");
+                self.nested(|this| {
+                    this.expression("condition: ", &if_statement.condition);
+                    this.block("then", &if_statement.body);
+
+                    for (condition, body) in &if_statement.else_if_branches {
+                        this.expression("elseif: ", condition);
+                        this.block("then", body);
+                    }
+
+                    if let Some(ref else_branch) = if_statement.else_branch {
+                        this.block("else", else_branch);
+                    }
+                });
+            },
+            StatementKind::DoBlock(ref do_block) => {
+                self.line("", "Do");
+                self.nested(|this| this.statements(&do_block.body));
+            },
+            StatementKind::WhileLoop(ref while_loop) => {
+                self.line("", "While");
+                self.nested(|this| {
+                    this.expression("condition: ", &while_loop.condition);
+                    this.block("do", &while_loop.body);
+                });
+            },
+            StatementKind::RepeatLoop(ref repeat_loop) => {
+                self.line("", "Repeat");
+                self.nested(|this| {
+                    this.block("do", &repeat_loop.body);
+                    this.expression("until: ", &repeat_loop.condition);
+                });
+            },
+            StatementKind::FunctionDeclaration(ref declaration) => {
+                let mut text = String::from(if declaration.local { "LocalFunction " } else { "Function " });
+                text.push_str(&declaration.name.path.join("."));
+
+                if let Some(ref method) = declaration.name.method {
+                    let _ = write!(text, ":{}", method);
+                }
+
+                let mut parameters = declaration.parameters.iter().map(|name| name.to_string()).collect::<Vec<_>>();
+                if declaration.is_variadic {
+                    parameters.push("...".to_string());
+                }
+
+                let _ = write!(text, "({})", parameters.join(", "));
+                self.line("", &text);
+                self.nested(|this| this.statements(&declaration.body));
+            },
+            StatementKind::Goto(ref goto) => self.line("", &format!("Goto {}", goto.label)),
+            StatementKind::Label(ref label) => self.line("", &format!("Label {}", label.name)),
+            StatementKind::Return(ref return_statement) => {
+                self.line("", "Return");
+                self.nested(|this| {
+                    for value in &return_statement.values {
+                        this.expression("", value);
+                    }
+                });
+            },
+            StatementKind::Break => self.line("", "Break"),
+            StatementKind::Empty => self.line("", "Empty"),
+        }
+    }
+
+    fn expression(&mut self, prefix: &str, expression: &Expression) {
+        match expression.kind {
+            ExpressionKind::Nil => self.line(prefix, "Nil"),
+            ExpressionKind::Bool(value) => self.line(prefix, &format!("Bool {}", value)),
+            ExpressionKind::Number(ref value) => self.line(prefix, &format!("Number {}", value)),
+            ExpressionKind::String(ref literal) => self.line(prefix, &string(literal)),
+            ExpressionKind::VarArg => self.line(prefix, "VarArg"),
+            ExpressionKind::Table(ref table) => self.table(prefix, table),
+            ExpressionKind::FunctionCall(ref call) => self.call(prefix, call),
+            ExpressionKind::Name(ref name) => self.line(prefix, &format!("Name {}", name)),
+            ExpressionKind::FieldAccess(ref field_access) => {
+                self.line(prefix, &format!("Field {}", field_access.name));
+                self.nested(|this| this.expression("", &field_access.target));
+            },
+            ExpressionKind::Index(ref index) => {
+                self.line(prefix, "Index");
+                self.nested(|this| {
+                    this.expression("", &index.target);
+                    this.expression("key: ", &index.key);
+                });
+            },
+            ExpressionKind::ParenExpression(ref inner) => {
+                self.line(prefix, "Parenthesized");
+                self.nested(|this| this.expression("", inner));
+            },
+            ExpressionKind::UnaryOp(ref unary_op) => {
+                self.line(prefix, &format!("Unary {}", unary_op.operator.to_str()));
+                self.nested(|this| this.expression("", &unary_op.argument));
+            },
+            ExpressionKind::BinaryOp(ref binary_op) => {
+                self.line(prefix, &format!("Binary {}", binary_op.operator.to_str()));
+                self.nested(|this| {
+                    this.expression("", &binary_op.left);
+                    this.expression("", &binary_op.right);
+                });
+            },
+        }
+    }
+
+    fn call(&mut self, prefix: &str, call: &FunctionCall) {
+        match call.method {
+            Some(ref method) => self.line(prefix, &format!("MethodCall {}", method)),
+            None => self.line(prefix, "Call"),
+        }
+
+        self.nested(|this| {
+            this.expression("function: ", &call.name_expression);
+
+            match call.arguments {
+                FunctionArguments::Parenthesized(ref arguments) => {
+                    for argument in arguments {
+                        this.expression("argument: ", argument);
+                    }
+                },
+                FunctionArguments::String(ref literal) => this.line("argument: ", &string(literal)),
+                FunctionArguments::Table(ref table) => this.table("argument: ", table),
+            }
+        });
+    }
+
+    fn table(&mut self, prefix: &str, table: &TableLiteral) {
+        self.line(prefix, "Table");
+        self.nested(|this| {
+            for (key, value) in &table.items {
+                match *key {
+                    None => this.expression("", value),
+                    Some(TableKey::Name(ref name)) => this.expression(&format!("{} = ", name), value),
+                    Some(TableKey::Expression(ref key)) => {
+                        this.line("", "Keyed");
+                        this.nested(|this| {
+                            this.expression("key: ", key);
+                            this.expression("value: ", value);
+                        });
+                    },
+                }
+            }
+        });
+    }
+}
+
+fn string(literal: &StringLiteral) -> String {
+    format!("String {:?}", String::from_utf8_lossy(literal.value()))
+}
+
+#[cfg(test)]
+mod tests {
+    use tokenizer::tokenize;
+    use parser::parse_from_tokens;
+
+    fn tree(source: &str) -> String {
+        let tokens = tokenize(source).unwrap();
+        parse_from_tokens(&tokens).unwrap().debug_tree().to_string()
+    }
+
+    #[test]
+    fn statements() {
+        assert_eq!(tree("
+            local a, b = 1
+            for i = 1, #t, 2 do break end
+            while x do x = x:next() end
+            function m.f(y, ...) return { y, n = 1, [2] = ... } end
+        "), "\
+Local a, b
+  Number 1
+NumericFor i
+  start: Number 1
+  end: Unary #
+    Name t
+  step: Number 2
+  do
+    Break
+While
+  condition: Name x
+  do
+    Assignment
+      target: Name x
+      value: MethodCall next
+        function: Name x
+Function m.f(y, ...)
+  Return
+    Table
+      Name y
+      n = Number 1
+      Keyed
+        key: Number 2
+        value: VarArg
+");
+    }
+}
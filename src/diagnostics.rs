@@ -0,0 +1,155 @@
+//! Conversions from mab's errors to the diagnostic types of other crates, so
+//! that tools can print them with labels and underlines without writing their
+//! own renderer. Each conversion is behind a feature named after its crate:
+//! `codespan-reporting` or `ariadne`.
+
+use std::ops::Range;
+
+use parser::{ParseError, ParseErrorKind};
+use tokenizer::TokenizeError;
+
+// What to write under the offending part of the source.
+fn parse_error_label(error: &ParseError) -> String {
+    match error.kind {
+        ParseErrorKind::UnexpectedToken { .. } => format!("unexpected {}", error.found),
+        ParseErrorKind::KeywordAsName { .. } => "expected a name here".to_string(),
+        ParseErrorKind::VarArgNotLast => "parameter after `...`".to_string(),
+        ParseErrorKind::StatementAfterLastStatement { .. } => "statement after the end of the block".to_string(),
+    }
+}
+
+// The outer frames of a parse error. The innermost one is already in the
+// message.
+fn parse_error_notes(error: &ParseError) -> Vec<String> {
+    let outer = error.context.len().saturating_sub(1);
+
+    error.context[..outer].iter().rev()
+        .map(|frame| format!("while parsing {}", frame))
+        .collect()
+}
+
+// Tokenize errors only know where they start, which is always a character
+// boundary, so they get an empty span there.
+fn tokenize_error_span(error: &TokenizeError) -> Range<usize> {
+    let start = error.position().bytes;
+    start..start
+}
+
+/// Diagnostics take the ID of the file they belong to, as in
+/// `codespan_reporting::files`.
+#[cfg(feature = "codespan-reporting")]
+pub mod codespan {
+    use codespan_reporting::diagnostic::{Diagnostic, Label};
+
+    use parser::ParseError;
+    use tokenizer::TokenizeError;
+
+    pub fn parse_error<FileId>(error: &ParseError, file_id: FileId) -> Diagnostic<FileId> {
+        Diagnostic::error()
+            .with_code(error.code())
+            .with_message(error.message())
+            .with_labels(vec![
+                Label::primary(file_id, error.span()).with_message(super::parse_error_label(error)),
+            ])
+            .with_notes(super::parse_error_notes(error))
+    }
+
+    pub fn tokenize_error<FileId>(error: &TokenizeError, file_id: FileId) -> Diagnostic<FileId> {
+        Diagnostic::error()
+            .with_code(error.code())
+            .with_message(error.message())
+            .with_labels(vec![
+                Label::primary(file_id, super::tokenize_error_span(error)),
+            ])
+    }
+}
+
+/// Reports use byte offsets into a single source, which is what
+/// `ariadne::Source::from` expects.
+#[cfg(feature = "ariadne")]
+pub mod ariadne {
+    use std::ops::Range;
+
+    use ariadne::{Config, IndexType, Label, Report, ReportKind};
+
+    use parser::ParseError;
+    use tokenizer::TokenizeError;
+
+    fn config() -> Config {
+        Config::default().with_index_type(IndexType::Byte)
+    }
+
+    pub fn parse_error(error: &ParseError) -> Report<'static, Range<usize>> {
+        let mut report = Report::build(ReportKind::Error, error.span())
+            .with_config(config())
+            .with_code(error.code())
+            .with_message(error.message())
+            .with_label(Label::new(error.span()).with_message(super::parse_error_label(error)));
+
+        for note in super::parse_error_notes(error) {
+            report = report.with_note(note);
+        }
+
+        report.finish()
+    }
+
+    pub fn tokenize_error(error: &TokenizeError) -> Report<'static, Range<usize>> {
+        let span = super::tokenize_error_span(error);
+
+        Report::build(ReportKind::Error, span.clone())
+            .with_config(config())
+            .with_code(error.code())
+            .with_message(error.message())
+            .with_label(Label::new(span))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokenizer::tokenize;
+    use parser::parse_from_tokens;
+
+    #[cfg(feature = "codespan-reporting")]
+    #[test]
+    fn codespan_diagnostics() {
+        use codespan_reporting::diagnostic::LabelStyle;
+
+        let tokens = tokenize("local t = { f(1 2) }").unwrap();
+        let error = parse_from_tokens(&tokens).unwrap_err();
+        let diagnostic = super::codespan::parse_error(&error, ());
+
+        assert_eq!(diagnostic.message, "Unexpected `2`, expected `,` or `)` while parsing the arguments of a function call");
+        assert_eq!(diagnostic.code, Some("P001".to_string()));
+        assert_eq!(diagnostic.labels.len(), 1);
+        assert_eq!(diagnostic.labels[0].style, LabelStyle::Primary);
+        assert_eq!(diagnostic.labels[0].range, 16..17);
+        assert_eq!(diagnostic.labels[0].message, "unexpected `2`");
+        assert_eq!(diagnostic.notes, vec!["while parsing a table constructor", "while parsing a `local` declaration"]);
+
+        let error = tokenize("x = \"abc").unwrap_err();
+        let diagnostic = super::codespan::tokenize_error(&error, ());
+        assert_eq!(diagnostic.message, "Unterminated string");
+        assert_eq!(diagnostic.labels[0].range, 4..4);
+    }
+
+    #[cfg(feature = "ariadne")]
+    #[test]
+    fn ariadne_reports() {
+        use ariadne::Source;
+
+        let source = "local x = = 1";
+        let tokens = tokenize(source).unwrap();
+        let error = parse_from_tokens(&tokens).unwrap_err();
+
+        let mut output = Vec::new();
+        super::ariadne::parse_error(&error)
+            .write(Source::from(source), &mut output)
+            .unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        assert!(output.contains("P001"));
+        assert!(output.contains("Unexpected `=`, expected an expression"));
+        assert!(output.contains("unexpected `=`"));
+    }
+}
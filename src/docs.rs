@@ -0,0 +1,378 @@
+//! Reads documentation comments written for LDoc or EmmyLua, and works out
+//! which functions and assignments they're about.
+//!
+//! ```
+//! use mab::cst::Cst;
+//! use mab::docs::doc_comments;
+//!
+//! let tokens = mab::tokenize("
+//! --- Moves an entity.
+//! ---@param entity Entity The entity to move.
+//! ---@param dx number
+//! ---@return boolean # Whether it moved.
+//! local function move(entity, dx)
+//! end
+//! ").unwrap();
+//! let cst = Cst::parse(&tokens).unwrap();
+//!
+//! let docs = doc_comments(&cst);
+//! let doc = &docs[&cst.chunk().statements[0].id];
+//!
+//! assert_eq!(doc.description, "Moves an entity.");
+//! assert_eq!(doc.params[0].name, "entity");
+//! assert_eq!(doc.params[0].type_name.as_ref().unwrap(), "Entity");
+//! assert_eq!(doc.params[0].description, "The entity to move.");
+//! assert_eq!(doc.returns[0].description, "Whether it moved.");
+//! ```
+//!
+//! A doc comment is a run of single-line comments that starts with `---` and
+//! ends right before a function declaration, assignment, or `local`
+//! statement, without a blank line in between.
+//!
+//! Tags written straight after the dashes, like `---@param x number`, are
+//! read the way EmmyLua reads them, with the type after the name. Tags after
+//! a space, like `-- @param x The x`, are read the way LDoc reads them, which
+//! has no types unless the tag is `@tparam` or `@treturn`. Any other tag ends
+//! up in [DocComment::tags][DocComment::tags] as it's written.
+
+use std::collections::HashMap;
+
+use ast::*;
+use cst::Cst;
+use tokenizer::{Comment, TokenPrefix};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DocComment {
+    /// The text before the first tag, with its lines joined by newlines.
+    pub description: String,
+
+    pub params: Vec<DocParam>,
+    pub returns: Vec<DocReturn>,
+
+    /// Tags that don't have their own field, like `@see` or `@deprecated`.
+    pub tags: Vec<DocTag>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DocParam {
+    pub name: String,
+    pub type_name: Option<String>,
+
+    /// Whether EmmyLua's `?` after the name says the parameter can be left
+    /// out. The `?` isn't part of the name.
+    pub optional: bool,
+
+    pub description: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DocReturn {
+    pub type_name: Option<String>,
+    pub description: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DocTag {
+    /// The tag's name, without the `@`.
+    pub name: String,
+
+    /// Everything after the name.
+    pub text: String,
+}
+
+// Which field a line that isn't a tag adds to.
+enum Continuing {
+    Description,
+    Param,
+    Return,
+    Tag,
+}
+
+impl DocComment {
+    /// Reads a doc comment from the text of its comments, which is everything
+    /// after each one's first two dashes.
+    pub fn parse<S: AsRef<str>>(comments: &[S]) -> DocComment {
+        let mut doc = DocComment::default();
+        let mut description = Vec::new();
+        let mut continuing = Continuing::Description;
+
+        for comment in comments {
+            let comment = comment.as_ref();
+            let emmy_lua = comment.starts_with("-@");
+            let line = comment.trim_start_matches('-').trim();
+
+            if !line.starts_with('@') {
+                let target = match continuing {
+                    Continuing::Description => {
+                        description.push(line);
+                        continue;
+                    },
+                    Continuing::Param => doc.params.last_mut().map(|param| &mut param.description),
+                    Continuing::Return => doc.returns.last_mut().map(|return_doc| &mut return_doc.description),
+                    Continuing::Tag => doc.tags.last_mut().map(|tag| &mut tag.text),
+                };
+
+                if let Some(target) = target {
+                    append_line(target, line);
+                }
+
+                continue;
+            }
+
+            let (tag, rest) = split_word(&line[1..]);
+
+            continuing = match (tag, emmy_lua) {
+                ("param", true) => {
+                    let (name, rest) = split_word(rest);
+                    let (type_name, rest) = split_type(rest);
+                    doc.params.push(param(name, type_name, rest));
+                    Continuing::Param
+                },
+                ("param", false) => {
+                    let (name, rest) = split_word(rest);
+                    doc.params.push(param(name, None, rest));
+                    Continuing::Param
+                },
+                ("tparam", _) => {
+                    let (type_name, rest) = split_type(rest);
+                    let (name, rest) = split_word(rest);
+                    doc.params.push(param(name, type_name, rest));
+                    Continuing::Param
+                },
+                ("return", true) | ("treturn", _) => {
+                    let (type_name, rest) = split_type(rest);
+                    doc.returns.push(DocReturn {
+                        type_name,
+                        description: description_text(rest),
+                    });
+                    Continuing::Return
+                },
+                ("return", false) => {
+                    doc.returns.push(DocReturn {
+                        type_name: None,
+                        description: description_text(rest),
+                    });
+                    Continuing::Return
+                },
+                _ => {
+                    doc.tags.push(DocTag {
+                        name: tag.to_string(),
+                        text: rest.to_string(),
+                    });
+                    Continuing::Tag
+                },
+            };
+        }
+
+        doc.description = description.join("\n").trim().to_string();
+        doc
+    }
+}
+
+fn param(name: &str, type_name: Option<String>, description: &str) -> DocParam {
+    let optional = name.ends_with('?');
+
+    DocParam {
+        name: name.trim_end_matches('?').to_string(),
+        type_name,
+        optional,
+        description: description_text(description),
+    }
+}
+
+// EmmyLua puts a `#` between a type and its description.
+fn description_text(text: &str) -> String {
+    text.trim_start_matches('#').trim().to_string()
+}
+
+fn append_line(text: &mut String, line: &str) {
+    if !text.is_empty() && !line.is_empty() {
+        text.push(' ');
+    }
+
+    text.push_str(line);
+}
+
+fn split_word(text: &str) -> (&str, &str) {
+    let text = text.trim_start();
+    let end = text.find(char::is_whitespace).unwrap_or(text.len());
+    (&text[..end], text[end..].trim_start())
+}
+
+// Types can have spaces inside brackets, like `fun(a: number): string` or
+// `table<string, number>`.
+fn split_type(text: &str) -> (Option<String>, &str) {
+    let text = text.trim_start();
+    let mut depth = 0usize;
+    let mut end = text.len();
+
+    for (index, character) in text.char_indices() {
+        match character {
+            '(' | '<' | '[' | '{' => depth += 1,
+            ')' | '>' | ']' | '}' => depth = depth.saturating_sub(1),
+            _ if character.is_whitespace() && depth == 0 => {
+                // `fun(): string` keeps going after the `:`.
+                if text[..index].ends_with(':') {
+                    continue;
+                }
+
+                end = index;
+                break;
+            },
+            _ => {},
+        }
+    }
+
+    let type_name = &text[..end];
+    let type_name = if type_name.is_empty() || type_name.starts_with('#') { None } else { Some(type_name.to_string()) };
+
+    match type_name {
+        Some(type_name) => (Some(type_name), text[end..].trim_start()),
+        None => (None, text),
+    }
+}
+
+/// The doc comment in front of each function declaration and assignment
+/// that has one, by the statement's ID. Statements at any depth count.
+pub fn doc_comments(cst: &Cst) -> HashMap<NodeId, DocComment> {
+    let mut docs = HashMap::new();
+
+    for node in cst.chunk().descendants() {
+        let statement = match node {
+            NodeRef::Statement(statement) => statement,
+            NodeRef::Expression(_) => continue,
+        };
+
+        match statement.kind {
+            StatementKind::FunctionDeclaration(_) | StatementKind::Assignment(_) | StatementKind::LocalAssignment(_) => {},
+            _ => continue,
+        }
+
+        let first = match cst.tokens(statement.id).and_then(|tokens| tokens.first()) {
+            Some(token) => token,
+            None => continue,
+        };
+
+        if let Some(comments) = doc_block(&first.prefix) {
+            docs.insert(statement.id, DocComment::parse(&comments));
+        }
+    }
+
+    docs
+}
+
+// The contents of the doc comment at the end of a token's prefix.
+fn doc_block<'t>(prefix: &'t [TokenPrefix]) -> Option<Vec<&'t str>> {
+    let mut block = Vec::new();
+
+    for item in prefix.iter().rev() {
+        match *item {
+            TokenPrefix::Comment(Comment::SingleLine { ref content }) => block.push(content.as_ref()),
+            TokenPrefix::Whitespace(ref whitespace) if whitespace.matches('\n').count() <= 1 => {},
+            _ => break,
+        }
+    }
+
+    block.reverse();
+
+    let start = block.iter().position(|content| content.starts_with('-'))?;
+    Some(block.split_off(start))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokenizer::tokenize;
+
+    #[test]
+    fn emmy_lua() {
+        let doc = DocComment::parse(&[
+            "- Looks something up.",
+            "- Slowly.",
+            "-@param key string|number",
+            "-@param callback? fun(value: any): boolean # Called with",
+            "- the value.",
+            "-@return table<string, number> # The results.",
+            "-@deprecated Use find instead.",
+        ]);
+
+        assert_eq!(doc, DocComment {
+            description: "Looks something up.\nSlowly.".to_string(),
+            params: vec![
+                DocParam {
+                    name: "key".to_string(),
+                    type_name: Some("string|number".to_string()),
+                    optional: false,
+                    description: String::new(),
+                },
+                DocParam {
+                    name: "callback".to_string(),
+                    type_name: Some("fun(value: any): boolean".to_string()),
+                    optional: true,
+                    description: "Called with the value.".to_string(),
+                },
+            ],
+            returns: vec![DocReturn {
+                type_name: Some("table<string, number>".to_string()),
+                description: "The results.".to_string(),
+            }],
+            tags: vec![DocTag {
+                name: "deprecated".to_string(),
+                text: "Use find instead.".to_string(),
+            }],
+        });
+    }
+
+    #[test]
+    fn ldoc() {
+        let doc = DocComment::parse(&[
+            "- Adds things.",
+            " @param a the first thing",
+            " @tparam number b the second thing",
+            " @return the sum",
+            " @treturn string a message",
+        ]);
+
+        let params = doc.params.iter()
+            .map(|param| (param.name.as_str(), param.type_name.as_deref(), param.description.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(params, [("a", None, "the first thing"), ("b", Some("number"), "the second thing")]);
+
+        let returns = doc.returns.iter()
+            .map(|return_doc| (return_doc.type_name.as_deref(), return_doc.description.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(returns, [(None, "the sum"), (Some("string"), "a message")]);
+    }
+
+    #[test]
+    fn attaching() {
+        let source = "
+            -- Not a doc comment.
+            local a = 1
+
+            --- Separated by a blank line.
+
+            local b = 2
+
+            -- Ordinary comment first.
+            --- Doc for t.c.
+            function t.c()
+                --- Doc for d.
+                d = 1
+            end
+
+            --- Not for a call.
+            print(a, b)
+        ";
+
+        let tokens = tokenize(source).unwrap();
+        let cst = Cst::parse(&tokens).unwrap();
+
+        let mut descriptions = doc_comments(&cst).values()
+            .map(|doc| doc.description.clone())
+            .collect::<Vec<_>>();
+        descriptions.sort();
+
+        assert_eq!(descriptions, ["Doc for d.", "Doc for t.c."]);
+    }
+}
@@ -0,0 +1,425 @@
+//! Changing a tree without breaking its IDs.
+
+use std::mem;
+
+use ast::*;
+use index::{NodeIndex, ParentMap};
+use visit::{self, VisitorMut};
+
+/// Owns a chunk and edits it, making sure every node keeps a unique ID.
+///
+/// Nodes are found by ID, and every node added gets a fresh one, including
+/// the nodes inside it. That makes it safe to insert the same built node
+/// twice, or one copied from elsewhere in the tree. New IDs count up from the
+/// largest one in the chunk, so existing IDs never change.
+///
+/// ```
+/// use mab::builder::{expr, stmt};
+/// use mab::editor::AstEditor;
+///
+/// let tokens = mab::tokenize("local x = 1 print(x)").unwrap();
+/// let chunk = mab::parse_from_tokens(&tokens).unwrap();
+/// let print_id = chunk.statements[1].id;
+///
+/// let mut editor = AstEditor::new(chunk);
+/// let id = editor.insert_statement_before(print_id, stmt::assign([expr::name("x")], [expr::num("2")])).unwrap();
+///
+/// assert_eq!(editor.chunk().statements[1].id, id);
+/// assert_eq!(editor.parents().parent(id), None);
+/// ```
+#[derive(Debug, Clone)]
+pub struct AstEditor<'a> {
+    chunk: Chunk<'a>,
+    last_id: u32,
+}
+
+impl<'a> AstEditor<'a> {
+    /// Starts editing a chunk. Any nodes with [NodeId::DUMMY][NodeId::DUMMY],
+    /// like ones from the [builder][::builder] module, get fresh IDs.
+    pub fn new(chunk: Chunk<'a>) -> AstEditor<'a> {
+        let last_id = chunk.descendants()
+            .map(|node| node.id())
+            .filter(|&id| id != NodeId::DUMMY)
+            .map(NodeId::get)
+            .max()
+            .unwrap_or(0);
+
+        let mut editor = AstEditor {
+            chunk,
+            last_id,
+        };
+
+        let mut assigner = IdAssigner {
+            last_id: &mut editor.last_id,
+            only_dummies: true,
+        };
+
+        assigner.visit_chunk_mut(&mut editor.chunk);
+        editor
+    }
+
+    pub fn chunk(&self) -> &Chunk<'a> {
+        &self.chunk
+    }
+
+    pub fn into_chunk(self) -> Chunk<'a> {
+        self.chunk
+    }
+
+    /// An index of the chunk as it is now.
+    pub fn index(&self) -> NodeIndex<'_> {
+        NodeIndex::new(&self.chunk)
+    }
+
+    /// The parents of the chunk's nodes as they are now.
+    pub fn parents(&self) -> ParentMap<'_> {
+        ParentMap::new(&self.chunk)
+    }
+
+    /// Adds a statement to the end of the chunk.
+    pub fn push_statement(&mut self, statement: Statement<'a>) -> NodeId {
+        let statement = self.fresh_statement(statement);
+        let id = statement.id;
+        self.chunk.statements.push(statement);
+        id
+    }
+
+    /// Adds a statement to the same block as `target`, just before it.
+    /// Returns the new statement's ID, or `None` if `target` isn't a
+    /// statement in the chunk.
+    pub fn insert_statement_before(&mut self, target: NodeId, statement: Statement<'a>) -> Option<NodeId> {
+        let statement = self.fresh_statement(statement);
+        let id = statement.id;
+        self.edit_statements(target, ListEdit::InsertBefore(statement)).map(|_| id)
+    }
+
+    /// Like [insert_statement_before][AstEditor::insert_statement_before],
+    /// but just after `target`.
+    pub fn insert_statement_after(&mut self, target: NodeId, statement: Statement<'a>) -> Option<NodeId> {
+        let statement = self.fresh_statement(statement);
+        let id = statement.id;
+        self.edit_statements(target, ListEdit::InsertAfter(statement)).map(|_| id)
+    }
+
+    /// Puts a statement in place of `target`. Returns the new statement's ID,
+    /// or `None` if `target` isn't a statement in the chunk.
+    pub fn replace_statement(&mut self, target: NodeId, statement: Statement<'a>) -> Option<NodeId> {
+        let statement = self.fresh_statement(statement);
+        let id = statement.id;
+        self.edit_statements(target, ListEdit::Replace(statement)).map(|_| id)
+    }
+
+    /// Takes a statement out of the chunk, along with everything inside it.
+    pub fn remove_statement(&mut self, target: NodeId) -> Option<Statement<'a>> {
+        self.edit_statements(target, ListEdit::Remove).and_then(|removed| removed)
+    }
+
+    /// Adds an expression to the same list as `target`, just before it. Lists
+    /// are the targets and values of assignments, the values of `local`,
+    /// `return` and `for ... in`, and parenthesized call arguments.
+    ///
+    /// Returns the new expression's ID, or `None` if `target` isn't an
+    /// expression in one of those lists.
+    pub fn insert_expression_before(&mut self, target: NodeId, expression: Expression<'a>) -> Option<NodeId> {
+        let expression = self.fresh_expression(expression);
+        let id = expression.id;
+        self.edit_expressions(target, ListEdit::InsertBefore(expression)).map(|_| id)
+    }
+
+    /// Like [insert_expression_before][AstEditor::insert_expression_before],
+    /// but just after `target`.
+    pub fn insert_expression_after(&mut self, target: NodeId, expression: Expression<'a>) -> Option<NodeId> {
+        let expression = self.fresh_expression(expression);
+        let id = expression.id;
+        self.edit_expressions(target, ListEdit::InsertAfter(expression)).map(|_| id)
+    }
+
+    /// Takes an expression out of a list, as described for
+    /// [insert_expression_before][AstEditor::insert_expression_before].
+    pub fn remove_expression(&mut self, target: NodeId) -> Option<Expression<'a>> {
+        self.edit_expressions(target, ListEdit::Remove).and_then(|removed| removed)
+    }
+
+    /// Puts an expression in place of `target`, which can be any expression
+    /// in the chunk. Returns the new expression's ID, or `None` if `target`
+    /// isn't an expression in the chunk.
+    pub fn replace_expression(&mut self, target: NodeId, expression: Expression<'a>) -> Option<NodeId> {
+        let expression = self.fresh_expression(expression);
+        let id = expression.id;
+
+        let mut replacer = ExpressionReplacer {
+            target,
+            replacement: Some(expression),
+        };
+
+        replacer.visit_chunk_mut(&mut self.chunk);
+
+        match replacer.replacement {
+            Some(_) => None,
+            None => Some(id),
+        }
+    }
+
+    fn fresh_statement(&mut self, mut statement: Statement<'a>) -> Statement<'a> {
+        self.assigner().visit_statement_mut(&mut statement);
+        statement
+    }
+
+    fn fresh_expression(&mut self, mut expression: Expression<'a>) -> Expression<'a> {
+        self.assigner().visit_expression_mut(&mut expression);
+        expression
+    }
+
+    fn assigner(&mut self) -> IdAssigner<'_> {
+        IdAssigner {
+            last_id: &mut self.last_id,
+            only_dummies: false,
+        }
+    }
+
+    // Returns `None` if the target wasn't found, and otherwise the removed
+    // node, if any.
+    fn edit_statements(&mut self, target: NodeId, edit: ListEdit<Statement<'a>>) -> Option<Option<Statement<'a>>> {
+        let mut editor = ListEditor::new(target, edit);
+        editor.visit_chunk_mut(&mut self.chunk);
+        editor.result()
+    }
+
+    fn edit_expressions(&mut self, target: NodeId, edit: ListEdit<Expression<'a>>) -> Option<Option<Expression<'a>>> {
+        let mut editor = ListEditor::new(target, edit);
+        editor.visit_chunk_mut(&mut self.chunk);
+        editor.result()
+    }
+}
+
+// Gives nodes new IDs, counting up from `last_id`.
+struct IdAssigner<'e> {
+    last_id: &'e mut u32,
+    only_dummies: bool,
+}
+
+impl<'e> IdAssigner<'e> {
+    fn assign(&mut self, id: &mut NodeId) {
+        if !self.only_dummies || *id == NodeId::DUMMY {
+            *self.last_id += 1;
+            *id = NodeId::new(*self.last_id).expect("IDs start at 1");
+        }
+    }
+}
+
+impl<'e, 'a> VisitorMut<'a> for IdAssigner<'e> {
+    fn visit_statement_mut(&mut self, statement: &mut Statement<'a>) {
+        self.assign(&mut statement.id);
+        visit::walk_statement_mut(self, statement);
+    }
+
+    fn visit_expression_mut(&mut self, expression: &mut Expression<'a>) {
+        self.assign(&mut expression.id);
+        visit::walk_expression_mut(self, expression);
+    }
+}
+
+enum ListEdit<T> {
+    InsertBefore(T),
+    InsertAfter(T),
+    Replace(T),
+    Remove,
+}
+
+// Finds the list that contains `target` and makes one edit to it.
+struct ListEditor<T> {
+    target: NodeId,
+    edit: Option<ListEdit<T>>,
+    removed: Option<T>,
+}
+
+impl<T: HasId> ListEditor<T> {
+    fn new(target: NodeId, edit: ListEdit<T>) -> ListEditor<T> {
+        ListEditor {
+            target,
+            edit: Some(edit),
+            removed: None,
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        self.edit.is_none()
+    }
+
+    fn apply(&mut self, list: &mut Vec<T>) {
+        let position = match list.iter().position(|node| node.id() == self.target) {
+            Some(position) => position,
+            None => return,
+        };
+
+        match self.edit.take() {
+            Some(ListEdit::InsertBefore(node)) => list.insert(position, node),
+            Some(ListEdit::InsertAfter(node)) => list.insert(position + 1, node),
+            Some(ListEdit::Replace(node)) => self.removed = Some(mem::replace(&mut list[position], node)),
+            Some(ListEdit::Remove) => self.removed = Some(list.remove(position)),
+            None => {},
+        }
+    }
+
+    fn result(self) -> Option<Option<T>> {
+        if self.is_done() {
+            Some(self.removed)
+        } else {
+            None
+        }
+    }
+}
+
+impl<'a> VisitorMut<'a> for ListEditor<Statement<'a>> {
+    fn visit_chunk_mut(&mut self, chunk: &mut Chunk<'a>) {
+        self.apply(&mut chunk.statements);
+
+        if !self.is_done() {
+            visit::walk_chunk_mut(self, chunk);
+        }
+    }
+
+    fn visit_statement_mut(&mut self, statement: &mut Statement<'a>) {
+        if !self.is_done() {
+            visit::walk_statement_mut(self, statement);
+        }
+    }
+
+    // Expressions can't contain statements.
+    fn visit_expression_mut(&mut self, _expression: &mut Expression<'a>) {}
+}
+
+impl<'a> VisitorMut<'a> for ListEditor<Expression<'a>> {
+    fn visit_statement_mut(&mut self, statement: &mut Statement<'a>) {
+        if !self.is_done() {
+            visit::walk_statement_mut(self, statement);
+        }
+    }
+
+    fn visit_expression_mut(&mut self, expression: &mut Expression<'a>) {
+        if !self.is_done() {
+            visit::walk_expression_mut(self, expression);
+        }
+    }
+
+    fn visit_assignment_mut(&mut self, assignment: &mut Assignment<'a>) {
+        self.apply(&mut assignment.targets);
+        self.apply(&mut assignment.values);
+        visit::walk_assignment_mut(self, assignment);
+    }
+
+    fn visit_local_assignment_mut(&mut self, local_assignment: &mut LocalAssignment<'a>) {
+        self.apply(&mut local_assignment.values);
+        visit::walk_local_assignment_mut(self, local_assignment);
+    }
+
+    fn visit_function_arguments_mut(&mut self, arguments: &mut FunctionArguments<'a>) {
+        if let FunctionArguments::Parenthesized(ref mut values) = *arguments {
+            self.apply(values);
+        }
+
+        visit::walk_function_arguments_mut(self, arguments);
+    }
+
+    fn visit_generic_for_mut(&mut self, generic_for: &mut GenericFor<'a>) {
+        self.apply(&mut generic_for.item_source);
+        visit::walk_generic_for_mut(self, generic_for);
+    }
+
+    fn visit_return_mut(&mut self, return_statement: &mut Return<'a>) {
+        self.apply(&mut return_statement.values);
+        visit::walk_return_mut(self, return_statement);
+    }
+}
+
+struct ExpressionReplacer<'a> {
+    target: NodeId,
+    replacement: Option<Expression<'a>>,
+}
+
+impl<'a> VisitorMut<'a> for ExpressionReplacer<'a> {
+    fn visit_statement_mut(&mut self, statement: &mut Statement<'a>) {
+        if self.replacement.is_some() {
+            visit::walk_statement_mut(self, statement);
+        }
+    }
+
+    fn visit_expression_mut(&mut self, expression: &mut Expression<'a>) {
+        if expression.id == self.target {
+            if let Some(replacement) = self.replacement.take() {
+                *expression = replacement;
+            }
+        } else if self.replacement.is_some() {
+            visit::walk_expression_mut(self, expression);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use builder::{chunk, expr, stmt};
+    use tokenizer::tokenize;
+    use parser::parse_from_tokens;
+
+    fn parse(source: &str) -> Chunk<'static> {
+        let tokens = tokenize(source).unwrap();
+        parse_from_tokens(&tokens).unwrap().into_owned()
+    }
+
+    fn body<'c, 'a>(statement: &'c Statement<'a>) -> &'c Chunk<'a> {
+        match statement.kind {
+            StatementKind::WhileLoop(ref while_loop) => &while_loop.body,
+            _ => panic!("expected a while loop"),
+        }
+    }
+
+    #[test]
+    fn edits_statements() {
+        let mut editor = AstEditor::new(parse("while x do a() b() end"));
+        let (a, b) = {
+            let body = &body(&editor.chunk().statements[0]).statements;
+            (body[0].id, body[1].id)
+        };
+
+        let first = editor.insert_statement_before(a, stmt::call(expr::name("g"), Vec::new())).unwrap();
+        let last = editor.insert_statement_after(b, stmt::brk()).unwrap();
+        assert_ne!(first, last);
+
+        assert!(editor.remove_statement(a).is_some());
+        assert!(editor.remove_statement(a).is_none());
+        assert!(editor.replace_statement(b, stmt::call(expr::name("c"), Vec::new())).is_some());
+        editor.push_statement(stmt::ret(Vec::new()));
+
+        assert!(editor.chunk().structural_eq(&parse("while x do g() c() break end return")));
+        assert_eq!(validate(editor.chunk()), Vec::new());
+        assert_eq!(editor.parents().parent(first), Some(editor.index().get(editor.chunk().statements[0].id).unwrap()));
+    }
+
+    #[test]
+    fn edits_expressions() {
+        let mut editor = AstEditor::new(parse("f(a, b + c)"));
+        let nodes = editor.chunk().descendants().map(|node| node.id()).collect::<Vec<_>>();
+        let (a, sum, c) = (nodes[2], nodes[3], nodes[5]);
+
+        let d = editor.insert_expression_after(a, expr::name("d")).unwrap();
+        editor.insert_expression_before(d, expr::name("e")).unwrap();
+        assert!(editor.remove_expression(a).is_some());
+
+        // Only list members can be removed.
+        assert!(editor.remove_expression(c).is_none());
+        assert!(editor.replace_expression(c, expr::num("1")).is_some());
+        assert!(editor.replace_expression(sum, expr::nil()).is_some());
+        assert!(editor.replace_expression(sum, expr::nil()).is_none());
+
+        assert!(editor.chunk().structural_eq(&parse("f(e, d, nil)")));
+        assert_eq!(validate(editor.chunk()), Vec::new());
+    }
+
+    #[test]
+    fn gives_built_nodes_ids() {
+        let editor = AstEditor::new(chunk(vec![stmt::local(["x"], [expr::num("1")]), stmt::brk()]));
+        assert!(editor.chunk().descendants().all(|node| node.id() != NodeId::DUMMY));
+        assert_eq!(editor.index().len(), 3);
+    }
+}
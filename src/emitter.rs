@@ -1,88 +1,846 @@
+//! Turns an AST back into Lua source, which is what codemods need after
+//! changing a tree.
+//!
+//! Each statement goes on its own line, and blocks are indented with tabs.
+//! Comments and the original formatting aren't in the AST, so they're lost.
+//!
+//! ```
+//! let tokens = mab::tokenize("if x then  print( 'hi' ) end").unwrap();
+//! let chunk = mab::parse_from_tokens(&tokens).unwrap();
+//!
+//! assert_eq!(mab::emitter::print_chunk(&chunk), "if x then\n\tprint('hi')\nend\n");
+//! ```
+//!
+//! Hand-built trees get parentheses wherever the precedence of their operators
+//! calls for them, so that the source parses back into the same operations.
+//! Parsed trees already have a `ParenExpression` for each pair.
+//!
+//! To lay code out in some other style, see the [format][::format] module.
+
+use std::borrow::Cow;
+use std::cmp::Reverse;
 use std::fmt::{self, Write};
+use std::ops::Range;
 
 use ast::*;
+use format::{FormatConfig, IndentStyle, QuoteStyle};
+use parser::SourceMap;
+use tokenizer::StringLiteral;
 
-pub fn emit_chunk<'a>(w: &mut Write, chunk: &Chunk<'a>) -> fmt::Result {
-    for statement in &chunk.statements {
-        emit_statement(w, statement)?;
-    }
+// Keeps everything as it is, one statement to a line.
+const PLAIN: FormatConfig = FormatConfig {
+    indent_style: IndentStyle::Tabs,
+    indent_width: 4,
+    quote_style: QuoteStyle::Preserve,
+    trailing_table_separator: false,
+    max_line_width: usize::MAX,
+};
+
+pub fn emit_chunk<'a>(w: &mut dyn Write, chunk: &Chunk<'a>) -> fmt::Result {
+    emit_formatted(w, chunk, &PLAIN)
+}
+
+/// Like [emit_chunk][emit_chunk], but laid out according to `config`.
+pub fn emit_formatted(w: &mut dyn Write, chunk: &Chunk, config: &FormatConfig) -> fmt::Result {
+    Emitter::new(w, config).emit_block(chunk)
+}
+
+/// Like [emit_chunk][emit_chunk], but into a new string.
+pub fn print_chunk(chunk: &Chunk) -> String {
+    let mut output = String::new();
+    emit_chunk(&mut output, chunk).expect("writing to a String can't fail");
+    output
+}
+
+/// Prints a single expression, like a suggested fix for part of a line.
+///
+/// ```
+/// use mab::builder::expr;
+/// use mab::ast::BinaryOpKind;
+///
+/// let sum = expr::binary(BinaryOpKind::Add, expr::name("a"), expr::num("1"));
+/// assert_eq!(mab::emitter::print_expression(&sum), "a + 1");
+/// ```
+pub fn print_expression(expression: &Expression) -> String {
+    let mut output = String::new();
+    Emitter::new(&mut output, &PLAIN).emit_expression(expression).expect("writing to a String can't fail");
+    output
+}
+
+/// Prints a single statement, without a line break at the end. Blocks inside
+/// it are indented as if it were at the top level.
+pub fn print_statement(statement: &Statement) -> String {
+    let mut output = String::new();
+    Emitter::new(&mut output, &PLAIN).emit_statement(statement).expect("writing to a String can't fail");
+    output
+}
 
-    Ok(())
+/// Like [print_chunk][print_chunk], but also maps the printed code back to
+/// `source`, which the chunk was parsed from with `source_map`.
+///
+/// ```
+/// let source = "if x then\n\n\n  error('oops')\nend";
+/// let tokens = mab::tokenize(source).unwrap();
+/// let (chunk, source_map) = mab::parse_from_tokens_with_source_map(&tokens).unwrap();
+///
+/// let (printed, print_map) = mab::emitter::print_chunk_with_map(&chunk, &source_map, source);
+/// assert_eq!(printed, "if x then\n\terror('oops')\nend\n");
+///
+/// // An error on line 2 of the printed code is from line 4 of the source.
+/// assert_eq!(print_map.source_line(2), Some(4));
+/// ```
+pub fn print_chunk_with_map(chunk: &Chunk, source_map: &SourceMap, source: &str) -> (String, PrintMap) {
+    let mut output = String::new();
+    let spans = {
+        let mut emitter = Emitter::new(&mut output, &PLAIN);
+        emitter.spans = Some(Vec::new());
+        emitter.emit_block(chunk).expect("writing to a String can't fail");
+        emitter.spans.unwrap()
+    };
+
+    let output_lines = LineStarts::new(&output);
+    let source_lines = LineStarts::new(source);
+
+    let mut mappings = spans.into_iter()
+        .filter_map(|(id, output_span)| {
+            let source_span = source_map.span(id)?;
+
+            Some(Mapping {
+                output: output_lines.span(output_span),
+                source: source_lines.span(source_span),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    // Outer nodes are finished after the nodes inside them, but go first.
+    mappings.sort_by_key(|mapping| (mapping.output.start, Reverse(mapping.output.end)));
+
+    (output, PrintMap {
+        version: PRINT_MAP_VERSION,
+        mappings,
+    })
+}
+
+/// The version of the [PrintMap][PrintMap] format, which goes up whenever
+/// the JSON changes.
+pub const PRINT_MAP_VERSION: u32 = 1;
+
+/// Where each node of printed code came from in the original source. Made by
+/// [print_chunk_with_map][print_chunk_with_map].
+///
+/// Serialized to JSON, this looks like:
+///
+/// ```json
+/// {
+///   "version": 1,
+///   "mappings": [
+///     {
+///       "output": { "start": 0, "end": 22, "start_line": 1, "end_line": 3 },
+///       "source": { "start": 0, "end": 33, "start_line": 1, "end_line": 5 }
+///     }
+///   ]
+/// }
+/// ```
+///
+/// There's a mapping for each statement and expression that has a span in
+/// the source map, ordered by where they start in the output, with outer
+/// nodes before the nodes inside them. Offsets are in bytes from 0, and lines
+/// count from 1.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PrintMap {
+    pub version: u32,
+    pub mappings: Vec<Mapping>,
 }
 
-fn emit_statement<'a>(w: &mut Write, statement: &Statement<'a>) -> fmt::Result {
-    match statement {
-        &Statement::Assignment(ref value) => emit_assignment(w, value)?,
-        &Statement::LocalAssignment(ref value) => emit_local_assignment(w, value)?,
-        &Statement::FunctionCall(ref value) => emit_function_call(w, value)?,
-        &Statement::NumericFor(ref value) => emit_numeric_for(w, value)?,
-        &Statement::GenericFor(ref value) => emit_generic_for(w, value)?,
-        &Statement::IfStatement(ref value) => emit_if_statement(w, value)?,
-        &Statement::WhileLoop(ref value) => emit_while_loop(w, value)?,
-        &Statement::RepeatLoop(ref value) => emit_repeat_loop(w, value)?,
-        &Statement::FunctionDeclaration(ref value) => emit_function_declaration(w, value)?,
+impl PrintMap {
+    /// The source line the code on a line of output came from, for mapping
+    /// line numbers in error messages. This goes by the smallest node that
+    /// covers the line.
+    pub fn source_line(&self, output_line: usize) -> Option<usize> {
+        self.mappings.iter()
+            .filter(|mapping| mapping.output.start_line <= output_line && output_line <= mapping.output.end_line)
+            .min_by_key(|mapping| mapping.output.end - mapping.output.start)
+            .map(|mapping| mapping.source.start_line)
     }
+}
 
-    Ok(())
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Mapping {
+    pub output: MappedSpan,
+    pub source: MappedSpan,
 }
 
-fn emit_assignment<'a>(w: &mut Write, _assignment: &Assignment<'a>) -> fmt::Result {
-    write!(w, "assignment")?;
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MappedSpan {
+    pub start: usize,
+    pub end: usize,
+    pub start_line: usize,
+    pub end_line: usize,
+}
+
+// The byte offset of each line in some text, for finding the lines of spans.
+struct LineStarts(Vec<usize>);
+
+impl LineStarts {
+    fn new(text: &str) -> LineStarts {
+        let starts = ::std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(index, _)| index + 1))
+            .collect();
 
-    Ok(())
+        LineStarts(starts)
+    }
+
+    fn line(&self, bytes: usize) -> usize {
+        self.0.partition_point(|&start| start <= bytes)
+    }
+
+    fn span(&self, span: Range<usize>) -> MappedSpan {
+        MappedSpan {
+            start: span.start,
+            end: span.end,
+            start_line: self.line(span.start),
+            end_line: self.line(span.end.saturating_sub(1).max(span.start)),
+        }
+    }
 }
 
-fn emit_local_assignment<'a>(w: &mut Write, _assignment: &LocalAssignment<'a>) -> fmt::Result {
-    write!(w, "local assignment")?;
+// How tightly unary operators bind, between `*` and `^`.
+const UNARY_PRECEDENCE: u8 = 11;
 
-    Ok(())
+// Keeps track of how far along the output and the current line it is.
+struct Output<'w> {
+    w: &'w mut dyn Write,
+    bytes: usize,
+    column: usize,
+    tab_width: usize,
 }
 
-fn emit_function_call<'a>(w: &mut Write, _function_call: &FunctionCall<'a>) -> fmt::Result {
-    write!(w, "function call")?;
+impl<'w> Write for Output<'w> {
+    fn write_str(&mut self, text: &str) -> fmt::Result {
+        self.bytes += text.len();
+
+        for character in text.chars() {
+            self.column = match character {
+                '\n' => 0,
+                '\t' => self.column + self.tab_width,
+                _ => self.column + 1,
+            };
+        }
 
-    Ok(())
+        self.w.write_str(text)
+    }
 }
 
-fn emit_numeric_for<'a>(w: &mut Write, _numeric_for: &NumericFor<'a>) -> fmt::Result {
-    write!(w, "numeric for")?;
+struct Emitter<'w> {
+    w: Output<'w>,
+    config: &'w FormatConfig,
+    indent: usize,
 
-    Ok(())
+    // Where each node went in the output, if anyone wants to know.
+    spans: Option<Vec<(NodeId, Range<usize>)>>,
 }
 
-fn emit_generic_for<'a>(w: &mut Write, _generic_for: &GenericFor<'a>) -> fmt::Result {
-    write!(w, "generic for")?;
+impl<'w> Emitter<'w> {
+    fn new(w: &'w mut dyn Write, config: &'w FormatConfig) -> Emitter<'w> {
+        Emitter {
+            w: Output {
+                w,
+                bytes: 0,
+                column: 0,
+                tab_width: config.indent_width,
+            },
+            config,
+            indent: 0,
+            spans: None,
+        }
+    }
+
+    fn record_span(&mut self, id: NodeId, start: usize) {
+        let end = self.w.bytes;
+
+        if let Some(ref mut spans) = self.spans {
+            spans.push((id, start..end));
+        }
+    }
+
+    fn write_indent(&mut self) -> fmt::Result {
+        for _ in 0..self.indent {
+            match self.config.indent_style {
+                IndentStyle::Tabs => self.w.write_char('\t')?,
+                IndentStyle::Spaces => {
+                    for _ in 0..self.config.indent_width {
+                        self.w.write_char(' ')?;
+                    }
+                },
+            }
+        }
+
+        Ok(())
+    }
+
+    // Whether what `emit` writes fits on the rest of the line when it's all
+    // written on one line.
+    fn fits<F: FnOnce(&mut Emitter) -> fmt::Result>(&self, emit: F) -> bool {
+        if self.config.max_line_width == usize::MAX {
+            return true;
+        }
+
+        let config = FormatConfig {
+            max_line_width: usize::MAX,
+            ..self.config.clone()
+        };
+
+        let mut text = String::new();
+        let _ = emit(&mut Emitter::new(&mut text, &config));
+
+        // Long strings can have line breaks, and only their first line
+        // counts.
+        let width = text.lines().next().map_or(0, |line| line.chars().count());
+        self.w.column + width <= self.config.max_line_width
+    }
+
+    // Starts a line for an item of a list that's been split up. The items
+    // are indented a level past the brackets around them.
+    fn start_split_item(&mut self, index: usize) -> fmt::Result {
+        if index > 0 {
+            self.w.write_char(',')?;
+        }
+
+        self.w.write_char('\n')?;
+        self.write_indent()
+    }
+
+    fn emit_block(&mut self, chunk: &Chunk) -> fmt::Result {
+        for (index, statement) in chunk.statements.iter().enumerate() {
+            self.write_indent()?;
+
+            // Without a `;`, a statement starting with `(` would be read as
+            // arguments to a call at the end of the one before it.
+            if index > 0 && statement_starts_with_paren(statement) {
+                self.w.write_char(';')?;
+            }
+
+            self.emit_statement(statement)?;
+            self.w.write_char('\n')?;
+        }
+
+        Ok(())
+    }
+
+    fn emit_indented_block(&mut self, chunk: &Chunk) -> fmt::Result {
+        self.indent += 1;
+        let result = self.emit_block(chunk);
+        self.indent -= 1;
+        result
+    }
+
+    // Ends a line that opens a block, then writes the block and the indent of
+    // whatever closes it.
+    fn emit_body(&mut self, chunk: &Chunk) -> fmt::Result {
+        self.w.write_char('\n')?;
+        self.emit_indented_block(chunk)?;
+        self.write_indent()
+    }
+
+    fn emit_statement(&mut self, statement: &Statement) -> fmt::Result {
+        let start = self.w.bytes;
+        let result = match statement.kind {
+            StatementKind::Assignment(ref value) => self.emit_assignment(value),
+            StatementKind::LocalAssignment(ref value) => self.emit_local_assignment(value),
+            StatementKind::FunctionCall(ref value) => self.emit_function_call(value),
+            StatementKind::NumericFor(ref value) => self.emit_numeric_for(value),
+            StatementKind::GenericFor(ref value) => self.emit_generic_for(value),
+            StatementKind::IfStatement(ref value) => self.emit_if_statement(value),
+            StatementKind::DoBlock(ref value) => self.emit_do_block(value),
+            StatementKind::WhileLoop(ref value) => self.emit_while_loop(value),
+            StatementKind::RepeatLoop(ref value) => self.emit_repeat_loop(value),
+            StatementKind::FunctionDeclaration(ref value) => self.emit_function_declaration(value),
+            StatementKind::Goto(ref value) => write!(self.w, "goto {}", value.label),
+            StatementKind::Label(ref value) => write!(self.w, "::{}::", value.name),
+            StatementKind::Return(ref value) => self.emit_return(value),
+            StatementKind::Break => self.w.write_str("break"),
+            StatementKind::Empty => self.w.write_str(";"),
+        };
+
+        self.record_span(statement.id, start);
+        result
+    }
+
+    fn emit_assignment(&mut self, assignment: &Assignment) -> fmt::Result {
+        self.emit_expression_list(&assignment.targets)?;
+        self.w.write_str(" = ")?;
+        self.emit_expression_list(&assignment.values)
+    }
+
+    fn emit_local_assignment(&mut self, assignment: &LocalAssignment) -> fmt::Result {
+        self.w.write_str("local ")?;
+        self.emit_annotated_names(&assignment.names, &assignment.types)?;
+
+        if !assignment.values.is_empty() {
+            self.w.write_str(" = ")?;
+            self.emit_expression_list(&assignment.values)?;
+        }
+
+        Ok(())
+    }
+
+    fn emit_function_call(&mut self, call: &FunctionCall) -> fmt::Result {
+        self.emit_prefix_expression(&call.name_expression)?;
+
+        if let Some(ref method) = call.method {
+            write!(self.w, ":{}", method)?;
+        }
+
+        match call.arguments {
+            FunctionArguments::Parenthesized(ref arguments) => {
+                let split = !arguments.is_empty() && !self.fits(|emitter| {
+                    emitter.w.write_char('(')?;
+                    emitter.emit_expression_list(arguments)?;
+                    emitter.w.write_char(')')
+                });
+
+                if !split {
+                    self.w.write_char('(')?;
+                    self.emit_expression_list(arguments)?;
+                    return self.w.write_char(')');
+                }
+
+                self.w.write_char('(')?;
+                self.indent += 1;
+
+                for (index, argument) in arguments.iter().enumerate() {
+                    self.start_split_item(index)?;
+                    self.emit_expression(argument)?;
+                }
+
+                self.indent -= 1;
+                self.w.write_char('\n')?;
+                self.write_indent()?;
+                self.w.write_char(')')
+            },
+            FunctionArguments::String(ref literal) => {
+                self.w.write_char(' ')?;
+                self.emit_string(literal)
+            },
+            FunctionArguments::Table(ref table) => {
+                self.w.write_char(' ')?;
+                self.emit_table(table)
+            },
+        }
+    }
+
+    fn emit_numeric_for(&mut self, numeric_for: &NumericFor) -> fmt::Result {
+        write!(self.w, "for {} = ", numeric_for.var)?;
+        self.emit_expression(&numeric_for.start)?;
+        self.w.write_str(", ")?;
+        self.emit_expression(&numeric_for.end)?;
+
+        if let Some(ref step) = numeric_for.step {
+            self.w.write_str(", ")?;
+            self.emit_expression(step)?;
+        }
+
+        self.w.write_str(" do")?;
+        self.emit_body(&numeric_for.body)?;
+        self.w.write_str("end")
+    }
+
+    fn emit_generic_for(&mut self, generic_for: &GenericFor) -> fmt::Result {
+        write!(self.w, "for {} in ", generic_for.vars.join(", "))?;
+        self.emit_expression_list(&generic_for.item_source)?;
+        self.w.write_str(" do")?;
+        self.emit_body(&generic_for.body)?;
+        self.w.write_str("end")
+    }
+
+    fn emit_if_statement(&mut self, if_statement: &IfStatement) -> fmt::Result {
+        self.w.write_str("if ")?;
+        self.emit_expression(&if_statement.condition)?;
+        self.w.write_str(" then")?;
+        self.emit_body(&if_statement.body)?;
+
+        for (condition, body) in &if_statement.else_if_branches {
+            self.w.write_str("elseif ")?;
+            self.emit_expression(condition)?;
+            self.w.write_str(" then")?;
+            self.emit_body(body)?;
+        }
+
+        if let Some(ref else_branch) = if_statement.else_branch {
+            self.w.write_str("else")?;
+            self.emit_body(else_branch)?;
+        }
+
+        self.w.write_str("end")
+    }
+
+    fn emit_do_block(&mut self, do_block: &DoBlock) -> fmt::Result {
+        self.w.write_str("do")?;
+        self.emit_body(&do_block.body)?;
+        self.w.write_str("end")
+    }
+
+    fn emit_while_loop(&mut self, while_loop: &WhileLoop) -> fmt::Result {
+        self.w.write_str("while ")?;
+        self.emit_expression(&while_loop.condition)?;
+        self.w.write_str(" do")?;
+        self.emit_body(&while_loop.body)?;
+        self.w.write_str("end")
+    }
+
+    fn emit_repeat_loop(&mut self, repeat_loop: &RepeatLoop) -> fmt::Result {
+        self.w.write_str("repeat")?;
+        self.emit_body(&repeat_loop.body)?;
+        self.w.write_str("until ")?;
+        self.emit_expression(&repeat_loop.condition)
+    }
+
+    fn emit_function_declaration(&mut self, declaration: &FunctionDeclaration) -> fmt::Result {
+        if declaration.local {
+            self.w.write_str("local ")?;
+        }
+
+        write!(self.w, "function {}", declaration.name.path.join("."))?;
+
+        if let Some(ref method) = declaration.name.method {
+            write!(self.w, ":{}", method)?;
+        }
+
+        self.w.write_char('(')?;
+        self.emit_annotated_names(&declaration.parameters, &declaration.parameter_types)?;
+
+        if declaration.is_variadic {
+            if !declaration.parameters.is_empty() {
+                self.w.write_str(", ")?;
+            }
 
-    Ok(())
+            self.w.write_str("...")?;
+        }
+
+        self.w.write_char(')')?;
+
+        if let Some(ref return_types) = declaration.return_types {
+            match return_types[..] {
+                [ref only] => write!(self.w, ": {}", only)?,
+                _ => {
+                    let types = return_types.iter().map(|annotation| annotation.to_string()).collect::<Vec<_>>();
+                    write!(self.w, ": ({})", types.join(", "))?;
+                },
+            }
+        }
+
+        self.emit_body(&declaration.body)?;
+        self.w.write_str("end")
+    }
+
+    // `types` is either empty or has a type annotation for each name.
+    fn emit_annotated_names(&mut self, names: &[Cow<str>], types: &[Option<TypeAnnotation>]) -> fmt::Result {
+        for (index, name) in names.iter().enumerate() {
+            if index > 0 {
+                self.w.write_str(", ")?;
+            }
+
+            self.w.write_str(name)?;
+
+            if let Some(Some(annotation)) = types.get(index) {
+                write!(self.w, ": {}", annotation)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn emit_return(&mut self, return_statement: &Return) -> fmt::Result {
+        self.w.write_str("return")?;
+
+        if !return_statement.values.is_empty() {
+            self.w.write_char(' ')?;
+            self.emit_expression_list(&return_statement.values)?;
+        }
+
+        Ok(())
+    }
+
+    fn emit_expression_list(&mut self, expressions: &[Expression]) -> fmt::Result {
+        for (index, expression) in expressions.iter().enumerate() {
+            if index > 0 {
+                self.w.write_str(", ")?;
+            }
+
+            self.emit_expression(expression)?;
+        }
+
+        Ok(())
+    }
+
+    fn emit_expression(&mut self, expression: &Expression) -> fmt::Result {
+        let start = self.w.bytes;
+        let result = match expression.kind {
+            ExpressionKind::Nil => self.w.write_str("nil"),
+            ExpressionKind::Bool(value) => write!(self.w, "{}", value),
+            ExpressionKind::Number(ref value) => self.w.write_str(value),
+            ExpressionKind::String(ref literal) => self.emit_string(literal),
+            ExpressionKind::VarArg => self.w.write_str("..."),
+            ExpressionKind::Table(ref table) => self.emit_table(table),
+            ExpressionKind::FunctionCall(ref call) => self.emit_function_call(call),
+            ExpressionKind::Name(ref name) => self.w.write_str(name),
+            ExpressionKind::FieldAccess(ref field_access) => {
+                self.emit_prefix_expression(&field_access.target)?;
+                write!(self.w, ".{}", field_access.name)
+            },
+            ExpressionKind::Index(ref index) => {
+                self.emit_prefix_expression(&index.target)?;
+                self.emit_bracketed(&index.key)
+            },
+            ExpressionKind::ParenExpression(ref inner) => {
+                self.w.write_char('(')?;
+                self.emit_expression(inner)?;
+                self.w.write_char(')')
+            },
+            ExpressionKind::UnaryOp(ref unary_op) => self.emit_unary_op(unary_op),
+            ExpressionKind::BinaryOp(ref binary_op) => self.emit_binary_op(binary_op),
+        };
+
+        self.record_span(expression.id, start);
+        result
+    }
+
+    // Calls, fields, and indexes can only be made on names, other prefix
+    // expressions, and parenthesized expressions.
+    fn emit_prefix_expression(&mut self, expression: &Expression) -> fmt::Result {
+        match expression.kind {
+            ExpressionKind::Name(_) |
+            ExpressionKind::FieldAccess(_) |
+            ExpressionKind::Index(_) |
+            ExpressionKind::FunctionCall(_) |
+            ExpressionKind::ParenExpression(_) => self.emit_expression(expression),
+            _ => self.emit_parenthesized(expression),
+        }
+    }
+
+    fn emit_parenthesized(&mut self, expression: &Expression) -> fmt::Result {
+        self.w.write_char('(')?;
+        self.emit_expression(expression)?;
+        self.w.write_char(')')
+    }
+
+    // Writes `[key]`, with spaces if the key would otherwise run into the
+    // bracket and start a long string.
+    fn emit_bracketed(&mut self, key: &Expression) -> fmt::Result {
+        if starts_with_long_bracket(key) {
+            self.w.write_str("[ ")?;
+            self.emit_expression(key)?;
+            self.w.write_str(" ]")
+        } else {
+            self.w.write_char('[')?;
+            self.emit_expression(key)?;
+            self.w.write_char(']')
+        }
+    }
+
+    fn emit_unary_op(&mut self, unary_op: &UnaryOp) -> fmt::Result {
+        self.w.write_str(unary_op.operator.to_str())?;
+
+        match (&unary_op.operator, &unary_op.argument.kind) {
+            (&UnaryOpKind::BooleanNot, _) => self.w.write_char(' ')?,
+            // `--` would start a comment.
+            (&UnaryOpKind::Negate, &ExpressionKind::UnaryOp(UnaryOp { operator: UnaryOpKind::Negate, .. })) => {
+                self.w.write_char(' ')?;
+            },
+            _ => {},
+        }
+
+        match binding_precedence(&unary_op.argument) {
+            Some(precedence) if precedence < UNARY_PRECEDENCE => self.emit_parenthesized(&unary_op.argument),
+            _ => self.emit_expression(&unary_op.argument),
+        }
+    }
+
+    fn emit_binary_op(&mut self, binary_op: &BinaryOp) -> fmt::Result {
+        let precedence = binary_op.operator.precedence();
+        let right_associative = binary_op.operator.is_right_associative();
+
+        // An operand at the same precedence only needs parentheses on the
+        // side the operator doesn't associate toward.
+        let left_needs_parens = match binding_precedence(&binary_op.left) {
+            Some(left) => left < precedence || (left == precedence && right_associative),
+            None => false,
+        };
+
+        // Unary operators are always read as part of the operand on the
+        // right, whatever their precedence.
+        let right_needs_parens = match binary_op.right.kind {
+            ExpressionKind::BinaryOp(ref right) => {
+                let right = right.operator.precedence();
+                right < precedence || (right == precedence && !right_associative)
+            },
+            _ => false,
+        };
+
+        if left_needs_parens {
+            self.emit_parenthesized(&binary_op.left)?;
+        } else {
+            self.emit_expression(&binary_op.left)?;
+        }
+
+        write!(self.w, " {} ", binary_op.operator.to_str())?;
+
+        if right_needs_parens {
+            self.emit_parenthesized(&binary_op.right)
+        } else {
+            self.emit_expression(&binary_op.right)
+        }
+    }
+
+    fn emit_string(&mut self, literal: &StringLiteral) -> fmt::Result {
+        let (raw_content, quote) = match *literal {
+            StringLiteral::DoubleQuote { ref raw_content, .. } => (raw_content, '"'),
+            StringLiteral::SingleQuote { ref raw_content, .. } => (raw_content, '\''),
+            StringLiteral::LongForm { ref raw_content, depth } => {
+                let equals = "=".repeat(depth as usize);
+                return write!(self.w, "[{}[{}]{}]", equals, raw_content, equals);
+            },
+        };
+
+        let new_quote = match self.config.quote_style {
+            QuoteStyle::Preserve => quote,
+            QuoteStyle::PreferDouble => preferred_quote(raw_content, '"', '\''),
+            QuoteStyle::PreferSingle => preferred_quote(raw_content, '\'', '"'),
+        };
+
+        if new_quote == quote {
+            write!(self.w, "{}{}{}", quote, raw_content, quote)
+        } else {
+            write!(self.w, "{}{}{}", new_quote, requote(raw_content, quote, new_quote), new_quote)
+        }
+    }
+
+    fn emit_table(&mut self, table: &TableLiteral) -> fmt::Result {
+        let split = !table.items.is_empty() && !self.fits(|emitter| emitter.emit_table(table));
+
+        self.w.write_char('{')?;
+
+        if split {
+            self.indent += 1;
+        }
+
+        for (index, (key, value)) in table.items.iter().enumerate() {
+            if split {
+                self.start_split_item(index)?;
+            } else if index > 0 {
+                self.w.write_str(", ")?;
+            }
+
+            match *key {
+                Some(TableKey::Name(ref name)) => write!(self.w, "{} = ", name)?,
+                Some(TableKey::Expression(ref key)) => {
+                    self.emit_bracketed(key)?;
+                    self.w.write_str(" = ")?;
+                },
+                None => {},
+            }
+
+            self.emit_expression(value)?;
+        }
+
+        if split {
+            if self.config.trailing_table_separator {
+                self.w.write_char(',')?;
+            }
+
+            self.indent -= 1;
+            self.w.write_char('\n')?;
+            self.write_indent()?;
+        }
+
+        self.w.write_char('}')
+    }
 }
 
-fn emit_if_statement<'a>(w: &mut Write, _if_statement: &IfStatement<'a>) -> fmt::Result {
-    write!(w, "if statement")?;
+// The quote to use for a string, which is `preferred` unless the string has
+// more of those in it than `other`s.
+fn preferred_quote(raw_content: &str, preferred: char, other: char) -> char {
+    let count = |quote| raw_content.chars().filter(|&character| character == quote).count();
 
-    Ok(())
+    if count(preferred) > count(other) {
+        other
+    } else {
+        preferred
+    }
 }
 
-fn emit_while_loop<'a>(w: &mut Write, _while_loop: &WhileLoop<'a>) -> fmt::Result {
-    write!(w, "while loop")?;
+// Changes the escapes in a quoted string's contents to suit new quotes.
+fn requote(raw_content: &str, old_quote: char, new_quote: char) -> String {
+    let mut requoted = String::with_capacity(raw_content.len());
+    let mut characters = raw_content.chars();
+
+    while let Some(character) = characters.next() {
+        if character == '\\' {
+            match characters.next() {
+                Some(escaped) if escaped == old_quote => requoted.push(escaped),
+                Some(escaped) => {
+                    requoted.push('\\');
+                    requoted.push(escaped);
+                },
+                None => requoted.push('\\'),
+            }
+        } else {
+            if character == new_quote {
+                requoted.push('\\');
+            }
+
+            requoted.push(character);
+        }
+    }
 
-    Ok(())
+    requoted
 }
 
-fn emit_repeat_loop<'a>(w: &mut Write, _repeat_loop: &RepeatLoop<'a>) -> fmt::Result {
-    write!(w, "repeat loop")?;
+// The precedence of the operator an expression is made of, if any.
+fn binding_precedence(expression: &Expression) -> Option<u8> {
+    match expression.kind {
+        ExpressionKind::BinaryOp(ref binary_op) => Some(binary_op.operator.precedence()),
+        ExpressionKind::UnaryOp(_) => Some(UNARY_PRECEDENCE),
+        _ => None,
+    }
+}
 
-    Ok(())
+fn starts_with_long_bracket(expression: &Expression) -> bool {
+    match expression.kind {
+        ExpressionKind::String(StringLiteral::LongForm { .. }) => true,
+        ExpressionKind::BinaryOp(ref binary_op) => starts_with_long_bracket(&binary_op.left),
+        _ => false,
+    }
 }
 
-fn emit_function_declaration<'a>(w: &mut Write, _function_declaration: &FunctionDeclaration<'a>) -> fmt::Result {
-    write!(w, "function declaration")?;
+fn starts_with_paren(expression: &Expression) -> bool {
+    match expression.kind {
+        ExpressionKind::ParenExpression(_) => true,
+        ExpressionKind::FieldAccess(ref field_access) => starts_with_paren(&field_access.target),
+        ExpressionKind::Index(ref index) => starts_with_paren(&index.target),
+        ExpressionKind::FunctionCall(ref call) => starts_with_paren(&call.name_expression),
+        // Anything else is parenthesized when it's used as a prefix.
+        ExpressionKind::Name(_) => false,
+        _ => true,
+    }
+}
 
-    Ok(())
+fn statement_starts_with_paren(statement: &Statement) -> bool {
+    match statement.kind {
+        StatementKind::FunctionCall(ref call) => starts_with_paren(&call.name_expression),
+        StatementKind::Assignment(ref assignment) => assignment.targets.first().is_some_and(starts_with_paren),
+        _ => false,
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    extern crate serde_json;
+
     use super::*;
+    use builder::{chunk, expr, stmt};
+    use tokenizer::tokenize;
+    use parser::{parse_from_tokens, parse_from_tokens_with_source_map};
 
     #[test]
     fn emit_empty_chunk() {
@@ -95,4 +853,131 @@ mod tests {
 
         assert_eq!(&output, "");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn statements() {
+        let tokens = tokenize("
+            local a, b = 1, { 2, x = 3, [ [[k]] ] = 4 }
+            function m.n:f(x, ...) return ... end
+            for i = 1, 10, 2 do repeat break until true end
+            if a then elseif b then f 'x' else g { } end
+            while not a do t[1].x = - -1 end
+            ::top:: goto top
+        ").unwrap();
+        let chunk = parse_from_tokens(&tokens).unwrap();
+
+        assert_eq!(print_chunk(&chunk), "\
+local a, b = 1, {2, x = 3, [ [[k]] ] = 4}
+function m.n:f(x, ...)
+\treturn ...
+end
+for i = 1, 10, 2 do
+\trepeat
+\t\tbreak
+\tuntil true
+end
+if a then
+elseif b then
+\tf 'x'
+else
+\tg {}
+end
+while not a do
+\tt[1].x = - -1
+end
+::top::
+goto top
+");
+    }
+
+    #[test]
+    fn type_annotations() {
+        use parser::parse_from_tokens_with_types;
+        use tokenizer::tokenize_luau;
+
+        let source = "\
+local a: number?, b = 1
+local function f(x: {string}, y: (number) -> (), ...): (number, string | nil)
+\treturn #x, nil
+end
+function t:m(): (A | B)?
+end
+";
+        let tokens = tokenize_luau(source);
+        let chunk = parse_from_tokens_with_types(&tokens).unwrap();
+
+        assert_eq!(print_chunk(&chunk), source);
+    }
+
+    #[test]
+    fn parenthesizes_built_trees() {
+        use ast::BinaryOpKind::*;
+
+        let print = |expression| print_chunk(&chunk(vec![stmt::ret([expression])]));
+        let (a, b, c) = (|| expr::name("a"), || expr::name("b"), || expr::name("c"));
+
+        assert_eq!(print(expr::binary(Multiply, expr::binary(Add, a(), b()), c())), "return (a + b) * c\n");
+        assert_eq!(print(expr::binary(Subtract, a(), expr::binary(Subtract, b(), c()))), "return a - (b - c)\n");
+        assert_eq!(print(expr::binary(Subtract, expr::binary(Subtract, a(), b()), c())), "return a - b - c\n");
+        assert_eq!(print(expr::binary(Exponent, expr::binary(Exponent, a(), b()), c())), "return (a ^ b) ^ c\n");
+        assert_eq!(print(expr::binary(Exponent, a(), expr::binary(Exponent, b(), c()))), "return a ^ b ^ c\n");
+        assert_eq!(print(expr::binary(Exponent, expr::unary(UnaryOpKind::Negate, a()), b())), "return (-a) ^ b\n");
+        assert_eq!(print(expr::unary(UnaryOpKind::Negate, expr::binary(Exponent, a(), b()))), "return -a ^ b\n");
+        assert_eq!(print(expr::unary(UnaryOpKind::Length, expr::binary(Concat, a(), b()))), "return #(a .. b)\n");
+        assert_eq!(print(expr::field(expr::string("s"), "len")), "return (\"s\").len\n");
+    }
+
+    #[test]
+    fn separates_ambiguous_calls() {
+        let chunk = chunk(vec![
+            stmt::local(["a"], [expr::name("f")]),
+            stmt::call(expr::paren(expr::name("g")), Vec::new()),
+        ]);
+
+        assert_eq!(print_chunk(&chunk), "local a = f\n;(g)()\n");
+    }
+
+    #[test]
+    fn print_map() {
+        let source = "local a = 1\n\n\nreturn  a";
+        let tokens = tokenize(source).unwrap();
+        let (chunk, source_map) = parse_from_tokens_with_source_map(&tokens).unwrap();
+
+        let (printed, print_map) = print_chunk_with_map(&chunk, &source_map, source);
+        assert_eq!(printed, "local a = 1\nreturn a\n");
+
+        assert_eq!(serde_json::to_value(&print_map).unwrap(), serde_json::json!({
+            "version": 1,
+            "mappings": [
+                {
+                    "output": { "start": 0, "end": 11, "start_line": 1, "end_line": 1 },
+                    "source": { "start": 0, "end": 11, "start_line": 1, "end_line": 1 },
+                },
+                {
+                    "output": { "start": 10, "end": 11, "start_line": 1, "end_line": 1 },
+                    "source": { "start": 10, "end": 11, "start_line": 1, "end_line": 1 },
+                },
+                {
+                    "output": { "start": 12, "end": 20, "start_line": 2, "end_line": 2 },
+                    "source": { "start": 14, "end": 23, "start_line": 4, "end_line": 4 },
+                },
+                {
+                    "output": { "start": 19, "end": 20, "start_line": 2, "end_line": 2 },
+                    "source": { "start": 22, "end": 23, "start_line": 4, "end_line": 4 },
+                },
+            ],
+        }));
+
+        assert_eq!(print_map.source_line(2), Some(4));
+        assert_eq!(print_map.source_line(3), None);
+    }
+
+    #[test]
+    fn single_nodes() {
+        let statement = stmt::if_then(expr::name("x"), [stmt::ret([expr::string("y")])]);
+        assert_eq!(print_statement(&statement), "if x then\n\treturn \"y\"\nend");
+
+        let call = expr::method_call(expr::name("t"), "m", [expr::num("1")]);
+        assert_eq!(print_expression(&call), "t:m(1)");
+    }
+}
@@ -0,0 +1,411 @@
+//! Transforming an AST into a new one.
+//!
+//! A [Fold][Fold] takes ownership of each node and returns its replacement. By
+//! default, every method rebuilds its node from the folded children using the
+//! matching `walk_*` function, so overriding just the methods you care about
+//! gives you a copy of the tree with those nodes changed:
+//!
+//! ```
+//! use mab::ast::*;
+//! use mab::fold::{self, Fold};
+//!
+//! struct Negate;
+//!
+//! impl<'a> Fold<'a> for Negate {
+//!     fn fold_expression(&mut self, expression: Expression<'a>) -> Expression<'a> {
+//!         let mut expression = fold::walk_expression(self, expression);
+//!
+//!         if let ExpressionKind::Bool(ref mut value) = expression.kind {
+//!             *value = !*value;
+//!         }
+//!
+//!         expression
+//!     }
+//! }
+//!
+//! let tokens = mab::tokenize("x = true").unwrap();
+//! let chunk = mab::parse_from_tokens(&tokens).unwrap();
+//! let folded = Negate.fold_chunk(chunk.clone());
+//!
+//! let tokens = mab::tokenize("x = false").unwrap();
+//! assert!(folded.structural_eq(&mab::parse_from_tokens(&tokens).unwrap()));
+//! ```
+//!
+//! Unlike [VisitorMut][::visit::VisitorMut], this never needs mutable access to
+//! the original tree, and a method can return a different kind of node than it
+//! was given, like a [DoBlock][StatementKind::DoBlock] in place of a
+//! [WhileLoop][StatementKind::WhileLoop].
+
+use ast::*;
+
+pub trait Fold<'a> {
+    fn fold_chunk(&mut self, chunk: Chunk<'a>) -> Chunk<'a> {
+        walk_chunk(self, chunk)
+    }
+
+    fn fold_statement(&mut self, statement: Statement<'a>) -> Statement<'a> {
+        walk_statement(self, statement)
+    }
+
+    fn fold_expression(&mut self, expression: Expression<'a>) -> Expression<'a> {
+        walk_expression(self, expression)
+    }
+
+    fn fold_assignment(&mut self, assignment: Assignment<'a>) -> Assignment<'a> {
+        walk_assignment(self, assignment)
+    }
+
+    fn fold_local_assignment(&mut self, local_assignment: LocalAssignment<'a>) -> LocalAssignment<'a> {
+        walk_local_assignment(self, local_assignment)
+    }
+
+    /// Called for calls both as statements and as expressions.
+    fn fold_function_call(&mut self, call: FunctionCall<'a>) -> FunctionCall<'a> {
+        walk_function_call(self, call)
+    }
+
+    fn fold_function_arguments(&mut self, arguments: FunctionArguments<'a>) -> FunctionArguments<'a> {
+        walk_function_arguments(self, arguments)
+    }
+
+    fn fold_numeric_for(&mut self, numeric_for: NumericFor<'a>) -> NumericFor<'a> {
+        walk_numeric_for(self, numeric_for)
+    }
+
+    fn fold_generic_for(&mut self, generic_for: GenericFor<'a>) -> GenericFor<'a> {
+        walk_generic_for(self, generic_for)
+    }
+
+    fn fold_if_statement(&mut self, if_statement: IfStatement<'a>) -> IfStatement<'a> {
+        walk_if_statement(self, if_statement)
+    }
+
+    fn fold_do_block(&mut self, do_block: DoBlock<'a>) -> DoBlock<'a> {
+        walk_do_block(self, do_block)
+    }
+
+    fn fold_while_loop(&mut self, while_loop: WhileLoop<'a>) -> WhileLoop<'a> {
+        walk_while_loop(self, while_loop)
+    }
+
+    fn fold_repeat_loop(&mut self, repeat_loop: RepeatLoop<'a>) -> RepeatLoop<'a> {
+        walk_repeat_loop(self, repeat_loop)
+    }
+
+    fn fold_function_declaration(&mut self, function_declaration: FunctionDeclaration<'a>) -> FunctionDeclaration<'a> {
+        walk_function_declaration(self, function_declaration)
+    }
+
+    fn fold_goto(&mut self, goto: Goto<'a>) -> Goto<'a> {
+        goto
+    }
+
+    fn fold_label(&mut self, label: Label<'a>) -> Label<'a> {
+        label
+    }
+
+    fn fold_return(&mut self, return_statement: Return<'a>) -> Return<'a> {
+        walk_return(self, return_statement)
+    }
+
+    fn fold_table_literal(&mut self, table: TableLiteral<'a>) -> TableLiteral<'a> {
+        walk_table_literal(self, table)
+    }
+
+    fn fold_field_access(&mut self, field_access: FieldAccess<'a>) -> FieldAccess<'a> {
+        walk_field_access(self, field_access)
+    }
+
+    fn fold_index(&mut self, index: Index<'a>) -> Index<'a> {
+        walk_index(self, index)
+    }
+
+    fn fold_unary_op(&mut self, unary_op: UnaryOp<'a>) -> UnaryOp<'a> {
+        walk_unary_op(self, unary_op)
+    }
+
+    fn fold_binary_op(&mut self, binary_op: BinaryOp<'a>) -> BinaryOp<'a> {
+        walk_binary_op(self, binary_op)
+    }
+}
+
+fn fold_expressions<'a, F: Fold<'a> + ?Sized>(folder: &mut F, expressions: Vec<Expression<'a>>) -> Vec<Expression<'a>> {
+    expressions.into_iter()
+        .map(|expression| folder.fold_expression(expression))
+        .collect()
+}
+
+// Reuses the box's allocation for the folded expression.
+fn fold_boxed<'a, F: Fold<'a> + ?Sized>(folder: &mut F, mut expression: Box<Expression<'a>>) -> Box<Expression<'a>> {
+    *expression = folder.fold_expression(*expression);
+    expression
+}
+
+pub fn walk_chunk<'a, F: Fold<'a> + ?Sized>(folder: &mut F, chunk: Chunk<'a>) -> Chunk<'a> {
+    Chunk {
+        statements: chunk.statements.into_iter()
+            .map(|statement| folder.fold_statement(statement))
+            .collect(),
+    }
+}
+
+pub fn walk_statement<'a, F: Fold<'a> + ?Sized>(folder: &mut F, statement: Statement<'a>) -> Statement<'a> {
+    let kind = match statement.kind {
+        StatementKind::Assignment(value) => StatementKind::Assignment(folder.fold_assignment(value)),
+        StatementKind::LocalAssignment(value) => StatementKind::LocalAssignment(folder.fold_local_assignment(value)),
+        StatementKind::FunctionCall(value) => StatementKind::FunctionCall(folder.fold_function_call(value)),
+        StatementKind::NumericFor(value) => StatementKind::NumericFor(folder.fold_numeric_for(value)),
+        StatementKind::GenericFor(value) => StatementKind::GenericFor(folder.fold_generic_for(value)),
+        StatementKind::IfStatement(value) => StatementKind::IfStatement(folder.fold_if_statement(value)),
+        StatementKind::DoBlock(value) => StatementKind::DoBlock(folder.fold_do_block(value)),
+        StatementKind::WhileLoop(value) => StatementKind::WhileLoop(folder.fold_while_loop(value)),
+        StatementKind::RepeatLoop(value) => StatementKind::RepeatLoop(folder.fold_repeat_loop(value)),
+        StatementKind::FunctionDeclaration(value) => StatementKind::FunctionDeclaration(folder.fold_function_declaration(value)),
+        StatementKind::Goto(value) => StatementKind::Goto(folder.fold_goto(value)),
+        StatementKind::Label(value) => StatementKind::Label(folder.fold_label(value)),
+        StatementKind::Return(value) => StatementKind::Return(folder.fold_return(value)),
+        StatementKind::Break => StatementKind::Break,
+        StatementKind::Empty => StatementKind::Empty,
+    };
+
+    Statement {
+        id: statement.id,
+        kind,
+    }
+}
+
+pub fn walk_expression<'a, F: Fold<'a> + ?Sized>(folder: &mut F, expression: Expression<'a>) -> Expression<'a> {
+    let kind = match expression.kind {
+        ExpressionKind::Table(value) => ExpressionKind::Table(folder.fold_table_literal(value)),
+        ExpressionKind::FunctionCall(value) => ExpressionKind::FunctionCall(folder.fold_function_call(value)),
+        ExpressionKind::FieldAccess(value) => ExpressionKind::FieldAccess(folder.fold_field_access(value)),
+        ExpressionKind::Index(value) => ExpressionKind::Index(folder.fold_index(value)),
+        ExpressionKind::ParenExpression(value) => ExpressionKind::ParenExpression(fold_boxed(folder, value)),
+        ExpressionKind::UnaryOp(value) => ExpressionKind::UnaryOp(folder.fold_unary_op(value)),
+        ExpressionKind::BinaryOp(value) => ExpressionKind::BinaryOp(folder.fold_binary_op(value)),
+        kind @ (ExpressionKind::Nil | ExpressionKind::Bool(_) | ExpressionKind::Number(_) |
+            ExpressionKind::String(_) | ExpressionKind::VarArg | ExpressionKind::Name(_)) => kind,
+    };
+
+    Expression {
+        id: expression.id,
+        kind,
+    }
+}
+
+pub fn walk_assignment<'a, F: Fold<'a> + ?Sized>(folder: &mut F, assignment: Assignment<'a>) -> Assignment<'a> {
+    Assignment {
+        targets: fold_expressions(folder, assignment.targets),
+        values: fold_expressions(folder, assignment.values),
+    }
+}
+
+pub fn walk_local_assignment<'a, F: Fold<'a> + ?Sized>(folder: &mut F, local_assignment: LocalAssignment<'a>) -> LocalAssignment<'a> {
+    LocalAssignment {
+        names: local_assignment.names,
+        values: fold_expressions(folder, local_assignment.values),
+        types: local_assignment.types,
+    }
+}
+
+pub fn walk_function_call<'a, F: Fold<'a> + ?Sized>(folder: &mut F, call: FunctionCall<'a>) -> FunctionCall<'a> {
+    FunctionCall {
+        name_expression: fold_boxed(folder, call.name_expression),
+        method: call.method,
+        arguments: folder.fold_function_arguments(call.arguments),
+    }
+}
+
+pub fn walk_function_arguments<'a, F: Fold<'a> + ?Sized>(folder: &mut F, arguments: FunctionArguments<'a>) -> FunctionArguments<'a> {
+    match arguments {
+        FunctionArguments::Parenthesized(values) => FunctionArguments::Parenthesized(fold_expressions(folder, values)),
+        FunctionArguments::Table(table) => FunctionArguments::Table(folder.fold_table_literal(table)),
+        FunctionArguments::String(value) => FunctionArguments::String(value),
+    }
+}
+
+pub fn walk_numeric_for<'a, F: Fold<'a> + ?Sized>(folder: &mut F, numeric_for: NumericFor<'a>) -> NumericFor<'a> {
+    NumericFor {
+        var: numeric_for.var,
+        start: folder.fold_expression(numeric_for.start),
+        end: folder.fold_expression(numeric_for.end),
+        step: numeric_for.step.map(|step| folder.fold_expression(step)),
+        body: folder.fold_chunk(numeric_for.body),
+    }
+}
+
+pub fn walk_generic_for<'a, F: Fold<'a> + ?Sized>(folder: &mut F, generic_for: GenericFor<'a>) -> GenericFor<'a> {
+    GenericFor {
+        vars: generic_for.vars,
+        item_source: fold_expressions(folder, generic_for.item_source),
+        body: folder.fold_chunk(generic_for.body),
+    }
+}
+
+pub fn walk_if_statement<'a, F: Fold<'a> + ?Sized>(folder: &mut F, if_statement: IfStatement<'a>) -> IfStatement<'a> {
+    IfStatement {
+        condition: folder.fold_expression(if_statement.condition),
+        body: folder.fold_chunk(if_statement.body),
+        else_if_branches: if_statement.else_if_branches.into_iter()
+            .map(|(condition, body)| (folder.fold_expression(condition), folder.fold_chunk(body)))
+            .collect(),
+        else_branch: if_statement.else_branch.map(|body| folder.fold_chunk(body)),
+    }
+}
+
+pub fn walk_do_block<'a, F: Fold<'a> + ?Sized>(folder: &mut F, do_block: DoBlock<'a>) -> DoBlock<'a> {
+    DoBlock {
+        body: folder.fold_chunk(do_block.body),
+    }
+}
+
+pub fn walk_while_loop<'a, F: Fold<'a> + ?Sized>(folder: &mut F, while_loop: WhileLoop<'a>) -> WhileLoop<'a> {
+    WhileLoop {
+        condition: folder.fold_expression(while_loop.condition),
+        body: folder.fold_chunk(while_loop.body),
+    }
+}
+
+/// The body is folded first, to match the order it's run in.
+pub fn walk_repeat_loop<'a, F: Fold<'a> + ?Sized>(folder: &mut F, repeat_loop: RepeatLoop<'a>) -> RepeatLoop<'a> {
+    let body = folder.fold_chunk(repeat_loop.body);
+
+    RepeatLoop {
+        condition: folder.fold_expression(repeat_loop.condition),
+        body,
+    }
+}
+
+pub fn walk_function_declaration<'a, F: Fold<'a> + ?Sized>(folder: &mut F, function_declaration: FunctionDeclaration<'a>) -> FunctionDeclaration<'a> {
+    FunctionDeclaration {
+        body: folder.fold_chunk(function_declaration.body),
+        ..function_declaration
+    }
+}
+
+pub fn walk_return<'a, F: Fold<'a> + ?Sized>(folder: &mut F, return_statement: Return<'a>) -> Return<'a> {
+    Return {
+        values: fold_expressions(folder, return_statement.values),
+    }
+}
+
+pub fn walk_table_literal<'a, F: Fold<'a> + ?Sized>(folder: &mut F, table: TableLiteral<'a>) -> TableLiteral<'a> {
+    let items = table.items.into_iter()
+        .map(|(key, value)| {
+            let key = key.map(|key| match key {
+                TableKey::Expression(key) => TableKey::Expression(folder.fold_expression(key)),
+                TableKey::Name(name) => TableKey::Name(name),
+            });
+
+            (key, folder.fold_expression(value))
+        })
+        .collect();
+
+    TableLiteral {
+        items,
+    }
+}
+
+pub fn walk_field_access<'a, F: Fold<'a> + ?Sized>(folder: &mut F, field_access: FieldAccess<'a>) -> FieldAccess<'a> {
+    FieldAccess {
+        target: fold_boxed(folder, field_access.target),
+        name: field_access.name,
+    }
+}
+
+pub fn walk_index<'a, F: Fold<'a> + ?Sized>(folder: &mut F, index: Index<'a>) -> Index<'a> {
+    Index {
+        target: fold_boxed(folder, index.target),
+        key: fold_boxed(folder, index.key),
+    }
+}
+
+pub fn walk_unary_op<'a, F: Fold<'a> + ?Sized>(folder: &mut F, unary_op: UnaryOp<'a>) -> UnaryOp<'a> {
+    UnaryOp {
+        operator: unary_op.operator,
+        argument: fold_boxed(folder, unary_op.argument),
+    }
+}
+
+pub fn walk_binary_op<'a, F: Fold<'a> + ?Sized>(folder: &mut F, binary_op: BinaryOp<'a>) -> BinaryOp<'a> {
+    BinaryOp {
+        operator: binary_op.operator,
+        left: fold_boxed(folder, binary_op.left),
+        right: fold_boxed(folder, binary_op.right),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use super::*;
+    use tokenizer::tokenize;
+    use parser::parse_from_tokens;
+
+    // Turns `while` loops into `do` blocks that run once, and prefixes every
+    // name with an underscore.
+    struct Unroll;
+
+    impl<'a> Fold<'a> for Unroll {
+        fn fold_statement(&mut self, statement: Statement<'a>) -> Statement<'a> {
+            let statement = walk_statement(self, statement);
+
+            let kind = match statement.kind {
+                StatementKind::WhileLoop(while_loop) => StatementKind::DoBlock(DoBlock {
+                    body: while_loop.body,
+                }),
+                other => other,
+            };
+
+            Statement {
+                kind,
+                ..statement
+            }
+        }
+
+        fn fold_expression(&mut self, expression: Expression<'a>) -> Expression<'a> {
+            let expression = walk_expression(self, expression);
+
+            let kind = match expression.kind {
+                ExpressionKind::Name(name) => ExpressionKind::Name(Cow::from(format!("_{}", name))),
+                other => other,
+            };
+
+            Expression {
+                kind,
+                ..expression
+            }
+        }
+    }
+
+    #[test]
+    fn builds_a_new_tree() {
+        let tokens = tokenize("while a do b = c.d[e] + f(g, { h, [i] = j }) end").unwrap();
+        let original = parse_from_tokens(&tokens).unwrap();
+        let folded = Unroll.fold_chunk(original.clone());
+
+        let expected_tokens = tokenize("do _b = _c.d[_e] + _f(_g, { _h, [_i] = _j }) end").unwrap();
+        let expected = parse_from_tokens(&expected_tokens).unwrap();
+        assert!(folded.structural_eq(&expected));
+        assert_ne!(folded, original);
+    }
+
+    #[test]
+    fn default_fold_is_identity() {
+        struct Identity;
+        impl<'a> Fold<'a> for Identity {}
+
+        let source = "
+            local x, y = 1, ...
+            for i = 1, 10, 2 do if x then return y elseif z then break else goto a end end
+            for k, v in pairs(t) do repeat ::a:: until #k > -v end
+            local function f(a, ...) return a:b 'c' end
+            ;
+        ";
+
+        let tokens = tokenize(source).unwrap();
+        let chunk = parse_from_tokens(&tokens).unwrap();
+        assert_eq!(Identity.fold_chunk(chunk.clone()), chunk);
+    }
+}
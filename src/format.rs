@@ -0,0 +1,297 @@
+//! A formatter that lays out chunks in a consistent style, built on the
+//! [emitter][::emitter].
+//!
+//! ```
+//! use mab::format::{format_chunk, FormatConfig, IndentStyle};
+//!
+//! let tokens = mab::tokenize("if x then print('hi', {1,2,3}) end").unwrap();
+//! let chunk = mab::parse_from_tokens(&tokens).unwrap();
+//!
+//! let config = FormatConfig {
+//!     indent_style: IndentStyle::Spaces,
+//!     indent_width: 2,
+//!     max_line_width: 16,
+//!     ..FormatConfig::default()
+//! };
+//!
+//! assert_eq!(format_chunk(&chunk, &config), "\
+//! if x then
+//!   print(
+//!     \"hi\",
+//!     {1, 2, 3}
+//!   )
+//! end
+//! ");
+//! ```
+//!
+//! Like the emitter, the formatter works on the AST, so comments are lost.
+//!
+//! Printing and formatting are meant to round-trip: printed code parses back
+//! into a tree that's structurally equal to the one printed, and formatting
+//! formatted code doesn't change it. [verify_roundtrip][verify_roundtrip]
+//! checks both for a piece of source, for anyone who'd rather be sure before
+//! formatting their code.
+
+use std::error::Error;
+use std::fmt;
+
+use emitter::{emit_formatted, print_chunk};
+use ast::{AstEq, Chunk};
+use parser::{parse_from_tokens, parse_from_tokens_with_types, ParseError};
+use tokenizer::{Dialect, Token, TokenizeError, Tokenizer};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndentStyle {
+    Tabs,
+    Spaces,
+}
+
+/// Which quotes to put around strings. Long strings like `[[...]]` are always
+/// left alone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuoteStyle {
+    /// Keep each string's quotes as they are.
+    Preserve,
+
+    /// Use double quotes, unless the string has more double quotes than
+    /// single ones in it.
+    PreferDouble,
+
+    /// Use single quotes, unless the string has more single quotes than
+    /// double ones in it.
+    PreferSingle,
+}
+
+#[derive(Debug, Clone)]
+pub struct FormatConfig {
+    pub indent_style: IndentStyle,
+
+    /// How many spaces make up an indent. With tabs, this is how wide a tab
+    /// counts as when measuring lines.
+    pub indent_width: usize,
+
+    pub quote_style: QuoteStyle,
+
+    /// Whether tables that are split across lines get a separator after their
+    /// last item.
+    pub trailing_table_separator: bool,
+
+    /// Tables and argument lists that would make a line longer than this are
+    /// split up, one item per line. Lines can still end up longer when there's
+    /// nothing to split.
+    pub max_line_width: usize,
+}
+
+impl Default for FormatConfig {
+    fn default() -> FormatConfig {
+        FormatConfig {
+            indent_style: IndentStyle::Tabs,
+            indent_width: 4,
+            quote_style: QuoteStyle::PreferDouble,
+            trailing_table_separator: true,
+            max_line_width: 120,
+        }
+    }
+}
+
+pub fn format_chunk(chunk: &Chunk, config: &FormatConfig) -> String {
+    let mut output = String::new();
+    emit_formatted(&mut output, chunk, config).expect("writing to a String can't fail");
+    output
+}
+
+/// Why [verify_roundtrip][verify_roundtrip] failed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RoundtripError {
+    /// The source itself didn't tokenize, so there was nothing to check.
+    Tokenize(TokenizeError),
+
+    /// The source itself didn't parse, so there was nothing to check.
+    Parse(ParseError),
+
+    /// Printed or formatted code didn't tokenize or parse.
+    Invalid {
+        printed: String,
+        error: String,
+    },
+
+    /// Printed or formatted code parsed into a different tree.
+    Changed {
+        printed: String,
+    },
+
+    /// Formatting the formatted code changed it again.
+    NotIdempotent {
+        formatted: String,
+        reformatted: String,
+    },
+}
+
+impl fmt::Display for RoundtripError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RoundtripError::Tokenize(ref error) => write!(f, "the source didn't tokenize: {}", error),
+            RoundtripError::Parse(ref error) => write!(f, "the source didn't parse: {}", error),
+            RoundtripError::Invalid { ref error, .. } => write!(f, "printed code didn't parse: {}", error),
+            RoundtripError::Changed { .. } => write!(f, "printed code parsed into a different tree"),
+            RoundtripError::NotIdempotent { .. } => write!(f, "formatting formatted code changed it"),
+        }
+    }
+}
+
+impl Error for RoundtripError {}
+
+/// Checks that `source` prints and formats with `config` without changing
+/// what it means, and that formatting it again leaves it alone. `source` and
+/// the printed code are read as `dialect`, with type annotations in Luau.
+///
+/// ```
+/// use mab::Dialect;
+/// use mab::format::{verify_roundtrip, FormatConfig};
+///
+/// assert_eq!(verify_roundtrip("local t = {1, 2, [3] = 'x'}", &FormatConfig::default(), Dialect::Lua53), Ok(()));
+/// ```
+#[allow(clippy::result_large_err)]
+pub fn verify_roundtrip(source: &str, config: &FormatConfig, dialect: Dialect) -> Result<(), RoundtripError> {
+    let tokens = Tokenizer::new(source).dialect(dialect).collect::<Result<Vec<_>, _>>().map_err(RoundtripError::Tokenize)?;
+    let chunk = parse_in(&tokens, dialect).map_err(RoundtripError::Parse)?;
+
+    let printed = print_chunk(&chunk);
+    reparse(&printed, config, dialect, |reparsed| chunk.structural_eq(reparsed))?;
+
+    // Changing quotes counts as changing the tree, so with other quote
+    // styles the formatted tree can only be checked through idempotence.
+    let formatted = format_chunk(&chunk, config);
+    let reformatted = reparse(&formatted, config, dialect, |reparsed| {
+        config.quote_style != QuoteStyle::Preserve || chunk.structural_eq(reparsed)
+    })?;
+
+    if reformatted != formatted {
+        return Err(RoundtripError::NotIdempotent {
+            formatted,
+            reformatted,
+        });
+    }
+
+    Ok(())
+}
+
+// Parses printed code and checks it with `same`, then formats it again.
+#[allow(clippy::result_large_err)]
+fn reparse<F: FnOnce(&Chunk) -> bool>(printed: &str, config: &FormatConfig, dialect: Dialect, same: F) -> Result<String, RoundtripError> {
+    let invalid = |error: String| RoundtripError::Invalid {
+        printed: printed.to_string(),
+        error,
+    };
+
+    let tokens = Tokenizer::new(printed).dialect(dialect).collect::<Result<Vec<_>, _>>().map_err(|error| invalid(error.to_string()))?;
+    let reparsed = parse_in(&tokens, dialect).map_err(|error| invalid(error.to_string()))?;
+
+    if !same(&reparsed) {
+        return Err(RoundtripError::Changed {
+            printed: printed.to_string(),
+        });
+    }
+
+    Ok(format_chunk(&reparsed, config))
+}
+
+// Only Luau has type annotations.
+#[allow(clippy::result_large_err)]
+fn parse_in<'a>(tokens: &'a [Token<'a>], dialect: Dialect) -> Result<Chunk<'a>, ParseError> {
+    if dialect == Dialect::Luau {
+        parse_from_tokens_with_types(tokens)
+    } else {
+        parse_from_tokens(tokens)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokenizer::tokenize;
+    use parser::parse_from_tokens;
+
+    fn format(source: &str, config: &FormatConfig) -> String {
+        let tokens = tokenize(source).unwrap();
+        format_chunk(&parse_from_tokens(&tokens).unwrap(), config)
+    }
+
+    #[test]
+    fn quote_styles() {
+        let source = r#"print('a', "b", 'say "hi"', "it's", 'both \' "', [[long]])"#;
+
+        let double = FormatConfig::default();
+        assert_eq!(format(source, &double), "print(\"a\", \"b\", 'say \"hi\"', \"it's\", \"both ' \\\"\", [[long]])\n");
+
+        let single = FormatConfig { quote_style: QuoteStyle::PreferSingle, ..FormatConfig::default() };
+        assert_eq!(format(source, &single), "print('a', 'b', 'say \"hi\"', \"it's\", 'both \\' \"', [[long]])\n");
+
+        let preserve = FormatConfig { quote_style: QuoteStyle::Preserve, ..FormatConfig::default() };
+        assert_eq!(format(source, &preserve), format!("{}\n", source));
+    }
+
+    #[test]
+    fn splits_long_tables() {
+        let source = "local t = { alpha = 1, beta = { 2, 3 }, [gamma] = f(x, y) }";
+
+        let config = FormatConfig { max_line_width: 30, ..FormatConfig::default() };
+        assert_eq!(format(source, &config), "\
+local t = {
+\talpha = 1,
+\tbeta = {2, 3},
+\t[gamma] = f(x, y),
+}
+");
+
+        let config = FormatConfig {
+            indent_style: IndentStyle::Spaces,
+            trailing_table_separator: false,
+            max_line_width: 12,
+            ..FormatConfig::default()
+        };
+        assert_eq!(format(source, &config), "\
+local t = {
+    alpha = 1,
+    beta = {
+        2,
+        3
+    },
+    [gamma] = f(
+        x,
+        y
+    )
+}
+");
+    }
+
+    #[test]
+    fn roundtrip_errors() {
+        let config = FormatConfig::default();
+
+        match verify_roundtrip("local = 1", &config, Dialect::Lua53) {
+            Err(RoundtripError::Parse(_)) => {},
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+
+        match verify_roundtrip("x = 'unclosed", &config, Dialect::Lua53) {
+            Err(RoundtripError::Tokenize(_)) => {},
+            other => panic!("expected a tokenize error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn roundtrips_type_annotations() {
+        let source = "local x: number? = 1\nlocal function f(a: {string}, b): (number, string) return 1, a[1] end";
+        let config = FormatConfig { max_line_width: 20, ..FormatConfig::default() };
+
+        assert_eq!(verify_roundtrip(source, &config, Dialect::Luau), Ok(()));
+        assert!(verify_roundtrip(source, &config, Dialect::Lua53).is_err());
+    }
+
+    #[test]
+    fn short_lines_stay_put() {
+        let source = "do\n\tlocal t = {1, 2}\nend\n";
+        assert_eq!(format(source, &FormatConfig::default()), source);
+    }
+}
@@ -0,0 +1,192 @@
+//! Lookup tables built from a parsed chunk, for tools that keep node
+//! [IDs][::ast::NodeId] around and need to get back to the nodes.
+
+use std::collections::HashMap;
+
+use ast::*;
+use visit::{self, Visitor};
+
+/// Finds any statement or expression in a chunk by its ID.
+///
+/// ```
+/// use mab::ast::NodeRef;
+/// use mab::index::NodeIndex;
+///
+/// let tokens = mab::tokenize("local x = f()").unwrap();
+/// let chunk = mab::parse_from_tokens(&tokens).unwrap();
+/// let index = NodeIndex::new(&chunk);
+///
+/// let statement = &chunk.statements[0];
+/// assert_eq!(index.get(statement.id), Some(NodeRef::Statement(statement)));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct NodeIndex<'a> {
+    nodes: HashMap<NodeId, NodeRef<'a>>,
+}
+
+impl<'a> NodeIndex<'a> {
+    pub fn new(chunk: &'a Chunk<'a>) -> NodeIndex<'a> {
+        let mut index = NodeIndex::default();
+        index.visit_chunk(chunk);
+        index
+    }
+
+    pub fn get(&self, id: NodeId) -> Option<NodeRef<'a>> {
+        self.nodes.get(&id).cloned()
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+}
+
+impl<'a> Visitor<'a> for NodeIndex<'a> {
+    fn visit_statement(&mut self, statement: &'a Statement<'a>) {
+        self.nodes.insert(statement.id, NodeRef::Statement(statement));
+        visit::walk_statement(self, statement);
+    }
+
+    fn visit_expression(&mut self, expression: &'a Expression<'a>) {
+        self.nodes.insert(expression.id, NodeRef::Expression(expression));
+        visit::walk_expression(self, expression);
+    }
+}
+
+/// Finds the statement or expression that directly contains a node. Nodes at
+/// the top level of the chunk don't have a parent, and neither do IDs that
+/// aren't in the chunk.
+///
+/// A statement's parent is the statement whose block it's in, like the `if`
+/// around it. Expressions can have either kind of parent.
+#[derive(Debug, Clone, Default)]
+pub struct ParentMap<'a> {
+    parents: HashMap<NodeId, NodeRef<'a>>,
+}
+
+impl<'a> ParentMap<'a> {
+    pub fn new(chunk: &'a Chunk<'a>) -> ParentMap<'a> {
+        let mut builder = ParentMapBuilder {
+            map: ParentMap::default(),
+            ancestors: Vec::new(),
+        };
+
+        builder.visit_chunk(chunk);
+        builder.map
+    }
+
+    pub fn parent(&self, id: NodeId) -> Option<NodeRef<'a>> {
+        self.parents.get(&id).cloned()
+    }
+
+    /// The parent, grandparent, and so on, up to a top-level statement.
+    pub fn ancestors(&self, id: NodeId) -> Vec<NodeRef<'a>> {
+        let mut ancestors = Vec::new();
+        let mut id = id;
+
+        while let Some(parent) = self.parent(id) {
+            ancestors.push(parent);
+            id = parent.id();
+        }
+
+        ancestors
+    }
+}
+
+struct ParentMapBuilder<'a> {
+    map: ParentMap<'a>,
+    ancestors: Vec<NodeRef<'a>>,
+}
+
+impl<'a> ParentMapBuilder<'a> {
+    fn enter(&mut self, node: NodeRef<'a>) {
+        if let Some(&parent) = self.ancestors.last() {
+            self.map.parents.insert(node.id(), parent);
+        }
+
+        self.ancestors.push(node);
+    }
+}
+
+impl<'a> Visitor<'a> for ParentMapBuilder<'a> {
+    fn visit_statement(&mut self, statement: &'a Statement<'a>) {
+        self.enter(NodeRef::Statement(statement));
+        visit::walk_statement(self, statement);
+        self.ancestors.pop();
+    }
+
+    fn visit_expression(&mut self, expression: &'a Expression<'a>) {
+        self.enter(NodeRef::Expression(expression));
+        visit::walk_expression(self, expression);
+        self.ancestors.pop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokenizer::tokenize;
+    use parser::parse_from_tokens;
+
+    #[test]
+    fn finds_every_node() {
+        let tokens = tokenize("while a do b.c = { d + 1 } end").unwrap();
+        let chunk = parse_from_tokens(&tokens).unwrap();
+        let index = NodeIndex::new(&chunk);
+
+        // The loop, the assignment, and `a`, `b`, `b.c`, the table, `d + 1`,
+        // `d`, and `1`.
+        assert_eq!(index.len(), 9);
+
+        let while_loop = match chunk.statements[0].kind {
+            StatementKind::WhileLoop(ref while_loop) => while_loop,
+            _ => panic!("expected a while loop"),
+        };
+
+        let condition = &while_loop.condition;
+        assert_eq!(index.get(condition.id), Some(NodeRef::Expression(condition)));
+
+        let assignment = &while_loop.body.statements[0];
+        assert_eq!(index.get(assignment.id), Some(NodeRef::Statement(assignment)));
+
+        assert_eq!(index.get(NodeId::DUMMY), None);
+    }
+
+    #[test]
+    fn finds_parents() {
+        let tokens = tokenize("if a then f(b + c) end").unwrap();
+        let chunk = parse_from_tokens(&tokens).unwrap();
+        let parents = ParentMap::new(&chunk);
+
+        let if_statement = &chunk.statements[0];
+        let (condition, call) = match if_statement.kind {
+            StatementKind::IfStatement(ref if_statement) => (&if_statement.condition, &if_statement.body.statements[0]),
+            _ => panic!("expected an if statement"),
+        };
+
+        let argument = match call.kind {
+            StatementKind::FunctionCall(FunctionCall { arguments: FunctionArguments::Parenthesized(ref arguments), .. }) => &arguments[0],
+            _ => panic!("expected a function call"),
+        };
+
+        let left = match argument.kind {
+            ExpressionKind::BinaryOp(ref binary_op) => &binary_op.left,
+            _ => panic!("expected a binary operator"),
+        };
+
+        assert_eq!(parents.parent(if_statement.id), None);
+        assert_eq!(parents.parent(condition.id), Some(NodeRef::Statement(if_statement)));
+        assert_eq!(parents.parent(call.id), Some(NodeRef::Statement(if_statement)));
+        assert_eq!(parents.parent(argument.id), Some(NodeRef::Statement(call)));
+        assert_eq!(parents.parent(left.id), Some(NodeRef::Expression(argument)));
+
+        assert_eq!(parents.ancestors(left.id), vec![
+            NodeRef::Expression(argument),
+            NodeRef::Statement(call),
+            NodeRef::Statement(if_statement),
+        ]);
+    }
+}
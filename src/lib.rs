@@ -3,13 +3,52 @@
 extern crate serde;
 extern crate regex;
 
+#[cfg(feature = "codespan-reporting")] extern crate codespan_reporting;
+#[cfg(feature = "ariadne")] extern crate ariadne;
+#[cfg(feature = "bincode")] extern crate bincode;
+#[cfg(feature = "rmp-serde")] extern crate rmp_serde;
+#[cfg(feature = "toml")] extern crate toml;
+#[cfg(feature = "macros")] extern crate mab_macros;
+
 #[macro_use]
 mod parser_core;
 
 pub mod ast;
+pub mod builder;
 pub mod emitter;
+pub mod format;
+pub mod minify;
+pub mod obfuscate;
+pub mod normalize;
+pub mod constant_fold;
+pub mod dead_code;
 pub mod tokenizer;
 pub mod parser;
+pub mod visit;
+pub mod fold;
+pub mod index;
+pub mod editor;
+pub mod cst;
+pub mod docs;
+pub mod analysis;
+pub mod lint;
+pub mod schema;
+mod validate;
+mod owned;
+mod debug_tree;
+
+#[cfg(any(feature = "codespan-reporting", feature = "ariadne"))]
+pub mod diagnostics;
+
+#[cfg(any(feature = "bincode", feature = "rmp-serde"))]
+pub mod binary;
+
+#[cfg(feature = "macros")]
+#[doc(hidden)]
+pub mod quote;
+
+#[cfg(feature = "macros")]
+pub use mab_macros::lua;
 
 pub use tokenizer::*;
 pub use parser::*;
\ No newline at end of file
@@ -0,0 +1,476 @@
+//! Settings for which lints to run and how, usually read from `mab.toml`
+//! files.
+//!
+//! ```toml
+//! standard_library = "lua51"
+//! globals = ["game", "workspace"]
+//!
+//! [unused_locals]
+//! ignore_underscore = false
+//!
+//! [undefined_globals]
+//! enabled = false
+//!
+//! [metrics]
+//! max_cyclomatic_complexity = 15
+//! max_parameters = 6
+//!
+//! [impossible_operations]
+//! enabled = false
+//!
+//! [security]
+//! enabled = true
+//!
+//! [mismatched_assignments]
+//! enabled = false
+//!
+//! [strict_globals]
+//! enabled = true
+//! ```
+//!
+//! Every setting can be left out. [Config::for_file][Config::for_file] reads
+//! the `mab.toml` in a file's directory and in each directory above it, with
+//! files further down overriding the ones above them, and every file's
+//! `globals` added together. A file with `root = true` stops the search, so
+//! nothing above it applies.
+//!
+//! Reading files needs the `toml` feature.
+
+#[cfg(feature = "toml")] use std::error::Error;
+#[cfg(feature = "toml")] use std::fmt;
+#[cfg(feature = "toml")] use std::io;
+#[cfg(feature = "toml")] use std::path::{Path, PathBuf};
+
+use cst::Cst;
+use analysis::scope::ReferenceKind;
+use lint::{impossible_operations, metrics, mismatched_assignments, security, strict_globals, unused_locals, undefined_globals, Lint, LintKind};
+use lint::undefined_globals::StandardLibrary;
+
+/// The name of configuration files.
+pub const CONFIG_FILE_NAME: &str = "mab.toml";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Whether to ignore configuration files in the directories above this
+    /// one.
+    pub root: bool,
+
+    /// Which Lua's globals the code can use. Defaults to Lua 5.4's.
+    pub standard_library: Option<StandardLibrary>,
+
+    /// Globals the code can use on top of the standard library's.
+    pub globals: Vec<String>,
+
+    pub unused_locals: UnusedLocalsConfig,
+    pub undefined_globals: UndefinedGlobalsConfig,
+    pub metrics: MetricsConfig,
+    pub impossible_operations: ImpossibleOperationsConfig,
+    pub security: SecurityConfig,
+    pub mismatched_assignments: MismatchedAssignmentsConfig,
+    pub strict_globals: StrictGlobalsConfig,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UnusedLocalsConfig {
+    /// Whether the lint runs at all. Defaults to true.
+    pub enabled: Option<bool>,
+
+    /// See [Options::ignore_underscore][unused_locals::Options::ignore_underscore].
+    pub ignore_underscore: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UndefinedGlobalsConfig {
+    /// Whether the lint runs at all. Defaults to true.
+    pub enabled: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MetricsConfig {
+    /// Whether the lint runs at all. Defaults to true, but it doesn't report
+    /// anything until some limits are set.
+    pub enabled: Option<bool>,
+
+    pub max_cyclomatic_complexity: Option<usize>,
+    pub max_nesting_depth: Option<usize>,
+    pub max_parameters: Option<usize>,
+    pub max_statements: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ImpossibleOperationsConfig {
+    /// Whether the lint runs at all. Defaults to true.
+    pub enabled: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SecurityConfig {
+    /// Whether the lint runs at all. Defaults to false, since it's only
+    /// meant for code that isn't trusted.
+    pub enabled: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MismatchedAssignmentsConfig {
+    /// Whether the lint runs at all. Defaults to true.
+    pub enabled: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StrictGlobalsConfig {
+    /// Whether the lint runs at all. Defaults to false. While it runs, it
+    /// reports assignments to globals instead of
+    /// [undefined_globals][undefined_globals], so they're only reported once
+    /// and the files' `allow-global` comments apply.
+    pub enabled: Option<bool>,
+}
+
+impl Config {
+    /// Layers `child`, from a directory further down, over this config.
+    pub fn merge(mut self, child: Config) -> Config {
+        self.root = self.root || child.root;
+        self.standard_library = child.standard_library.or(self.standard_library);
+        self.globals.extend(child.globals);
+
+        self.unused_locals = UnusedLocalsConfig {
+            enabled: child.unused_locals.enabled.or(self.unused_locals.enabled),
+            ignore_underscore: child.unused_locals.ignore_underscore.or(self.unused_locals.ignore_underscore),
+        };
+
+        self.undefined_globals = UndefinedGlobalsConfig {
+            enabled: child.undefined_globals.enabled.or(self.undefined_globals.enabled),
+        };
+
+        self.metrics = MetricsConfig {
+            enabled: child.metrics.enabled.or(self.metrics.enabled),
+            max_cyclomatic_complexity: child.metrics.max_cyclomatic_complexity.or(self.metrics.max_cyclomatic_complexity),
+            max_nesting_depth: child.metrics.max_nesting_depth.or(self.metrics.max_nesting_depth),
+            max_parameters: child.metrics.max_parameters.or(self.metrics.max_parameters),
+            max_statements: child.metrics.max_statements.or(self.metrics.max_statements),
+        };
+
+        self.impossible_operations = ImpossibleOperationsConfig {
+            enabled: child.impossible_operations.enabled.or(self.impossible_operations.enabled),
+        };
+
+        self.security = SecurityConfig {
+            enabled: child.security.enabled.or(self.security.enabled),
+        };
+
+        self.mismatched_assignments = MismatchedAssignmentsConfig {
+            enabled: child.mismatched_assignments.enabled.or(self.mismatched_assignments.enabled),
+        };
+
+        self.strict_globals = StrictGlobalsConfig {
+            enabled: child.strict_globals.enabled.or(self.strict_globals.enabled),
+        };
+
+        self
+    }
+
+    pub fn unused_locals_options(&self) -> unused_locals::Options {
+        let defaults = unused_locals::Options::default();
+
+        unused_locals::Options {
+            ignore_underscore: self.unused_locals.ignore_underscore.unwrap_or(defaults.ignore_underscore),
+        }
+    }
+
+    pub fn undefined_globals_options(&self) -> undefined_globals::Options {
+        let defaults = undefined_globals::Options::default();
+
+        undefined_globals::Options {
+            standard_library: self.standard_library.unwrap_or(defaults.standard_library),
+            globals: self.globals.clone(),
+        }
+    }
+
+    pub fn metrics_options(&self) -> metrics::Options {
+        metrics::Options {
+            max_cyclomatic_complexity: self.metrics.max_cyclomatic_complexity,
+            max_nesting_depth: self.metrics.max_nesting_depth,
+            max_parameters: self.metrics.max_parameters,
+            max_statements: self.metrics.max_statements,
+        }
+    }
+
+    /// Runs every lint that's enabled, and returns what they found in the
+    /// order it appears in the source.
+    pub fn check(&self, cst: &Cst) -> Vec<Lint> {
+        let mut lints = Vec::new();
+
+        if self.unused_locals.enabled.unwrap_or(true) {
+            lints.extend(unused_locals::check(cst, &self.unused_locals_options()));
+        }
+
+        let strict_globals = self.strict_globals.enabled.unwrap_or(false);
+
+        if self.undefined_globals.enabled.unwrap_or(true) {
+            lints.extend(undefined_globals::check(cst, &self.undefined_globals_options()).into_iter()
+                .filter(|lint| !strict_globals || !matches!(lint.kind, LintKind::UndefinedGlobal { kind: ReferenceKind::Write, .. })));
+        }
+
+        if self.metrics.enabled.unwrap_or(true) {
+            lints.extend(metrics::check(cst, &self.metrics_options()));
+        }
+
+        if self.impossible_operations.enabled.unwrap_or(true) {
+            lints.extend(impossible_operations::check(cst));
+        }
+
+        if self.security.enabled.unwrap_or(false) {
+            lints.extend(security::check(cst));
+        }
+
+        if self.mismatched_assignments.enabled.unwrap_or(true) {
+            lints.extend(mismatched_assignments::check(cst));
+        }
+
+        if strict_globals {
+            lints.extend(strict_globals::check(cst));
+        }
+
+        lints.sort_by_key(|lint| lint.start_position.bytes);
+        lints
+    }
+}
+
+#[cfg(feature = "toml")]
+impl Config {
+    pub fn from_toml(text: &str) -> Result<Config, ConfigError> {
+        toml::from_str(text).map_err(|error| ConfigError::Toml {
+            path: None,
+            error,
+        })
+    }
+
+    /// Reads and merges the configuration files that apply to the file at
+    /// `path`. Directories without one are skipped, and if there aren't any
+    /// at all, this is the default config.
+    pub fn for_file(path: &Path) -> Result<Config, ConfigError> {
+        let mut configs = Vec::new();
+
+        for directory in path.ancestors().skip(1) {
+            let config_path = directory.join(CONFIG_FILE_NAME);
+
+            let text = match ::std::fs::read_to_string(&config_path) {
+                Ok(text) => text,
+                Err(ref error) if error.kind() == io::ErrorKind::NotFound => continue,
+                Err(error) => return Err(ConfigError::Io { path: config_path, error }),
+            };
+
+            let config: Config = toml::from_str(&text).map_err(|error| ConfigError::Toml {
+                path: Some(config_path),
+                error,
+            })?;
+
+            let root = config.root;
+            configs.push(config);
+
+            if root {
+                break;
+            }
+        }
+
+        Ok(configs.into_iter().rev().fold(Config::default(), Config::merge))
+    }
+}
+
+/// Why a configuration file couldn't be read.
+#[cfg(feature = "toml")]
+#[derive(Debug)]
+pub enum ConfigError {
+    Io {
+        path: PathBuf,
+        error: io::Error,
+    },
+
+    /// The file isn't valid TOML, or has settings that don't exist.
+    Toml {
+        /// The file, unless the config came from a string.
+        path: Option<PathBuf>,
+        error: toml::de::Error,
+    },
+}
+
+#[cfg(feature = "toml")]
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ConfigError::Io { ref path, ref error } => write!(f, "couldn't read {}: {}", path.display(), error),
+            ConfigError::Toml { path: Some(ref path), ref error } => write!(f, "invalid config in {}: {}", path.display(), error),
+            ConfigError::Toml { path: None, ref error } => write!(f, "invalid config: {}", error),
+        }
+    }
+}
+
+#[cfg(feature = "toml")]
+impl Error for ConfigError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokenizer::tokenize;
+
+    #[test]
+    fn merging() {
+        let parent = Config {
+            standard_library: Some(StandardLibrary::Lua51),
+            globals: vec!["game".to_string()],
+            unused_locals: UnusedLocalsConfig {
+                enabled: Some(false),
+                ignore_underscore: Some(false),
+            },
+            ..Config::default()
+        };
+
+        let child = Config {
+            globals: vec!["plugin".to_string()],
+            unused_locals: UnusedLocalsConfig {
+                enabled: Some(true),
+                ignore_underscore: None,
+            },
+            ..Config::default()
+        };
+
+        let merged = parent.merge(child);
+        assert_eq!(merged.standard_library, Some(StandardLibrary::Lua51));
+        assert_eq!(merged.globals, ["game", "plugin"]);
+        assert_eq!(merged.unused_locals.enabled, Some(true));
+        assert!(!merged.unused_locals_options().ignore_underscore);
+    }
+
+    // The codes of the lints `config` finds in `source`, in order.
+    fn codes(source: &str, config: &Config) -> Vec<&'static str> {
+        let tokens = tokenize(source).unwrap();
+        let cst = Cst::parse(&tokens).unwrap();
+
+        config.check(&cst).iter().map(|lint| lint.code()).collect()
+    }
+
+    #[test]
+    fn check() {
+        let source = "local x print(y) local function f(a, b) return a, b end f()";
+        assert_eq!(codes(source, &Config::default()), ["L001", "L002"]);
+
+        let config = Config {
+            globals: vec!["y".to_string()],
+            undefined_globals: UndefinedGlobalsConfig { enabled: Some(true) },
+            ..Config::default()
+        };
+        assert_eq!(codes(source, &config), ["L001"]);
+
+        let config = Config {
+            unused_locals: UnusedLocalsConfig { enabled: Some(false), ignore_underscore: None },
+            ..Config::default()
+        };
+        assert_eq!(codes(source, &config), ["L002"]);
+
+        let config = Config {
+            metrics: MetricsConfig { max_parameters: Some(1), ..MetricsConfig::default() },
+            ..Config::default()
+        };
+        assert_eq!(codes(source, &config), ["L001", "L002", "L003"]);
+    }
+
+    #[test]
+    fn impossible_operations() {
+        let source = "local n = 1 n()";
+        assert_eq!(codes(source, &Config::default()), ["L004"]);
+
+        let config = Config {
+            impossible_operations: ImpossibleOperationsConfig { enabled: Some(false) },
+            ..Config::default()
+        };
+        assert!(codes(source, &config).is_empty());
+    }
+
+    #[test]
+    fn security() {
+        let source = "os.execute('ls')";
+        assert!(codes(source, &Config::default()).is_empty());
+
+        let config = Config {
+            security: SecurityConfig { enabled: Some(true) },
+            ..Config::default()
+        };
+        assert_eq!(codes(source, &config), ["L005"]);
+    }
+
+    #[test]
+    fn mismatched_assignments() {
+        let source = "local a, b = 1 print(a, b)";
+        assert_eq!(codes(source, &Config::default()), ["L006"]);
+
+        let config = Config {
+            mismatched_assignments: MismatchedAssignmentsConfig { enabled: Some(false) },
+            ..Config::default()
+        };
+        assert!(codes(source, &config).is_empty());
+    }
+
+    #[test]
+    fn strict_globals() {
+        let source = "-- mab: allow-global y\nx = 1 y = z";
+        assert_eq!(codes(source, &Config::default()), ["L002", "L002", "L002"]);
+
+        // Strict mode takes over reporting writes from undefined_globals.
+        let config = Config {
+            strict_globals: StrictGlobalsConfig { enabled: Some(true) },
+            ..Config::default()
+        };
+        assert_eq!(codes(source, &config), ["L007", "L002"]);
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn from_toml() {
+        let config = Config::from_toml("
+            standard_library = \"lua51\"
+            globals = [\"game\"]
+
+            [undefined_globals]
+            enabled = false
+        ").unwrap();
+
+        assert_eq!(config, Config {
+            standard_library: Some(StandardLibrary::Lua51),
+            globals: vec!["game".to_string()],
+            undefined_globals: UndefinedGlobalsConfig { enabled: Some(false) },
+            ..Config::default()
+        });
+
+        assert!(Config::from_toml("unknown_rule = true").is_err());
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn for_file() {
+        use std::fs;
+
+        let top = ::std::env::temp_dir().join(format!("mab-config-test-{}", ::std::process::id()));
+        let project = top.join("project");
+        let nested = project.join("src").join("nested");
+        fs::create_dir_all(&nested).unwrap();
+
+        fs::write(top.join(CONFIG_FILE_NAME), "globals = [\"outside\"]").unwrap();
+        fs::write(project.join(CONFIG_FILE_NAME), "root = true\nglobals = [\"game\"]\nstandard_library = \"lua51\"").unwrap();
+        fs::write(nested.join(CONFIG_FILE_NAME), "globals = [\"plugin\"]\n[unused_locals]\nenabled = false").unwrap();
+
+        let config = Config::for_file(&nested.join("init.lua"));
+        let _ = fs::remove_dir_all(&top);
+        let config = config.unwrap();
+
+        assert_eq!(config.globals, ["game", "plugin"]);
+        assert_eq!(config.standard_library, Some(StandardLibrary::Lua51));
+        assert_eq!(config.unused_locals.enabled, Some(false));
+    }
+}
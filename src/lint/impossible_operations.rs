@@ -0,0 +1,205 @@
+//! Finds operations that always fail, like calling a number or concatenating
+//! a table, using the types [inference][::analysis::inference] can work out.
+//!
+//! ```
+//! use mab::cst::Cst;
+//! use mab::lint::impossible_operations::check;
+//!
+//! let tokens = mab::tokenize("
+//!     local count = 3
+//!     local items = {}
+//!     print('items: ' .. items)
+//!     count()
+//! ").unwrap();
+//! let cst = Cst::parse(&tokens).unwrap();
+//!
+//! let messages = check(&cst).iter()
+//!     .map(|lint| lint.to_string())
+//!     .collect::<Vec<_>>();
+//!
+//! assert_eq!(messages, [
+//!     "Attempt to concatenate a table value at line 4, column 24",
+//!     "Attempt to call a number value at line 5, column 5",
+//! ]);
+//! ```
+//!
+//! Since only types that can't be anything else are inferred, this doesn't
+//! report anything that could work, like calling a table that might have a
+//! `__call` metamethod.
+
+use std::fmt;
+
+use analysis::inference::{BasicType, TypeInference};
+use ast::*;
+use cst::Cst;
+use lint::{Lint, LintKind};
+use visit::{self, Visitor};
+
+/// Something done to a value, for saying which one can't be done.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operation {
+    Call,
+    Concatenate,
+    Arithmetic,
+    Index,
+    Length,
+}
+
+impl Operation {
+    /// Whether doing this to a value of type `found` always fails.
+    pub fn fails_on(self, found: BasicType) -> bool {
+        use self::BasicType::*;
+
+        match self {
+            Operation::Call => found != Function,
+            Operation::Concatenate | Operation::Arithmetic => matches!(found, Nil | Boolean | Table | Function),
+            Operation::Index | Operation::Length => matches!(found, Nil | Boolean | Number | Function),
+        }
+    }
+}
+
+/// Writes the operation the way Lua's error messages do, like "perform
+/// arithmetic on".
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let text = match *self {
+            Operation::Call => "call",
+            Operation::Concatenate => "concatenate",
+            Operation::Arithmetic => "perform arithmetic on",
+            Operation::Index => "index",
+            Operation::Length => "get the length of",
+        };
+
+        f.write_str(text)
+    }
+}
+
+/// Reports each operation that always fails, pointing at the start of the
+/// value it's done to.
+pub fn check(cst: &Cst) -> Vec<Lint> {
+    let mut checker = Checker {
+        cst,
+        inference: TypeInference::new(cst.chunk()),
+        lints: Vec::new(),
+    };
+
+    checker.visit_chunk(cst.chunk());
+    checker.lints
+}
+
+struct Checker<'c, 'a: 'c> {
+    cst: &'c Cst<'a>,
+    inference: TypeInference<'a>,
+    lints: Vec<Lint>,
+}
+
+impl<'c, 'a> Checker<'c, 'a> {
+    fn check(&mut self, operation: Operation, operand: &Expression) {
+        let found = match self.inference.type_of(operand) {
+            Some(found) if operation.fails_on(found) => found,
+            _ => return,
+        };
+
+        let token = match self.cst.tokens(operand.id).and_then(|tokens| tokens.first()) {
+            Some(token) => token,
+            None => return,
+        };
+
+        self.lints.push(Lint::new(LintKind::ImpossibleOperation { operation, found }, token));
+    }
+}
+
+impl<'c, 'a> Visitor<'a> for Checker<'c, 'a> {
+    fn visit_function_call(&mut self, call: &'a FunctionCall<'a>) {
+        // `a:b()` looks `b` up in `a` before calling it.
+        let operation = if call.method.is_some() { Operation::Index } else { Operation::Call };
+        self.check(operation, &call.name_expression);
+
+        visit::walk_function_call(self, call);
+    }
+
+    fn visit_field_access(&mut self, field_access: &'a FieldAccess<'a>) {
+        self.check(Operation::Index, &field_access.target);
+        visit::walk_field_access(self, field_access);
+    }
+
+    fn visit_index(&mut self, index: &'a Index<'a>) {
+        self.check(Operation::Index, &index.target);
+        visit::walk_index(self, index);
+    }
+
+    fn visit_unary_op(&mut self, unary_op: &'a UnaryOp<'a>) {
+        match unary_op.operator {
+            UnaryOpKind::Negate => self.check(Operation::Arithmetic, &unary_op.argument),
+            UnaryOpKind::Length => self.check(Operation::Length, &unary_op.argument),
+            UnaryOpKind::BooleanNot => {},
+        }
+
+        visit::walk_unary_op(self, unary_op);
+    }
+
+    fn visit_binary_op(&mut self, binary_op: &'a BinaryOp<'a>) {
+        let operation = match binary_op.operator {
+            BinaryOpKind::Concat => Some(Operation::Concatenate),
+            BinaryOpKind::Add | BinaryOpKind::Subtract | BinaryOpKind::Multiply | BinaryOpKind::Divide |
+            BinaryOpKind::FloorDivide | BinaryOpKind::Modulo | BinaryOpKind::Exponent => Some(Operation::Arithmetic),
+            _ => None,
+        };
+
+        if let Some(operation) = operation {
+            self.check(operation, &binary_op.left);
+            self.check(operation, &binary_op.right);
+        }
+
+        visit::walk_binary_op(self, binary_op);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lint;
+
+    fn found(source: &str) -> Vec<(String, usize)> {
+        lint::found(source, "L004", check)
+    }
+
+    #[test]
+    fn operations() {
+        assert_eq!(found("local n = 1 local s = 'a' local t = {} local f = nil n() s:upper() n:m() print(f.x, t[1], #n, -true, t + 1, x .. t)"), [
+            ("Attempt to call a number value".to_string(), 53),
+            ("Attempt to index a number value".to_string(), 67),
+            ("Attempt to index a nil value".to_string(), 79),
+            ("Attempt to get the length of a number value".to_string(), 91),
+            ("Attempt to perform arithmetic on a boolean value".to_string(), 95),
+            ("Attempt to perform arithmetic on a table value".to_string(), 101),
+            ("Attempt to concatenate a table value".to_string(), 113),
+        ]);
+    }
+
+    #[test]
+    fn unknown_types() {
+        assert!(found("
+            local t = {}
+            setmetatable(t, { __call = print, __concat = print })
+            t() print(t .. 'x')
+
+            local n = 1
+            n = print
+            n()
+
+            local passed = {}
+            init(passed)
+            passed()
+
+            local aliased = {}
+            local alias = aliased
+            setmetatable(alias, { __call = print })
+            aliased()
+
+            local function f() end
+            f()
+            print(x(), y.z, 1 + '2', #'s', ('x'):rep(2))
+        ").is_empty());
+    }
+}
@@ -0,0 +1,119 @@
+//! Finds functions that are too big or complicated, going by limits on their
+//! [metrics][::analysis::metrics].
+//!
+//! ```
+//! use mab::cst::Cst;
+//! use mab::lint::metrics::{check, Options};
+//!
+//! let tokens = mab::tokenize("
+//!     local function update(entity, dt, world, input, sound)
+//!         entity.x = entity.x + dt
+//!     end
+//! ").unwrap();
+//! let cst = Cst::parse(&tokens).unwrap();
+//!
+//! let options = Options {
+//!     max_parameters: Some(4),
+//!     ..Options::default()
+//! };
+//!
+//! let messages = check(&cst, &options).iter()
+//!     .map(|lint| lint.to_string())
+//!     .collect::<Vec<_>>();
+//!
+//! assert_eq!(messages, ["`update` has a parameter count of 5, over the limit of 4 at line 2, column 20"]);
+//! ```
+//!
+//! There aren't any limits by default, so nothing is reported until some are
+//! set. The chunk itself is never reported.
+
+use analysis::metrics::{function_metrics, Metric};
+use cst::Cst;
+use lint::{Lint, LintKind};
+use tokenizer::{Symbol, Token, TokenKind};
+
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    pub max_cyclomatic_complexity: Option<usize>,
+    pub max_nesting_depth: Option<usize>,
+    pub max_parameters: Option<usize>,
+    pub max_statements: Option<usize>,
+}
+
+impl Options {
+    pub fn limit(&self, metric: Metric) -> Option<usize> {
+        match metric {
+            Metric::CyclomaticComplexity => self.max_cyclomatic_complexity,
+            Metric::NestingDepth => self.max_nesting_depth,
+            Metric::Parameters => self.max_parameters,
+            Metric::Statements => self.max_statements,
+        }
+    }
+}
+
+const METRICS: &[Metric] = &[Metric::CyclomaticComplexity, Metric::NestingDepth, Metric::Parameters, Metric::Statements];
+
+/// Reports each metric of each function that's over its limit, pointing at
+/// the function's name.
+pub fn check(cst: &Cst, options: &Options) -> Vec<Lint> {
+    let mut lints = Vec::new();
+
+    for metrics in function_metrics(cst.chunk()) {
+        let token = match metrics.statement.and_then(|statement| name_token(cst.tokens(statement)?)) {
+            Some(token) => token,
+            None => continue,
+        };
+
+        for &metric in METRICS {
+            let (value, limit) = match options.limit(metric) {
+                Some(limit) if metrics.get(metric) > limit => (metrics.get(metric), limit),
+                _ => continue,
+            };
+
+            let kind = LintKind::MetricOverLimit {
+                function: metrics.name.clone().unwrap_or_default(),
+                metric,
+                value,
+                limit,
+            };
+
+            lints.push(Lint::new(kind, token));
+        }
+    }
+
+    lints
+}
+
+// The first name after `function`, which comes after `local` for local
+// functions.
+fn name_token<'a>(tokens: &'a [Token<'a>]) -> Option<&'a Token<'a>> {
+    let function = tokens.iter().position(|token| token.kind == TokenKind::Symbol(Symbol::Function))?;
+    tokens.get(function + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lint;
+
+    #[test]
+    fn limits() {
+        let source = "function a.b(x) if x then if x then return end end end local function c(x, y) return x and y end";
+        let found = |options: &Options| lint::found(source, "L003", |cst| check(cst, options));
+
+        assert!(found(&Options::default()).is_empty());
+
+        let options = Options {
+            max_cyclomatic_complexity: Some(2),
+            max_nesting_depth: Some(1),
+            max_parameters: Some(1),
+            ..Options::default()
+        };
+
+        assert_eq!(found(&options), [
+            ("`a.b` has a cyclomatic complexity of 3, over the limit of 2".to_string(), 9),
+            ("`a.b` has a nesting depth of 2, over the limit of 1".to_string(), 9),
+            ("`c` has a parameter count of 2, over the limit of 1".to_string(), 70),
+        ]);
+    }
+}
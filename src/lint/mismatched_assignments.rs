@@ -0,0 +1,123 @@
+//! Finds assignments with a different number of names than values, which
+//! quietly sets the extra names to `nil` or throws the extra values away.
+//!
+//! ```
+//! use mab::cst::Cst;
+//! use mab::lint::mismatched_assignments::check;
+//!
+//! let tokens = mab::tokenize("
+//!     local width, height = 10
+//!     x, y = 1, 2, 3
+//!     local a, b = f()
+//! ").unwrap();
+//! let cst = Cst::parse(&tokens).unwrap();
+//!
+//! let messages = check(&cst).iter()
+//!     .map(|lint| lint.to_string())
+//!     .collect::<Vec<_>>();
+//!
+//! assert_eq!(messages, [
+//!     "Assigning 1 value to 2 names at line 2, column 18",
+//!     "Assigning 3 values to 2 names at line 3, column 18",
+//! ]);
+//! ```
+//!
+//! The lint points at the first name without a value, or the first value
+//! without a name. Assignments whose last value is a function call or `...`
+//! aren't reported when they have more names than values, since those can
+//! have any number of values. `local` statements without any values are
+//! left alone too, since they're meant to set everything to `nil`.
+
+use ast::*;
+use cst::Cst;
+use lint::{names_in_list, Lint, LintKind};
+use tokenizer::Token;
+
+/// Reports each assignment with mismatched counts, in the order they appear
+/// in the source.
+pub fn check(cst: &Cst) -> Vec<Lint> {
+    let mut lints = Vec::new();
+
+    for node in cst.chunk().descendants() {
+        let statement = match node {
+            NodeRef::Statement(statement) => statement,
+            NodeRef::Expression(_) => continue,
+        };
+
+        let (names, values) = match statement.kind {
+            StatementKind::LocalAssignment(ref local) if !local.values.is_empty() => (local.names.len(), &local.values),
+            StatementKind::Assignment(ref assignment) => (assignment.targets.len(), &assignment.values),
+            _ => continue,
+        };
+
+        let token = if names > values.len() {
+            if let Some(ExpressionKind::FunctionCall(_)) | Some(ExpressionKind::VarArg) = values.last().map(|value| &value.kind) {
+                continue;
+            }
+
+            name_token(cst, statement, values.len())
+        } else if names < values.len() {
+            cst.tokens(values[names].id).and_then(|tokens| tokens.first())
+        } else {
+            continue;
+        };
+
+        // Fall back to the start of the statement when the name can't be
+        // found, like when it has a type annotation before it.
+        let token = match token.or_else(|| cst.tokens(statement.id).and_then(|tokens| tokens.first())) {
+            Some(token) => token,
+            None => continue,
+        };
+
+        let kind = LintKind::MismatchedAssignment {
+            names,
+            values: values.len(),
+        };
+
+        lints.push(Lint::new(kind, token));
+    }
+
+    lints.sort_by_key(|lint| lint.start_position.bytes);
+    lints
+}
+
+// The token the name at `position` in an assignment starts with.
+fn name_token<'a>(cst: &Cst<'a>, statement: &Statement, position: usize) -> Option<&'a Token<'a>> {
+    match statement.kind {
+        StatementKind::Assignment(ref assignment) => {
+            cst.tokens(assignment.targets.get(position)?.id)?.first()
+        },
+        _ => names_in_list(&cst.tokens(statement.id)?[1..]).nth(position),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lint;
+
+    fn found(source: &str) -> Vec<(String, usize)> {
+        lint::found(source, "L006", check)
+    }
+
+    #[test]
+    fn mismatches() {
+        assert_eq!(found("local a, b, c = 1, {} t.x, t[1] = nil local d = 1, g()"), [
+            ("Assigning 2 values to 3 names".to_string(), 12),
+            ("Assigning 1 value to 2 names".to_string(), 27),
+            ("Assigning 2 values to 1 name".to_string(), 51),
+        ]);
+    }
+
+    #[test]
+    fn matching_counts() {
+        assert!(found("
+            local a, b
+            local c, d = 1, 2
+            local e, f = g()
+            local h, i = 1, ...
+            a, b = b, a
+            function j(...) local k, l = ... end
+        ").is_empty());
+    }
+}
@@ -0,0 +1,224 @@
+//! Checks for code that's valid, but probably not what was meant.
+//!
+//! Each kind of lint is in its own module, with a `check` function that takes
+//! a [Cst][::cst::Cst] so it can point at the tokens it's about.
+
+use std::fmt;
+use std::ops::Range;
+
+use analysis::inference::BasicType;
+use analysis::metrics::Metric;
+use analysis::scope::{Declaration, DeclarationKind, Reference, ReferenceKind};
+use ast::*;
+use cst::Cst;
+use index::NodeIndex;
+use tokenizer::{SourcePosition, Symbol, Token, TokenKind};
+
+pub mod unused_locals;
+pub mod undefined_globals;
+pub mod metrics;
+pub mod impossible_operations;
+pub mod security;
+pub mod mismatched_assignments;
+pub mod strict_globals;
+pub mod config;
+
+/// What a [Lint][Lint] is about.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LintKind {
+    /// A local that's declared but never read.
+    UnusedLocal {
+        name: String,
+        kind: DeclarationKind,
+    },
+
+    /// A global that isn't in the environment the code is meant to run in.
+    UndefinedGlobal {
+        name: String,
+        kind: ReferenceKind,
+    },
+
+    /// A function that's too big or complicated.
+    MetricOverLimit {
+        function: String,
+        metric: Metric,
+        value: usize,
+        limit: usize,
+    },
+
+    /// Something done to a value that always fails for its type, like
+    /// calling a number.
+    ImpossibleOperation {
+        operation: impossible_operations::Operation,
+        found: BasicType,
+    },
+
+    /// A use of the standard library that untrusted code shouldn't be able
+    /// to do, like running a shell command.
+    SecurityRisk {
+        function: String,
+        risk: security::Risk,
+    },
+
+    /// An assignment with more names than values, or more values than
+    /// names.
+    MismatchedAssignment {
+        names: usize,
+        values: usize,
+    },
+
+    /// An assignment to a global that the file doesn't say it means to make.
+    UndeclaredGlobalWrite {
+        name: String,
+    },
+}
+
+impl LintKind {
+    /// Like [ParseErrorKind::code][::parser::ParseErrorKind::code], but for
+    /// lints, which use codes L001 and up.
+    pub fn code(&self) -> &'static str {
+        match *self {
+            LintKind::UnusedLocal { .. } => "L001",
+            LintKind::UndefinedGlobal { .. } => "L002",
+            LintKind::MetricOverLimit { .. } => "L003",
+            LintKind::ImpossibleOperation { .. } => "L004",
+            LintKind::SecurityRisk { .. } => "L005",
+            LintKind::MismatchedAssignment { .. } => "L006",
+            LintKind::UndeclaredGlobalWrite { .. } => "L007",
+        }
+    }
+}
+
+/// A problem a lint found, along with the token it's about.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lint {
+    pub kind: LintKind,
+    pub start_position: SourcePosition,
+    pub end_position: SourcePosition,
+}
+
+impl Lint {
+    pub fn new(kind: LintKind, token: &Token) -> Lint {
+        Lint {
+            kind,
+            start_position: token.start_position,
+            end_position: token.end_position,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        self.kind.code()
+    }
+
+    /// The byte range of the token the lint is about.
+    pub fn span(&self) -> Range<usize> {
+        self.start_position.bytes..self.end_position.bytes
+    }
+
+    /// Describes the problem without saying where it is.
+    pub fn message(&self) -> String {
+        match self.kind {
+            LintKind::UnusedLocal { ref name, kind } => {
+                let what = match kind {
+                    DeclarationKind::Local | DeclarationKind::ImplicitSelf => "local",
+                    DeclarationKind::LocalFunction => "local function",
+                    DeclarationKind::Parameter => "parameter",
+                    DeclarationKind::LoopVariable => "loop variable",
+                };
+
+                format!("Unused {} `{}`", what, name)
+            },
+            LintKind::UndefinedGlobal { ref name, kind: ReferenceKind::Read } => format!("Undefined global `{}`", name),
+            LintKind::UndefinedGlobal { ref name, kind: ReferenceKind::Write } => format!("Assignment to undefined global `{}`", name),
+            LintKind::MetricOverLimit { ref function, metric, value, limit } => {
+                format!("`{}` has a {} of {}, over the limit of {}", function, metric, value, limit)
+            },
+            LintKind::ImpossibleOperation { operation, found } => format!("Attempt to {} a {} value", operation, found),
+            LintKind::SecurityRisk { ref function, risk } => format!("`{}` {}", function, risk),
+            LintKind::MismatchedAssignment { names, values } => {
+                let plural = |count| if count == 1 { "" } else { "s" };
+                format!("Assigning {} value{} to {} name{}", values, plural(values), names, plural(names))
+            },
+            LintKind::UndeclaredGlobalWrite { ref name } => format!("Assignment to undeclared global `{}`", name),
+        }
+    }
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at line {}, column {}", self.message(), self.start_position.line, self.start_position.column)
+    }
+}
+
+// The token a local's name is written with. An implicit `self` doesn't have
+// one.
+fn declaration_token<'a>(cst: &Cst<'a>, index: &NodeIndex, declaration: &Declaration) -> Option<&'a Token<'a>> {
+    let tokens = cst.tokens(declaration.statement)?;
+
+    let function = match index.get(declaration.statement) {
+        Some(NodeRef::Statement(statement)) => match statement.kind {
+            StatementKind::FunctionDeclaration(ref function) => Some(function),
+            _ => None,
+        },
+        _ => None,
+    };
+
+    let names = match function {
+        Some(function) => {
+            if declaration.kind == DeclarationKind::ImplicitSelf {
+                return None;
+            }
+
+            // `local function f`, then the parameters.
+            let name = if function.local { tokens.get(2) } else { None };
+            let start = tokens.iter().position(|token| token.kind == TokenKind::Symbol(Symbol::LeftParen))?;
+
+            name.into_iter()
+                .chain(names_in_list(&tokens[start + 1..]))
+                .collect::<Vec<_>>()
+        },
+
+        // `local a, b` and `for a, b`.
+        None => names_in_list(&tokens[1..]).collect(),
+    };
+
+    let position = match function {
+        Some(function) if function.is_method => declaration.position - 1,
+        _ => declaration.position,
+    };
+
+    names.get(position).cloned()
+}
+
+// The token a name is used at. For a `function` statement, that's the first
+// name after `function`.
+fn reference_token<'a>(cst: &Cst<'a>, reference: &Reference) -> Option<&'a Token<'a>> {
+    let tokens = cst.tokens(reference.node)?;
+
+    match tokens.first()?.kind {
+        TokenKind::Symbol(Symbol::Function) => tokens.get(1),
+        _ => tokens.first(),
+    }
+}
+
+// The names at the start of a comma-separated list of them.
+fn names_in_list<'t, 'a>(tokens: &'t [Token<'a>]) -> impl Iterator<Item = &'t Token<'a>> {
+    tokens.iter()
+        .take_while(|token| matches!(token.kind, TokenKind::Identifier(_) | TokenKind::Symbol(Symbol::Comma)))
+        .filter(|token| token.kind != TokenKind::Symbol(Symbol::Comma))
+}
+
+// Runs `check` on `source`, checks that every lint it finds has `code`, and
+// returns each one's message and starting byte.
+#[cfg(test)]
+fn found<F: Fn(&Cst) -> Vec<Lint>>(source: &str, code: &str, check: F) -> Vec<(String, usize)> {
+    let tokens = ::tokenizer::tokenize(source).unwrap();
+    let cst = Cst::parse(&tokens).unwrap();
+
+    check(&cst).iter()
+        .map(|lint| {
+            assert_eq!(lint.code(), code);
+            (lint.message(), lint.span().start)
+        })
+        .collect()
+}
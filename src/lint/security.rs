@@ -0,0 +1,285 @@
+//! Finds code that can do more than a sandboxed plugin should: running code
+//! built at runtime, running shell commands, or changing the environment
+//! functions see their globals in.
+//!
+//! ```
+//! use mab::cst::Cst;
+//! use mab::lint::security::check;
+//!
+//! let tokens = mab::tokenize("
+//!     local code = 'return ' .. input
+//!     local run = loadstring(code)
+//!     os.execute('rm -rf /')
+//! ").unwrap();
+//! let cst = Cst::parse(&tokens).unwrap();
+//!
+//! let messages = check(&cst).iter()
+//!     .map(|lint| lint.to_string())
+//!     .collect::<Vec<_>>();
+//!
+//! assert_eq!(messages, [
+//!     "`loadstring` runs code that isn't a string literal at line 3, column 17",
+//!     "`os.execute` runs a shell command at line 4, column 5",
+//! ]);
+//! ```
+//!
+//! This is meant for reviewing code from people who aren't trusted, like
+//! user-submitted plugins, so it's off by default in
+//! [Config][::lint::config::Config]. `os.execute`, `io.popen`, `setfenv`,
+//! and `debug.setfenv` are reported wherever they're used, even without
+//! being called, since they could be called through another name. `load`
+//! and `loadstring` are reported wherever they're used except in calls with
+//! a string literal.
+//!
+//! Names only count when they refer to the standard library's globals.
+//! Spellings like `_G.os`, `_ENV.os`, `os["execute"]`, and locals that hold
+//! a global, like `local o = os`, count as the global, but locals that are
+//! assigned to more than once, or that hold anything else, don't.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::str;
+
+use analysis::scope::{DeclarationId, DeclarationKind, ReferenceKind, ScopeTree};
+use ast::*;
+use cst::Cst;
+use index::NodeIndex;
+use lint::{Lint, LintKind};
+
+/// Why a [Lint][Lint] from this module is a risk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Risk {
+    /// Code that's compiled at runtime from something other than a literal,
+    /// which could be anything.
+    DynamicCode,
+
+    ShellCommand,
+
+    /// Changing which globals a function sees, which can get around a
+    /// sandbox or hide what code does.
+    EnvironmentChange,
+}
+
+impl fmt::Display for Risk {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let text = match *self {
+            Risk::DynamicCode => "runs code that isn't a string literal",
+            Risk::ShellCommand => "runs a shell command",
+            Risk::EnvironmentChange => "changes a function's environment",
+        };
+
+        f.write_str(text)
+    }
+}
+
+// Standard library functions that are a risk however they're used.
+const RISKY_FUNCTIONS: &[(&str, Risk)] = &[
+    ("os.execute", Risk::ShellCommand),
+    ("io.popen", Risk::ShellCommand),
+    ("setfenv", Risk::EnvironmentChange),
+    ("debug.setfenv", Risk::EnvironmentChange),
+];
+
+// Functions that compile the string they're given.
+const LOADERS: &[&str] = &["load", "loadstring"];
+
+/// Reports each risky use of the standard library, in the order they appear
+/// in the source.
+pub fn check(cst: &Cst) -> Vec<Lint> {
+    let names = Names::new(cst.chunk());
+
+    // The functions that are called, which aren't bare references to them.
+    let mut called = HashSet::new();
+
+    for node in cst.chunk().descendants() {
+        match node {
+            NodeRef::Statement(Statement { kind: StatementKind::FunctionCall(call), .. }) |
+            NodeRef::Expression(Expression { kind: ExpressionKind::FunctionCall(call), .. }) => {
+                called.insert(call.name_expression.id);
+            },
+            _ => {},
+        }
+    }
+
+    let mut lints = Vec::new();
+
+    for node in cst.chunk().descendants() {
+        let (id, call) = match node {
+            NodeRef::Statement(statement) => match statement.kind {
+                StatementKind::FunctionCall(ref call) => (statement.id, Some(call)),
+                _ => continue,
+            },
+            NodeRef::Expression(expression) => match expression.kind {
+                ExpressionKind::FunctionCall(ref call) => (expression.id, Some(call)),
+                _ => (expression.id, None),
+            },
+        };
+
+        let found = match (node, call) {
+            (_, Some(call)) => names.name_of(&call.name_expression)
+                .filter(|name| LOADERS.contains(&name.as_str()) && !loads_literal(call))
+                .map(|name| (name, Risk::DynamicCode)),
+            (NodeRef::Expression(expression), None) => names.name_of(expression)
+                .and_then(|name| {
+                    // A loader that isn't called here could be called
+                    // through another name with anything.
+                    if LOADERS.contains(&name.as_str()) && !called.contains(&expression.id) {
+                        return Some((name, Risk::DynamicCode));
+                    }
+
+                    let &(_, risk) = RISKY_FUNCTIONS.iter().find(|&&(function, _)| function == name)?;
+                    Some((name, risk))
+                }),
+            _ => None,
+        };
+
+        let (function, risk) = match found {
+            Some(found) => found,
+            None => continue,
+        };
+
+        if let Some(token) = cst.tokens(id).and_then(|tokens| tokens.first()) {
+            lints.push(Lint::new(LintKind::SecurityRisk { function, risk }, token));
+        }
+    }
+
+    lints.sort_by_key(|lint| lint.start_position.bytes);
+    lints
+}
+
+// Works out which global, or field of one, expressions refer to.
+struct Names<'a> {
+    scopes: ScopeTree<'a>,
+    index: NodeIndex<'a>,
+
+    // The local each name refers to, by the name's ID.
+    declarations: HashMap<NodeId, DeclarationId>,
+
+    // Locals that are assigned to after they're declared.
+    reassigned: HashSet<DeclarationId>,
+}
+
+impl<'a> Names<'a> {
+    fn new(chunk: &'a Chunk<'a>) -> Names<'a> {
+        let scopes = ScopeTree::new(chunk);
+
+        let declarations = scopes.references().iter()
+            .filter_map(|reference| Some((reference.node, reference.declaration?)))
+            .collect();
+
+        let reassigned = scopes.references().iter()
+            .filter(|reference| reference.kind == ReferenceKind::Write)
+            .filter_map(|reference| reference.declaration)
+            .collect();
+
+        Names {
+            scopes,
+            index: NodeIndex::new(chunk),
+            declarations,
+            reassigned,
+        }
+    }
+
+    // The dotted name of the global `expression` is, like `os.execute`. This
+    // sees through `_G`, `_ENV`, string keys, and locals that are never reassigned,
+    // so `local o = _G.os` followed by `o["execute"]` is `os.execute` too.
+    fn name_of(&self, expression: &Expression) -> Option<String> {
+        let name = match expression.kind {
+            ExpressionKind::Name(ref name) => match self.declarations.get(&expression.id) {
+                Some(&declaration) => return self.local_value(declaration),
+                None => name.to_string(),
+            },
+            ExpressionKind::FieldAccess(ref field_access) => {
+                format!("{}.{}", self.name_of(&field_access.target)?, field_access.name)
+            },
+            ExpressionKind::Index(ref index) => match index.key.kind {
+                ExpressionKind::String(ref key) => {
+                    format!("{}.{}", self.name_of(&index.target)?, str::from_utf8(key.value()).ok()?)
+                },
+                _ => return None,
+            },
+            ExpressionKind::ParenExpression(ref inner) => return self.name_of(inner),
+            _ => return None,
+        };
+
+        // A global `_ENV` is the table of globals on Lua 5.2 and later. A
+        // local one was declared, so it's been handled like any other local.
+        match name.strip_prefix("_G.").or_else(|| name.strip_prefix("_ENV.")) {
+            Some(rest) => Some(rest.to_string()),
+            None => Some(name),
+        }
+    }
+
+    // The name of the global a local holds, if it's set to one when it's
+    // declared and never changed.
+    fn local_value(&self, id: DeclarationId) -> Option<String> {
+        if self.reassigned.contains(&id) {
+            return None;
+        }
+
+        let declaration = self.scopes.declaration(id);
+
+        match (declaration.kind, self.index.get(declaration.statement)?) {
+            (DeclarationKind::Local, NodeRef::Statement(Statement { kind: StatementKind::LocalAssignment(local), .. })) => {
+                self.name_of(local.values.get(declaration.position)?)
+            },
+            _ => None,
+        }
+    }
+}
+
+fn loads_literal(call: &FunctionCall) -> bool {
+    match call.arguments {
+        FunctionArguments::String(_) => true,
+        FunctionArguments::Parenthesized(ref arguments) => {
+            matches!(arguments.first().map(|argument| &argument.kind), Some(ExpressionKind::String(_)))
+        },
+        FunctionArguments::Table(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lint;
+
+    fn found(source: &str) -> Vec<(String, usize)> {
+        lint::found(source, "L005", check)
+    }
+
+    #[test]
+    fn risks() {
+        assert_eq!(found("load(source) load 'return 1' load('x', 'name') loadstring(reader()) local run = io.popen setfenv(1, {})"), [
+            ("`load` runs code that isn't a string literal".to_string(), 0),
+            ("`loadstring` runs code that isn't a string literal".to_string(), 47),
+            ("`io.popen` runs a shell command".to_string(), 80),
+            ("`setfenv` changes a function's environment".to_string(), 89),
+        ]);
+    }
+
+    #[test]
+    fn other_spellings() {
+        let shell = |start| ("`os.execute` runs a shell command".to_string(), start);
+
+        assert_eq!(found("os['execute']('rm -rf /')"), [shell(0)]);
+        assert_eq!(found("_G.os.execute('ls')"), [shell(0)]);
+        assert_eq!(found("_G['os'].execute('ls')"), [shell(0)]);
+        assert_eq!(found("local o = os o.execute('ls')"), [shell(13)]);
+        assert_eq!(found("local g = _G local o = g.os o['execute']('ls')"), [shell(28)]);
+        assert_eq!(found("_ENV.os.execute('ls')"), [shell(0)]);
+        assert_eq!(found("_ENV[\"io\"].popen(cmd)"), [("`io.popen` runs a shell command".to_string(), 0)]);
+        assert_eq!(found("local e = _ENV print(e.setfenv)"), [("`setfenv` changes a function's environment".to_string(), 21)]);
+        assert_eq!(found("local l = load l(code)"), [
+            ("`load` runs code that isn't a string literal".to_string(), 10),
+            ("`load` runs code that isn't a string literal".to_string(), 15),
+        ]);
+        assert_eq!(found("run(_G.loadstring)"), [("`loadstring` runs code that isn't a string literal".to_string(), 4)]);
+    }
+
+    #[test]
+    fn locals_are_not_the_standard_library() {
+        assert!(found("local load, os, setfenv = ... load(x) os.execute('ls') setfenv(1, {})").is_empty());
+        assert!(found("local o = os o = {} o.execute('ls') local t = { load = f } t.load(x)").is_empty());
+        assert!(found("local _ENV = { os = sandbox } _ENV.os.execute('ls')").is_empty());
+    }
+}
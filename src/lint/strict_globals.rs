@@ -0,0 +1,133 @@
+//! Finds assignments to globals, the way `strict.lua` does at runtime, so
+//! that a missing `local` can't quietly make one.
+//!
+//! ```
+//! use mab::cst::Cst;
+//! use mab::lint::strict_globals::check;
+//!
+//! let tokens = mab::tokenize("
+//!     -- mab: allow-global Module
+//!     Module = {}
+//!     function Module.load()
+//!         loaded = true
+//!     end
+//! ").unwrap();
+//! let cst = Cst::parse(&tokens).unwrap();
+//!
+//! let messages = check(&cst).iter()
+//!     .map(|lint| lint.to_string())
+//!     .collect::<Vec<_>>();
+//!
+//! assert_eq!(messages, ["Assignment to undeclared global `loaded` at line 5, column 9"]);
+//! ```
+//!
+//! Every assignment to a global and every global `function` statement is
+//! reported, even for globals the standard library has, unless the file
+//! allows it with a comment like `-- mab: allow-global a, b`. That's meant
+//! for globals a file makes on purpose, like a module's table. The comment
+//! can be anywhere in the file.
+//!
+//! Reading globals isn't this lint's business, since
+//! [undefined_globals][::lint::undefined_globals] covers that.
+
+use std::collections::HashSet;
+
+use analysis::scope::{ReferenceKind, ScopeTree};
+use cst::Cst;
+use lint::{reference_token, Lint, LintKind};
+use tokenizer::{Comment, TokenPrefix};
+
+/// Reports every assignment to a global the file doesn't allow, in the order
+/// they appear in the source.
+pub fn check(cst: &Cst) -> Vec<Lint> {
+    let scopes = ScopeTree::new(cst.chunk());
+    let allowed = allowed_globals(cst);
+
+    let mut lints = scopes.references().iter()
+        .filter(|reference| reference.declaration.is_none() && reference.kind == ReferenceKind::Write)
+        .filter(|reference| !allowed.contains(reference.name))
+        .filter_map(|reference| {
+            let token = reference_token(cst, reference)?;
+            let kind = LintKind::UndeclaredGlobalWrite {
+                name: reference.name.to_string(),
+            };
+
+            Some(Lint::new(kind, token))
+        })
+        .collect::<Vec<_>>();
+
+    lints.sort_by_key(|lint| lint.start_position.bytes);
+    lints
+}
+
+/// The globals a file allows assignments to, from its `-- mab: allow-global`
+/// comments. Names can be separated by commas or spaces.
+pub fn allowed_globals<'a>(cst: &Cst<'a>) -> HashSet<&'a str> {
+    let mut allowed = HashSet::new();
+
+    for token in cst.all_tokens() {
+        for prefix in &token.prefix {
+            let content = match *prefix {
+                TokenPrefix::Comment(Comment::SingleLine { ref content }) => content,
+                _ => continue,
+            };
+
+            let directive = match content.trim().strip_prefix("mab:") {
+                Some(directive) => directive.trim_start(),
+                None => continue,
+            };
+
+            // The keyword has to end where the names start, so something
+            // like `allow-globals` isn't read as allowing `s`.
+            let names = match directive.strip_prefix("allow-global") {
+                Some(names) if names.is_empty() || names.starts_with(char::is_whitespace) => names,
+                _ => continue,
+            };
+
+            allowed.extend(names.split(|c: char| c == ',' || c.is_whitespace()).filter(|name| !name.is_empty()));
+        }
+    }
+
+    allowed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lint;
+
+    fn found(source: &str) -> Vec<(String, usize)> {
+        lint::found(source, "L007", check)
+    }
+
+    #[test]
+    fn writes() {
+        assert_eq!(found("local t = {} x, t.y = 1, 2 print = nil function f() end function t.g() h = 1 end return y"), [
+            ("Assignment to undeclared global `x`".to_string(), 13),
+            ("Assignment to undeclared global `print`".to_string(), 27),
+            ("Assignment to undeclared global `f`".to_string(), 48),
+            ("Assignment to undeclared global `h`".to_string(), 71),
+        ]);
+    }
+
+    #[test]
+    fn directives() {
+        assert_eq!(found("
+            -- mab: allow-global a, b
+            --mab:allow-global c
+            -- allow-global d
+            a, b, c, d = 1, 2, 3, 4
+            function b() end
+            -- mab: allow-global e
+        "), [("Assignment to undeclared global `d`".to_string(), 123)]);
+    }
+
+    #[test]
+    fn directive_keyword_must_end() {
+        assert_eq!(found("-- mab: allow-globals x\n-- mab: allow-global,y\ns = 1 x = 2 y = 3"), [
+            ("Assignment to undeclared global `s`".to_string(), 47),
+            ("Assignment to undeclared global `x`".to_string(), 53),
+            ("Assignment to undeclared global `y`".to_string(), 59),
+        ]);
+    }
+}
@@ -0,0 +1,158 @@
+//! Finds globals that the code's environment doesn't have, which is usually a
+//! misspelled local or a missing `local`.
+//!
+//! ```
+//! use mab::cst::Cst;
+//! use mab::lint::undefined_globals::{check, Options};
+//!
+//! let tokens = mab::tokenize("
+//!     local count = 0
+//!     for _, item in ipairs(items) do
+//!         cuont = count + 1
+//!     end
+//! ").unwrap();
+//! let cst = Cst::parse(&tokens).unwrap();
+//!
+//! let options = Options {
+//!     globals: vec!["items".to_string()],
+//!     ..Options::default()
+//! };
+//!
+//! let messages = check(&cst, &options).iter()
+//!     .map(|lint| lint.to_string())
+//!     .collect::<Vec<_>>();
+//!
+//! assert_eq!(messages, ["Assignment to undefined global `cuont` at line 4, column 9"]);
+//! ```
+
+use analysis::scope::ScopeTree;
+use cst::Cst;
+use lint::{reference_token, Lint, LintKind};
+
+/// The globals a version of Lua starts out with. In configuration files,
+/// these are written in lowercase, like `"lua51"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StandardLibrary {
+    /// No globals at all, for code that runs somewhere that provides its own.
+    None,
+    Lua51,
+    Lua52,
+    Lua53,
+    Lua54,
+}
+
+// Everything from 5.1 through 5.4 has these.
+const COMMON_GLOBALS: &[&str] = &[
+    "_G", "_VERSION", "assert", "collectgarbage", "coroutine", "debug", "dofile", "error", "getmetatable",
+    "io", "ipairs", "load", "loadfile", "math", "next", "os", "package", "pairs", "pcall", "print", "rawequal",
+    "rawget", "rawset", "require", "select", "setmetatable", "string", "table", "tonumber", "tostring",
+    "type", "xpcall",
+];
+
+impl StandardLibrary {
+    /// The globals that the version has on top of the ones every version has.
+    fn extra_globals(self) -> &'static [&'static str] {
+        match self {
+            StandardLibrary::None => &[],
+            StandardLibrary::Lua51 => &["getfenv", "loadstring", "module", "setfenv", "unpack"],
+            StandardLibrary::Lua52 => &["_ENV", "bit32", "rawlen"],
+            StandardLibrary::Lua53 => &["_ENV", "rawlen", "utf8"],
+            StandardLibrary::Lua54 => &["_ENV", "rawlen", "utf8", "warn"],
+        }
+    }
+
+    pub fn has_global(self, name: &str) -> bool {
+        if self == StandardLibrary::None {
+            return false;
+        }
+
+        COMMON_GLOBALS.contains(&name) || self.extra_globals().contains(&name)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub standard_library: StandardLibrary,
+
+    /// Globals the code can use on top of the standard library's, like the
+    /// ones a game engine provides for its scripts.
+    pub globals: Vec<String>,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            standard_library: StandardLibrary::Lua54,
+            globals: Vec::new(),
+        }
+    }
+}
+
+impl Options {
+    pub fn is_defined(&self, name: &str) -> bool {
+        self.standard_library.has_global(name) || self.globals.iter().any(|global| global == name)
+    }
+}
+
+/// Reports every use of a global that isn't defined, including assignments
+/// and `function` statements, since those make globals the environment
+/// didn't have.
+pub fn check(cst: &Cst, options: &Options) -> Vec<Lint> {
+    let scopes = ScopeTree::new(cst.chunk());
+
+    scopes.references().iter()
+        .filter(|reference| reference.declaration.is_none() && !options.is_defined(reference.name))
+        .filter_map(|reference| {
+            let token = reference_token(cst, reference)?;
+            let kind = LintKind::UndefinedGlobal {
+                name: reference.name.to_string(),
+                kind: reference.kind,
+            };
+
+            Some(Lint::new(kind, token))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lint;
+
+    fn undefined(source: &str, options: &Options) -> Vec<(String, usize)> {
+        lint::found(source, "L002", |cst| check(cst, options))
+    }
+
+    #[test]
+    fn reads_and_writes() {
+        let source = "local t = {} x = y function f() end function g.h() end function t.m() return print, t end";
+
+        assert_eq!(undefined(source, &Options::default()), [
+            ("Assignment to undefined global `x`".to_string(), 13),
+            ("Undefined global `y`".to_string(), 17),
+            ("Assignment to undefined global `f`".to_string(), 28),
+            ("Undefined global `g`".to_string(), 45),
+        ]);
+    }
+
+    #[test]
+    fn environments() {
+        let source = "print(unpack(t), utf8.char(65), game)";
+
+        let options = Options {
+            standard_library: StandardLibrary::Lua51,
+            globals: vec!["game".to_string()],
+        };
+        assert_eq!(undefined(source, &options), [
+            ("Undefined global `t`".to_string(), 13),
+            ("Undefined global `utf8`".to_string(), 17),
+        ]);
+
+        let options = Options {
+            standard_library: StandardLibrary::None,
+            globals: Vec::new(),
+        };
+        assert_eq!(undefined(source, &options).len(), 5);
+    }
+}
@@ -0,0 +1,117 @@
+//! Finds locals, parameters, and loop variables that are never read.
+//!
+//! ```
+//! use mab::cst::Cst;
+//! use mab::lint::unused_locals::{check, Options};
+//!
+//! let tokens = mab::tokenize("
+//!     local function area(width, height)
+//!         local unused = 2
+//!         return width * width
+//!     end
+//!     print(area(1, 2))
+//! ").unwrap();
+//! let cst = Cst::parse(&tokens).unwrap();
+//!
+//! let messages = check(&cst, &Options::default()).iter()
+//!     .map(|lint| lint.to_string())
+//!     .collect::<Vec<_>>();
+//!
+//! assert_eq!(messages, [
+//!     "Unused parameter `height` at line 2, column 32",
+//!     "Unused local `unused` at line 3, column 15",
+//! ]);
+//! ```
+//!
+//! Assigning to a local doesn't count as reading it. Methods' implicit `self`
+//! is never reported.
+
+use std::collections::HashSet;
+
+use analysis::scope::{DeclarationKind, ReferenceKind, ScopeTree};
+use cst::Cst;
+use index::NodeIndex;
+use lint::{declaration_token, Lint, LintKind};
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// Whether to skip locals whose names start with `_`, which is how Lua
+    /// code usually says a value is unused on purpose, like the `_` in
+    /// `for _, v in ipairs(t)`.
+    pub ignore_underscore: bool,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            ignore_underscore: true,
+        }
+    }
+}
+
+pub fn check(cst: &Cst, options: &Options) -> Vec<Lint> {
+    let scopes = ScopeTree::new(cst.chunk());
+    let index = NodeIndex::new(cst.chunk());
+
+    let read = scopes.references().iter()
+        .filter(|reference| reference.kind == ReferenceKind::Read)
+        .filter_map(|reference| reference.declaration)
+        .collect::<HashSet<_>>();
+
+    scopes.declaration_ids()
+        .filter(|id| !read.contains(id))
+        .map(|id| scopes.declaration(id))
+        .filter(|declaration| declaration.kind != DeclarationKind::ImplicitSelf)
+        .filter(|declaration| !(options.ignore_underscore && declaration.name.starts_with('_')))
+        .filter_map(|declaration| {
+            let token = declaration_token(cst, &index, declaration)?;
+            let kind = LintKind::UnusedLocal {
+                name: declaration.name.to_string(),
+                kind: declaration.kind,
+            };
+
+            Some(Lint::new(kind, token))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lint;
+
+    fn unused(source: &str, options: &Options) -> Vec<(String, usize)> {
+        lint::found(source, "L001", |cst| check(cst, options))
+    }
+
+    #[test]
+    fn kinds() {
+        let source = "local a, b = 1 b = 2 for i, _v in f() do end local function g(x) end function t:m(y) return self end";
+
+        assert_eq!(unused(source, &Options::default()), [
+            ("Unused local `a`".to_string(), 6),
+            ("Unused local `b`".to_string(), 9),
+            ("Unused loop variable `i`".to_string(), 25),
+            ("Unused local function `g`".to_string(), 60),
+            ("Unused parameter `x`".to_string(), 62),
+            ("Unused parameter `y`".to_string(), 82),
+        ]);
+    }
+
+    #[test]
+    fn underscores() {
+        let source = "for _, v in ipairs(t) do print(v) end local _unused";
+        assert_eq!(unused(source, &Options::default()), []);
+
+        let options = Options { ignore_underscore: false };
+        assert_eq!(unused(source, &options), [
+            ("Unused loop variable `_`".to_string(), 4),
+            ("Unused local `_unused`".to_string(), 44),
+        ]);
+    }
+
+    #[test]
+    fn reads() {
+        assert_eq!(unused("local f local function r() return r() end repeat local x until x print(f)", &Options::default()), []);
+    }
+}
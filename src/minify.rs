@@ -0,0 +1,230 @@
+//! Prints chunks as small as they'll go, for shipping Lua somewhere space is
+//! tight.
+//!
+//! ```
+//! let tokens = mab::tokenize("local function add(a, b)\n\treturn a + b -- sum\nend\n").unwrap();
+//! let chunk = mab::parse_from_tokens(&tokens).unwrap();
+//!
+//! let minified = mab::minify::minify_chunk(&chunk, mab::Dialect::Lua53).unwrap();
+//! assert_eq!(minified, "local function add(a,b)return a+b end");
+//! ```
+//!
+//! Whitespace is only kept where tokens would otherwise run together. Strings
+//! get whichever quotes need the fewest escapes, and numbers lose digits that
+//! don't change their value, without turning integers into floats or the
+//! other way around. Names are left alone.
+
+use std::fmt::Write;
+
+use ast::Chunk;
+use emitter::print_chunk;
+use tokenizer::{Dialect, StringLiteral, Token, TokenKind, TokenizeError, Tokenizer};
+
+/// Prints the chunk and squeezes the result. `dialect` should be the one the
+/// chunk was tokenized with, since the printed code is tokenized again, and
+/// things like type annotations only tokenize in some dialects.
+pub fn minify_chunk(chunk: &Chunk, dialect: Dialect) -> Result<String, TokenizeError> {
+    let printed = print_chunk(chunk);
+    let tokens = Tokenizer::new(&printed).dialect(dialect).collect::<Result<Vec<_>, _>>()?;
+
+    Ok(minify_tokens(&tokens))
+}
+
+fn minify_tokens(tokens: &[Token]) -> String {
+    let mut output = String::new();
+    let mut previous = String::new();
+
+    for token in tokens {
+        let text = match token.kind {
+            TokenKind::StringLiteral(ref literal) => shortest_string(literal),
+            TokenKind::NumberLiteral(ref number) => compact_number(number),
+            _ => token.kind.source_text().into_owned(),
+        };
+
+        if text.is_empty() {
+            continue;
+        }
+
+        if needs_space(&previous, &text) {
+            output.push(' ');
+        }
+
+        output.push_str(&text);
+        previous = text;
+    }
+
+    output
+}
+
+// Whether two tokens would be read as something else if they were written
+// with nothing between them.
+fn needs_space(before: &str, after: &str) -> bool {
+    let (last, first) = match (before.chars().next_back(), after.chars().next()) {
+        (Some(last), Some(first)) => (last, first),
+        _ => return false,
+    };
+
+    let is_word = |character: char| character.is_alphanumeric() || character == '_';
+
+    match (last, first) {
+        // Names, keywords, and numbers would merge.
+        (last, first) if is_word(last) && is_word(first) => true,
+
+        // `1..x` is a malformed number, and `.. ...` would become `.....`.
+        (last, '.') => last.is_ascii_digit() || last == '.',
+
+        // A number ending in `.`, like `0x1.`, would run into a name after
+        // it, or into its hex digits.
+        ('.', first) if is_word(first) => before.starts_with(|character: char| character.is_ascii_digit()),
+
+        // These would start a comment, a long string, or a longer operator.
+        ('-', '-') | ('[', '[') | ('[', '=') => true,
+        ('=', '=') | ('~', '=') | ('<', '=') | ('>', '=') => true,
+        ('/', '/') | (':', ':') | ('<', '<') | ('>', '>') => true,
+
+        _ => false,
+    }
+}
+
+fn shortest_string(literal: &StringLiteral) -> String {
+    let candidates = vec![
+        literal.clone(),
+        StringLiteral::double_quoted(literal.value()),
+        StringLiteral::single_quoted(literal.value()),
+    ];
+
+    candidates.into_iter()
+        .map(|candidate| TokenKind::StringLiteral(candidate).source_text().into_owned())
+        .min_by_key(|text| text.len())
+        .unwrap()
+}
+
+// Drops leading and trailing zeros and other digits that don't matter. Floats
+// keep their `.` or exponent so they stay floats.
+fn compact_number(number: &str) -> String {
+    if number.starts_with("0x") || number.starts_with("0X") {
+        return compact_hex(&number[2..]).unwrap_or_else(|| number.to_string());
+    }
+
+    let (mantissa, exponent) = match number.find(['e', 'E']) {
+        Some(index) => (&number[..index], Some(&number[index + 1..])),
+        None => (number, None),
+    };
+
+    let (integer, fraction) = match mantissa.find('.') {
+        Some(index) => (&mantissa[..index], Some(&mantissa[index + 1..])),
+        None => (mantissa, None),
+    };
+
+    let integer = integer.trim_start_matches('0');
+    let mut compact = match fraction.map(|fraction| fraction.trim_end_matches('0')) {
+        // With an exponent, the `.` isn't needed to make it a float.
+        Some("") if exponent.is_some() => if integer.is_empty() { "0".to_string() } else { integer.to_string() },
+        // `10.` is a valid float, but the `0` keeps it from looking like
+        // the start of a field access.
+        Some("") if integer.is_empty() => "0.0".to_string(),
+        Some("") => format!("{}.0", integer),
+        Some(fraction) => format!("{}.{}", integer, fraction),
+        None if integer.is_empty() => "0".to_string(),
+        None => integer.to_string(),
+    };
+
+    if let Some(exponent) = exponent {
+        let (sign, digits) = match exponent.chars().next() {
+            Some('-') => ("-", &exponent[1..]),
+            Some('+') => ("", &exponent[1..]),
+            _ => ("", exponent),
+        };
+
+        let digits = digits.trim_start_matches('0');
+        let _ = write!(compact, "e{}{}", sign, if digits.is_empty() { "0" } else { digits });
+    }
+
+    compact
+}
+
+// Hex integers can be shorter in decimal. Hex floats are left as they are.
+fn compact_hex(digits: &str) -> Option<String> {
+    if !digits.chars().all(|character| character.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let digits = digits.trim_start_matches('0');
+    let digits = if digits.is_empty() { "0" } else { digits };
+    let hex = format!("0x{}", digits);
+
+    // Big hex numbers wrap around to negative ones, which decimal can't do.
+    match u64::from_str_radix(digits, 16) {
+        Ok(value) if value <= i64::MAX as u64 && value.to_string().len() < hex.len() => Some(value.to_string()),
+        _ => Some(hex),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parser::{parse_from_tokens, parse_from_tokens_with_types};
+
+    use tokenizer::{tokenize, tokenize_luau};
+
+    fn minify(source: &str) -> String {
+        let tokens = tokenize(source).unwrap();
+        minify_chunk(&parse_from_tokens(&tokens).unwrap(), Dialect::Lua53).unwrap()
+    }
+
+    #[test]
+    fn spacing() {
+        assert_eq!(minify("local a = 1 local b = -a - -a"), "local a=1 local b=-a- -a");
+        assert_eq!(minify("x = 1 .. 'y' .. ..."), "x=1 ..'y'.. ...");
+        assert_eq!(minify("t[ [[\"k\" 'k' \"k\"]] ] = { a = 1, [2] = 3 }"), "t[ [[\"k\" 'k' \"k\"]]]={a=1,[2]=3}");
+        assert_eq!(minify("if not x then return end"), "if not x then return end");
+    }
+
+    #[test]
+    fn strings() {
+        assert_eq!(minify(r#"f("it's", 'say "hi"', "\65\066", [[long]])"#), r#"f("it's",'say "hi"',"AB","long")"#);
+        assert_eq!(minify(r#"f("\u{48}i\n", "\x00" .. "\x00\x31", "\xFF\xFE\x41")"#), r#"f("Hi\n","\0".."\0001","\255\254A")"#);
+        assert_eq!(minify(r#"f [["a" 'b' "c" 'd']]"#), r#"f[["a" 'b' "c" 'd']]"#);
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(compact_number("007"), "7");
+        assert_eq!(compact_number("0"), "0");
+        assert_eq!(compact_number("0.50"), ".5");
+        assert_eq!(compact_number("10.0"), "10.0");
+        assert_eq!(compact_number("10.000"), "10.0");
+        assert_eq!(compact_number("0.0"), "0.0");
+        assert_eq!(compact_number("5."), "5.0");
+        assert_eq!(compact_number("1.0e+05"), "1e5");
+        assert_eq!(compact_number("2.5E-03"), "2.5e-3");
+        assert_eq!(compact_number("0x10"), "16");
+        assert_eq!(compact_number("0x00FF"), "255");
+        assert_eq!(compact_number("0xDEADBEEF"), "0xDEADBEEF");
+        assert_eq!(compact_number("0xFFFFFFFFFFFFFFFF"), "0xFFFFFFFFFFFFFFFF");
+        assert_eq!(compact_number("0x1p4"), "0x1p4");
+    }
+
+    #[test]
+    fn floats_before_names() {
+        let minified = minify("x = 10.0 y = 0.0 z = 5. w = 0x1. v = 1");
+        assert_eq!(minified, "x=10.0 y=0.0 z=5.0 w=0x1. v=1");
+
+        let tokens = tokenize(&minified).unwrap();
+        assert_eq!(parse_from_tokens(&tokens).unwrap().statements.len(), 5);
+
+        assert!(needs_space("0x1.", "f"));
+        assert!(!needs_space("t.", "x"));
+    }
+
+    #[test]
+    fn type_annotations() {
+        let source = "local a: number? = 1 local function f(x: string): number return #x end";
+        let tokens = tokenize_luau(source);
+        let chunk = parse_from_tokens_with_types(&tokens).unwrap();
+
+        let minified = minify_chunk(&chunk, Dialect::Luau).unwrap();
+        assert_eq!(minified, "local a:number?=1 local function f(x:string):number return#x end");
+        assert!(minify_chunk(&chunk, Dialect::Lua53).is_err());
+    }
+}
@@ -0,0 +1,160 @@
+//! Rewrites chunks into a canonical form, so that code that means the same
+//! thing looks the same. Two normalized chunks that are
+//! [structurally equal][::ast::AstEq] are the same code, however they were
+//! written.
+//!
+//! ```
+//! use mab::ast::AstEq;
+//!
+//! let tokens = mab::tokenize("print(t['x'], ((a + b)));").unwrap();
+//! let first = mab::normalize::normalize(mab::parse_from_tokens(&tokens).unwrap());
+//!
+//! let tokens = mab::tokenize("print(t.x, a + b)").unwrap();
+//! let second = mab::normalize::normalize(mab::parse_from_tokens(&tokens).unwrap());
+//!
+//! assert!(first.structural_eq(&second));
+//! ```
+//!
+//! Normalizing:
+//!
+//! - writes every string in double quotes, escaped the same way
+//! - turns indexes and table keys that are strings into names where they can
+//!   be, like `a["x"]` into `a.x`
+//! - removes parentheses, except ones that cut a call or `...` down to one
+//!   value
+//! - puts parentheses around string and table arguments, like `f "x"`
+//! - removes empty statements
+//!
+//! Arguments that get parentheses are new expressions, with
+//! [NodeId::DUMMY][::ast::NodeId::DUMMY] IDs.
+
+use std::borrow::Cow;
+
+use ast::*;
+use fold::{self, Fold};
+use tokenizer::{is_identifier, StringLiteral};
+
+pub fn normalize(chunk: Chunk) -> Chunk {
+    Normalizer.fold_chunk(chunk)
+}
+
+struct Normalizer;
+
+impl<'a> Fold<'a> for Normalizer {
+    fn fold_chunk(&mut self, chunk: Chunk<'a>) -> Chunk<'a> {
+        let mut chunk = fold::walk_chunk(self, chunk);
+        chunk.statements.retain(|statement| statement.kind != StatementKind::Empty);
+        chunk
+    }
+
+    fn fold_expression(&mut self, expression: Expression<'a>) -> Expression<'a> {
+        let expression = fold::walk_expression(self, expression);
+
+        let kind = match expression.kind {
+            ExpressionKind::ParenExpression(inner) => match inner.kind {
+                ExpressionKind::FunctionCall(_) | ExpressionKind::VarArg => ExpressionKind::ParenExpression(inner),
+                _ => return *inner,
+            },
+            ExpressionKind::String(literal) => ExpressionKind::String(canonical_string(&literal)),
+            ExpressionKind::Index(index) => match name_for(&index.key) {
+                Some(name) => ExpressionKind::FieldAccess(FieldAccess {
+                    target: index.target,
+                    name,
+                }),
+                None => ExpressionKind::Index(index),
+            },
+            kind => kind,
+        };
+
+        Expression {
+            id: expression.id,
+            kind,
+        }
+    }
+
+    fn fold_function_arguments(&mut self, arguments: FunctionArguments<'a>) -> FunctionArguments<'a> {
+        let argument = match fold::walk_function_arguments(self, arguments) {
+            FunctionArguments::String(literal) => ExpressionKind::String(canonical_string(&literal)),
+            FunctionArguments::Table(table) => ExpressionKind::Table(table),
+            arguments => return arguments,
+        };
+
+        FunctionArguments::Parenthesized(vec![Expression {
+            id: NodeId::DUMMY,
+            kind: argument,
+        }])
+    }
+
+    fn fold_table_literal(&mut self, table: TableLiteral<'a>) -> TableLiteral<'a> {
+        let mut table = fold::walk_table_literal(self, table);
+
+        for (key, _) in &mut table.items {
+            let name = match *key {
+                Some(TableKey::Expression(ref key)) => name_for(key),
+                _ => None,
+            };
+
+            if let Some(name) = name {
+                *key = Some(TableKey::Name(name));
+            }
+        }
+
+        table
+    }
+}
+
+fn canonical_string(literal: &StringLiteral) -> StringLiteral<'static> {
+    StringLiteral::double_quoted(literal.value())
+}
+
+// The name a string key could be written as instead.
+fn name_for<'a>(key: &Expression) -> Option<Cow<'a, str>> {
+    match key.kind {
+        ExpressionKind::String(ref literal) => {
+            let name = ::std::str::from_utf8(literal.value()).ok()?;
+
+            if is_identifier(name) {
+                Some(Cow::Owned(name.to_string()))
+            } else {
+                None
+            }
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use emitter::print_chunk;
+    use tokenizer::tokenize;
+    use parser::parse_from_tokens;
+
+    fn normalized(source: &str) -> String {
+        let tokens = tokenize(source).unwrap();
+        print_chunk(&normalize(parse_from_tokens(&tokens).unwrap()))
+    }
+
+    #[test]
+    fn strings() {
+        assert_eq!(normalized(r#"x = 'a', "a", [[a]], '\97', "it's", 'say "hi"'"#), r#"x = "a", "a", "a", "a", "it's", "say \"hi\""
+"#);
+    }
+
+    #[test]
+    fn names() {
+        assert_eq!(normalized(r#"t["x"] = { ["y"] = 1, ["not a name"] = 2, ["end"] = 3, [1] = 4 }"#), r#"t.x = {y = 1, ["not a name"] = 2, ["end"] = 3, [1] = 4}
+"#);
+        assert_eq!(normalized("return t['x']['y'][z]"), "return t.x.y[z]\n");
+    }
+
+    #[test]
+    fn parentheses() {
+        assert_eq!(normalized("x = ((a + b)) * (c), (f()), (...), ('s'):len()"), "x = (a + b) * c, (f()), (...), (\"s\"):len()\n");
+    }
+
+    #[test]
+    fn arguments_and_empty_statements() {
+        assert_eq!(normalized("f 'x'; g { 1 };;"), "f(\"x\")\ng({1})\n");
+    }
+}
@@ -0,0 +1,389 @@
+//! Renames local variables to short made-up names, for shipping code that's
+//! smaller and harder to read.
+//!
+//! ```
+//! let tokens = mab::tokenize("
+//!     local function greet(name)
+//!         local greeting = 'hi, ' .. name
+//!         print(greeting)
+//!     end
+//! ").unwrap();
+//! let chunk = mab::parse_from_tokens(&tokens).unwrap();
+//!
+//! assert_eq!(
+//!     mab::obfuscate::obfuscate_chunk(&chunk, mab::Dialect::Lua53).unwrap(),
+//!     "local function a(b)local c='hi, '..b print(c)end",
+//! );
+//! ```
+//!
+//! Globals, fields, and `self` keep their names. New names never match a
+//! global that the chunk uses, so renamed locals can't hide one.
+
+use std::borrow::Cow;
+use std::collections::HashSet;
+
+use ast::*;
+use minify::minify_chunk;
+use tokenizer::{is_identifier, Dialect, TokenizeError};
+
+/// Renames the chunk's locals and minifies it. See
+/// [minify_chunk][minify_chunk] for what `dialect` is for.
+pub fn obfuscate_chunk(chunk: &Chunk, dialect: Dialect) -> Result<String, TokenizeError> {
+    let mut chunk = chunk.clone();
+    rename_locals(&mut chunk);
+    minify_chunk(&chunk, dialect)
+}
+
+/// Gives every local variable, local function, loop variable, and parameter a
+/// short generated name, and updates everything that refers to them.
+pub fn rename_locals(chunk: &mut Chunk) {
+    // The first pass finds the globals, so the second can stay clear of them.
+    let mut finder = Renamer::default();
+    finder.block(chunk);
+
+    let mut renamer = Renamer {
+        renaming: true,
+        reserved: finder.globals,
+        ..Renamer::default()
+    };
+
+    renamer.reserved.insert("self".to_string());
+    renamer.block(chunk);
+}
+
+#[derive(Default)]
+struct Renamer {
+    renaming: bool,
+
+    // The locals in scope, innermost last, with the name each one is being
+    // given.
+    scope: Vec<(String, String)>,
+
+    // Names that are used without being declared.
+    globals: HashSet<String>,
+
+    // Names that generated names have to avoid.
+    reserved: HashSet<String>,
+
+    // Generated names, in the order they're handed out.
+    names: Vec<String>,
+}
+
+impl Renamer {
+    // Each local gets the name for its position in the scope, so a local
+    // never shares a name with another one that's visible at the same time.
+    fn declare<'a>(&mut self, name: &mut Cow<'a, str>) {
+        if !self.renaming {
+            self.scope.push((name.to_string(), name.to_string()));
+            return;
+        }
+
+        let index = self.scope.len();
+        while self.names.len() <= index {
+            let name = self.generate_name();
+            self.names.push(name);
+        }
+
+        let new_name = self.names[index].clone();
+        self.scope.push((name.to_string(), new_name.clone()));
+        *name = Cow::Owned(new_name);
+    }
+
+    fn generate_name(&self) -> String {
+        const FIRST: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ_";
+        const REST: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ_0123456789";
+
+        let mut count = self.names.last().map_or(0, |last| name_number(last, FIRST, REST) + 1);
+
+        loop {
+            let mut number = count;
+            let mut name = vec![FIRST[number % FIRST.len()]];
+            number /= FIRST.len();
+
+            while number > 0 {
+                number -= 1;
+                name.push(REST[number % REST.len()]);
+                number /= REST.len();
+            }
+
+            let name = String::from_utf8(name).unwrap();
+            if !self.reserved.contains(&name) && is_identifier(&name) {
+                return name;
+            }
+
+            count += 1;
+        }
+    }
+
+    fn resolve<'a>(&mut self, name: &mut Cow<'a, str>) {
+        let found = self.scope.iter()
+            .rev()
+            .find(|(original, _)| original == name)
+            .map(|(_, new_name)| new_name.clone());
+
+        match found {
+            Some(new_name) => {
+                if self.renaming {
+                    *name = Cow::Owned(new_name);
+                }
+            },
+            None => {
+                self.globals.insert(name.to_string());
+            },
+        }
+    }
+
+    fn block(&mut self, chunk: &mut Chunk) {
+        let depth = self.scope.len();
+        self.statements(chunk);
+        self.scope.truncate(depth);
+    }
+
+    fn statements(&mut self, chunk: &mut Chunk) {
+        for statement in &mut chunk.statements {
+            self.statement(statement);
+        }
+    }
+
+    fn statement(&mut self, statement: &mut Statement) {
+        match statement.kind {
+            StatementKind::Assignment(ref mut assignment) => {
+                self.expressions(&mut assignment.targets);
+                self.expressions(&mut assignment.values);
+            },
+            StatementKind::LocalAssignment(ref mut local) => {
+                // The values can't see the locals they're assigned to.
+                self.expressions(&mut local.values);
+
+                for name in &mut local.names {
+                    self.declare(name);
+                }
+            },
+            StatementKind::FunctionCall(ref mut call) => self.call(call),
+            StatementKind::NumericFor(ref mut numeric_for) => {
+                self.expression(&mut numeric_for.start);
+                self.expression(&mut numeric_for.end);
+
+                if let Some(ref mut step) = numeric_for.step {
+                    self.expression(step);
+                }
+
+                let depth = self.scope.len();
+                self.declare(&mut numeric_for.var);
+                self.block(&mut numeric_for.body);
+                self.scope.truncate(depth);
+            },
+            StatementKind::GenericFor(ref mut generic_for) => {
+                self.expressions(&mut generic_for.item_source);
+
+                let depth = self.scope.len();
+                for var in &mut generic_for.vars {
+                    self.declare(var);
+                }
+
+                self.block(&mut generic_for.body);
+                self.scope.truncate(depth);
+            },
+            StatementKind::IfStatement(ref mut if_statement) => {
+                self.expression(&mut if_statement.condition);
+                self.block(&mut if_statement.body);
+
+                for (condition, body) in &mut if_statement.else_if_branches {
+                    self.expression(condition);
+                    self.block(body);
+                }
+
+                if let Some(ref mut else_branch) = if_statement.else_branch {
+                    self.block(else_branch);
+                }
+            },
+            StatementKind::DoBlock(ref mut do_block) => self.block(&mut do_block.body),
+            StatementKind::WhileLoop(ref mut while_loop) => {
+                self.expression(&mut while_loop.condition);
+                self.block(&mut while_loop.body);
+            },
+            StatementKind::RepeatLoop(ref mut repeat_loop) => {
+                // The condition can see the body's locals.
+                let depth = self.scope.len();
+                self.statements(&mut repeat_loop.body);
+                self.expression(&mut repeat_loop.condition);
+                self.scope.truncate(depth);
+            },
+            StatementKind::FunctionDeclaration(ref mut declaration) => {
+                // A local function can call itself, so its name is declared
+                // before its body.
+                if declaration.local {
+                    self.declare(&mut declaration.name.path[0]);
+                } else {
+                    self.resolve(&mut declaration.name.path[0]);
+                }
+
+                let depth = self.scope.len();
+                if declaration.is_method {
+                    self.scope.push(("self".to_string(), "self".to_string()));
+                }
+
+                for parameter in &mut declaration.parameters {
+                    self.declare(parameter);
+                }
+
+                self.block(&mut declaration.body);
+                self.scope.truncate(depth);
+            },
+            StatementKind::Return(ref mut return_statement) => self.expressions(&mut return_statement.values),
+            StatementKind::Goto(_) | StatementKind::Label(_) | StatementKind::Break | StatementKind::Empty => {},
+        }
+    }
+
+    fn expressions(&mut self, expressions: &mut [Expression]) {
+        for expression in expressions {
+            self.expression(expression);
+        }
+    }
+
+    fn expression(&mut self, expression: &mut Expression) {
+        match expression.kind {
+            ExpressionKind::Name(ref mut name) => self.resolve(name),
+            ExpressionKind::Table(ref mut table) => self.table(table),
+            ExpressionKind::FunctionCall(ref mut call) => self.call(call),
+            ExpressionKind::FieldAccess(ref mut field_access) => self.expression(&mut field_access.target),
+            ExpressionKind::Index(ref mut index) => {
+                self.expression(&mut index.target);
+                self.expression(&mut index.key);
+            },
+            ExpressionKind::ParenExpression(ref mut inner) => self.expression(inner),
+            ExpressionKind::UnaryOp(ref mut unary_op) => self.expression(&mut unary_op.argument),
+            ExpressionKind::BinaryOp(ref mut binary_op) => {
+                self.expression(&mut binary_op.left);
+                self.expression(&mut binary_op.right);
+            },
+            ExpressionKind::Nil |
+            ExpressionKind::Bool(_) |
+            ExpressionKind::Number(_) |
+            ExpressionKind::String(_) |
+            ExpressionKind::VarArg => {},
+        }
+    }
+
+    fn call(&mut self, call: &mut FunctionCall) {
+        self.expression(&mut call.name_expression);
+
+        match call.arguments {
+            FunctionArguments::Parenthesized(ref mut arguments) => self.expressions(arguments),
+            FunctionArguments::Table(ref mut table) => self.table(table),
+            FunctionArguments::String(_) => {},
+        }
+    }
+
+    fn table(&mut self, table: &mut TableLiteral) {
+        for (key, value) in &mut table.items {
+            if let Some(TableKey::Expression(key)) = key {
+                self.expression(key);
+            }
+
+            self.expression(value);
+        }
+    }
+}
+
+// The inverse of the numbering in `generate_name`.
+fn name_number(name: &str, first: &[u8], rest: &[u8]) -> usize {
+    let bytes = name.as_bytes();
+    let mut number = 0;
+
+    for &byte in bytes[1..].iter().rev() {
+        number = number * rest.len() + rest.iter().position(|&c| c == byte).unwrap() + 1;
+    }
+
+    number * first.len() + first.iter().position(|&c| c == bytes[0]).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use emitter::print_chunk;
+    use tokenizer::{tokenize, tokenize_luau};
+    use parser::{parse_from_tokens, parse_from_tokens_with_types};
+
+    fn rename(source: &str) -> String {
+        let tokens = tokenize(source).unwrap();
+        let mut chunk = parse_from_tokens(&tokens).unwrap();
+        rename_locals(&mut chunk);
+        print_chunk(&chunk)
+    }
+
+    #[test]
+    fn scoping() {
+        assert_eq!(rename("
+            local x = 1
+            local x = x + 1
+            do local y = x end
+            for i = x, 10 do local z = i end
+            repeat local r = x until r
+            local function f(p, ...) return f(p) end
+            function t.m:method(q) return self, q end
+        "), "\
+local a = 1
+local b = a + 1
+do
+\tlocal c = b
+end
+for c = b, 10 do
+\tlocal d = c
+end
+repeat
+\tlocal c = b
+until c
+local function c(d, ...)
+\treturn c(d)
+end
+function t.m:method(e)
+\treturn self, e
+end
+");
+    }
+
+    #[test]
+    fn avoids_globals() {
+        assert_eq!(rename("local x, y = a, b print(x, y, c.d)"), "local d, e = a, b\nprint(d, e, c.d)\n");
+    }
+
+    #[test]
+    fn floats_before_names() {
+        let tokens = tokenize("local n = 10.0 local m = 0.0 print(n, m)").unwrap();
+        let obfuscated = obfuscate_chunk(&parse_from_tokens(&tokens).unwrap(), Dialect::Lua53).unwrap();
+        assert_eq!(obfuscated, "local a=10.0 local b=0.0 print(a,b)");
+
+        let tokens = tokenize(&obfuscated).unwrap();
+        assert_eq!(parse_from_tokens(&tokens).unwrap().statements.len(), 3);
+    }
+
+    #[test]
+    fn type_annotations() {
+        let source = "local count: number = 1 local function f(x: string): number return #x + count end";
+        let tokens = tokenize_luau(source);
+        let obfuscated = obfuscate_chunk(&parse_from_tokens_with_types(&tokens).unwrap(), Dialect::Luau).unwrap();
+        assert_eq!(obfuscated, "local a:number=1 local function b(c:string):number return#c+a end");
+
+        let tokens = tokenize_luau(&obfuscated);
+        assert_eq!(parse_from_tokens_with_types(&tokens).unwrap().statements.len(), 2);
+    }
+
+    #[test]
+    fn names() {
+        let mut renamer = Renamer {
+            renaming: true,
+            ..Renamer::default()
+        };
+
+        let names = (0..120).map(|_| {
+            let name = renamer.generate_name();
+            renamer.names.push(name.clone());
+            name
+        }).collect::<Vec<_>>();
+
+        assert_eq!(&names[..3], ["a", "b", "c"]);
+        assert_eq!(&names[52..56], ["_", "aa", "ba", "ca"]);
+        assert!(!names.iter().any(|name| name == "do" || name == "if" || name == "in" || name == "or"));
+    }
+}
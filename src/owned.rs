@@ -0,0 +1,284 @@
+use std::fmt;
+use std::marker::PhantomData;
+
+use serde::de::{self, Deserialize, DeserializeSeed, Deserializer, EnumAccess, MapAccess, SeqAccess, VariantAccess, Visitor};
+
+/// Deserializes a `'static` AST without borrowing from the input, so it works
+/// with transient buffers and anything that needs `DeserializeOwned`.
+///
+/// The AST types borrow strings from the input when they can, which ties them
+/// to its lifetime. `Owned` copies them instead:
+///
+/// ```
+/// # extern crate serde_json;
+/// # extern crate mab;
+/// use mab::ast::{Chunk, Owned};
+///
+/// # fn main() {
+/// let json = String::from(r#"{"statements":[{"id":1,"kind":"Break"}]}"#);
+/// let Owned(chunk): Owned<Chunk<'static>> = serde_json::from_str(&json).unwrap();
+/// drop(json);
+///
+/// assert_eq!(chunk.statements.len(), 1);
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Owned<T>(pub T);
+
+impl<T> Owned<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<'de, T: Deserialize<'static>> Deserialize<'de> for Owned<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        T::deserialize(copying_deserializer(deserializer)).map(Owned)
+    }
+}
+
+// Everything below wraps a piece of serde's deserialization machinery so that
+// it hands out copies where it would otherwise hand out borrows. Since nothing
+// borrowed ever gets through, the wrappers can claim any lifetime at all, and
+// `'static` is the one `T` needs.
+
+struct CopyingDeserializer<'de, D>(D, PhantomData<&'de ()>);
+struct CopyingVisitor<V>(V);
+struct CopyingSeed<S>(S);
+struct CopyingAccess<'de, A>(A, PhantomData<&'de ()>);
+
+fn copying_deserializer<'de, D: Deserializer<'de>>(deserializer: D) -> CopyingDeserializer<'de, D> {
+    CopyingDeserializer(deserializer, PhantomData)
+}
+
+fn copying_access<'de, A>(access: A) -> CopyingAccess<'de, A> {
+    CopyingAccess(access, PhantomData)
+}
+
+macro_rules! forward_deserialize {
+    ($($method:ident($($arg:ident: $ty:ty),*)),* $(,)*) => {
+        $(
+            fn $method<V: Visitor<'static>>(self, $($arg: $ty,)* visitor: V) -> Result<V::Value, D::Error> {
+                self.0.$method($($arg,)* CopyingVisitor(visitor))
+            }
+        )*
+    }
+}
+
+impl<'de, D: Deserializer<'de>> Deserializer<'static> for CopyingDeserializer<'de, D> {
+    type Error = D::Error;
+
+    forward_deserialize! {
+        deserialize_any(),
+        deserialize_bool(),
+        deserialize_i8(),
+        deserialize_i16(),
+        deserialize_i32(),
+        deserialize_i64(),
+        deserialize_i128(),
+        deserialize_u8(),
+        deserialize_u16(),
+        deserialize_u32(),
+        deserialize_u64(),
+        deserialize_u128(),
+        deserialize_f32(),
+        deserialize_f64(),
+        deserialize_char(),
+        deserialize_str(),
+        deserialize_string(),
+        deserialize_bytes(),
+        deserialize_byte_buf(),
+        deserialize_option(),
+        deserialize_unit(),
+        deserialize_unit_struct(name: &'static str),
+        deserialize_newtype_struct(name: &'static str),
+        deserialize_seq(),
+        deserialize_tuple(len: usize),
+        deserialize_tuple_struct(name: &'static str, len: usize),
+        deserialize_map(),
+        deserialize_struct(name: &'static str, fields: &'static [&'static str]),
+        deserialize_enum(name: &'static str, variants: &'static [&'static str]),
+        deserialize_identifier(),
+        deserialize_ignored_any(),
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.0.is_human_readable()
+    }
+}
+
+macro_rules! forward_visit {
+    ($($method:ident($ty:ty)),* $(,)*) => {
+        $(
+            fn $method<E: de::Error>(self, value: $ty) -> Result<V::Value, E> {
+                self.0.$method(value)
+            }
+        )*
+    }
+}
+
+impl<'de, V: Visitor<'static>> Visitor<'de> for CopyingVisitor<V> {
+    type Value = V::Value;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        self.0.expecting(formatter)
+    }
+
+    forward_visit! {
+        visit_bool(bool),
+        visit_i8(i8),
+        visit_i16(i16),
+        visit_i32(i32),
+        visit_i64(i64),
+        visit_i128(i128),
+        visit_u8(u8),
+        visit_u16(u16),
+        visit_u32(u32),
+        visit_u64(u64),
+        visit_u128(u128),
+        visit_f32(f32),
+        visit_f64(f64),
+        visit_char(char),
+        visit_str(&str),
+        visit_string(String),
+        visit_bytes(&[u8]),
+        visit_byte_buf(Vec<u8>),
+    }
+
+    fn visit_borrowed_str<E: de::Error>(self, value: &'de str) -> Result<V::Value, E> {
+        self.0.visit_str(value)
+    }
+
+    fn visit_borrowed_bytes<E: de::Error>(self, value: &'de [u8]) -> Result<V::Value, E> {
+        self.0.visit_bytes(value)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<V::Value, E> {
+        self.0.visit_none()
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<V::Value, D::Error> {
+        self.0.visit_some(copying_deserializer(deserializer))
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<V::Value, E> {
+        self.0.visit_unit()
+    }
+
+    fn visit_newtype_struct<D: Deserializer<'de>>(self, deserializer: D) -> Result<V::Value, D::Error> {
+        self.0.visit_newtype_struct(copying_deserializer(deserializer))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<V::Value, A::Error> {
+        self.0.visit_seq(copying_access(seq))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<V::Value, A::Error> {
+        self.0.visit_map(copying_access(map))
+    }
+
+    fn visit_enum<A: EnumAccess<'de>>(self, data: A) -> Result<V::Value, A::Error> {
+        self.0.visit_enum(copying_access(data))
+    }
+}
+
+impl<'de, S: DeserializeSeed<'static>> DeserializeSeed<'de> for CopyingSeed<S> {
+    type Value = S::Value;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<S::Value, D::Error> {
+        self.0.deserialize(copying_deserializer(deserializer))
+    }
+}
+
+impl<'de, A: SeqAccess<'de>> SeqAccess<'static> for CopyingAccess<'de, A> {
+    type Error = A::Error;
+
+    fn next_element_seed<S: DeserializeSeed<'static>>(&mut self, seed: S) -> Result<Option<S::Value>, A::Error> {
+        self.0.next_element_seed(CopyingSeed(seed))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.0.size_hint()
+    }
+}
+
+impl<'de, A: MapAccess<'de>> MapAccess<'static> for CopyingAccess<'de, A> {
+    type Error = A::Error;
+
+    fn next_key_seed<S: DeserializeSeed<'static>>(&mut self, seed: S) -> Result<Option<S::Value>, A::Error> {
+        self.0.next_key_seed(CopyingSeed(seed))
+    }
+
+    fn next_value_seed<S: DeserializeSeed<'static>>(&mut self, seed: S) -> Result<S::Value, A::Error> {
+        self.0.next_value_seed(CopyingSeed(seed))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.0.size_hint()
+    }
+}
+
+impl<'de, A: EnumAccess<'de>> EnumAccess<'static> for CopyingAccess<'de, A> {
+    type Error = A::Error;
+    type Variant = CopyingAccess<'de, A::Variant>;
+
+    fn variant_seed<S: DeserializeSeed<'static>>(self, seed: S) -> Result<(S::Value, Self::Variant), A::Error> {
+        self.0.variant_seed(CopyingSeed(seed))
+            .map(|(value, variant)| (value, copying_access(variant)))
+    }
+}
+
+impl<'de, A: VariantAccess<'de>> VariantAccess<'static> for CopyingAccess<'de, A> {
+    type Error = A::Error;
+
+    fn unit_variant(self) -> Result<(), A::Error> {
+        self.0.unit_variant()
+    }
+
+    fn newtype_variant_seed<S: DeserializeSeed<'static>>(self, seed: S) -> Result<S::Value, A::Error> {
+        self.0.newtype_variant_seed(CopyingSeed(seed))
+    }
+
+    fn tuple_variant<V: Visitor<'static>>(self, len: usize, visitor: V) -> Result<V::Value, A::Error> {
+        self.0.tuple_variant(len, CopyingVisitor(visitor))
+    }
+
+    fn struct_variant<V: Visitor<'static>>(self, fields: &'static [&'static str], visitor: V) -> Result<V::Value, A::Error> {
+        self.0.struct_variant(fields, CopyingVisitor(visitor))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate serde_json;
+
+    use std::borrow::Cow;
+    use serde::de::DeserializeOwned;
+
+    use super::*;
+    use ast::*;
+    use tokenizer::tokenize;
+    use parser::parse_from_tokens;
+
+    fn from_reader<T: DeserializeOwned>(json: &[u8]) -> T {
+        serde_json::from_reader(json).unwrap()
+    }
+
+    #[test]
+    fn copies_strings() {
+        let tokens = tokenize("local x = 'a\\nb' .. [[c]]").unwrap();
+        let chunk = parse_from_tokens(&tokens).unwrap();
+        let json = serde_json::to_vec(&chunk).unwrap();
+
+        let Owned(copy): Owned<Chunk<'static>> = from_reader(&json);
+        drop(json);
+        assert_eq!(copy, chunk);
+
+        let names = match copy.statements[0].kind {
+            StatementKind::LocalAssignment(ref local) => &local.names,
+            _ => panic!("expected a local assignment"),
+        };
+
+        assert!(matches!(names[0], Cow::Owned(_)));
+    }
+}
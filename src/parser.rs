@@ -1,60 +1,580 @@
 use std::borrow::Cow;
+use std::error::Error;
+use std::fmt;
+use std::ops::Range;
 
-use tokenizer::{Token, TokenKind, Symbol, StringLiteral};
+use tokenizer::{Token, TokenKind, Symbol, StringLiteral, SourcePosition};
 use ast::*;
 use parser_core::*;
 
-pub fn parse_from_tokens<'a>(tokens: &'a [Token<'a>]) -> Result<Chunk<'a>, String> {
-    let state = ParseState::new(tokens);
+/// What went wrong in a [ParseError][ParseError].
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseErrorKind {
+    /// The parser couldn't make sense of the token at all.
+    UnexpectedToken {
+        /// Descriptions of what would have been accepted instead, like
+        /// "`then`" or "an expression". This can be empty.
+        expected: Vec<String>,
+    },
+
+    /// A keyword like `end` was used where a name was needed.
+    KeywordAsName {
+        keyword: Symbol,
+    },
+
+    /// A parameter came after `...` in a parameter list.
+    VarArgNotLast,
+
+    /// A statement came after `return` or `break` in the same block.
+    StatementAfterLastStatement {
+        /// Either `return` or `break`.
+        last_statement: Symbol,
+    },
+}
+
+impl ParseErrorKind {
+    /// A short code for this kind of error that won't change between versions,
+    /// so that tools can filter or suppress errors by it. Parser errors use
+    /// codes P001 through P009, and [TokenizeError][::tokenizer::TokenizeError] uses P010
+    /// and up.
+    pub fn code(&self) -> &'static str {
+        match *self {
+            ParseErrorKind::UnexpectedToken { .. } => "P001",
+            ParseErrorKind::KeywordAsName { .. } => "P002",
+            ParseErrorKind::VarArgNotLast => "P003",
+            ParseErrorKind::StatementAfterLastStatement { .. } => "P004",
+        }
+    }
+}
+
+/// An error that stopped the parser, along with the token that caused it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+
+    /// The offending token.
+    pub found: TokenKind<'static>,
+
+    pub start_position: SourcePosition,
+    pub end_position: SourcePosition,
+
+    /// Descriptions of the constructs being parsed when the error happened,
+    /// outermost first, like "the condition of a `while` loop".
+    pub context: Vec<&'static str>,
+}
+
+impl ParseError {
+    pub fn new(kind: ParseErrorKind, token: &Token) -> ParseError {
+        ParseError {
+            kind,
+            found: token.kind.clone().into_owned(),
+            start_position: token.start_position,
+            end_position: token.end_position,
+            context: Vec::new(),
+        }
+    }
+
+    fn in_context(self, context: Vec<&'static str>) -> ParseError {
+        ParseError {
+            context,
+            ..self
+        }
+    }
+
+    /// Creates an error for the token at `position`, which is allowed to be
+    /// past the last token if the source ended early.
+    fn at(kind: ParseErrorKind, tokens: &[Token], position: usize) -> ParseError {
+        match tokens.get(position) {
+            Some(token) => ParseError::new(kind, token),
+            None => {
+                let end_position = tokens.last()
+                    .map(|token| token.end_position)
+                    .unwrap_or(SourcePosition { bytes: 0, line: 1, column: 1 });
+
+                ParseError {
+                    kind,
+                    found: TokenKind::EndOfFile,
+                    start_position: end_position,
+                    end_position,
+                    context: Vec::new(),
+                }
+            },
+        }
+    }
+
+    /// See [ParseErrorKind::code][ParseErrorKind::code].
+    pub fn code(&self) -> &'static str {
+        self.kind.code()
+    }
+
+    /// The byte range of the offending token in the source.
+    pub fn span(&self) -> Range<usize> {
+        self.start_position.bytes..self.end_position.bytes
+    }
+
+    /// Formats the error followed by the line of `source` it happened on, with
+    /// the offending token underlined:
+    ///
+    /// ```text
+    /// Unexpected `=`, expected an expression at line 1, column 11
+    ///   |
+    /// 1 | local x = = 1
+    ///   |           ^
+    /// ```
+    ///
+    /// `source` should be the text that was tokenized to produce the error.
+    pub fn render(&self, source: &str) -> String {
+        let start = self.start_position.bytes.min(source.len());
+        let line_start = source[..start].rfind('\n').map_or(0, |index| index + 1);
+        let line_end = source[start..].find(['\r', '\n']).map_or(source.len(), |index| start + index);
+        let line = &source[line_start..line_end];
+
+        // Keep tabs so that the underline lines up however they're displayed.
+        let indent: String = source[line_start..start].chars()
+            .map(|character| if character == '\t' { '\t' } else { ' ' })
+            .collect();
+
+        // Tokens that span lines, like long strings, are only underlined up to
+        // the end of the first one.
+        let end = self.end_position.bytes.clamp(start, line_end);
+        let width = ::std::cmp::max(1, source[start..end].chars().count());
+
+        let line_number = self.start_position.line.to_string();
+        let gutter = " ".repeat(line_number.len());
+
+        format!("{}\n{} |\n{} | {}\n{} | {}{}",
+            self, gutter, line_number, line, gutter, indent, "^".repeat(width))
+    }
+
+    /// Describes the error without saying where it happened, for tools that
+    /// show the location some other way.
+    pub fn message(&self) -> String {
+        struct Message<'e>(&'e ParseError);
+
+        impl<'e> fmt::Display for Message<'e> {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                self.0.fmt_message(f)
+            }
+        }
+
+        Message(self).to_string()
+    }
+
+    fn fmt_message(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            ParseErrorKind::UnexpectedToken { ref expected } => {
+                write!(f, "Unexpected {}", self.found)?;
+
+                if let Some((last, rest)) = expected.split_last() {
+                    if rest.is_empty() {
+                        write!(f, ", expected {}", last)?;
+                    } else {
+                        write!(f, ", expected {} or {}", rest.join(", "), last)?;
+                    }
+                }
+            },
+            ParseErrorKind::KeywordAsName { .. } => write!(f, "Expected a name, but found the keyword {}", self.found)?,
+            ParseErrorKind::VarArgNotLast => write!(f, "`...` must be the last parameter, but found {} after it", self.found)?,
+            ParseErrorKind::StatementAfterLastStatement { last_statement } => {
+                write!(f, "`{}` must be the last statement in its block, but found {} after it", last_statement, self.found)?
+            },
+        }
+
+        if let Some(frame) = self.context.last() {
+            write!(f, " while parsing {}", frame)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_message(f)?;
+        write!(f, " at line {}, column {}", self.start_position.line, self.start_position.column)
+    }
+}
+
+impl Error for ParseError {}
+
+/// Something suspicious that the parser accepted anyway.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseWarningKind {
+    /// Two or more `;` in a row, like `f();;`. Only the first `;` is needed.
+    EmptyStatements,
+
+    /// A call whose `(` is on a different line than the function, like
+    ///
+    /// ```lua
+    /// local x = f
+    /// (g or h)()
+    /// ```
+    ///
+    /// which is parsed as `local x = f(g or h)()`. Lua 5.1 rejects this.
+    AmbiguousCall,
+}
+
+impl ParseWarningKind {
+    /// Like [ParseErrorKind::code][ParseErrorKind::code], but for warnings.
+    pub fn code(&self) -> &'static str {
+        match *self {
+            ParseWarningKind::EmptyStatements => "W001",
+            ParseWarningKind::AmbiguousCall => "W002",
+        }
+    }
+}
+
+/// A non-fatal diagnostic from the parser, along with the token it's about.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseWarning {
+    pub kind: ParseWarningKind,
+    pub start_position: SourcePosition,
+    pub end_position: SourcePosition,
+}
 
+impl ParseWarning {
+    pub fn new(kind: ParseWarningKind, token: &Token) -> ParseWarning {
+        ParseWarning {
+            kind,
+            start_position: token.start_position,
+            end_position: token.end_position,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        self.kind.code()
+    }
+
+    /// The byte range of the token the warning is about.
+    pub fn span(&self) -> Range<usize> {
+        self.start_position.bytes..self.end_position.bytes
+    }
+
+    /// Describes the warning without saying where it is.
+    pub fn message(&self) -> &'static str {
+        match self.kind {
+            ParseWarningKind::EmptyStatements => "Unnecessary `;`",
+            ParseWarningKind::AmbiguousCall => "Call arguments start on a new line, so this continues the previous expression",
+        }
+    }
+}
+
+impl fmt::Display for ParseWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at line {}, column {}", self.message(), self.start_position.line, self.start_position.column)
+    }
+}
+
+// ParseError is big, but it's only built once per parse, so boxing it would
+// just make it more awkward to match on.
+#[allow(clippy::result_large_err)]
+pub fn parse_from_tokens<'a>(tokens: &'a [Token<'a>]) -> Result<Chunk<'a>, ParseError> {
+    let context = ParseContext::default();
+    parse_chunk(ParseState::new(tokens, &context))
+}
+
+#[allow(clippy::result_large_err)]
+fn parse_chunk<'a>(state: ParseState<'a, '_>) -> Result<Chunk<'a>, ParseError> {
     let (state, chunk) = match ParseChunk.parse(state) {
         Ok(result) => result,
-        Err(ParseAbort::NoMatch) => return Err("No error reported".to_string()),
-        Err(ParseAbort::Error(message)) => return Err(message),
+        Err(ParseAbort::NoMatch) => unreachable!("ParseChunk always matches"),
+        Err(ParseAbort::Error(error)) => return Err(*error),
     };
 
     match state.peek() {
-        Some(Token { kind: TokenKind::EndOfFile, .. }) => {},
-        Some(token) => return Err(format!("A token was left at the end of the stream: {:?}", token)),
-        None => {},
+        Some(Token { kind: TokenKind::EndOfFile, .. }) | None => Ok(chunk),
+        Some(_) => Err(unexpected_token(state)),
+    }
+}
+
+/// Like [parse_from_tokens][parse_from_tokens], but also returns warnings about
+/// things that parsed fine but are probably mistakes.
+#[allow(clippy::result_large_err)]
+pub fn parse_from_tokens_with_warnings<'a>(tokens: &'a [Token<'a>]) -> Result<(Chunk<'a>, Vec<ParseWarning>), ParseError> {
+    let context = ParseContext::default();
+    let chunk = parse_chunk(ParseState::new(tokens, &context))?;
+
+    Ok((chunk, context.into_warnings()))
+}
+
+/// Like [parse_from_tokens][parse_from_tokens], but also returns where in
+/// the source each node came from.
+#[allow(clippy::result_large_err)]
+pub fn parse_from_tokens_with_source_map<'a>(tokens: &'a [Token<'a>]) -> Result<(Chunk<'a>, SourceMap), ParseError> {
+    let context = ParseContext::default();
+    let chunk = parse_chunk(ParseState::new(tokens, &context))?;
+
+    Ok((chunk, SourceMap { spans: context.into_spans() }))
+}
+
+/// Like [parse_from_tokens][parse_from_tokens], but also parses Luau's type
+/// annotations on locals, parameters, and return values, like
+/// `local function f(x: number?): string`. Their `?` and `->` are only
+/// tokens in the [Luau dialect][::tokenizer::Dialect::Luau].
+///
+/// ```
+/// use mab::ast::{StatementKind, TypeAnnotation};
+/// use mab::tokenizer::{Dialect, Tokenizer};
+///
+/// let tokens = Tokenizer::new("local x: number?, y = 1").dialect(Dialect::Luau).collect::<Result<Vec<_>, _>>().unwrap();
+/// let chunk = mab::parser::parse_from_tokens_with_types(&tokens).unwrap();
+///
+/// match chunk.statements[0].kind {
+///     StatementKind::LocalAssignment(ref local) => {
+///         assert_eq!(local.types[0].as_ref().unwrap().to_string(), "number?");
+///         assert_eq!(local.types[1], None);
+///     },
+///     _ => unreachable!(),
+/// }
+/// ```
+#[allow(clippy::result_large_err)]
+pub fn parse_from_tokens_with_types<'a>(tokens: &'a [Token<'a>]) -> Result<Chunk<'a>, ParseError> {
+    let context = ParseContext::default().with_type_annotations();
+    parse_chunk(ParseState::new(tokens, &context))
+}
+
+/// The span of source each node in a parsed chunk came from, for quoting the
+/// code a lint or error is about. Made by
+/// [parse_from_tokens_with_source_map][parse_from_tokens_with_source_map].
+///
+/// Spans are byte ranges that go from the start of a node's first token to
+/// the end of its last one. Nodes that didn't come from the parse, like ones
+/// added later, don't have spans.
+///
+/// ```
+/// let source = "local x = f(a, b)";
+/// let tokens = mab::tokenize(source).unwrap();
+/// let (chunk, source_map) = mab::parse_from_tokens_with_source_map(&tokens).unwrap();
+///
+/// let statement = &chunk.statements[0];
+/// assert_eq!(source_map.source_text(statement.id, source), Some("local x = f(a, b)"));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SourceMap {
+    spans: Vec<Range<usize>>,
+}
+
+impl SourceMap {
+    pub fn span(&self, id: NodeId) -> Option<Range<usize>> {
+        self.spans.get(id.get() as usize - 1).cloned()
+    }
+
+    /// The node's code, from the source the chunk was parsed from.
+    pub fn source_text<'s>(&self, id: NodeId, source: &'s str) -> Option<&'s str> {
+        self.span(id).and_then(|span| source.get(span))
+    }
+}
+
+/// Options for [parse_from_tokens_with_options][parse_from_tokens_with_options].
+#[derive(Debug, Clone, Default)]
+pub struct ParserOptions {
+    /// Stop parsing after this many errors, so that pathological input can't
+    /// produce an endless list of them. The rest of the source is skipped
+    /// after the last error, and any statements that it interrupted are left
+    /// out of the result.
+    pub max_errors: Option<usize>,
+
+    /// Number the nodes in source order, with no gaps, using
+    /// [Chunk::renumber_ids][::ast::Chunk::renumber_ids].
+    pub renumber_ids: bool,
+
+    /// Parse Luau's type annotations, like
+    /// [parse_from_tokens_with_types][parse_from_tokens_with_types] does.
+    pub type_annotations: bool,
+}
+
+/// Like [parse_from_tokens][parse_from_tokens], but keeps going after errors
+/// by skipping ahead to the next thing that looks like the start of a
+/// statement. Returns whatever could be parsed along with every error found,
+/// which is useful for editors and linters that want to keep working on
+/// broken files.
+pub fn parse_from_tokens_with_recovery<'a>(tokens: &'a [Token<'a>]) -> (Chunk<'a>, Vec<ParseError>) {
+    parse_from_tokens_with_options(tokens, &ParserOptions::default())
+}
+
+/// Like [parse_from_tokens_with_recovery][parse_from_tokens_with_recovery],
+/// with options to control how hard the parser tries.
+pub fn parse_from_tokens_with_options<'a>(tokens: &'a [Token<'a>], options: &ParserOptions) -> (Chunk<'a>, Vec<ParseError>) {
+    let mut context = ParseContext::recovering(options.max_errors);
+    if options.type_annotations {
+        context = context.with_type_annotations();
+    }
+
+    let mut state = ParseState::new(tokens, &context);
+    let mut statements = Vec::new();
+
+    loop {
+        match ParseChunk.parse(state) {
+            Ok((next_state, chunk)) => {
+                statements.extend(chunk.statements);
+                state = next_state;
+            },
+            Err(ParseAbort::NoMatch) => unreachable!("ParseChunk always matches"),
+            Err(ParseAbort::Error(_)) => unreachable!("ParseChunk reports errors when recovering"),
+        }
+
+        // A chunk can only stop early at something like a stray `end`.
+        match state.peek() {
+            Some(&Token { kind: TokenKind::EndOfFile, .. }) | None => break,
+            Some(_) => {
+                state = match recover(state, unexpected_token(state)) {
+                    Ok(state) => state,
+                    Err(_) => unreachable!("recover always succeeds when recovering"),
+                };
+            },
+        }
+    }
+
+    let mut chunk = Chunk { statements };
+
+    if options.renumber_ids {
+        chunk.renumber_ids();
     }
 
-    Ok(chunk)
+    (chunk, context.into_errors())
+}
+
+// The chunk stopped early because something in it failed to match, and the
+// furthest point any parser reached is usually where the mistake is.
+fn unexpected_token(state: ParseState) -> ParseError {
+    let expected = state.context.expected();
+
+    if expected.position >= state.position {
+        ParseError::at(ParseErrorKind::UnexpectedToken { expected: expected.items }, state.tokens, expected.position)
+            .in_context(expected.context)
+    } else {
+        ParseError::at(ParseErrorKind::UnexpectedToken { expected: Vec::new() }, state.tokens, state.position)
+    }
+}
+
+// Creates an error for a token the parser is looking at right now, so it's in
+// the context of whatever is being parsed.
+fn error_at(state: ParseState, kind: ParseErrorKind, token: &Token) -> ParseError {
+    ParseError::new(kind, token).in_context(state.context.frames())
+}
+
+// The bytes of source between two states.
+fn span_between(start: ParseState, end: ParseState) -> Range<usize> {
+    if end.position <= start.position {
+        let bytes = start.peek().map_or(0, |token| token.start_position.bytes);
+        return bytes..bytes;
+    }
+
+    let last = &end.tokens[end.position - 1];
+    start.tokens[start.position].start_position.bytes..last.end_position.bytes
+}
+
+// Nodes are made from the tokens between `start` and `end`.
+fn new_expression<'a>(start: ParseState, end: ParseState, kind: ExpressionKind<'a>) -> Expression<'a> {
+    Expression {
+        id: start.context.next_id(span_between(start, end)),
+        kind,
+    }
+}
+
+fn new_statement<'a>(start: ParseState, end: ParseState, kind: StatementKind<'a>) -> Statement<'a> {
+    Statement {
+        id: start.context.next_id(span_between(start, end)),
+        kind,
+    }
+}
+
+// Reports an error, then skips ahead to a token that can probably start a
+// statement, like a keyword or the first token on a line. Without recovery,
+// this just fails with the error instead.
+fn recover<'a, 'c>(state: ParseState<'a, 'c>, error: ParseError) -> Result<ParseState<'a, 'c>, ParseAbort> {
+    // Always skip at least one token so that we make progress, and skip past
+    // anything the failed parser was able to understand.
+    let skip_to = ::std::cmp::max(state.position + 1, state.context.expected().position);
+    state.context.report(error)?;
+
+    // Once there are too many errors, the rest of the source is skipped so
+    // that everything still open can wrap up quietly.
+    if state.context.is_full() {
+        return Ok(state.advance(state.tokens.len() - state.position));
+    }
+
+    let mut state = state.advance(skip_to - state.position);
+
+    while let Some(token) = state.peek() {
+        let previous = &state.tokens[state.position - 1];
+        let starts_line = token.start_position.line > previous.end_position.line;
+
+        if starts_line || ends_block(token) || starts_statement(token) {
+            break;
+        }
+
+        state = state.advance(1);
+    }
+
+    Ok(state)
+}
+
+fn ends_block(token: &Token) -> bool {
+    matches!(token.kind,
+        TokenKind::EndOfFile |
+        TokenKind::Symbol(Symbol::End) | TokenKind::Symbol(Symbol::Else) |
+        TokenKind::Symbol(Symbol::ElseIf) | TokenKind::Symbol(Symbol::Until))
+}
+
+fn starts_statement(token: &Token) -> bool {
+    match token.kind {
+        TokenKind::Symbol(symbol) => matches!(symbol,
+            Symbol::Local | Symbol::Function | Symbol::If | Symbol::While |
+            Symbol::For | Symbol::Repeat | Symbol::Do | Symbol::Return |
+            Symbol::Break | Symbol::Goto | Symbol::DoubleColon),
+        _ => false,
+    }
 }
 
 struct ParseToken<'a>(pub TokenKind<'a>);
 
-define_parser!(ParseToken<'state>, &'state Token<'state>, |this: &ParseToken<'state>, state: ParseState<'state>| {
+define_parser!(ParseToken<'state>, &'state Token<'state>, |this, state| {
     match state.peek() {
-        Some(token) => {
-            if token.kind == this.0 {
-                Ok((state.advance(1), token))
-            } else {
-                Err(ParseAbort::NoMatch)
-            }
+        Some(token) if token.kind == this.0 => Ok((state.advance(1), token)),
+        _ => {
+            state.expect(this.0.to_string());
+            Err(ParseAbort::NoMatch)
         },
-        None => Err(ParseAbort::NoMatch),
     }
 });
 
 struct ParseNumber;
-define_parser!(ParseNumber, Cow<'state, str>, |_, state: ParseState<'state>| {
+define_parser!(ParseNumber, Cow<'state, str>, |_, state| {
     match state.peek() {
         Some(&Token { kind: TokenKind::NumberLiteral(ref value), .. }) => Ok((state.advance(1), Cow::from(value.as_ref()))),
-        _ => Err(ParseAbort::NoMatch),
+        _ => {
+            state.expect("a number");
+            Err(ParseAbort::NoMatch)
+        },
     }
 });
 
 struct ParseIdentifier;
-define_parser!(ParseIdentifier, Cow<'state, str>, |_, state: ParseState<'state>| {
+define_parser!(ParseIdentifier, Cow<'state, str>, |_, state| {
     match state.peek() {
         Some(&Token { kind: TokenKind::Identifier(ref name), .. }) => Ok((state.advance(1), Cow::from(name.as_ref()))),
-        _ => Err(ParseAbort::NoMatch),
+        _ => {
+            state.expect("a name");
+            Err(ParseAbort::NoMatch)
+        },
+    }
+});
+
+// Like ParseIdentifier, but for places where only a name can appear. Finding a
+// keyword there is reported as an error instead of just failing to match.
+struct ParseName;
+define_parser!(ParseName, Cow<'state, str>, |_, state| {
+    match state.peek() {
+        Some(token @ &Token { kind: TokenKind::Symbol(keyword), .. }) if keyword.is_keyword() => {
+            Err(ParseAbort::Error(Box::new(error_at(state, ParseErrorKind::KeywordAsName { keyword }, token))))
+        },
+        _ => ParseIdentifier.parse(state),
     }
 });
 
 struct ParseSymbol(pub Symbol);
-define_parser!(ParseSymbol, Symbol, |this: &ParseSymbol, state: ParseState<'state>| {
+define_parser!(ParseSymbol, Symbol, |this, state| {
     let (state, token) = ParseToken(TokenKind::Symbol(this.0)).parse(state)?;
     let symbol = match token.kind {
         TokenKind::Symbol(symbol) => symbol,
@@ -68,14 +588,84 @@ define_parser!(ParseSymbol, Symbol, |this: &ParseSymbol, state: ParseState<'stat
 // chunk ::= {stat [`;´]} [laststat [`;´]]
 struct ParseChunk;
 define_parser!(ParseChunk, Chunk<'state>, |_, state| {
-    let (state, statements) = ZeroOrMore(ParseStatement).parse(state)?;
+    let mut statements: Vec<Statement> = Vec::new();
+    let mut state = state;
+
+    loop {
+        match Labeled("a statement", ParseStatement).parse(state) {
+            Ok((next_state, statement)) => {
+                // Only warn about the first extra `;` in a run of them.
+                if let StatementKind::Empty = statement.kind {
+                    let run_length = statements.iter().rev()
+                        .take_while(|statement| matches!(statement.kind, StatementKind::Empty))
+                        .count();
+
+                    if run_length == 1 {
+                        state.warn(ParseWarning::new(ParseWarningKind::EmptyStatements, &state.tokens[state.position]));
+                    }
+                }
+
+                statements.push(statement);
+                state = next_state;
+                continue;
+            },
+            Err(ParseAbort::NoMatch) => {},
+            Err(ParseAbort::Error(error)) => {
+                state = recover(state, *error)?;
+                continue;
+            },
+        }
+
+        // A laststat always ends the block, so whatever follows it (other than
+        // its optional semicolon) has to be consumed by the enclosing construct.
+        match Quiet(ParseLastStatement).parse(state) {
+            Ok((next_state, last_statement)) => {
+                let keyword = match state.peek() {
+                    Some(&Token { kind: TokenKind::Symbol(keyword), .. }) => keyword,
+                    _ => unreachable!("laststat always starts with a keyword"),
+                };
+                statements.push(last_statement);
+
+                state = match Quiet(ParseSymbol(Symbol::Semicolon)).parse(next_state) {
+                    Ok((after_semicolon, _)) => {
+                        statements.push(new_statement(next_state, after_semicolon, StatementKind::Empty));
+                        after_semicolon
+                    },
+                    Err(_) => next_state,
+                };
+
+                match state.peek() {
+                    Some(token) if !ends_block(token) => {
+                        let error = error_at(state, ParseErrorKind::StatementAfterLastStatement { last_statement: keyword }, token);
+
+                        // The statements that follow are still worth parsing.
+                        state.context.report(error)?;
+                    },
+                    _ => break,
+                }
+            },
+            Err(ParseAbort::NoMatch) => {
+                // Anything else that isn't the end of the block is garbage,
+                // which the enclosing construct reports unless we can recover
+                // from it here.
+                match state.peek() {
+                    Some(token) if !ends_block(token) && state.context.is_recovering() => {
+                        state = recover(state, unexpected_token(state))?;
+                    },
+                    _ => break,
+                }
+            },
+            Err(ParseAbort::Error(error)) => state = recover(state, *error)?,
+        }
+    }
 
     Ok((state, Chunk {
         statements,
     }))
 });
 
-// stat ::= varlist `=´ explist |
+// stat ::= `;´ |
+//     varlist `=´ explist |
 //     functioncall |
 //     do chunk end |
 //     while exp do chunk end |
@@ -85,23 +675,66 @@ define_parser!(ParseChunk, Chunk<'state>, |_, state| {
 //     for namelist in explist do chunk end |
 //     function funcname funcbody |
 //     local function Name funcbody |
-//     local namelist [`=´ explist]
+//     local namelist [`=´ explist] |
+//     goto Name |
+//     label
 struct ParseStatement;
 define_parser!(ParseStatement, Statement<'state>, |_, state| {
+    let (end, kind) = ParseStatementKind.parse(state)?;
+    Ok((end, new_statement(state, end, kind)))
+});
+
+struct ParseStatementKind;
+define_parser!(ParseStatementKind, StatementKind<'state>, |_, state| {
+    parse_first_of!(state, {
+        // Has to come before ParseLocalAssignment, which would otherwise
+        // complain about `function` not being a valid name.
+        ParseFunctionDeclaration => StatementKind::FunctionDeclaration,
+        ParseLocalAssignment => StatementKind::LocalAssignment,
+        ParseAssignment => StatementKind::Assignment,
+        ParseFunctionCall => StatementKind::FunctionCall,
+        ParseNumericFor => StatementKind::NumericFor,
+        ParseGenericFor => StatementKind::GenericFor,
+        ParseIfStatement => StatementKind::IfStatement,
+        ParseDoBlock => StatementKind::DoBlock,
+        ParseWhileLoop => StatementKind::WhileLoop,
+        ParseRepeatLoop => StatementKind::RepeatLoop,
+        ParseGoto => StatementKind::Goto,
+        ParseLabel => StatementKind::Label,
+        // Hack: parse_first_of! cannot handle unit values
+        ParseSymbol(Symbol::Semicolon) => |_| StatementKind::Empty,
+    })
+});
+
+// laststat ::= return [explist] | break
+struct ParseLastStatement;
+define_parser!(ParseLastStatement, Statement<'state>, |_, state| {
+    let (end, kind) = ParseLastStatementKind.parse(state)?;
+    Ok((end, new_statement(state, end, kind)))
+});
+
+struct ParseLastStatementKind;
+define_parser!(ParseLastStatementKind, StatementKind<'state>, |_, state| {
     parse_first_of!(state, {
-        ParseLocalAssignment => Statement::LocalAssignment,
-        ParseFunctionCall => Statement::FunctionCall,
-        ParseNumericFor => Statement::NumericFor,
-        ParseGenericFor => Statement::GenericFor,
-        ParseIfStatement => Statement::IfStatement,
-        ParseWhileLoop => Statement::WhileLoop,
-        ParseRepeatLoop => Statement::RepeatLoop,
-        ParseFunctionDeclaration => Statement::FunctionDeclaration,
+        ParseReturn => StatementKind::Return,
+        // Hack: parse_first_of! cannot handle unit values
+        ParseSymbol(Symbol::Break) => |_| StatementKind::Break,
     })
 });
 
+struct ParseReturn;
+define_parser!(ParseReturn, Return<'state>, |_, state| {
+    let (state, _) = ParseSymbol(Symbol::Return).parse(state)?;
+    let (state, values) = WhileParsing("a `return` statement",
+        Optional(DelimitedOneOrMore(ParseExpression, ParseSymbol(Symbol::Comma)))).parse(state)?;
+
+    Ok((state, Return {
+        values: values.unwrap_or_default(),
+    }))
+});
+
 struct ParseUnaryOp;
-define_parser!(ParseUnaryOp, UnaryOpKind, |_, state: ParseState<'state>| {
+define_parser!(ParseUnaryOp, UnaryOpKind, |_, state| {
     if let Some(&Token { kind: TokenKind::Symbol(symbol), .. }) = state.peek() {
         let kind = match symbol {
             Symbol::Minus => UnaryOpKind::Negate,
@@ -117,15 +750,25 @@ define_parser!(ParseUnaryOp, UnaryOpKind, |_, state: ParseState<'state>| {
 });
 
 struct ParseBinaryOp;
-define_parser!(ParseBinaryOp, BinaryOpKind, |_, state: ParseState<'state>| {
+define_parser!(ParseBinaryOp, BinaryOpKind, |_, state| {
     if let Some(&Token { kind: TokenKind::Symbol(symbol), .. }) = state.peek() {
         let kind = match symbol {
             Symbol::Plus => BinaryOpKind::Add,
             Symbol::Minus => BinaryOpKind::Subtract,
             Symbol::Star => BinaryOpKind::Multiply,
             Symbol::Slash => BinaryOpKind::Divide,
+            Symbol::DoubleSlash => BinaryOpKind::FloorDivide,
+            Symbol::Percent => BinaryOpKind::Modulo,
             Symbol::Caret => BinaryOpKind::Exponent,
             Symbol::TwoDots => BinaryOpKind::Concat,
+            Symbol::And => BinaryOpKind::And,
+            Symbol::Or => BinaryOpKind::Or,
+            Symbol::LessThan => BinaryOpKind::LessThan,
+            Symbol::LessThanEqual => BinaryOpKind::LessThanEqual,
+            Symbol::GreaterThan => BinaryOpKind::GreaterThan,
+            Symbol::GreaterThanEqual => BinaryOpKind::GreaterThanEqual,
+            Symbol::TwoEqual => BinaryOpKind::Equal,
+            Symbol::NotEqual => BinaryOpKind::NotEqual,
             _ => return Err(ParseAbort::NoMatch),
         };
 
@@ -135,17 +778,15 @@ define_parser!(ParseBinaryOp, BinaryOpKind, |_, state: ParseState<'state>| {
     }
 });
 
+// Precedence climbing: parses an expression whose binary operators all bind at
+// least as tightly as the given precedence.
 struct ParseExpressionAtPrecedence(u8);
-define_parser!(ParseExpressionAtPrecedence, Expression<'state>, |this: &ParseExpressionAtPrecedence, state| {
+define_parser!(ParseExpressionAtPrecedence, Expression<'state>, |this, state| {
     let min_precedence = this.0;
-    let (mut state, mut atom_lhs) = ParseExpressionAtom.parse(state)?;
-
-    loop {
-        let (next_state, operator) = match ParseBinaryOp.parse(state) {
-            Ok(v) => v,
-            Err(_) => break,
-        };
+    let start = state;
+    let (mut state, mut atom_lhs) = Labeled("an expression", ParseExpressionAtom).parse(state)?;
 
+    while let Ok((next_state, operator)) = ParseBinaryOp.parse(state) {
         if operator.precedence() < min_precedence {
             break;
         }
@@ -159,11 +800,11 @@ define_parser!(ParseExpressionAtPrecedence, Expression<'state>, |this: &ParseExp
         let (next_state, atom_rhs) = ParseExpressionAtPrecedence(next_min_precedence).parse(next_state)?;
         state = next_state;
 
-        atom_lhs = Expression::BinaryOp(BinaryOp {
+        atom_lhs = new_expression(start, state, ExpressionKind::BinaryOp(BinaryOp {
             operator,
             left: Box::new(atom_lhs),
             right: Box::new(atom_rhs),
-        });
+        }));
     }
 
     Ok((state, atom_lhs))
@@ -176,48 +817,53 @@ define_parser!(ParseExpression, Expression<'state>, |_, state| {
 
 struct ParseExpressionAtom;
 define_parser!(ParseExpressionAtom, Expression<'state>, |_, state| {
-    if let Ok((next_state, expression)) = ParseUnaryExpression.parse(state) {
-        Ok((next_state, expression))
-    } else if let Ok((next_state, expression)) = ParseParenExpression.parse(state) {
-        Ok((next_state, expression))
-    } else if let Ok((next_state, expression)) = ParseValue.parse(state) {
-        Ok((next_state, expression))
-    } else {
-        Err(ParseAbort::NoMatch)
-    }
+    parse_first_of!(state, {
+        ParseUnaryExpression => |expression| expression,
+        ParseValue => |expression| expression,
+    })
 });
 
 struct ParseUnaryExpression;
 define_parser!(ParseUnaryExpression, Expression<'state>, |_, state| {
+    let start = state;
     let (state, operator) = ParseUnaryOp.parse(state)?;
     let (state, argument) = ParseExpressionAtPrecedence(operator.precedence()).parse(state)?;
 
-    Ok((state, Expression::UnaryOp(UnaryOp {
+    Ok((state, new_expression(start, state, ExpressionKind::UnaryOp(UnaryOp {
         operator,
         argument: Box::new(argument),
-    })))
+    }))))
 });
 
 struct ParseParenExpression;
 define_parser!(ParseParenExpression, Expression<'state>, |_, state| {
+    let start = state;
     let (state, _) = ParseSymbol(Symbol::LeftParen).parse(state)?;
     let (state, expression) = ParseExpression.parse(state)?;
     let (state, _) = ParseSymbol(Symbol::RightParen).parse(state)?;
 
-    Ok((state, Expression::ParenExpression(Box::new(expression))))
+    Ok((state, new_expression(start, state, ExpressionKind::ParenExpression(Box::new(expression)))))
 });
 
 struct ParseValue;
 define_parser!(ParseValue, Expression<'state>, |_, state| {
+    match ParseLiteral.parse(state) {
+        Ok((end, kind)) => Ok((end, new_expression(state, end, kind))),
+        Err(ParseAbort::NoMatch) => ParsePrefixExpression.parse(state),
+        Err(error) => Err(error),
+    }
+});
+
+struct ParseLiteral;
+define_parser!(ParseLiteral, ExpressionKind<'state>, |_, state| {
     parse_first_of!(state, {
-        ParseNumber => Expression::Number,
-        ParseFunctionCall => Expression::FunctionCall,
-        ParseIdentifier => Expression::Name,
-        ParseTableLiteral => Expression::Table,
-        ParseBoolean => Expression::Bool,
+        ParseNumber => ExpressionKind::Number,
+        ParseTableLiteral => ExpressionKind::Table,
+        ParseBoolean => ExpressionKind::Bool,
         // Hack: parse_first_of! cannot handle unit values
-        ParseNil => |_| Expression::Nil,
-        ParseString => Expression::String,
+        ParseNil => |_| ExpressionKind::Nil,
+        ParseSymbol(Symbol::Ellipse) => |_| ExpressionKind::VarArg,
+        ParseString => ExpressionKind::String,
     })
 });
 
@@ -234,10 +880,13 @@ define_parser!(ParseNil, (), |_, state| {
 });
 
 struct ParseString;
-define_parser!(ParseString, StringLiteral<'state>, |_, state: ParseState<'state>| {
+define_parser!(ParseString, StringLiteral<'state>, |_, state| {
     match state.peek() {
         Some(&Token { kind: TokenKind::StringLiteral(ref value), .. }) => Ok((state.advance(1), value.clone())),
-        _ => Err(ParseAbort::NoMatch),
+        _ => {
+            state.expect("a string");
+            Err(ParseAbort::NoMatch)
+        },
     }
 });
 
@@ -245,8 +894,13 @@ define_parser!(ParseString, StringLiteral<'state>, |_, state: ParseState<'state>
 struct ParseLocalAssignment;
 define_parser!(ParseLocalAssignment, LocalAssignment<'state>, |_, state| {
     let (state, _) = ParseSymbol(Symbol::Local).parse(state)?;
+    WhileParsing("a `local` declaration", ParseLocalNamesAndValues).parse(state)
+});
 
-    let (state, names) = DelimitedOneOrMore(ParseIdentifier, ParseSymbol(Symbol::Comma)).parse(state)?;
+struct ParseLocalNamesAndValues;
+define_parser!(ParseLocalNamesAndValues, LocalAssignment<'state>, |_, state| {
+    let (state, names) = DelimitedOneOrMore(ParseAnnotatedName, ParseSymbol(Symbol::Comma)).parse(state)?;
+    let (names, types) = names.into_iter().unzip();
 
     let (state, expressions) = match ParseSymbol(Symbol::Equal).parse(state) {
         Ok((state, _)) => DelimitedOneOrMore(ParseExpression, ParseSymbol(Symbol::Comma)).parse(state)?,
@@ -254,31 +908,143 @@ define_parser!(ParseLocalAssignment, LocalAssignment<'state>, |_, state| {
     };
 
     Ok((state, LocalAssignment {
-        names: names,
+        names,
         values: expressions,
+        types: annotations(types),
     }))
 });
 
+// varlist `=´ explist
+// Both this and a function call statement start with a name, so this backs out
+// with NoMatch unless the names are followed by `=´.
+struct ParseAssignment;
+define_parser!(ParseAssignment, Assignment<'state>, |_, state| {
+    let (state, targets) = DelimitedOneOrMore(ParseVar, ParseSymbol(Symbol::Comma)).parse(state)?;
+    let (state, _) = ParseSymbol(Symbol::Equal).parse(state)?;
+    let (state, values) = DelimitedOneOrMore(ParseExpression, ParseSymbol(Symbol::Comma)).parse(state)?;
+
+    Ok((state, Assignment {
+        targets,
+        values,
+    }))
+});
+
+// var ::= Name | prefixexp `[´ exp `]´ | prefixexp `.´ Name
+struct ParseVar;
+define_parser!(ParseVar, Expression<'state>, |_, state| {
+    let (state, expression) = ParsePrefixExpression.parse(state)?;
+
+    match expression.kind {
+        ExpressionKind::Name(_) | ExpressionKind::FieldAccess(_) | ExpressionKind::Index(_) => Ok((state, expression)),
+        _ => Err(ParseAbort::NoMatch),
+    }
+});
+
 // functioncall ::= prefixexp args | prefixexp `:´ Name args
-// right now:
-// functioncall ::= Name `(` explist `)`
 struct ParseFunctionCall;
 define_parser!(ParseFunctionCall, FunctionCall<'state>, |_, state| {
-    let (state, name) = ParseIdentifier.parse(state)?;
+    let (state, expression) = ParsePrefixExpression.parse(state)?;
+
+    match expression.kind {
+        ExpressionKind::FunctionCall(call) => Ok((state, call)),
+        _ => Err(ParseAbort::NoMatch),
+    }
+});
+
+// prefixexp ::= prefix {suffix}
+// prefix ::= `(´ exp `)´ | Name
+// suffix ::= `[´ exp `]´ | `.´ Name | args | `:´ Name args
+struct ParsePrefixExpression;
+define_parser!(ParsePrefixExpression, Expression<'state>, |_, state| {
+    let start = state;
+    let (mut state, mut expression) = match ParseParenExpression.parse(state) {
+        Ok(result) => result,
+        Err(ParseAbort::NoMatch) => {
+            let (state, name) = ParseIdentifier.parse(state)?;
+            (state, new_expression(start, state, ExpressionKind::Name(name)))
+        },
+        Err(error) => return Err(error),
+    };
+
+    loop {
+        // Suffixes are optional, so they're parsed quietly to keep them out
+        // of error messages.
+        if let Ok((next_state, _)) = Quiet(ParseSymbol(Symbol::Dot)).parse(state) {
+            let (next_state, name) = ParseName.parse(next_state)?;
+            state = next_state;
+
+            expression = new_expression(start, state, ExpressionKind::FieldAccess(FieldAccess {
+                target: Box::new(expression),
+                name,
+            }));
+        } else if let Ok((next_state, _)) = Quiet(ParseSymbol(Symbol::LeftBracket)).parse(state) {
+            let (next_state, key) = ParseExpression.parse(next_state)?;
+            let (next_state, _) = ParseSymbol(Symbol::RightBracket).parse(next_state)?;
+            state = next_state;
+
+            expression = new_expression(start, state, ExpressionKind::Index(Index {
+                target: Box::new(expression),
+                key: Box::new(key),
+            }));
+        } else if let Ok((next_state, _)) = Quiet(ParseSymbol(Symbol::Colon)).parse(state) {
+            let (next_state, method) = ParseName.parse(next_state)?;
+            let (next_state, arguments) = ParseCallArguments.parse(next_state)?;
+            state = next_state;
+
+            expression = new_expression(start, state, ExpressionKind::FunctionCall(FunctionCall {
+                name_expression: Box::new(expression),
+                method: Some(method),
+                arguments,
+            }));
+        } else if let Ok((next_state, arguments)) = Quiet(ParseCallArguments).parse(state) {
+            if let FunctionArguments::Parenthesized(_) = arguments {
+                let paren = &state.tokens[state.position];
+
+                if paren.start_position.line > state.tokens[state.position - 1].end_position.line {
+                    state.warn(ParseWarning::new(ParseWarningKind::AmbiguousCall, paren));
+                }
+            }
+
+            state = next_state;
+
+            expression = new_expression(start, state, ExpressionKind::FunctionCall(FunctionCall {
+                name_expression: Box::new(expression),
+                method: None,
+                arguments,
+            }));
+        } else {
+            break;
+        }
+    }
+
+    Ok((state, expression))
+});
+
+// args ::= `(´ [explist] `)´ | tableconstructor | String
+struct ParseCallArguments;
+define_parser!(ParseCallArguments, FunctionArguments<'state>, |_, state| {
+    parse_first_of!(state, {
+        ParseParenthesizedArguments => FunctionArguments::Parenthesized,
+        ParseTableLiteral => FunctionArguments::Table,
+        ParseString => FunctionArguments::String,
+    })
+});
+
+struct ParseParenthesizedArguments;
+define_parser!(ParseParenthesizedArguments, Vec<Expression<'state>>, |_, state| {
     let (state, _) = ParseSymbol(Symbol::LeftParen).parse(state)?;
-    let (state, expressions) = DelimitedZeroOrMore(ParseExpression, ParseSymbol(Symbol::Comma), false).parse(state)?;
+    let (state, arguments) = WhileParsing("the arguments of a function call",
+        DelimitedZeroOrMore(ParseExpression, ParseSymbol(Symbol::Comma), false)).parse(state)?;
     let (state, _) = ParseSymbol(Symbol::RightParen).parse(state)?;
 
-    Ok((state, FunctionCall {
-        name_expression: Box::new(Expression::Name(name)),
-        arguments: expressions,
-    }))
+    Ok((state, arguments))
 });
 
+// for Name `=´ exp `,´ exp [`,´ exp] do chunk end
 struct ParseNumericFor;
 define_parser!(ParseNumericFor, NumericFor<'state>, |_, state| {
     let (state, _) = ParseSymbol(Symbol::For).parse(state)?;
-    let (state, var) = ParseIdentifier.parse(state)?;
+    let (state, var) = ParseName.parse(state)?;
     let (state, _) = ParseSymbol(Symbol::Equal).parse(state)?;
     let (state, start) = ParseExpression.parse(state)?;
     let (state, _) = ParseSymbol(Symbol::Comma).parse(state)?;
@@ -309,10 +1075,13 @@ define_parser!(ParseNumericFor, NumericFor<'state>, |_, state| {
     }))
 });
 
+// for namelist in explist do chunk end
+// Shares its `for´ prefix with ParseNumericFor, which is tried first and backs
+// out with NoMatch as soon as it doesn't find `=´ after the first name.
 struct ParseGenericFor;
 define_parser!(ParseGenericFor, GenericFor<'state>, |_, state| {
     let (state, _) = ParseSymbol(Symbol::For).parse(state)?;
-    let (state, vars) = DelimitedOneOrMore(ParseIdentifier, ParseSymbol(Symbol::Comma)).parse(state)?;
+    let (state, vars) = DelimitedOneOrMore(ParseName, ParseSymbol(Symbol::Comma)).parse(state)?;
     let (state, _) = ParseSymbol(Symbol::In).parse(state)?;
     let (state, item_source) = DelimitedOneOrMore(ParseExpression, ParseSymbol(Symbol::Comma)).parse(state)?;
     let (state, _) = ParseSymbol(Symbol::Do).parse(state)?;
@@ -329,20 +1098,13 @@ define_parser!(ParseGenericFor, GenericFor<'state>, |_, state| {
 struct ParseIfStatement;
 define_parser!(ParseIfStatement, IfStatement<'state>, |_, state| {
     let (state, _) = ParseSymbol(Symbol::If).parse(state)?;
-    let (state, condition) = ParseExpression.parse(state)?;
-    let (state, _) = ParseSymbol(Symbol::Then).parse(state)?;
+    let (state, condition) = WhileParsing("the condition of an `if` statement", ParseCondition(Symbol::Then)).parse(state)?;
     let (state, body) = ParseChunk.parse(state)?;
 
     let mut state = state;
     let mut else_if_branches = Vec::new();
-    loop {
-        let (next_state, _) = match ParseSymbol(Symbol::ElseIf).parse(state) {
-            Ok(v) => v,
-            Err(_) => break,
-        };
-
-        let (next_state, condition) = ParseExpression.parse(next_state)?;
-        let (next_state, _) = ParseSymbol(Symbol::Then).parse(next_state)?;
+    while let Ok((next_state, _)) = ParseSymbol(Symbol::ElseIf).parse(state) {
+        let (next_state, condition) = WhileParsing("the condition of an `elseif` branch", ParseCondition(Symbol::Then)).parse(next_state)?;
         let (next_state, body) = ParseChunk.parse(next_state)?;
 
         state = next_state;
@@ -368,11 +1130,30 @@ define_parser!(ParseIfStatement, IfStatement<'state>, |_, state| {
     }))
 });
 
+// exp followed by the keyword that starts the body, like `then´ or `do´
+struct ParseCondition(Symbol);
+define_parser!(ParseCondition, Expression<'state>, |this, state| {
+    let (state, condition) = ParseExpression.parse(state)?;
+    let (state, _) = ParseSymbol(this.0).parse(state)?;
+
+    Ok((state, condition))
+});
+
+struct ParseDoBlock;
+define_parser!(ParseDoBlock, DoBlock<'state>, |_, state| {
+    let (state, _) = ParseSymbol(Symbol::Do).parse(state)?;
+    let (state, body) = ParseChunk.parse(state)?;
+    let (state, _) = ParseSymbol(Symbol::End).parse(state)?;
+
+    Ok((state, DoBlock {
+        body,
+    }))
+});
+
 struct ParseWhileLoop;
 define_parser!(ParseWhileLoop, WhileLoop<'state>, |_, state| {
     let (state, _) = ParseSymbol(Symbol::While).parse(state)?;
-    let (state, condition) = ParseExpression.parse(state)?;
-    let (state, _) = ParseSymbol(Symbol::Do).parse(state)?;
+    let (state, condition) = WhileParsing("the condition of a `while` loop", ParseCondition(Symbol::Do)).parse(state)?;
     let (state, body) = ParseChunk.parse(state)?;
     let (state, _) = ParseSymbol(Symbol::End).parse(state)?;
 
@@ -387,7 +1168,7 @@ define_parser!(ParseRepeatLoop, RepeatLoop<'state>, |_, state| {
     let (state, _) = ParseSymbol(Symbol::Repeat).parse(state)?;
     let (state, body) = ParseChunk.parse(state)?;
     let (state, _) = ParseSymbol(Symbol::Until).parse(state)?;
-    let (state, condition) = ParseExpression.parse(state)?;
+    let (state, condition) = WhileParsing("the condition of a `repeat` loop", ParseExpression).parse(state)?;
 
     Ok((state, RepeatLoop {
         condition,
@@ -395,32 +1176,247 @@ define_parser!(ParseRepeatLoop, RepeatLoop<'state>, |_, state| {
     }))
 });
 
+// function funcname funcbody | local function Name funcbody
 struct ParseFunctionDeclaration;
 define_parser!(ParseFunctionDeclaration, FunctionDeclaration<'state>, |_, state| {
     let (state, local) = Optional(ParseSymbol(Symbol::Local)).parse(state)
         .map(|(state, value)| (state, value.is_some()))?;
 
     let (state, _) = ParseSymbol(Symbol::Function).parse(state)?;
-    let (state, name) = ParseIdentifier.parse(state)?;
+
+    let (state, name) = if local {
+        let (state, name) = ParseName.parse(state)?;
+
+        (state, FunctionName {
+            path: vec![name],
+            method: None,
+        })
+    } else {
+        ParseFunctionName.parse(state)?
+    };
     let (state, _) = ParseSymbol(Symbol::LeftParen).parse(state)?;
-    let (state, parameters) = DelimitedZeroOrMore(ParseIdentifier, ParseSymbol(Symbol::Comma), false).parse(state)?;
+    let (state, (parameters, is_variadic)) = WhileParsing("the parameters of a function", ParseParameterList).parse(state)?;
     let (state, _) = ParseSymbol(Symbol::RightParen).parse(state)?;
+    let (parameters, parameter_types) = parameters.into_iter().unzip();
+
+    let (state, return_types) = match ParseTypeAnnotationColon.parse(state) {
+        Ok((state, _)) => {
+            let (state, types) = WhileParsing("the return types of a function", ParseReturnTypes).parse(state)?;
+            (state, Some(types))
+        },
+        Err(_) => (state, None),
+    };
+
     let (state, body) = ParseChunk.parse(state)?;
     let (state, _) = ParseSymbol(Symbol::End).parse(state)?;
 
     Ok((state, FunctionDeclaration {
         local,
+        is_method: name.method.is_some(),
         name,
         parameters,
+        is_variadic,
         body,
+        parameter_types: annotations(parameter_types),
+        return_types,
+    }))
+});
+
+// funcname ::= Name {`.´ Name} [`:´ Name]
+struct ParseFunctionName;
+define_parser!(ParseFunctionName, FunctionName<'state>, |_, state| {
+    let (state, path) = DelimitedOneOrMore(ParseName, ParseSymbol(Symbol::Dot)).parse(state)?;
+
+    let (state, method) = match ParseSymbol(Symbol::Colon).parse(state) {
+        Ok((state, _)) => {
+            let (state, method) = ParseName.parse(state)?;
+            (state, Some(method))
+        },
+        Err(_) => (state, None),
+    };
+
+    Ok((state, FunctionName {
+        path,
+        method,
+    }))
+});
+
+// parlist ::= namelist [`,´ `...´] | `...´
+// Parsing is allowed to produce an empty list, which covers the optional
+// parlist in funcbody.
+struct ParseParameterList;
+define_parser!(ParseParameterList, (Vec<(Cow<'state, str>, Option<TypeAnnotation<'state>>)>, bool), |_, state| {
+    let mut parameters = Vec::new();
+    let mut state = state;
+
+    loop {
+        if let Ok((next_state, _)) = ParseSymbol(Symbol::Ellipse).parse(state) {
+            if let Some(token) = next_state.peek() {
+                if token.kind == TokenKind::Symbol(Symbol::Comma) {
+                    return Err(ParseAbort::Error(Box::new(error_at(state, ParseErrorKind::VarArgNotLast, token))));
+                }
+            }
+
+            return Ok((next_state, (parameters, true)));
+        }
+
+        let (next_state, name) = match ParseAnnotatedName.parse(state) {
+            Ok(result) => result,
+            Err(ParseAbort::NoMatch) if parameters.is_empty() => break,
+            Err(error) => return Err(error),
+        };
+
+        parameters.push(name);
+        state = next_state;
+
+        match ParseSymbol(Symbol::Comma).parse(state) {
+            Ok((next_state, _)) => state = next_state,
+            Err(_) => break,
+        }
+    }
+
+    Ok((state, (parameters, false)))
+});
+
+// Type annotations are either all there or not at all, so that code without
+// them parses the same either way.
+fn annotations(types: Vec<Option<TypeAnnotation>>) -> Vec<Option<TypeAnnotation>> {
+    if types.iter().any(Option::is_some) {
+        types
+    } else {
+        Vec::new()
+    }
+}
+
+// The `:´ that starts a type annotation, which only matches when type
+// annotations are being parsed.
+struct ParseTypeAnnotationColon;
+define_parser!(ParseTypeAnnotationColon, Symbol, |_, state| {
+    if !state.context.has_type_annotations() {
+        return Err(ParseAbort::NoMatch);
+    }
+
+    ParseSymbol(Symbol::Colon).parse(state)
+});
+
+// Name [`:´ type]
+struct ParseAnnotatedName;
+define_parser!(ParseAnnotatedName, (Cow<'state, str>, Option<TypeAnnotation<'state>>), |_, state| {
+    let (state, name) = ParseName.parse(state)?;
+
+    match ParseTypeAnnotationColon.parse(state) {
+        Ok((state, _)) => {
+            let (state, annotation) = WhileParsing("a type annotation", ParseType).parse(state)?;
+            Ok((state, (name, Some(annotation))))
+        },
+        Err(_) => Ok((state, (name, None))),
+    }
+});
+
+// type ::= optionaltype {`|´ optionaltype}
+struct ParseType;
+define_parser!(ParseType, TypeAnnotation<'state>, |_, state| {
+    let (state, mut types) = DelimitedOneOrMore(ParseOptionalType, ParseSymbol(Symbol::Pipe)).parse(state)?;
+
+    let annotation = if types.len() == 1 {
+        types.remove(0)
+    } else {
+        TypeAnnotation::Union(types)
+    };
+
+    Ok((state, annotation))
+});
+
+// optionaltype ::= simpletype [`?´]
+struct ParseOptionalType;
+define_parser!(ParseOptionalType, TypeAnnotation<'state>, |_, state| {
+    let (state, annotation) = ParseSimpleType.parse(state)?;
+
+    match ParseSymbol(Symbol::QuestionMark).parse(state) {
+        Ok((state, _)) => Ok((state, TypeAnnotation::Optional(Box::new(annotation)))),
+        Err(_) => Ok((state, annotation)),
+    }
+});
+
+// simpletype ::= nil | Name {`.´ Name} | `{´ type `}´ |
+//     `(´ type `)´ | `(´ [typelist] `)´ `->´ returntypes
+struct ParseSimpleType;
+define_parser!(ParseSimpleType, TypeAnnotation<'state>, |_, state| {
+    if let Ok((state, _)) = ParseSymbol(Symbol::Nil).parse(state) {
+        return Ok((state, TypeAnnotation::Nil));
+    }
+
+    if let Ok((state, _)) = ParseSymbol(Symbol::LeftBrace).parse(state) {
+        let (state, item) = ParseType.parse(state)?;
+        let (state, _) = ParseSymbol(Symbol::RightBrace).parse(state)?;
+        return Ok((state, TypeAnnotation::Array(Box::new(item))));
+    }
+
+    if let Ok((state, _)) = ParseSymbol(Symbol::LeftParen).parse(state) {
+        let (state, mut types) = DelimitedZeroOrMore(ParseType, ParseSymbol(Symbol::Comma), false).parse(state)?;
+        let (state, _) = ParseSymbol(Symbol::RightParen).parse(state)?;
+
+        return match ParseSymbol(Symbol::Arrow).parse(state) {
+            Ok((state, _)) => {
+                let (state, returns) = ParseReturnTypes.parse(state)?;
+                Ok((state, TypeAnnotation::Function {
+                    parameters: types,
+                    returns,
+                }))
+            },
+            Err(_) if types.len() == 1 => Ok((state, types.remove(0))),
+            Err(error) => Err(error),
+        };
+    }
+
+    let (state, path) = DelimitedOneOrMore(ParseName, ParseSymbol(Symbol::Dot)).parse(state)?;
+    Ok((state, TypeAnnotation::Named(path)))
+});
+
+// returntypes ::= type | `(´ [typelist] `)´
+struct ParseReturnTypes;
+define_parser!(ParseReturnTypes, Vec<TypeAnnotation<'state>>, |_, state| {
+    if let Ok((state, annotation)) = ParseType.parse(state) {
+        return Ok((state, vec![annotation]));
+    }
+
+    let (state, _) = ParseSymbol(Symbol::LeftParen).parse(state)?;
+    let (state, types) = DelimitedZeroOrMore(ParseType, ParseSymbol(Symbol::Comma), false).parse(state)?;
+    let (state, _) = ParseSymbol(Symbol::RightParen).parse(state)?;
+
+    Ok((state, types))
+});
+
+// goto Name
+struct ParseGoto;
+define_parser!(ParseGoto, Goto<'state>, |_, state| {
+    let (state, _) = ParseSymbol(Symbol::Goto).parse(state)?;
+    let (state, label) = ParseName.parse(state)?;
+
+    Ok((state, Goto {
+        label,
     }))
 });
 
+// label ::= `::´ Name `::´
+struct ParseLabel;
+define_parser!(ParseLabel, Label<'state>, |_, state| {
+    let (state, _) = ParseSymbol(Symbol::DoubleColon).parse(state)?;
+    let (state, name) = ParseName.parse(state)?;
+    let (state, _) = ParseSymbol(Symbol::DoubleColon).parse(state)?;
+
+    Ok((state, Label {
+        name,
+    }))
+});
+
+// The key half of a field, including the `=´ that follows it:
+// `[´ exp `]´ `=´ | Name `=´
 struct ParseTableKey;
 define_parser!(ParseTableKey, TableKey<'state>, |_, state| {
     // First, try parsing an identifier (Lua allows bare literals as table keys)
     let (state, key) = match ParseIdentifier.parse(state) {
-        Ok((state, identifier)) => (state, TableKey::Name(identifier.into())),
+        Ok((state, identifier)) => (state, TableKey::Name(identifier)),
         Err(ParseAbort::NoMatch) => {
             let (state, _) = ParseSymbol(Symbol::LeftBracket).parse(state)?;
             let (state, key) = ParseExpression.parse(state)?;
@@ -428,32 +1424,329 @@ define_parser!(ParseTableKey, TableKey<'state>, |_, state| {
 
             (state, TableKey::Expression(key))
         },
-        Err(ParseAbort::Error(message)) => return Err(ParseAbort::Error(message)),
+        Err(error) => return Err(error),
     };
 
+    // A name that isn't followed by `=´ is the start of a positional value
+    // like `{ a }` or `{ f() }`, so back out and let ParseTableValue have it.
+    let (state, _) = ParseSymbol(Symbol::Equal).parse(state)?;
+
     Ok((state, key))
 });
 
+// field ::= `[´ exp `]´ `=´ exp | Name `=´ exp | exp
 struct ParseTableValue;
 define_parser!(ParseTableValue, (Option<TableKey<'state>>, Expression<'state>), |_, state| {
     let (state, key) = Optional(ParseTableKey).parse(state)?;
-
-    // We only check for '=' if there was a key
-    let state = match key {
-        Some(_) => ParseSymbol(Symbol::Equal).parse(state)?.0,
-        None => state
-    };
-
     let (state, value) = ParseExpression.parse(state)?;
+
     Ok((state, (key, value)))
 });
 
 struct ParseTableLiteral;
 define_parser!(ParseTableLiteral, TableLiteral<'state>, |_, state| {
     let (state, _) = ParseSymbol(Symbol::LeftBrace).parse(state)?;
-    let (state, items) = DelimitedZeroOrMore(ParseTableValue, Or(&[ ParseSymbol(Symbol::Comma), ParseSymbol(Symbol::Semicolon) ]), true).parse(state)?;
+    let (state, items) = WhileParsing("a table constructor",
+        DelimitedZeroOrMore(ParseTableValue, Or(&[ ParseSymbol(Symbol::Comma), ParseSymbol(Symbol::Semicolon) ]), true)).parse(state)?;
     let (state, _) = ParseSymbol(Symbol::RightBrace).parse(state)?;
     Ok((state, TableLiteral {
         items
     }))
-});
\ No newline at end of file
+});
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokenizer::{tokenize, tokenize_luau};
+
+    fn parse_error(source: &str) -> String {
+        let tokens = tokenize(source).unwrap();
+        parse_from_tokens(&tokens).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn ids_start_over_for_each_parse() {
+        let tokens = tokenize("local x = 1").unwrap();
+        let first = parse_from_tokens(&tokens).unwrap();
+        let second = parse_from_tokens(&tokens).unwrap();
+
+        match first.statements[0].kind {
+            StatementKind::LocalAssignment(ref local) => assert_eq!(local.values[0].id.get(), 1),
+            _ => panic!("expected a local assignment"),
+        }
+
+        assert_eq!(first.statements[0].id.get(), 2);
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn source_map() {
+        let source = "while a.b[c] do\n  f(-x + 1, 'y') ; \nend";
+        let tokens = tokenize(source).unwrap();
+        let (chunk, source_map) = parse_from_tokens_with_source_map(&tokens).unwrap();
+
+        let texts = chunk.descendants()
+            .map(|node| source_map.source_text(node.id(), source).unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(texts, vec![
+            source, "a.b[c]", "a.b", "a", "c",
+            "f(-x + 1, 'y')", "f", "-x + 1", "-x", "x", "1", "'y'",
+            ";",
+        ]);
+
+        assert_eq!(source_map.span(NodeId::DUMMY), None);
+    }
+
+    #[test]
+    fn recovery() {
+        let parse = |source: &'static str| {
+            let tokens = tokenize(source).unwrap();
+            let (chunk, errors) = parse_from_tokens_with_recovery(&tokens);
+            let errors = errors.iter().map(|error| error.to_string()).collect::<Vec<_>>();
+
+            (chunk.statements.len(), errors)
+        };
+
+        assert_eq!(parse("local x = = 1\nprint(x)\nlocal y = )\nprint(y)"), (2, vec![
+            "Unexpected `=`, expected an expression while parsing a `local` declaration at line 1, column 11".to_string(),
+            "Unexpected `)`, expected an expression while parsing a `local` declaration at line 3, column 11".to_string(),
+        ]));
+
+        assert_eq!(parse("if x then\n  y =\nend\nz()"), (2, vec![
+            "Unexpected `end`, expected an expression at line 3, column 1".to_string(),
+        ]));
+
+        assert_eq!(parse("end\nx() end\ny()"), (2, vec![
+            "Unexpected `end`, expected a statement at line 1, column 1".to_string(),
+            "Unexpected `end`, expected a statement at line 2, column 5".to_string(),
+        ]));
+
+        assert_eq!(parse("do return 1 print(x) end"), (1, vec![
+            "`return` must be the last statement in its block, but found `print` after it at line 1, column 13".to_string(),
+        ]));
+
+        assert_eq!(parse("local x = 1\nprint(x)"), (2, Vec::new()));
+    }
+
+    #[test]
+    fn max_errors() {
+        let source = "x = = 1\ny = = 2\nprint(x)\nz = = 3\nw = = 4";
+        let tokens = tokenize(source).unwrap();
+        let parse = |max_errors| {
+            let (chunk, errors) = parse_from_tokens_with_options(&tokens, &ParserOptions { max_errors, ..ParserOptions::default() });
+            let lines = errors.iter().map(|error| error.start_position.line).collect::<Vec<_>>();
+
+            (chunk.statements.len(), lines)
+        };
+
+        assert_eq!(parse(None), (1, vec![1, 2, 4, 5]));
+        assert_eq!(parse(Some(4)), (1, vec![1, 2, 4, 5]));
+        assert_eq!(parse(Some(3)), (1, vec![1, 2, 4]));
+        assert_eq!(parse(Some(2)), (0, vec![1, 2]));
+        assert_eq!(parse(Some(1)), (0, vec![1]));
+        assert_eq!(parse(Some(0)), (0, Vec::new()));
+
+        // Errors in nested blocks count too.
+        let tokens = tokenize("while x do\n  y = = 1\n  z = = 2\nend").unwrap();
+        let (_, errors) = parse_from_tokens_with_options(&tokens, &ParserOptions { max_errors: Some(1), ..ParserOptions::default() });
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn structured_errors() {
+        let tokens = tokenize("local x = 1
+local end = 2").unwrap();
+        let error = parse_from_tokens(&tokens).unwrap_err();
+
+        assert_eq!(error.kind, ParseErrorKind::KeywordAsName { keyword: Symbol::End });
+        assert_eq!(error.found, TokenKind::Symbol(Symbol::End));
+        assert_eq!(error.start_position.line, 2);
+        assert_eq!(error.span(), 18..21);
+    }
+
+    #[test]
+    fn error_codes() {
+        let code = |source| {
+            let tokens = tokenize(source).unwrap();
+            parse_from_tokens(&tokens).unwrap_err().code()
+        };
+
+        assert_eq!(code("x = = 1"), "P001");
+        assert_eq!(code("local end"), "P002");
+        assert_eq!(code("function f(..., a) end"), "P003");
+        assert_eq!(code("break\nx()"), "P004");
+    }
+
+    #[test]
+    fn render() {
+        let render = |source| {
+            let tokens = tokenize(source).unwrap();
+            parse_from_tokens(&tokens).unwrap_err().render(source)
+        };
+
+        assert_eq!(render("local x = 1\nlocal y = = 2\nprint(x)"), "\
+Unexpected `=`, expected an expression while parsing a `local` declaration at line 2, column 11
+  |
+2 | local y = = 2
+  |           ^");
+
+        assert_eq!(render("if x then\n\tlocal end = 1\nend"), "\
+Expected a name, but found the keyword `end` while parsing a `local` declaration at line 2, column 8
+  |
+2 | \tlocal end = 1
+  | \t      ^^^");
+
+        assert_eq!(render("f(\"é\" \"x\")"), "\
+Unexpected `\"x\"`, expected `,` or `)` while parsing the arguments of a function call at line 1, column 8
+  |
+1 | f(\"é\" \"x\")
+  |       ^^^");
+
+        assert_eq!(render("x = [[a\nb]] )"), "\
+Unexpected `)`, expected `,` or a statement at line 2, column 5
+  |
+2 | b]] )
+  |     ^");
+
+        assert_eq!(render("x ="), "\
+Unexpected end of file, expected an expression at line 1, column 4
+  |
+1 | x =
+  |    ^");
+    }
+
+    #[test]
+    fn warnings() {
+        let warnings = |source| {
+            let tokens = tokenize(source).unwrap();
+            let (_, warnings) = parse_from_tokens_with_warnings(&tokens).unwrap();
+
+            warnings.iter().map(|warning| warning.to_string()).collect::<Vec<_>>()
+        };
+
+        assert_eq!(warnings("f();;; g();\nh();;"), vec![
+            "Unnecessary `;` at line 1, column 5",
+            "Unnecessary `;` at line 2, column 5",
+        ]);
+        assert_eq!(warnings("local x = f\n(g or h)()"), vec![
+            "Call arguments start on a new line, so this continues the previous expression at line 2, column 1",
+        ]);
+        assert_eq!(warnings("local x = f\n{ 1 }\ng(\n1\n)\n;(f)()"), Vec::<String>::new());
+
+        let tokens = tokenize("a = b\n(c)()").unwrap();
+        let (chunk, warnings) = parse_from_tokens_with_warnings(&tokens).unwrap();
+        assert_eq!(chunk.statements.len(), 1);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].code(), "W002");
+        assert_eq!(warnings[0].span(), 6..7);
+    }
+
+    #[test]
+    fn unexpected_token() {
+        assert_eq!(parse_error("x = 1 )"), "Unexpected `)`, expected `,` or a statement at line 1, column 7");
+        assert_eq!(parse_error("if x then end end"), "Unexpected `end`, expected a statement at line 1, column 15");
+    }
+
+    #[test]
+    fn expected_tokens() {
+        assert_eq!(parse_error("if x end"), "Unexpected `end`, expected `then` while parsing the condition of an `if` statement at line 1, column 6");
+        assert_eq!(parse_error("while x do y = end"), "Unexpected `end`, expected an expression at line 1, column 16");
+        assert_eq!(parse_error("local t = { a b }"), "Unexpected `b`, expected `=`, `,`, `;` or `}` while parsing a table constructor at line 1, column 15");
+        assert_eq!(parse_error("f(1 2)"), "Unexpected `2`, expected `,` or `)` while parsing the arguments of a function call at line 1, column 5");
+        assert_eq!(parse_error("x"), "Unexpected end of file, expected `,` or `=` at line 1, column 2");
+        assert_eq!(parse_error("do x = 1 )"), "Unexpected `)`, expected `,`, a statement or `end` at line 1, column 10");
+    }
+
+    #[test]
+    fn error_context() {
+        assert_eq!(parse_error("while x end"), "Unexpected `end`, expected `do` while parsing the condition of a `while` loop at line 1, column 9");
+        assert_eq!(parse_error("while do end"), "Unexpected `do`, expected an expression while parsing the condition of a `while` loop at line 1, column 7");
+        assert_eq!(parse_error("repeat until"), "Unexpected end of file, expected an expression while parsing the condition of a `repeat` loop at line 1, column 13");
+        assert_eq!(parse_error("if x then elseif then end"), "Unexpected `then`, expected an expression while parsing the condition of an `elseif` branch at line 1, column 18");
+
+        // Only the innermost construct is mentioned, and it's forgotten once
+        // that construct has been parsed.
+        assert_eq!(parse_error("local x = f({ 1, } +)"), "Unexpected `)`, expected an expression while parsing the arguments of a function call at line 1, column 21");
+        assert_eq!(parse_error("while x do y = end"), "Unexpected `end`, expected an expression at line 1, column 16");
+
+        let tokens = tokenize("local t = { f(1 2) }").unwrap();
+        let error = parse_from_tokens(&tokens).unwrap_err();
+        assert_eq!(error.context, vec!["a `local` declaration", "a table constructor", "the arguments of a function call"]);
+    }
+
+    #[test]
+    fn keyword_as_name() {
+        assert_eq!(parse_error("local end = 1"), "Expected a name, but found the keyword `end` while parsing a `local` declaration at line 1, column 7");
+        assert_eq!(parse_error("x = a.then"), "Expected a name, but found the keyword `then` at line 1, column 7");
+        assert_eq!(parse_error("function f(a, nil) end"), "Expected a name, but found the keyword `nil` while parsing the parameters of a function at line 1, column 15");
+        assert_eq!(parse_error("goto while"), "Expected a name, but found the keyword `while` at line 1, column 6");
+    }
+
+    #[test]
+    fn varargs_not_last() {
+        assert_eq!(parse_error("function f(..., a) end"), "`...` must be the last parameter, but found `,` after it while parsing the parameters of a function at line 1, column 15");
+        assert_eq!(parse_error("local function f(a, ..., b) end"), "`...` must be the last parameter, but found `,` after it while parsing the parameters of a function at line 1, column 24");
+    }
+
+    #[test]
+    fn statement_after_laststat() {
+        assert_eq!(parse_error("return 1\nlocal x"), "`return` must be the last statement in its block, but found `local` after it at line 2, column 1");
+        assert_eq!(parse_error("while x do break; x = 1 end"), "`break` must be the last statement in its block, but found `x` after it at line 1, column 19");
+        assert_eq!(parse_error("repeat break; break until x"), "`break` must be the last statement in its block, but found `break` after it at line 1, column 15");
+    }
+
+    #[test]
+    fn type_annotations() {
+        let source = "
+            local a: number?, b = 1
+            local c: A.B | {string} | nil
+            local function f(x: (number, string) -> boolean, y, ...): (number, (string)?)
+            end
+            function t:m(): () -> ()
+            end
+        ";
+        let tokens = tokenize_luau(source);
+        let chunk = parse_from_tokens_with_types(&tokens).unwrap();
+
+        let written = |types: &[Option<TypeAnnotation>]| types.iter()
+            .map(|annotation| annotation.as_ref().map(|annotation| annotation.to_string()))
+            .collect::<Vec<_>>();
+
+        match (&chunk.statements[0].kind, &chunk.statements[1].kind) {
+            (StatementKind::LocalAssignment(a), StatementKind::LocalAssignment(c)) => {
+                assert_eq!(written(&a.types), [Some("number?".to_string()), None]);
+                assert_eq!(written(&c.types), [Some("A.B | {string} | nil".to_string())]);
+            },
+            _ => panic!("expected local assignments"),
+        }
+
+        match (&chunk.statements[2].kind, &chunk.statements[3].kind) {
+            (StatementKind::FunctionDeclaration(f), StatementKind::FunctionDeclaration(m)) => {
+                assert_eq!(written(&f.parameter_types), [Some("(number, string) -> boolean".to_string()), None]);
+                assert!(f.is_variadic);
+                assert_eq!(written(&f.return_types.clone().unwrap().into_iter().map(Some).collect::<Vec<_>>()), [
+                    Some("number".to_string()),
+                    Some("string?".to_string()),
+                ]);
+
+                assert!(m.parameter_types.is_empty());
+                assert_eq!(m.return_types.as_ref().unwrap()[0].to_string(), "() -> ()");
+            },
+            _ => panic!("expected function declarations"),
+        }
+
+        // Without annotations, the tree is the same as usual.
+        let plain = tokenize("local x = 1 function f(a) end").unwrap();
+        assert_eq!(parse_from_tokens_with_types(&plain).unwrap(), parse_from_tokens(&plain).unwrap());
+
+        // Annotations aren't parsed unless they're asked for.
+        assert!(parse_from_tokens(&tokens).is_err());
+
+        let options = ParserOptions {
+            type_annotations: true,
+            ..ParserOptions::default()
+        };
+        assert!(parse_from_tokens_with_options(&tokens, &options).1.is_empty());
+    }
+}
\ No newline at end of file
@@ -1,3 +1,8 @@
+use std::cell::RefCell;
+use std::ops::Range;
+
+use ast::NodeId;
+use parser::{ParseError, ParseWarning};
 use tokenizer::Token;
 
 #[derive(Debug, Clone, PartialEq)]
@@ -7,22 +12,142 @@ pub enum ParseAbort {
     NoMatch,
 
     /// Indicates that the parser was unable to match the input and hit the
-    /// error described by the returned string.
-    #[allow(dead_code)]
-    Error(String)
+    /// given error. It's boxed to keep results small on the happy path.
+    Error(Box<ParseError>)
+}
+
+/// Failures to match that the parser keeps track of for its error messages.
+/// Only failures at the furthest position reached are kept, since those are
+/// the ones closest to the actual mistake.
+#[derive(Debug, Clone, Default)]
+pub struct Expected {
+    /// The token index where the parser got stuck.
+    pub position: usize,
+
+    /// Descriptions of what would have been accepted there, like "`then`" or
+    /// "an expression".
+    pub items: Vec<String>,
+
+    /// The constructs that were being parsed when the parser first got this
+    /// far, outermost first. See [WhileParsing][WhileParsing].
+    pub context: Vec<&'static str>,
+}
+
+/// State shared by every step of a single parse. Unlike ParseState, this isn't
+/// rewound when a parser backs out.
+#[derive(Debug, Default)]
+pub struct ParseContext {
+    expected: RefCell<Expected>,
+    frames: RefCell<Vec<&'static str>>,
+
+    /// Whether parsers should report errors here and keep going instead of
+    /// failing.
+    recovering: bool,
+    max_errors: Option<usize>,
+
+    /// Whether Luau's type annotations are parsed, like the `: number` in
+    /// `local x: number`.
+    type_annotations: bool,
+
+    errors: RefCell<Vec<ParseError>>,
+    warnings: RefCell<Vec<ParseWarning>>,
+
+    /// The bytes of source each node came from, indexed by ID - 1. Every ID
+    /// handed out so far has one.
+    spans: RefCell<Vec<Range<usize>>>,
+}
+
+impl ParseContext {
+    pub fn recovering(max_errors: Option<usize>) -> ParseContext {
+        ParseContext {
+            recovering: true,
+            max_errors,
+            ..ParseContext::default()
+        }
+    }
+
+    pub fn with_type_annotations(self) -> ParseContext {
+        ParseContext {
+            type_annotations: true,
+            ..self
+        }
+    }
+
+    pub fn is_recovering(&self) -> bool {
+        self.recovering
+    }
+
+    pub fn has_type_annotations(&self) -> bool {
+        self.type_annotations
+    }
+
+    pub fn expected(&self) -> Expected {
+        self.expected.borrow().clone()
+    }
+
+    /// Descriptions of the constructs currently being parsed, outermost
+    /// first.
+    pub fn frames(&self) -> Vec<&'static str> {
+        self.frames.borrow().clone()
+    }
+
+    /// Whether we've reported as many errors as we're allowed to, so parsing
+    /// should wrap up.
+    pub fn is_full(&self) -> bool {
+        self.max_errors.is_some_and(|max_errors| self.errors.borrow().len() >= max_errors)
+    }
+
+    /// Records an error so that the parser can recover from it. What was
+    /// expected before the error is forgotten, since it doesn't apply any more.
+    /// Errors past the maximum are dropped. If we aren't recovering, this
+    /// fails with the error instead.
+    pub fn report(&self, error: ParseError) -> Result<(), ParseAbort> {
+        if !self.recovering {
+            return Err(ParseAbort::Error(Box::new(error)));
+        }
+
+        if !self.is_full() {
+            self.errors.borrow_mut().push(error);
+        }
+
+        *self.expected.borrow_mut() = Expected::default();
+        Ok(())
+    }
+
+    /// A fresh ID for a node. IDs start at 1 for every parse. Parsers that
+    /// back out don't give theirs back, so there can be gaps.
+    pub fn next_id(&self, span: Range<usize>) -> NodeId {
+        let mut spans = self.spans.borrow_mut();
+        spans.push(span);
+        NodeId::new(spans.len() as u32).expect("IDs start at 1")
+    }
+
+    pub fn into_errors(self) -> Vec<ParseError> {
+        self.errors.into_inner()
+    }
+
+    pub fn into_warnings(self) -> Vec<ParseWarning> {
+        self.warnings.into_inner()
+    }
+
+    pub fn into_spans(self) -> Vec<Range<usize>> {
+        self.spans.into_inner()
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
-pub struct ParseState<'a> {
+pub struct ParseState<'a, 'c> {
     pub tokens: &'a [Token<'a>],
     pub position: usize,
+    pub context: &'c ParseContext,
 }
 
-impl<'a> ParseState<'a> {
-    pub fn new(tokens: &'a [Token]) -> ParseState<'a> {
+impl<'a, 'c> ParseState<'a, 'c> {
+    pub fn new(tokens: &'a [Token], context: &'c ParseContext) -> ParseState<'a, 'c> {
         ParseState {
             tokens,
             position: 0,
+            context,
         }
     }
 
@@ -30,10 +155,41 @@ impl<'a> ParseState<'a> {
         self.tokens.get(self.position)
     }
 
-    pub fn advance(&self, amount: usize) -> ParseState<'a> {
+    pub fn advance(&self, amount: usize) -> ParseState<'a, 'c> {
         ParseState {
             tokens: self.tokens,
             position: self.position + amount,
+            context: self.context,
+        }
+    }
+
+    /// Records something suspicious that doesn't stop the parse. Parsers can
+    /// back out of a construct and then parse it again, so a warning that's
+    /// already been recorded for the same spot is ignored.
+    pub fn warn(&self, warning: ParseWarning) {
+        let mut warnings = self.context.warnings.borrow_mut();
+
+        if !warnings.contains(&warning) {
+            warnings.push(warning);
+        }
+    }
+
+    /// Records that `description` would have been accepted at this position.
+    pub fn expect<S: Into<String>>(&self, description: S) {
+        let mut expected = self.context.expected.borrow_mut();
+
+        if self.position > expected.position {
+            expected.position = self.position;
+            expected.items.clear();
+            expected.context = self.context.frames();
+        }
+
+        if self.position == expected.position {
+            let description = description.into();
+
+            if !expected.items.contains(&description) {
+                expected.items.push(description);
+            }
         }
     }
 }
@@ -41,11 +197,12 @@ impl<'a> ParseState<'a> {
 pub trait Parser<'a> {
     type Item: 'a;
 
+    #[allow(dead_code)]
     fn item_name(&self) -> String {
         "UNNAMED_ITEM".to_string()
     }
 
-    fn parse(&self, state: ParseState<'a>) -> Result<(ParseState<'a>, Self::Item), ParseAbort>;
+    fn parse<'c>(&self, state: ParseState<'a, 'c>) -> Result<(ParseState<'a, 'c>, Self::Item), ParseAbort>;
 }
 
 #[macro_export]
@@ -67,12 +224,13 @@ macro_rules! parse_first_of {
 
 #[macro_export]
 macro_rules! define_parser {
-    ($name: ty, $result_type: ty, $body: expr) => {
+    ($name: ty, $result_type: ty, |$this: tt, $state: ident| $body: expr) => {
         impl<'state> Parser<'state> for $name {
             type Item = $result_type;
 
-            fn parse(&self, state: ParseState<'state>) -> Result<(ParseState<'state>, Self::Item), ParseAbort> {
-                $body(self, state)
+            fn parse<'context>(&self, $state: ParseState<'state, 'context>) -> Result<(ParseState<'state, 'context>, Self::Item), ParseAbort> {
+                let $this = self;
+                $body
             }
         }
     }
@@ -87,7 +245,7 @@ impl<'a, ItemParser: Parser<'a>> Parser<'a> for ZeroOrMore<ItemParser> {
         format!("zero or more {}", self.0.item_name())
     }
 
-    fn parse(&self, state: ParseState<'a>) -> Result<(ParseState<'a>, Self::Item), ParseAbort> {
+    fn parse<'c>(&self, state: ParseState<'a, 'c>) -> Result<(ParseState<'a, 'c>, Self::Item), ParseAbort> {
         let mut values = Vec::new();
         let mut state = state;
 
@@ -115,7 +273,7 @@ impl<'a, ItemParser: Parser<'a>, DelimiterParser: Parser<'a>> Parser<'a> for Del
         format!("one or more {} separated by {}", self.0.item_name(), self.1.item_name())
     }
 
-    fn parse(&self, state: ParseState<'a>) -> Result<(ParseState<'a>, Self::Item), ParseAbort> {
+    fn parse<'c>(&self, state: ParseState<'a, 'c>) -> Result<(ParseState<'a, 'c>, Self::Item), ParseAbort> {
         let mut values = Vec::new();
 
         let (mut state, value) = self.0.parse(state)?;
@@ -148,7 +306,7 @@ impl<'a, ItemParser: Parser<'a>, DelimiterParser: Parser<'a>> Parser<'a> for Del
         format!("zero or more {} separated by {}", self.0.item_name(), self.1.item_name())
     }
 
-    fn parse(&self, state: ParseState<'a>) -> Result<(ParseState<'a>, Self::Item), ParseAbort> {
+    fn parse<'c>(&self, state: ParseState<'a, 'c>) -> Result<(ParseState<'a, 'c>, Self::Item), ParseAbort> {
         let mut values = Vec::new();
 
         let mut state = match self.0.parse(state) {
@@ -198,7 +356,7 @@ impl<'a, ItemParser: Parser<'a>> Parser<'a> for Optional<ItemParser> {
         format!("optional {}", self.0.item_name())
     }
 
-    fn parse(&self, state: ParseState<'a>) -> Result<(ParseState<'a>, Self::Item), ParseAbort> {
+    fn parse<'c>(&self, state: ParseState<'a, 'c>) -> Result<(ParseState<'a, 'c>, Self::Item), ParseAbort> {
         match self.0.parse(state) {
             Ok((new_state, matched_value)) => Ok((new_state, Some(matched_value))),
             Err(ParseAbort::NoMatch) => Ok((state, None)),
@@ -212,7 +370,7 @@ pub struct Or<'a, InnerParser: 'a>(pub &'a [InnerParser]);
 impl<'a, InnerParser: Parser<'a>> Parser<'a> for Or<'a, InnerParser> {
     type Item = InnerParser::Item;
 
-    fn parse(&self, state: ParseState<'a>) -> Result<(ParseState<'a>, Self::Item), ParseAbort> {
+    fn parse<'c>(&self, state: ParseState<'a, 'c>) -> Result<(ParseState<'a, 'c>, Self::Item), ParseAbort> {
         for parser in self.0 {
             match parser.parse(state) {
                 Ok((new_state, matched_value)) => return Ok((new_state, matched_value)),
@@ -223,4 +381,81 @@ impl<'a, InnerParser: Parser<'a>> Parser<'a> for Or<'a, InnerParser> {
 
         Err(ParseAbort::NoMatch)
     }
-}
\ No newline at end of file
+}
+
+/// Names a construct for error messages. If the inner parser fails without
+/// getting past its first token, whatever it expected there is replaced with
+/// this description, so errors say "expected an expression" instead of
+/// listing every token that can start one.
+pub struct Labeled<InnerParser>(pub &'static str, pub InnerParser);
+
+impl<'a, ItemParser: Parser<'a>> Parser<'a> for Labeled<ItemParser> {
+    type Item = ItemParser::Item;
+
+    fn item_name(&self) -> String {
+        self.0.to_string()
+    }
+
+    fn parse<'c>(&self, state: ParseState<'a, 'c>) -> Result<(ParseState<'a, 'c>, Self::Item), ParseAbort> {
+        let before = state.context.expected();
+        let result = self.1.parse(state);
+
+        if let Err(ParseAbort::NoMatch) = result {
+            if state.context.expected.borrow().position == state.position {
+                *state.context.expected.borrow_mut() = before;
+                state.expect(self.0);
+            }
+        }
+
+        result
+    }
+}
+
+/// Like Labeled, but doesn't mention the construct at all when it fails at
+/// its first token. Useful for optional things like operators and suffixes,
+/// which would otherwise show up in every error message.
+pub struct Quiet<InnerParser>(pub InnerParser);
+
+impl<'a, ItemParser: Parser<'a>> Parser<'a> for Quiet<ItemParser> {
+    type Item = ItemParser::Item;
+
+    fn item_name(&self) -> String {
+        self.0.item_name()
+    }
+
+    fn parse<'c>(&self, state: ParseState<'a, 'c>) -> Result<(ParseState<'a, 'c>, Self::Item), ParseAbort> {
+        let before = state.context.expected();
+        let result = self.0.parse(state);
+
+        if let Err(ParseAbort::NoMatch) = result {
+            if state.context.expected.borrow().position == state.position {
+                *state.context.expected.borrow_mut() = before;
+            }
+        }
+
+        result
+    }
+}
+
+/// Marks the inner parser as parsing the given construct, so that errors
+/// inside it can say "while parsing the condition of a `while` loop". This
+/// should only wrap things that are definitely that construct, like what
+/// comes after a keyword, or backtracking parsers will leave misleading
+/// frames behind.
+pub struct WhileParsing<InnerParser>(pub &'static str, pub InnerParser);
+
+impl<'a, ItemParser: Parser<'a>> Parser<'a> for WhileParsing<ItemParser> {
+    type Item = ItemParser::Item;
+
+    fn item_name(&self) -> String {
+        self.1.item_name()
+    }
+
+    fn parse<'c>(&self, state: ParseState<'a, 'c>) -> Result<(ParseState<'a, 'c>, Self::Item), ParseAbort> {
+        state.context.frames.borrow_mut().push(self.0);
+        let result = self.1.parse(state);
+        state.context.frames.borrow_mut().pop();
+
+        result
+    }
+}
@@ -0,0 +1,57 @@
+//! What [lua!][::lua] expands to. This isn't meant to be used directly.
+
+use ast::*;
+use parser::parse_from_tokens;
+use tokenizer::tokenize;
+use visit::{self, VisitorMut};
+
+// The macro's stand-in for the Nth splice.
+const SPLICE_PREFIX: &str = "__mab_splice_";
+
+pub fn expand<'a>(source: &str, splices: Vec<Expression<'a>>) -> Chunk<'a> {
+    let tokens = tokenize(source)
+        .unwrap_or_else(|error| panic!("lua! couldn't tokenize `{}`: {}", source, error));
+    let chunk = parse_from_tokens(&tokens)
+        .unwrap_or_else(|error| panic!("lua! couldn't parse `{}`: {}", source, error));
+
+    let mut chunk: Chunk<'a> = chunk.into_owned();
+    let mut expander = Expander {
+        splices: splices.into_iter().map(Some).collect(),
+    };
+
+    expander.visit_chunk_mut(&mut chunk);
+    chunk
+}
+
+struct Expander<'a> {
+    splices: Vec<Option<Expression<'a>>>,
+}
+
+impl<'a> Expander<'a> {
+    fn take_splice(&mut self, expression: &Expression) -> Option<Expression<'a>> {
+        let index = match expression.kind {
+            ExpressionKind::Name(ref name) => name.strip_prefix(SPLICE_PREFIX)?.parse::<usize>().ok()?,
+            _ => return None,
+        };
+
+        self.splices.get_mut(index).and_then(Option::take)
+    }
+}
+
+impl<'a> VisitorMut<'a> for Expander<'a> {
+    fn visit_statement_mut(&mut self, statement: &mut Statement<'a>) {
+        statement.id = NodeId::DUMMY;
+        visit::walk_statement_mut(self, statement);
+    }
+
+    fn visit_expression_mut(&mut self, expression: &mut Expression<'a>) {
+        match self.take_splice(expression) {
+            // Spliced expressions are left exactly as they were given.
+            Some(splice) => *expression = splice,
+            None => {
+                expression.id = NodeId::DUMMY;
+                visit::walk_expression_mut(self, expression);
+            },
+        }
+    }
+}
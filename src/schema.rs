@@ -0,0 +1,553 @@
+//! A stable serialized form of the AST, for other tools to read.
+//!
+//! The AST types also implement `Serialize`, but that output follows their
+//! Rust definitions, so it changes whenever they do. [Document][Document]
+//! writes the format described here instead, which only changes along with
+//! [SCHEMA_VERSION][SCHEMA_VERSION]:
+//!
+//! ```
+//! let tokens = mab::tokenize("local x = 1").unwrap();
+//! let chunk = mab::parse_from_tokens(&tokens).unwrap();
+//! let document = mab::schema::Document::new(&chunk);
+//!
+//! // Any serde serializer works.
+//! # extern crate serde_json;
+//! let json = serde_json::to_string(&document).unwrap();
+//! assert!(json.starts_with("{\"schema_version\":2,\"chunk\":["));
+//! ```
+//!
+//! # Version 2
+//!
+//! Version 2 added type annotations, in the `types` of `Local` statements and
+//! the `parameter_types` and `return_types` of `Function` statements.
+//! Everything else is the same as in version 1.
+//!
+//! The top level is an object with `schema_version` and `chunk`, which is a
+//! block. A block is a list of statements.
+//!
+//! Statements and expressions are objects with an `id` (see
+//! [NodeId][::ast::NodeId]), a `type`, and more fields depending on the type.
+//! Optional fields are always present, and `null` when missing.
+//!
+//! | Statement `type`      | Fields |
+//! |-----------------------|--------|
+//! | `Assignment`          | `targets` and `values`, lists of expressions |
+//! | `Local`               | `names`, a list of strings; `types`, a list with an optional type for each name; and `values` |
+//! | `Call`                | Same as the `Call` expression |
+//! | `NumericFor`          | `var`, `start`, `end`, `step` (optional), and `body` |
+//! | `GenericFor`          | `vars`, `values`, and `body` |
+//! | `If`                  | `condition`, `body`, `elseif` (a list of objects with `condition` and `body`), and `else` (an optional block) |
+//! | `Do`                  | `body` |
+//! | `While`               | `condition` and `body` |
+//! | `Repeat`              | `body` and `condition` |
+//! | `Function`            | `path`, a list of names; `method`, an optional name; `parameters`; `parameter_types`, a list with an optional type for each parameter; `return_types`, an optional list of types; `is_variadic`; `local`; and `body` |
+//! | `Goto`                | `label` |
+//! | `Label`               | `name` |
+//! | `Return`              | `values` |
+//! | `Break`, `Empty`      | None |
+//!
+//! | Expression `type`     | Fields |
+//! |-----------------------|--------|
+//! | `Nil`, `VarArg`       | None |
+//! | `Boolean`             | `value` |
+//! | `Number`              | `value`, the number as written, like `"0x1p4"` |
+//! | `String`              | `quote` (`"double"`, `"single"`, or `"long"`), `raw` (the text between the delimiters), `depth` (the number of `=` in a long string's brackets, otherwise 0), and `value` (the decoded contents, or a list of bytes if they aren't valid UTF-8) |
+//! | `Table`               | `items`, a list of table items |
+//! | `Call`                | `function`, `method` (an optional name), and `arguments` |
+//! | `Name`                | `name` |
+//! | `Field`               | `target` and `name` |
+//! | `Index`               | `target` and `key` |
+//! | `Parenthesized`       | `expression` |
+//! | `Unary`               | `operator`, written as in Lua, and `argument` |
+//! | `Binary`              | `operator`, written as in Lua, `left`, and `right` |
+//!
+//! Call `arguments` are an object with a `type` of `Parenthesized` (with
+//! `values`), `String` (with `string`, a `String` expression without an `id`),
+//! or `Table` (with `items`).
+//!
+//! Table items are objects with a `type` of `Positional` (with `value`),
+//! `Named` (with `name` and `value`), or `Keyed` (with `key` and `value`).
+//!
+//! Types, from [type annotations][::parser::ParserOptions::type_annotations],
+//! are objects with a `type` of `Nil`, `Named` (with `path`, a list of
+//! names), `Optional` (with `inner`), `Union` (with `types`), `Array` (with
+//! `element`), or `Function` (with `parameters` and `returns`, lists of
+//! types).
+
+use std::str;
+
+use serde::ser::{Serialize, SerializeMap, SerializeSeq, Serializer};
+
+use ast::*;
+use tokenizer::StringLiteral;
+
+/// The version of the format written by [Document][Document]. This goes up
+/// whenever the format changes in a way that could break readers.
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// A chunk, serialized in the format described in the [module docs][self].
+pub struct Document<'c, 'a: 'c> {
+    chunk: &'c Chunk<'a>,
+}
+
+impl<'c, 'a> Document<'c, 'a> {
+    pub fn new(chunk: &'c Chunk<'a>) -> Document<'c, 'a> {
+        Document {
+            chunk,
+        }
+    }
+}
+
+impl<'c, 'a> Serialize for Document<'c, 'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(2))?;
+        map.serialize_entry("schema_version", &SCHEMA_VERSION)?;
+        map.serialize_entry("chunk", &Block(self.chunk))?;
+        map.end()
+    }
+}
+
+// Each of these wraps a node to serialize it in the documented format rather
+// than the derived one.
+
+struct Block<'c, 'a: 'c>(&'c Chunk<'a>);
+struct Stmt<'c, 'a: 'c>(&'c Statement<'a>);
+struct Expr<'c, 'a: 'c>(&'c Expression<'a>);
+struct Exprs<'c, 'a: 'c>(&'c [Expression<'a>]);
+struct Str<'c, 'a: 'c>(&'c StringLiteral<'a>);
+struct Args<'c, 'a: 'c>(&'c FunctionArguments<'a>);
+struct TableItems<'c, 'a: 'c>(&'c TableLiteral<'a>);
+struct ElseIfs<'c, 'a: 'c>(&'c [(Expression<'a>, Chunk<'a>)]);
+struct Type<'c, 'a: 'c>(&'c TypeAnnotation<'a>);
+struct Types<'c, 'a: 'c>(&'c [TypeAnnotation<'a>]);
+
+// The annotation for each of `count` names, which are all missing when the
+// chunk wasn't parsed with type annotations.
+struct Annotations<'c, 'a: 'c>(&'c [Option<TypeAnnotation<'a>>], usize);
+
+impl<'c, 'a> Serialize for Block<'c, 'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.0.statements.iter().map(Stmt))
+    }
+}
+
+impl<'c, 'a> Serialize for Exprs<'c, 'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.0.iter().map(Expr))
+    }
+}
+
+fn serialize_call<M: SerializeMap>(map: &mut M, call: &FunctionCall) -> Result<(), M::Error> {
+    map.serialize_entry("type", "Call")?;
+    map.serialize_entry("function", &Expr(&call.name_expression))?;
+    map.serialize_entry("method", &call.method)?;
+    map.serialize_entry("arguments", &Args(&call.arguments))
+}
+
+impl<'c, 'a> Serialize for Stmt<'c, 'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("id", &self.0.id)?;
+
+        match self.0.kind {
+            StatementKind::Assignment(ref assignment) => {
+                map.serialize_entry("type", "Assignment")?;
+                map.serialize_entry("targets", &Exprs(&assignment.targets))?;
+                map.serialize_entry("values", &Exprs(&assignment.values))?;
+            },
+            StatementKind::LocalAssignment(ref local) => {
+                map.serialize_entry("type", "Local")?;
+                map.serialize_entry("names", &local.names)?;
+                map.serialize_entry("types", &Annotations(&local.types, local.names.len()))?;
+                map.serialize_entry("values", &Exprs(&local.values))?;
+            },
+            StatementKind::FunctionCall(ref call) => serialize_call(&mut map, call)?,
+            StatementKind::NumericFor(ref numeric_for) => {
+                map.serialize_entry("type", "NumericFor")?;
+                map.serialize_entry("var", &numeric_for.var)?;
+                map.serialize_entry("start", &Expr(&numeric_for.start))?;
+                map.serialize_entry("end", &Expr(&numeric_for.end))?;
+                map.serialize_entry("step", &numeric_for.step.as_ref().map(Expr))?;
+                map.serialize_entry("body", &Block(&numeric_for.body))?;
+            },
+            StatementKind::GenericFor(ref generic_for) => {
+                map.serialize_entry("type", "GenericFor")?;
+                map.serialize_entry("vars", &generic_for.vars)?;
+                map.serialize_entry("values", &Exprs(&generic_for.item_source))?;
+                map.serialize_entry("body", &Block(&generic_for.body))?;
+            },
+            StatementKind::IfStatement(ref if_statement) => {
+                map.serialize_entry("type", "If")?;
+                map.serialize_entry("condition", &Expr(&if_statement.condition))?;
+                map.serialize_entry("body", &Block(&if_statement.body))?;
+                map.serialize_entry("elseif", &ElseIfs(&if_statement.else_if_branches))?;
+                map.serialize_entry("else", &if_statement.else_branch.as_ref().map(Block))?;
+            },
+            StatementKind::DoBlock(ref do_block) => {
+                map.serialize_entry("type", "Do")?;
+                map.serialize_entry("body", &Block(&do_block.body))?;
+            },
+            StatementKind::WhileLoop(ref while_loop) => {
+                map.serialize_entry("type", "While")?;
+                map.serialize_entry("condition", &Expr(&while_loop.condition))?;
+                map.serialize_entry("body", &Block(&while_loop.body))?;
+            },
+            StatementKind::RepeatLoop(ref repeat_loop) => {
+                map.serialize_entry("type", "Repeat")?;
+                map.serialize_entry("body", &Block(&repeat_loop.body))?;
+                map.serialize_entry("condition", &Expr(&repeat_loop.condition))?;
+            },
+            StatementKind::FunctionDeclaration(ref declaration) => {
+                map.serialize_entry("type", "Function")?;
+                map.serialize_entry("path", &declaration.name.path)?;
+                map.serialize_entry("method", &declaration.name.method)?;
+                map.serialize_entry("parameters", &declaration.parameters)?;
+                map.serialize_entry("parameter_types", &Annotations(&declaration.parameter_types, declaration.parameters.len()))?;
+                map.serialize_entry("return_types", &declaration.return_types.as_ref().map(|types| Types(types)))?;
+                map.serialize_entry("is_variadic", &declaration.is_variadic)?;
+                map.serialize_entry("local", &declaration.local)?;
+                map.serialize_entry("body", &Block(&declaration.body))?;
+            },
+            StatementKind::Goto(ref goto) => {
+                map.serialize_entry("type", "Goto")?;
+                map.serialize_entry("label", &goto.label)?;
+            },
+            StatementKind::Label(ref label) => {
+                map.serialize_entry("type", "Label")?;
+                map.serialize_entry("name", &label.name)?;
+            },
+            StatementKind::Return(ref return_statement) => {
+                map.serialize_entry("type", "Return")?;
+                map.serialize_entry("values", &Exprs(&return_statement.values))?;
+            },
+            StatementKind::Break => map.serialize_entry("type", "Break")?,
+            StatementKind::Empty => map.serialize_entry("type", "Empty")?,
+        }
+
+        map.end()
+    }
+}
+
+impl<'c, 'a> Serialize for Expr<'c, 'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("id", &self.0.id)?;
+
+        match self.0.kind {
+            ExpressionKind::Nil => map.serialize_entry("type", "Nil")?,
+            ExpressionKind::Bool(value) => {
+                map.serialize_entry("type", "Boolean")?;
+                map.serialize_entry("value", &value)?;
+            },
+            ExpressionKind::Number(ref value) => {
+                map.serialize_entry("type", "Number")?;
+                map.serialize_entry("value", value)?;
+            },
+            ExpressionKind::String(ref literal) => {
+                map.serialize_entry("type", "String")?;
+                serialize_string(&mut map, literal)?;
+            },
+            ExpressionKind::VarArg => map.serialize_entry("type", "VarArg")?,
+            ExpressionKind::Table(ref table) => {
+                map.serialize_entry("type", "Table")?;
+                map.serialize_entry("items", &TableItems(table))?;
+            },
+            ExpressionKind::FunctionCall(ref call) => serialize_call(&mut map, call)?,
+            ExpressionKind::Name(ref name) => {
+                map.serialize_entry("type", "Name")?;
+                map.serialize_entry("name", name)?;
+            },
+            ExpressionKind::FieldAccess(ref field_access) => {
+                map.serialize_entry("type", "Field")?;
+                map.serialize_entry("target", &Expr(&field_access.target))?;
+                map.serialize_entry("name", &field_access.name)?;
+            },
+            ExpressionKind::Index(ref index) => {
+                map.serialize_entry("type", "Index")?;
+                map.serialize_entry("target", &Expr(&index.target))?;
+                map.serialize_entry("key", &Expr(&index.key))?;
+            },
+            ExpressionKind::ParenExpression(ref expression) => {
+                map.serialize_entry("type", "Parenthesized")?;
+                map.serialize_entry("expression", &Expr(expression))?;
+            },
+            ExpressionKind::UnaryOp(ref unary_op) => {
+                map.serialize_entry("type", "Unary")?;
+                map.serialize_entry("operator", unary_op.operator.to_str())?;
+                map.serialize_entry("argument", &Expr(&unary_op.argument))?;
+            },
+            ExpressionKind::BinaryOp(ref binary_op) => {
+                map.serialize_entry("type", "Binary")?;
+                map.serialize_entry("operator", binary_op.operator.to_str())?;
+                map.serialize_entry("left", &Expr(&binary_op.left))?;
+                map.serialize_entry("right", &Expr(&binary_op.right))?;
+            },
+        }
+
+        map.end()
+    }
+}
+
+fn serialize_string<M: SerializeMap>(map: &mut M, literal: &StringLiteral) -> Result<(), M::Error> {
+    let (quote, raw, depth) = match *literal {
+        StringLiteral::DoubleQuote { ref raw_content, .. } => ("double", raw_content, 0),
+        StringLiteral::SingleQuote { ref raw_content, .. } => ("single", raw_content, 0),
+        StringLiteral::LongForm { ref raw_content, depth } => ("long", raw_content, depth),
+    };
+
+    map.serialize_entry("quote", quote)?;
+    map.serialize_entry("raw", raw)?;
+    map.serialize_entry("depth", &depth)?;
+
+    match str::from_utf8(literal.value()) {
+        Ok(text) => map.serialize_entry("value", text),
+        Err(_) => map.serialize_entry("value", literal.value()),
+    }
+}
+
+impl<'c, 'a> Serialize for Str<'c, 'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("type", "String")?;
+        serialize_string(&mut map, self.0)?;
+        map.end()
+    }
+}
+
+impl<'c, 'a> Serialize for Args<'c, 'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(2))?;
+
+        match *self.0 {
+            FunctionArguments::Parenthesized(ref values) => {
+                map.serialize_entry("type", "Parenthesized")?;
+                map.serialize_entry("values", &Exprs(values))?;
+            },
+            FunctionArguments::String(ref literal) => {
+                map.serialize_entry("type", "String")?;
+                map.serialize_entry("string", &Str(literal))?;
+            },
+            FunctionArguments::Table(ref table) => {
+                map.serialize_entry("type", "Table")?;
+                map.serialize_entry("items", &TableItems(table))?;
+            },
+        }
+
+        map.end()
+    }
+}
+
+impl<'c, 'a> Serialize for TableItems<'c, 'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.0.items.len()))?;
+
+        for (key, value) in &self.0.items {
+            seq.serialize_element(&TableItem(key.as_ref(), value))?;
+        }
+
+        seq.end()
+    }
+}
+
+struct TableItem<'c, 'a: 'c>(Option<&'c TableKey<'a>>, &'c Expression<'a>);
+
+impl<'c, 'a> Serialize for TableItem<'c, 'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+
+        match self.0 {
+            None => map.serialize_entry("type", "Positional")?,
+            Some(TableKey::Name(name)) => {
+                map.serialize_entry("type", "Named")?;
+                map.serialize_entry("name", name)?;
+            },
+            Some(TableKey::Expression(key)) => {
+                map.serialize_entry("type", "Keyed")?;
+                map.serialize_entry("key", &Expr(key))?;
+            },
+        }
+
+        map.serialize_entry("value", &Expr(self.1))?;
+        map.end()
+    }
+}
+
+impl<'c, 'a> Serialize for ElseIfs<'c, 'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.0.len()))?;
+
+        for (condition, body) in self.0 {
+            seq.serialize_element(&ElseIf(condition, body))?;
+        }
+
+        seq.end()
+    }
+}
+
+struct ElseIf<'c, 'a: 'c>(&'c Expression<'a>, &'c Chunk<'a>);
+
+impl<'c, 'a> Serialize for ElseIf<'c, 'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(2))?;
+        map.serialize_entry("condition", &Expr(self.0))?;
+        map.serialize_entry("body", &Block(self.1))?;
+        map.end()
+    }
+}
+
+impl<'c, 'a> Serialize for Type<'c, 'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+
+        match *self.0 {
+            TypeAnnotation::Nil => map.serialize_entry("type", "Nil")?,
+            TypeAnnotation::Named(ref path) => {
+                map.serialize_entry("type", "Named")?;
+                map.serialize_entry("path", path)?;
+            },
+            TypeAnnotation::Optional(ref inner) => {
+                map.serialize_entry("type", "Optional")?;
+                map.serialize_entry("inner", &Type(inner))?;
+            },
+            TypeAnnotation::Union(ref types) => {
+                map.serialize_entry("type", "Union")?;
+                map.serialize_entry("types", &Types(types))?;
+            },
+            TypeAnnotation::Array(ref element) => {
+                map.serialize_entry("type", "Array")?;
+                map.serialize_entry("element", &Type(element))?;
+            },
+            TypeAnnotation::Function { ref parameters, ref returns } => {
+                map.serialize_entry("type", "Function")?;
+                map.serialize_entry("parameters", &Types(parameters))?;
+                map.serialize_entry("returns", &Types(returns))?;
+            },
+        }
+
+        map.end()
+    }
+}
+
+impl<'c, 'a> Serialize for Types<'c, 'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.0.iter().map(Type))
+    }
+}
+
+impl<'c, 'a> Serialize for Annotations<'c, 'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let Annotations(annotations, count) = *self;
+        serializer.collect_seq((0..count).map(|index| annotations.get(index).and_then(Option::as_ref).map(Type)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate serde_json;
+
+    use self::serde_json::{json, Value};
+
+    use super::*;
+    use tokenizer::{tokenize, tokenize_luau};
+    use parser::{parse_from_tokens, parse_from_tokens_with_types};
+
+    fn document(source: &str) -> Value {
+        let tokens = tokenize(source).unwrap();
+        let chunk = parse_from_tokens(&tokens).unwrap();
+        serde_json::to_value(Document::new(&chunk)).unwrap()
+    }
+
+    #[test]
+    fn statements_and_expressions() {
+        assert_eq!(document("local x = -a.b + 'hi'"), json!({
+            "schema_version": 2,
+            "chunk": [{
+                "id": 6,
+                "type": "Local",
+                "names": ["x"],
+                "types": [null],
+                "values": [{
+                    "id": 5,
+                    "type": "Binary",
+                    "operator": "+",
+                    "left": {
+                        "id": 3,
+                        "type": "Unary",
+                        "operator": "-",
+                        "argument": {
+                            "id": 2,
+                            "type": "Field",
+                            "target": { "id": 1, "type": "Name", "name": "a" },
+                            "name": "b",
+                        },
+                    },
+                    "right": { "id": 4, "type": "String", "quote": "single", "raw": "hi", "depth": 0, "value": "hi" },
+                }],
+            }],
+        }));
+    }
+
+    #[test]
+    fn calls_and_tables() {
+        assert_eq!(document("f { 1, a = 2, [3] = 4 }")["chunk"][0], json!({
+            "id": 13,
+            "type": "Call",
+            "function": { "id": 7, "type": "Name", "name": "f" },
+            "method": null,
+            "arguments": {
+                "type": "Table",
+                "items": [
+                    { "type": "Positional", "value": { "id": 8, "type": "Number", "value": "1" } },
+                    { "type": "Named", "name": "a", "value": { "id": 9, "type": "Number", "value": "2" } },
+                    {
+                        "type": "Keyed",
+                        "key": { "id": 10, "type": "Number", "value": "3" },
+                        "value": { "id": 11, "type": "Number", "value": "4" },
+                    },
+                ],
+            },
+        }));
+    }
+
+    #[test]
+    fn blocks() {
+        let document = document("if x then return elseif y then break else end");
+        let if_statement = &document["chunk"][0];
+
+        assert_eq!(if_statement["type"], "If");
+        assert_eq!(if_statement["body"][0]["type"], "Return");
+        assert_eq!(if_statement["elseif"][0]["condition"]["name"], "y");
+        assert_eq!(if_statement["elseif"][0]["body"][0]["type"], "Break");
+        assert_eq!(if_statement["else"], json!([]));
+    }
+
+    #[test]
+    fn type_annotations() {
+        let tokens = tokenize_luau("local a: number?, b = 1 local function f(x: {A.B} | nil, y): (string, () -> ()) end");
+        let chunk = parse_from_tokens_with_types(&tokens).unwrap();
+        let annotated = serde_json::to_value(Document::new(&chunk)).unwrap();
+
+        assert_eq!(annotated["chunk"][0]["types"], json!([
+            { "type": "Optional", "inner": { "type": "Named", "path": ["number"] } },
+            null,
+        ]));
+
+        let function = &annotated["chunk"][1];
+        assert_eq!(function["parameter_types"], json!([
+            {
+                "type": "Union",
+                "types": [
+                    { "type": "Array", "element": { "type": "Named", "path": ["A", "B"] } },
+                    { "type": "Nil" },
+                ],
+            },
+            null,
+        ]));
+        assert_eq!(function["return_types"], json!([
+            { "type": "Named", "path": ["string"] },
+            { "type": "Function", "parameters": [], "returns": [] },
+        ]));
+
+        assert_eq!(document("local function g() end")["chunk"][0]["return_types"], Value::Null);
+    }
+}
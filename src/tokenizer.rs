@@ -4,8 +4,15 @@
 
 use std::borrow::Cow;
 use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read};
+use std::iter::Peekable;
+use std::ops::Range;
+use std::str::CharIndices;
 
 use regex::{self, Regex};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Symbol {
@@ -19,13 +26,27 @@ pub enum Symbol {
     Minus,
     Star,
     Slash,
+    DoubleSlash,
+    Percent,
     Caret,
     Hash,
+    Ampersand,
+    Pipe,
+    Tilde,
+    ShiftLeft,
+    ShiftRight,
     TwoDots,
     Equal,
+    TwoEqual,
+    NotEqual,
+    LessThan,
+    LessThanEqual,
+    GreaterThan,
+    GreaterThanEqual,
     Comma,
     Semicolon,
     Ellipse,
+    Dot,
     And,
     Or,
     Local,
@@ -45,9 +66,28 @@ pub enum Symbol {
     False,
     Nil,
     Not,
+    Return,
+    Break,
+    Goto,
+    Continue,
+    DoubleColon,
+    Colon,
+
+    /// Luau's `->`, between a function type's parameters and its returns.
+    Arrow,
+
+    /// Luau's `?`, which makes a type optional.
+    QuestionMark,
 }
 
 impl Symbol {
+    /// Whether this symbol is a reserved keyword like `end` rather than an
+    /// operator. Keywords are exactly the symbols that are spelled like
+    /// identifiers, which is how the tokenizer tells the two apart.
+    pub fn is_keyword(&self) -> bool {
+        self.to_str().chars().all(|c| c.is_ascii_alphabetic())
+    }
+
     pub fn to_str(&self) -> &'static str {
         match *self {
             Symbol::LeftBrace => "{",
@@ -60,13 +100,27 @@ impl Symbol {
             Symbol::Minus => "-",
             Symbol::Star => "*",
             Symbol::Slash => "/",
+            Symbol::DoubleSlash => "//",
+            Symbol::Percent => "%",
             Symbol::Caret => "^",
             Symbol::Hash => "#",
+            Symbol::Ampersand => "&",
+            Symbol::Pipe => "|",
+            Symbol::Tilde => "~",
+            Symbol::ShiftLeft => "<<",
+            Symbol::ShiftRight => ">>",
             Symbol::TwoDots => "..",
             Symbol::Equal => "=",
+            Symbol::TwoEqual => "==",
+            Symbol::NotEqual => "~=",
+            Symbol::LessThan => "<",
+            Symbol::LessThanEqual => "<=",
+            Symbol::GreaterThan => ">",
+            Symbol::GreaterThanEqual => ">=",
             Symbol::Comma => ",",
             Symbol::Semicolon => ";",
             Symbol::Ellipse => "...",
+            Symbol::Dot => ".",
             Symbol::And => "and",
             Symbol::Or => "or",
             Symbol::Not => "not",
@@ -86,10 +140,64 @@ impl Symbol {
             Symbol::True => "true",
             Symbol::False => "false",
             Symbol::Nil => "nil",
+            Symbol::Return => "return",
+            Symbol::Break => "break",
+            Symbol::Goto => "goto",
+            Symbol::Continue => "continue",
+            Symbol::DoubleColon => "::",
+            Symbol::Colon => ":",
+            Symbol::Arrow => "->",
+            Symbol::QuestionMark => "?",
+        }
+    }
+}
+
+/// The flavor of Lua being tokenized. Dialects disagree on which words are
+/// reserved, so the same source can tokenize differently in each.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Dialect {
+    Lua51,
+    Lua52,
+
+    /// Lua 5.3 and 5.4, which have the same keywords.
+    #[default]
+    Lua53,
+
+    /// Roblox's Lua 5.1 derivative, which adds `continue` and the `->` and
+    /// `?` symbols used in type annotations.
+    Luau,
+}
+
+impl Dialect {
+    /// Whether `symbol` is a reserved word in this dialect. Keywords a dialect
+    /// doesn't reserve are tokenized as identifiers instead.
+    pub fn has_keyword(&self, symbol: Symbol) -> bool {
+        match symbol {
+            Symbol::Goto => matches!(*self, Dialect::Lua52 | Dialect::Lua53),
+            Symbol::Continue => *self == Dialect::Luau,
+            _ => symbol.is_keyword(),
+        }
+    }
+
+    /// Whether `symbol` is tokenized as a symbol in this dialect at all. This
+    /// covers keywords, like [has_keyword][Dialect::has_keyword], and the
+    /// operators only some dialects have.
+    pub fn has_symbol(&self, symbol: Symbol) -> bool {
+        match symbol {
+            Symbol::Arrow | Symbol::QuestionMark => *self == Dialect::Luau,
+            _ if symbol.is_keyword() => self.has_keyword(symbol),
+            _ => true,
         }
     }
 }
 
+/// Writes the symbol as it's spelled in source, like `~=` or `end`.
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.to_str())
+    }
+}
+
 /// Represents a position in the source text.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct SourcePosition {
@@ -128,14 +236,47 @@ impl SourcePosition {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
-#[serde(tag = "type")]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum StringLiteral<'a> {
+    DoubleQuote {
+        /// Everything between the quotes, with escape sequences left as-is.
+        raw_content: Cow<'a, str>,
+
+        /// The bytes of the string after decoding escape sequences. Escapes
+        /// like `\255` can produce bytes that aren't valid UTF-8.
+        value: Cow<'a, [u8]>,
+    },
+    SingleQuote {
+        raw_content: Cow<'a, str>,
+        value: Cow<'a, [u8]>,
+    },
+
+    /// A long bracket string like `[[foo]]` or `[==[foo]==]`. `depth` is the
+    /// number of `=` in the brackets.
+    LongForm {
+        /// Everything between the brackets, including a leading newline that
+        /// Lua would skip.
+        raw_content: Cow<'a, str>,
+        depth: u32,
+    },
+}
+
+// Human-readable formats like JSON get string literals as objects with a
+// `type` field. Reading that back needs a self-describing format, so binary
+// formats like bincode get an ordinary enum instead.
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "StringLiteral", tag = "type")]
+enum TaggedStringLiteral<'a> {
     DoubleQuote {
         raw_content: Cow<'a, str>,
+        #[serde(with = "string_value")]
+        value: Cow<'a, [u8]>,
     },
     SingleQuote {
         raw_content: Cow<'a, str>,
+        #[serde(with = "string_value")]
+        value: Cow<'a, [u8]>,
     },
     LongForm {
         raw_content: Cow<'a, str>,
@@ -143,6 +284,235 @@ pub enum StringLiteral<'a> {
     },
 }
 
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "StringLiteral")]
+enum CompactStringLiteral<'a> {
+    DoubleQuote {
+        raw_content: Cow<'a, str>,
+        #[serde(with = "string_value")]
+        value: Cow<'a, [u8]>,
+    },
+    SingleQuote {
+        raw_content: Cow<'a, str>,
+        #[serde(with = "string_value")]
+        value: Cow<'a, [u8]>,
+    },
+    LongForm {
+        raw_content: Cow<'a, str>,
+        depth: u32,
+    },
+}
+
+impl<'a> Serialize for StringLiteral<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            TaggedStringLiteral::serialize(self, serializer)
+        } else {
+            CompactStringLiteral::serialize(self, serializer)
+        }
+    }
+}
+
+impl<'de, 'a> Deserialize<'de> for StringLiteral<'a> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            TaggedStringLiteral::deserialize(deserializer)
+        } else {
+            CompactStringLiteral::deserialize(deserializer)
+        }
+    }
+}
+
+/// Serializes decoded string values as text when they're valid UTF-8, which
+/// they almost always are, and as a list of bytes otherwise. Binary formats
+/// always get bytes.
+mod string_value {
+    use std::borrow::Cow;
+    use std::fmt;
+    use std::str;
+
+    use serde::de::{self, SeqAccess, Visitor};
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        if !serializer.is_human_readable() {
+            return serializer.serialize_bytes(value);
+        }
+
+        match str::from_utf8(value) {
+            Ok(text) => serializer.serialize_str(text),
+            Err(_) => serializer.collect_seq(value.iter()),
+        }
+    }
+
+    pub fn deserialize<'de, 'a, D: Deserializer<'de>>(deserializer: D) -> Result<Cow<'a, [u8]>, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Value {
+            Text(String),
+            Bytes(Vec<u8>),
+        }
+
+        if !deserializer.is_human_readable() {
+            return deserializer.deserialize_byte_buf(BytesVisitor).map(Cow::Owned);
+        }
+
+        Ok(match Value::deserialize(deserializer)? {
+            Value::Text(text) => Cow::Owned(text.into_bytes()),
+            Value::Bytes(bytes) => Cow::Owned(bytes),
+        })
+    }
+
+    struct BytesVisitor;
+
+    impl<'de> Visitor<'de> for BytesVisitor {
+        type Value = Vec<u8>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("bytes")
+        }
+
+        fn visit_bytes<E: de::Error>(self, value: &[u8]) -> Result<Vec<u8>, E> {
+            Ok(value.to_vec())
+        }
+
+        fn visit_byte_buf<E: de::Error>(self, value: Vec<u8>) -> Result<Vec<u8>, E> {
+            Ok(value)
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<u8>, A::Error> {
+            let mut bytes = Vec::new();
+
+            while let Some(byte) = seq.next_element()? {
+                bytes.push(byte);
+            }
+
+            Ok(bytes)
+        }
+    }
+}
+
+impl<'a> StringLiteral<'a> {
+    /// A double-quoted literal with the given value, with as few escapes as
+    /// it can get away with.
+    pub fn double_quoted(value: &[u8]) -> StringLiteral<'static> {
+        StringLiteral::DoubleQuote {
+            raw_content: Cow::Owned(escape(value, '"')),
+            value: Cow::Owned(value.to_vec()),
+        }
+    }
+
+    /// Like [double_quoted][StringLiteral::double_quoted], but with single
+    /// quotes.
+    pub fn single_quoted(value: &[u8]) -> StringLiteral<'static> {
+        StringLiteral::SingleQuote {
+            raw_content: Cow::Owned(escape(value, '\'')),
+            value: Cow::Owned(value.to_vec()),
+        }
+    }
+
+    /// Copies any borrowed text so the literal no longer borrows the source.
+    pub fn into_owned(self) -> StringLiteral<'static> {
+        match self {
+            StringLiteral::DoubleQuote { raw_content, value } => StringLiteral::DoubleQuote {
+                raw_content: Cow::Owned(raw_content.into_owned()),
+                value: Cow::Owned(value.into_owned()),
+            },
+            StringLiteral::SingleQuote { raw_content, value } => StringLiteral::SingleQuote {
+                raw_content: Cow::Owned(raw_content.into_owned()),
+                value: Cow::Owned(value.into_owned()),
+            },
+            StringLiteral::LongForm { raw_content, depth } => StringLiteral::LongForm {
+                raw_content: Cow::Owned(raw_content.into_owned()),
+                depth,
+            },
+        }
+    }
+
+    /// The contents of the literal as Lua sees them: escape sequences are
+    /// decoded for quoted strings, and a long string skips a newline directly
+    /// after its opening bracket.
+    pub fn value(&self) -> &[u8] {
+        match *self {
+            StringLiteral::DoubleQuote { ref value, .. } => value,
+            StringLiteral::SingleQuote { ref value, .. } => value,
+            StringLiteral::LongForm { ref raw_content, .. } => {
+                ["\r\n", "\n\r", "\n", "\r"]
+                    .iter()
+                    .filter_map(|newline| raw_content.strip_prefix(newline))
+                    .next()
+                    .unwrap_or(raw_content)
+                    .as_bytes()
+            },
+        }
+    }
+}
+
+enum Piece {
+    Char(char),
+    Byte(u8),
+}
+
+// Escapes a string's value to go between `quote`s. Bytes that aren't valid
+// UTF-8 are written as decimal escapes.
+fn escape(value: &[u8], quote: char) -> String {
+    let pieces = value.utf8_chunks()
+        .flat_map(|chunk| {
+            chunk.valid().chars().map(Piece::Char)
+                .chain(chunk.invalid().iter().map(|&byte| Piece::Byte(byte)))
+        })
+        .collect::<Vec<_>>();
+
+    let mut escaped = String::with_capacity(value.len());
+
+    for (index, piece) in pieces.iter().enumerate() {
+        let byte = match *piece {
+            Piece::Char('\\') => {
+                escaped.push_str("\\\\");
+                continue;
+            },
+            Piece::Char('\n') => {
+                escaped.push_str("\\n");
+                continue;
+            },
+            Piece::Char('\r') => {
+                escaped.push_str("\\r");
+                continue;
+            },
+            Piece::Char('\t') => {
+                escaped.push_str("\\t");
+                continue;
+            },
+            Piece::Char(character) if character == quote => {
+                escaped.push('\\');
+                escaped.push(character);
+                continue;
+            },
+            Piece::Char(character) if character.is_ascii_control() => character as u8,
+            Piece::Char(character) => {
+                escaped.push(character);
+                continue;
+            },
+            Piece::Byte(byte) => byte,
+        };
+
+        // A digit after a decimal escape would be read as part of it.
+        match pieces.get(index + 1) {
+            Some(&Piece::Char(next)) if next.is_ascii_digit() => escaped.push_str(&format!("\\{:03}", byte)),
+            _ => escaped.push_str(&format!("\\{}", byte)),
+        }
+    }
+
+    escaped
+}
+
+/// Whether `text` can be written as a name in Lua, which means it isn't a
+/// keyword. Only ASCII names count, since not every Lua allows others.
+pub fn is_identifier(text: &str) -> bool {
+    PATTERN_IDENTIFIER.find(text).is_some_and(|found| found.end() == text.len())
+        && !SYMBOLS.iter().any(|symbol| symbol.is_keyword() && symbol.to_str() == text)
+}
+
 /// Represents a token kind.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum TokenKind<'a> {
@@ -158,9 +528,65 @@ pub enum TokenKind<'a> {
 
     StringLiteral(StringLiteral<'a>),
 
+    /// Source text the tokenizer couldn't make sense of. Only produced by
+    /// [tolerant][Tokenizer::tolerant] tokenizers.
+    Error {
+        error: TokenizeError,
+
+        /// The invalid text that was skipped over.
+        text: Cow<'a, str>,
+    },
+
     EndOfFile,
 }
 
+impl<'a> TokenKind<'a> {
+    /// Copies any borrowed text so the token no longer borrows the source.
+    pub fn into_owned(self) -> TokenKind<'static> {
+        match self {
+            TokenKind::Symbol(symbol) => TokenKind::Symbol(symbol),
+            TokenKind::Identifier(name) => TokenKind::Identifier(Cow::Owned(name.into_owned())),
+            TokenKind::NumberLiteral(value) => TokenKind::NumberLiteral(Cow::Owned(value.into_owned())),
+            TokenKind::StringLiteral(literal) => TokenKind::StringLiteral(literal.into_owned()),
+            TokenKind::Error { error, text } => TokenKind::Error {
+                error,
+                text: Cow::Owned(text.into_owned()),
+            },
+            TokenKind::EndOfFile => TokenKind::EndOfFile,
+        }
+    }
+
+    /// The text of the token exactly as it appeared in the source, including
+    /// quotes on strings and the original spelling of numbers.
+    pub fn source_text(&self) -> Cow<'_, str> {
+        match *self {
+            TokenKind::Symbol(symbol) => Cow::Borrowed(symbol.to_str()),
+            TokenKind::Identifier(ref name) => Cow::Borrowed(name),
+            TokenKind::NumberLiteral(ref value) => Cow::Borrowed(value),
+            TokenKind::StringLiteral(StringLiteral::DoubleQuote { ref raw_content, .. }) => Cow::Owned(format!("\"{}\"", raw_content)),
+            TokenKind::StringLiteral(StringLiteral::SingleQuote { ref raw_content, .. }) => Cow::Owned(format!("'{}'", raw_content)),
+            TokenKind::StringLiteral(StringLiteral::LongForm { ref raw_content, depth }) => {
+                let equals = "=".repeat(depth as usize);
+                Cow::Owned(format!("[{}[{}]{}]", equals, raw_content, equals))
+            },
+            TokenKind::Error { ref text, .. } => Cow::Borrowed(text),
+            TokenKind::EndOfFile => Cow::Borrowed(""),
+        }
+    }
+}
+
+/// Describes the token for use in error messages, like ``"`end`"`` or
+/// `"end of file"`.
+impl<'a> fmt::Display for TokenKind<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TokenKind::Error { .. } => write!(f, "invalid token"),
+            TokenKind::EndOfFile => write!(f, "end of file"),
+            _ => write!(f, "`{}`", self.source_text()),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Comment<'a> {
     SingleLine {
@@ -172,6 +598,35 @@ pub enum Comment<'a> {
     },
 }
 
+impl<'a> Comment<'a> {
+    /// Copies the comment's text so it no longer borrows the source.
+    pub fn into_owned(self) -> Comment<'static> {
+        match self {
+            Comment::SingleLine { content } => Comment::SingleLine {
+                content: Cow::Owned(content.into_owned()),
+            },
+            Comment::MultiLine { content, depth } => Comment::MultiLine {
+                content: Cow::Owned(content.into_owned()),
+                depth,
+            },
+        }
+    }
+}
+
+/// Writes the comment exactly as it appeared in the source, like `-- foo` or
+/// `--[==[ foo ]==]`.
+impl<'a> fmt::Display for Comment<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Comment::SingleLine { ref content } => write!(f, "--{}", content),
+            Comment::MultiLine { ref content, depth } => {
+                let equals = "=".repeat(depth as usize);
+                write!(f, "--[{}[{}]{}]", equals, content, equals)
+            },
+        }
+    }
+}
+
 /// An item that appears before tokens, like comments and whitespace.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum TokenPrefix<'a> {
@@ -179,6 +634,32 @@ pub enum TokenPrefix<'a> {
     Whitespace(Cow<'a, str>),
 
     Comment(Comment<'a>),
+
+    /// A UTF-8 byte order mark at the very start of the source, which Windows
+    /// editors like to add.
+    ByteOrderMark,
+}
+
+impl<'a> TokenPrefix<'a> {
+    /// Copies any borrowed text so the trivia no longer borrows the source.
+    pub fn into_owned(self) -> TokenPrefix<'static> {
+        match self {
+            TokenPrefix::Whitespace(whitespace) => TokenPrefix::Whitespace(Cow::Owned(whitespace.into_owned())),
+            TokenPrefix::Comment(comment) => TokenPrefix::Comment(comment.into_owned()),
+            TokenPrefix::ByteOrderMark => TokenPrefix::ByteOrderMark,
+        }
+    }
+}
+
+/// Writes the trivia exactly as it appeared in the source.
+impl<'a> fmt::Display for TokenPrefix<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TokenPrefix::Whitespace(ref whitespace) => write!(f, "{}", whitespace),
+            TokenPrefix::Comment(ref comment) => write!(f, "{}", comment),
+            TokenPrefix::ByteOrderMark => write!(f, "{}", BYTE_ORDER_MARK),
+        }
+    }
 }
 
 /// A token in the source.
@@ -198,8 +679,34 @@ pub struct Token<'a> {
     pub end_position: SourcePosition,
 }
 
+impl<'a> Token<'a> {
+    /// Copies any borrowed text so the token no longer borrows the source.
+    pub fn into_owned(self) -> Token<'static> {
+        Token {
+            kind: self.kind.into_owned(),
+            prefix: self.prefix.into_iter().map(TokenPrefix::into_owned).collect(),
+            start_position: self.start_position,
+            end_position: self.end_position,
+        }
+    }
+
+    /// The byte range of the token in the source, not including whitespace or
+    /// comments before it. Editors can use this to replace the token's text.
+    pub fn span(&self) -> Range<usize> {
+        self.start_position.bytes..self.end_position.bytes
+    }
+
+    /// The comments that appear before this token, in source order.
+    pub fn comments(&self) -> impl Iterator<Item = &Comment<'a>> {
+        self.prefix.iter().filter_map(|item| match *item {
+            TokenPrefix::Comment(ref comment) => Some(comment),
+            TokenPrefix::Whitespace(_) | TokenPrefix::ByteOrderMark => None,
+        })
+    }
+}
+
 /// An error with information about why tokenization failed.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum TokenizeError {
     /// The tokenizer encountered an unknown sequence in the source that it
     /// could not parse.
@@ -208,29 +715,128 @@ pub enum TokenizeError {
         position: SourcePosition,
     },
 
-    /// A string was begun that never finished.
+    /// A string was begun that never finished, either because the source
+    /// ended or because a quoted string reached the end of its line.
     UnclosedString {
+        /// Where the string started.
         position: SourcePosition,
     },
 
-    /// An unclosed multi-line comment
+    /// A multi-line comment like `--[[` was never closed.
     UnclosedComment {
+        /// Where the comment started.
+        position: SourcePosition,
+    },
+
+    /// A string contained an escape sequence that Lua doesn't understand, like
+    /// `\q` or `\300`.
+    InvalidEscape {
+        /// The location of the backslash that starts the escape.
+        position: SourcePosition,
+    },
+
+    /// A `\u{XXX}` escape was missing its braces or digits, or named a code
+    /// point larger than 0x10FFFF.
+    InvalidUnicodeEscape {
+        /// The location of the backslash that starts the escape.
         position: SourcePosition,
     },
 }
 
+impl TokenizeError {
+    /// Where in the source the error happened. For unclosed strings and
+    /// comments, this is where they started.
+    pub fn position(&self) -> SourcePosition {
+        match *self {
+            TokenizeError::UnknownSequence { position } |
+            TokenizeError::UnclosedString { position } |
+            TokenizeError::UnclosedComment { position } |
+            TokenizeError::InvalidEscape { position } |
+            TokenizeError::InvalidUnicodeEscape { position } => position,
+        }
+    }
+
+    /// A short code for this kind of error that won't change between versions.
+    /// These share a numbering with
+    /// [ParseErrorKind::code][::parser::ParseErrorKind::code].
+    pub fn code(&self) -> &'static str {
+        match *self {
+            TokenizeError::UnknownSequence { .. } => "P010",
+            TokenizeError::InvalidEscape { .. } => "P011",
+            TokenizeError::InvalidUnicodeEscape { .. } => "P012",
+            TokenizeError::UnclosedComment { .. } => "P013",
+            TokenizeError::UnclosedString { .. } => "P014",
+        }
+    }
+
+    /// Describes the error without saying where it happened.
+    pub fn message(&self) -> &'static str {
+        match *self {
+            TokenizeError::UnknownSequence { .. } => "Unknown sequence of characters",
+            TokenizeError::UnclosedString { .. } => "Unterminated string",
+            TokenizeError::UnclosedComment { .. } => "Unterminated comment",
+            TokenizeError::InvalidEscape { .. } => "Invalid escape sequence in string",
+            TokenizeError::InvalidUnicodeEscape { .. } => "Invalid unicode escape in string",
+        }
+    }
+}
+
+impl fmt::Display for TokenizeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let position = self.position();
+
+        // These are only noticed at the end of the line or file, so point
+        // back at where they began.
+        let location = match *self {
+            TokenizeError::UnclosedString { .. } | TokenizeError::UnclosedComment { .. } => "starting at",
+            _ => "at",
+        };
+
+        write!(f, "{} {} line {}, column {}", self.message(), location, position.line, position.column)
+    }
+}
+
+impl Error for TokenizeError {}
+
+/// An error from [read_and_tokenize][read_and_tokenize].
+#[derive(Debug)]
+pub enum ReadError {
+    /// Reading failed, or the source wasn't valid UTF-8.
+    Io(io::Error),
+
+    Tokenize(TokenizeError),
+}
+
+impl From<io::Error> for ReadError {
+    fn from(error: io::Error) -> ReadError {
+        ReadError::Io(error)
+    }
+}
+
+impl From<TokenizeError> for ReadError {
+    fn from(error: TokenizeError) -> ReadError {
+        ReadError::Tokenize(error)
+    }
+}
+
 lazy_static! {
     static ref SYMBOLS: Vec<Symbol> = vec![
         Symbol::LeftBrace, Symbol::RightBrace,
         Symbol::LeftBracket, Symbol::RightBracket,
         Symbol::LeftParen, Symbol::RightParen,
 
-        Symbol::Plus, Symbol::Minus, Symbol::Star, Symbol::Slash, Symbol::Caret, Symbol::TwoDots,
+        Symbol::Plus, Symbol::Minus, Symbol::Star, Symbol::Slash, Symbol::DoubleSlash, Symbol::Percent, Symbol::Caret, Symbol::TwoDots,
         Symbol::And, Symbol::Or,
         Symbol::Hash,
+        Symbol::Ampersand, Symbol::Pipe, Symbol::Tilde, Symbol::ShiftLeft, Symbol::ShiftRight,
         Symbol::Equal,
+        Symbol::TwoEqual, Symbol::NotEqual,
+        Symbol::LessThan, Symbol::LessThanEqual, Symbol::GreaterThan, Symbol::GreaterThanEqual,
         Symbol::Comma, Symbol::Semicolon,
         Symbol::Ellipse,
+        Symbol::DoubleColon, Symbol::Colon,
+        Symbol::Dot,
+        Symbol::Arrow, Symbol::QuestionMark,
 
         Symbol::Local, Symbol::Function,
         Symbol::If, Symbol::While, Symbol::Repeat, Symbol::Until, Symbol::For,
@@ -238,6 +844,7 @@ lazy_static! {
         Symbol::In,
         Symbol::True, Symbol::False, Symbol::Nil,
         Symbol::Not,
+        Symbol::Return, Symbol::Break, Symbol::Goto, Symbol::Continue,
     ];
 
     static ref STR_TO_SYMBOL: HashMap<&'static str, Symbol> = {
@@ -250,23 +857,42 @@ lazy_static! {
         map
     };
 
-    static ref PATTERN_SYMBOL: Regex = {
-        let source = SYMBOLS
-            .iter()
-            .map(|v| regex::escape(v.to_str()))
-            .collect::<Vec<_>>()
-            .join("|");
-
-        Regex::new(&format!("^(?:{})", source)).unwrap()
-    };
+    static ref PATTERN_SYMBOL: Regex = symbol_pattern(Dialect::Lua53);
+    static ref PATTERN_LUAU_SYMBOL: Regex = symbol_pattern(Dialect::Luau);
 
     static ref PATTERN_IDENTIFIER: Regex = Regex::new(r"^[_a-zA-Z][_a-zA-Z0-9]*").unwrap();
-    static ref PATTERN_NUMBER_LITERAL: Regex = Regex::new(r"^((-?0x[A-Fa-f\d]+)|(-?((\d*\.\d+)|(\d+))([eE]-?\d+)?))").unwrap();
+    static ref PATTERN_UNICODE_IDENTIFIER: Regex = Regex::new(r"^[_\p{XID_Start}]\p{XID_Continue}*").unwrap();
+    static ref PATTERN_NUMBER_LITERAL: Regex = {
+        // Hex literals can have a fraction and a binary exponent, like 0xA.8p2
+        let hex = r"0[xX](?:[A-Fa-f\d]*\.[A-Fa-f\d]+|[A-Fa-f\d]+\.?)(?:[pP][+-]?\d+)?";
+        let decimal = r"(?:\d+\.?\d*|\.\d+)(?:[eE][+-]?\d+)?";
+
+        Regex::new(&format!("^(?:{}|{})", hex, decimal)).unwrap()
+    };
     static ref PATTERN_WHITESPACE: Regex = Regex::new(r"^\s+").unwrap();
-    static ref PATTERN_SINGLE_LINE_COMMENT: Regex = Regex::new(r"^--(.*)").unwrap();
+    static ref PATTERN_SINGLE_LINE_COMMENT: Regex = Regex::new(r"^--([^\r\n]*)").unwrap();
     static ref PATTERN_MULTI_LINE_COMMENT_START: Regex = Regex::new(r"^--\[(=*)\[").unwrap();
+    static ref PATTERN_LONG_STRING_START: Regex = Regex::new(r"^\[(=*)\[").unwrap();
+
+    static ref PATTERN_CHARS_AFTER_NEWLINE: Regex = Regex::new(r"\n[^\n]*$").unwrap();
+}
 
-    static ref PATTERN_CHARS_AFTER_NEWLINE: Regex = Regex::new(r"\n[^\n]+$").unwrap();
+fn symbol_pattern(dialect: Dialect) -> Regex {
+    // Alternations match leftmost-first, so longer symbols have to come
+    // before any symbols that are a prefix of them, like `...` and `..`.
+    let mut symbols = SYMBOLS.iter()
+        .cloned()
+        .filter(|symbol| dialect.has_symbol(*symbol))
+        .collect::<Vec<_>>();
+    symbols.sort_by_key(|v| ::std::cmp::Reverse(v.to_str().len()));
+
+    let source = symbols
+        .iter()
+        .map(|v| regex::escape(v.to_str()))
+        .collect::<Vec<_>>()
+        .join("|");
+
+    Regex::new(&format!("^(?:{})", source)).unwrap()
 }
 
 struct AdvanceResult<'a> {
@@ -319,77 +945,239 @@ macro_rules! try_advance {
     )
 }
 
-fn parse_identifier<'a>(current: &'a str, current_position: &SourcePosition) -> Result<(AdvanceResult<'a>, TokenKind<'a>), AdvanceError> {
-    advance_token(current, &current_position, &PATTERN_IDENTIFIER, |s| {
-        if let Some(&symbol) = STR_TO_SYMBOL.get(s) {
-            TokenKind::Symbol(symbol)
-        } else {
-            TokenKind::Identifier(s.into())
+fn parse_identifier<'a>(current: &'a str, current_position: &SourcePosition, unicode: bool, dialect: Dialect) -> Result<(AdvanceResult<'a>, TokenKind<'a>), AdvanceError> {
+    let pattern = if unicode {
+        &*PATTERN_UNICODE_IDENTIFIER
+    } else {
+        &*PATTERN_IDENTIFIER
+    };
+
+    advance_token(current, current_position, pattern, |s| {
+        match STR_TO_SYMBOL.get(s) {
+            Some(&symbol) if dialect.has_keyword(symbol) => TokenKind::Symbol(symbol),
+            _ => TokenKind::Identifier(s.into()),
         }
     })
 }
 
 fn parse_number_literal<'a>(current: &'a str, current_position: &SourcePosition) -> Result<(AdvanceResult<'a>, TokenKind<'a>), AdvanceError> {
-    advance_token(current, &current_position, &PATTERN_NUMBER_LITERAL, |s| TokenKind::NumberLiteral(s.into()))
+    advance_token(current, current_position, &PATTERN_NUMBER_LITERAL, |s| TokenKind::NumberLiteral(s.into()))
 }
 
-fn parse_symbol<'a>(current: &'a str, current_position: &SourcePosition) -> Result<(AdvanceResult<'a>, TokenKind<'a>), AdvanceError> {
-    advance_token(current, &current_position, &PATTERN_SYMBOL, |s| TokenKind::Symbol(*STR_TO_SYMBOL.get(s).unwrap()))
+fn parse_symbol<'a>(current: &'a str, current_position: &SourcePosition, dialect: Dialect) -> Result<(AdvanceResult<'a>, TokenKind<'a>), AdvanceError> {
+    let pattern = if dialect == Dialect::Luau {
+        &*PATTERN_LUAU_SYMBOL
+    } else {
+        &*PATTERN_SYMBOL
+    };
+
+    advance_token(current, current_position, pattern, |s| TokenKind::Symbol(*STR_TO_SYMBOL.get(s).unwrap()))
 }
 
-fn parse_string_literal<'a>(current: &'a str, current_position: &SourcePosition) -> Result<(AdvanceResult<'a>, TokenKind<'a>), AdvanceError> {
-    let quote_character = if current.starts_with("\"") {
-        '"'
-    } else if current.starts_with("'") {
-        '\''
-    } else {
-        return Err(AdvanceError::NoMatch);
+/// Decodes the escape sequence after a backslash into `decoded`. `position`
+/// is the location of the backslash, used for errors.
+fn decode_escape(chars: &mut Peekable<CharIndices>, decoded: &mut Vec<u8>, position: SourcePosition) -> Result<(), TokenizeError> {
+    let invalid = TokenizeError::InvalidEscape { position };
+
+    let character = match chars.next() {
+        Some((_, character)) => character,
+        None => return Err(invalid),
     };
 
-    let mut literal_end = None;
-    let mut last_was_escape = false;
+    let byte = match character {
+        'a' => 0x07,
+        'b' => 0x08,
+        'f' => 0x0C,
+        'n' => b'\n',
+        'r' => b'\r',
+        't' => b'\t',
+        'v' => 0x0B,
+        '\\' => b'\\',
+        '"' => b'"',
+        '\'' => b'\'',
+        '\n' | '\r' => {
+            // An escaped line break is a line break in the string. \r\n and
+            // \n\r count as a single line break.
+            if let Some(&(_, next)) = chars.peek() {
+                if (next == '\n' || next == '\r') && next != character {
+                    // Skip the second half of the line break.
+                    chars.next();
+                }
+            }
 
-    for (index, character) in current.char_indices().skip(1) {
-        if character == '\\' {
-            last_was_escape = !last_was_escape;
-        } else if character == quote_character {
-            if last_was_escape {
-                last_was_escape = false;
-            } else {
-                literal_end = Some(index);
-                break;
+            b'\n'
+        },
+        'z' => {
+            // The same characters as C's `isspace`, which unlike
+            // `is_ascii_whitespace` includes `\v`.
+            while let Some(&(_, next)) = chars.peek() {
+                if !matches!(next, ' ' | '\t' | '\n' | '\x0B' | '\x0C' | '\r') {
+                    break;
+                }
+
+                chars.next();
             }
-        } else if character == '\r' || character == '\n' {
-            return Err(AdvanceError::Error(TokenizeError::UnclosedString {
-                position: *current_position,
-            }));
-        } else {
-            last_was_escape = false;
-        }
-    }
 
-    let literal_end = match literal_end {
-        Some(v) => v,
-        None => {
-            return Err(AdvanceError::Error(TokenizeError::UnclosedString {
-                position: *current_position,
-            }));
+            return Ok(());
         },
-    };
+        'u' => {
+            let code_point = decode_unicode_escape(chars)
+                .ok_or(TokenizeError::InvalidUnicodeEscape { position })?;
 
-    let literal = match quote_character {
-        '"' => StringLiteral::DoubleQuote {
-            raw_content: Cow::from(&current[1..literal_end])
+            encode_utf8(code_point, decoded);
+            return Ok(());
         },
-        '\'' => StringLiteral::SingleQuote {
-            raw_content: Cow::from(&current[1..literal_end])
+        'x' => {
+            let mut byte = 0;
+
+            for _ in 0..2 {
+                match chars.next().and_then(|(_, digit)| digit.to_digit(16)) {
+                    Some(digit) => byte = byte * 16 + digit,
+                    None => return Err(invalid),
+                }
+            }
+
+            byte as u8
         },
-        _ => unreachable!(),
-    };
+        '0'..='9' => {
+            let mut byte = character.to_digit(10).unwrap();
 
-    let advance_result = AdvanceResult {
-        rest: &current[literal_end + 1..],
-        contents: "",
+            for _ in 0..2 {
+                match chars.peek().and_then(|&(_, digit)| digit.to_digit(10)) {
+                    Some(digit) => byte = byte * 10 + digit,
+                    None => break,
+                }
+
+                chars.next();
+            }
+
+            if byte > 255 {
+                return Err(invalid);
+            }
+
+            byte as u8
+        },
+        _ => return Err(invalid),
+    };
+
+    decoded.push(byte);
+    Ok(())
+}
+
+/// Reads the `{XXX}` part of a `\u{XXX}` escape, returning the code point if
+/// it's well-formed and no larger than 0x10FFFF.
+fn decode_unicode_escape(chars: &mut Peekable<CharIndices>) -> Option<u32> {
+    match chars.next() {
+        Some((_, '{')) => {},
+        _ => return None,
+    }
+
+    let mut code_point: u32 = 0;
+    let mut digit_count = 0;
+
+    loop {
+        match chars.next() {
+            Some((_, '}')) if digit_count > 0 => return Some(code_point),
+            Some((_, character)) => {
+                let digit = character.to_digit(16)?;
+                code_point = code_point * 16 + digit;
+                digit_count += 1;
+
+                if code_point > 0x10FFFF {
+                    return None;
+                }
+            },
+            None => return None,
+        }
+    }
+}
+
+/// Encodes a code point as UTF-8 the way Lua does, which unlike
+/// [char::encode_utf8] allows surrogates like `\u{D800}`.
+fn encode_utf8(code_point: u32, decoded: &mut Vec<u8>) {
+    if code_point < 0x80 {
+        decoded.push(code_point as u8);
+    } else if code_point < 0x800 {
+        decoded.push(0xC0 | (code_point >> 6) as u8);
+        decoded.push(0x80 | (code_point & 0x3F) as u8);
+    } else if code_point < 0x10000 {
+        decoded.push(0xE0 | (code_point >> 12) as u8);
+        decoded.push(0x80 | ((code_point >> 6) & 0x3F) as u8);
+        decoded.push(0x80 | (code_point & 0x3F) as u8);
+    } else {
+        decoded.push(0xF0 | (code_point >> 18) as u8);
+        decoded.push(0x80 | ((code_point >> 12) & 0x3F) as u8);
+        decoded.push(0x80 | ((code_point >> 6) & 0x3F) as u8);
+        decoded.push(0x80 | (code_point & 0x3F) as u8);
+    }
+}
+
+
+fn parse_string_literal<'a>(current: &'a str, current_position: &SourcePosition) -> Result<(AdvanceResult<'a>, TokenKind<'a>), AdvanceError> {
+    let quote_character = if current.starts_with("\"") {
+        '"'
+    } else if current.starts_with("'") {
+        '\''
+    } else {
+        return Err(AdvanceError::NoMatch);
+    };
+
+    let unclosed = AdvanceError::Error(TokenizeError::UnclosedString {
+        position: *current_position,
+    });
+
+    let mut decoded = Vec::new();
+    let mut chars = current.char_indices().peekable();
+    chars.next();
+
+    let literal_end = loop {
+        let (index, character) = match chars.next() {
+            Some(v) => v,
+            None => return Err(unclosed),
+        };
+
+        if character == quote_character {
+            break index;
+        }
+
+        match character {
+            '\r' | '\n' => return Err(unclosed),
+            '\\' => {
+                if chars.peek().is_none() {
+                    return Err(unclosed);
+                }
+
+                let position = current_position.next_position(&current[..index]);
+                decode_escape(&mut chars, &mut decoded, position).map_err(AdvanceError::Error)?;
+            },
+            _ => decoded.extend_from_slice(character.encode_utf8(&mut [0; 4]).as_bytes()),
+        }
+    };
+
+    let raw_content = &current[1..literal_end];
+
+    // Most strings have no escapes, so we can avoid copying them.
+    let value = if decoded == raw_content.as_bytes() {
+        Cow::from(raw_content.as_bytes())
+    } else {
+        Cow::from(decoded)
+    };
+
+    let literal = match quote_character {
+        '"' => StringLiteral::DoubleQuote {
+            raw_content: Cow::from(raw_content),
+            value,
+        },
+        '\'' => StringLiteral::SingleQuote {
+            raw_content: Cow::from(raw_content),
+            value,
+        },
+        _ => unreachable!(),
+    };
+
+    let advance_result = AdvanceResult {
+        rest: &current[literal_end + 1..],
+        contents: "",
         new_position: current_position.next_position(&current[..literal_end + 1]),
     };
 
@@ -397,16 +1185,48 @@ fn parse_string_literal<'a>(current: &'a str, current_position: &SourcePosition)
 }
 
 fn parse_long_string_literal<'a>(current: &'a str, current_position: &SourcePosition) -> Result<(AdvanceResult<'a>, TokenKind<'a>), AdvanceError> {
-    Err(AdvanceError::NoMatch)
+    let captures = match PATTERN_LONG_STRING_START.captures(current) {
+        Some(captures) => captures,
+        None => return Err(AdvanceError::NoMatch),
+    };
+
+    let content_start = captures.get(0).unwrap().end();
+    let depth = captures.get(1).unwrap().as_str().len();
+    let closing = format!("]{}]", "=".repeat(depth));
+
+    let content_end = match current[content_start..].find(&closing) {
+        Some(offset) => content_start + offset,
+        None => {
+            return Err(AdvanceError::Error(TokenizeError::UnclosedString {
+                position: *current_position,
+            }));
+        },
+    };
+
+    let literal_end = content_end + closing.len();
+    let literal = StringLiteral::LongForm {
+        raw_content: Cow::from(&current[content_start..content_end]),
+        depth: depth as u32,
+    };
+
+    let advance_result = AdvanceResult {
+        rest: &current[literal_end..],
+        contents: "",
+        new_position: current_position.next_position(&current[..literal_end]),
+    };
+
+    Ok((advance_result, TokenKind::StringLiteral(literal)))
 }
 
 /// Attempts to advance one token into the stream.
-fn tokenize_step<'a>(current: &'a str, current_position: &SourcePosition) -> Result<(AdvanceResult<'a>, TokenKind<'a>), AdvanceError> {
-    try_advance!(parse_identifier(current, current_position));
+fn tokenize_step<'a>(current: &'a str, current_position: &SourcePosition, unicode_identifiers: bool, dialect: Dialect) -> Result<(AdvanceResult<'a>, TokenKind<'a>), AdvanceError> {
+    try_advance!(parse_identifier(current, current_position, unicode_identifiers, dialect));
     try_advance!(parse_number_literal(current, current_position));
-    try_advance!(parse_symbol(current, current_position));
-    try_advance!(parse_string_literal(current, current_position));
+
+    // Long strings start with `[`, so they have to be tried before symbols.
     try_advance!(parse_long_string_literal(current, current_position));
+    try_advance!(parse_symbol(current, current_position, dialect));
+    try_advance!(parse_string_literal(current, current_position));
 
     Err(AdvanceError::NoMatch)
 }
@@ -421,21 +1241,20 @@ fn parse_multi_line_comment<'a>(current: &'a str, position: &SourcePosition) ->
         let rest = &current[start_capture.end()..];
 
         let depth = captures.get(1).unwrap().as_str().len() as u32;
+        let closing = format!("]{}]", "=".repeat(depth as usize));
 
-        let reg_str = format!(r".*?]={{{}}}]", depth); // Expands to something like .*?]={5}]
-        let end_reg = Regex::new(reg_str.as_str()).unwrap();
-
-        if let Some(end_match) = end_reg.captures(rest) {
-            let end_capture = end_match.get(0).unwrap();
-            let contents = &current[start_capture.start()..start_capture.end() + end_capture.end()];
-            let rest = &rest[end_capture.end()..];
-            let new_position = position.next_position(contents);
+        if let Some(content_end) = rest.find(&closing) {
+            let comment_end = content_end + closing.len();
+            let contents = &current[..start_capture.end() + comment_end];
 
             let comment = Comment::MultiLine {
-                content: contents[start_capture.end()..start_capture.end() + end_capture.start()].into(),
+                content: rest[..content_end].into(),
                 depth
             };
 
+            let rest = &rest[comment_end..];
+            let new_position = position.next_position(contents);
+
             Ok((AdvanceResult { rest, contents, new_position}, comment))
         } else {
             Err(AdvanceError::Error(TokenizeError::UnclosedComment{position: *position}))
@@ -466,82 +1285,230 @@ fn parse_comment<'a>(current: &'a str, position: &SourcePosition) -> Result<(Adv
     }
 }
 
-/// Tokenizes a source string completely and returns a [Vec][Vec] of [Tokens][Token].
+const BYTE_ORDER_MARK: char = '\u{FEFF}';
+
+/// Tokenizes a source string lazily, one [Token][Token] at a time.
 ///
-/// # Errors
-/// Will return an [UnknownSequence][TokenizeError::UnknownSequence] if it
-/// encounters a sequence of characters that it cannot parse.
-// TODO: Change to returning iterator?
-pub fn tokenize<'a>(source: &'a str) -> Result<Vec<Token<'a>>, TokenizeError> {
-    let mut tokens = Vec::new();
-    let mut current = source;
-    let mut current_position = SourcePosition {
-        line: 1,
-        column: 1,
-        bytes: 0,
-    };
+/// After yielding an error, the tokenizer stops and returns `None` from then
+/// on, unless it's [tolerant][Tokenizer::tolerant].
+pub struct Tokenizer<'a> {
+    current: &'a str,
+    position: SourcePosition,
+    finished: bool,
+    tolerant: bool,
+    unicode_identifiers: bool,
+    dialect: Dialect,
+}
 
-    loop {
+impl<'a> Tokenizer<'a> {
+    pub fn new(source: &'a str) -> Tokenizer<'a> {
+        Tokenizer {
+            current: source,
+            position: SourcePosition {
+                line: 1,
+                column: 1,
+                bytes: 0,
+            },
+            finished: false,
+            tolerant: false,
+            unicode_identifiers: false,
+            dialect: Dialect::default(),
+        }
+    }
+
+    /// Sets the dialect, which decides which words are keywords. The default
+    /// is [Lua53][Dialect::Lua53].
+    pub fn dialect(mut self, dialect: Dialect) -> Tokenizer<'a> {
+        self.dialect = dialect;
+        self
+    }
+
+    /// Allows identifiers made of any Unicode XID_Start and XID_Continue
+    /// characters, like `café`, which LuaJIT and some embedded dialects
+    /// accept. Standard Lua only allows ASCII letters, digits and `_`.
+    pub fn unicode_identifiers(mut self) -> Tokenizer<'a> {
+        self.unicode_identifiers = true;
+        self
+    }
+
+    /// Makes the tokenizer keep going after invalid input instead of failing,
+    /// turning the invalid text into [Error][TokenKind::Error] tokens. This is
+    /// useful for editors, which want to highlight the rest of a broken file.
+    pub fn tolerant(mut self) -> Tokenizer<'a> {
+        self.tolerant = true;
+        self
+    }
+
+    fn next_token(&mut self) -> Result<Option<Token<'a>>, TokenizeError> {
         let mut prefix = Vec::new();
 
+        if self.position.bytes == 0 && self.current.starts_with(BYTE_ORDER_MARK) {
+            self.current = &self.current[BYTE_ORDER_MARK.len_utf8()..];
+
+            // The mark is invisible, so it doesn't count towards the column.
+            self.position.bytes += BYTE_ORDER_MARK.len_utf8();
+
+            prefix.push(TokenPrefix::ByteOrderMark);
+        }
+
         loop {
-            if let Ok(result) = parse_whitespace(current, &current_position) {
-                current = result.rest;
-                current_position = result.new_position;
+            if let Ok(result) = parse_whitespace(self.current, &self.position) {
+                self.current = result.rest;
+                self.position = result.new_position;
 
                 prefix.push(TokenPrefix::Whitespace(result.contents.into()));
-            } else if let Ok((result, comment)) = parse_multi_line_comment(current, &current_position) {
-                current = result.rest;
-                current_position = result.new_position;
+                continue;
+            }
 
-                prefix.push(TokenPrefix::Comment(comment));
-            } else if let Ok((result, comment)) = parse_comment(current, &current_position) {
-                current = result.rest;
-                current_position = result.new_position;
+            // An unclosed multi-line comment would otherwise be read as a
+            // single-line one, and the error would show up somewhere later.
+            let comment = match parse_multi_line_comment(self.current, &self.position) {
+                Err(AdvanceError::NoMatch) => parse_comment(self.current, &self.position),
+                Err(AdvanceError::Error(error)) => return self.recover(prefix, error),
+                result => result,
+            };
 
-                prefix.push(TokenPrefix::Comment(comment));
-            } else {
-                break;
+            match comment {
+                Ok((result, comment)) => {
+                    self.current = result.rest;
+                    self.position = result.new_position;
+
+                    prefix.push(TokenPrefix::Comment(comment));
+                },
+                Err(_) => break,
             }
         }
 
-        if current.is_empty() {
-            if !prefix.is_empty() {
-                tokens.push(Token {
-                    prefix,
-                    kind: TokenKind::EndOfFile,
-                    start_position: current_position.clone(),
-                    end_position: current_position.clone(),
-                });
+        if self.current.is_empty() {
+            self.finished = true;
+
+            if prefix.is_empty() {
+                return Ok(None);
             }
 
-            break;
+            return Ok(Some(Token {
+                prefix,
+                kind: TokenKind::EndOfFile,
+                start_position: self.position,
+                end_position: self.position,
+            }));
         }
 
-        match tokenize_step(current, &current_position) {
+        match tokenize_step(self.current, &self.position, self.unicode_identifiers, self.dialect) {
             Ok((result, token_kind)) => {
-                tokens.push(Token {
+                let token = Token {
                     prefix,
                     kind: token_kind,
-                    start_position: current_position.clone(),
-                    end_position: result.new_position.clone(),
-                });
+                    start_position: self.position,
+                    end_position: result.new_position,
+                };
 
-                current = result.rest;
-                current_position = result.new_position;
+                self.current = result.rest;
+                self.position = result.new_position;
+
+                Ok(Some(token))
             },
-            Err(AdvanceError::Error(e)) => return Err(e),
+            Err(AdvanceError::Error(e)) => self.recover(prefix, e),
             Err(AdvanceError::NoMatch) => {
-                if !current.is_empty() {
-                    return Err(TokenizeError::UnknownSequence {
-                        position: current_position,
-                    });
-                }
+                let error = TokenizeError::UnknownSequence {
+                    position: self.position,
+                };
+
+                self.recover(prefix, error)
+            },
+        }
+    }
+
+    /// Skips past the invalid input that caused `error` and returns it as an
+    /// error token, or just returns the error if we aren't tolerant.
+    fn recover(&mut self, prefix: Vec<TokenPrefix<'a>>, error: TokenizeError) -> Result<Option<Token<'a>>, TokenizeError> {
+        if !self.tolerant {
+            return Err(error);
+        }
+
+        let length = match error {
+            TokenizeError::UnknownSequence { .. } => self.current.chars().next().map_or(0, char::len_utf8),
+
+            // Quoted strings can't span lines, so a broken one ends at the end
+            // of the line. Anything else, like a long string, eats the rest of
+            // the file, just like it would in Lua.
+            _ if self.current.starts_with('"') || self.current.starts_with('\'') => {
+                self.current.find(['\r', '\n']).unwrap_or(self.current.len())
             },
+            _ => self.current.len(),
+        };
+
+        let end_position = self.position.next_position(&self.current[..length]);
+        let token = Token {
+            prefix,
+            kind: TokenKind::Error {
+                error,
+                text: Cow::from(&self.current[..length]),
+            },
+            start_position: self.position,
+            end_position,
+        };
+
+        self.current = &self.current[length..];
+        self.position = end_position;
+
+        Ok(Some(token))
+    }
+}
+
+impl<'a> Iterator for Tokenizer<'a> {
+    type Item = Result<Token<'a>, TokenizeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
         }
+
+        let result = self.next_token();
+
+        if result.is_err() {
+            self.finished = true;
+        }
+
+        result.transpose()
     }
+}
+
+/// Tokenizes a source string completely and returns a [Vec][Vec] of [Tokens][Token].
+///
+/// # Errors
+/// Will return an [UnknownSequence][TokenizeError::UnknownSequence] if it
+/// encounters a sequence of characters that it cannot parse.
+pub fn tokenize<'a>(source: &'a str) -> Result<Vec<Token<'a>>, TokenizeError> {
+    Tokenizer::new(source).collect()
+}
+
+/// Reads all of `reader` into `source`, replacing what was there, and
+/// tokenizes it. This is a convenience for sources that don't start out as a
+/// string, like standard input. It doesn't stream: the whole source is read
+/// before tokenizing starts, and the tokens borrow from `source` instead of
+/// copying it.
+pub fn read_and_tokenize<'a, R: Read>(mut reader: R, source: &'a mut String) -> Result<Vec<Token<'a>>, ReadError> {
+    source.clear();
+    reader.read_to_string(source)?;
+
+    let source: &'a String = source;
+    Ok(tokenize(source)?)
+}
 
-    Ok(tokens)
+/// Tokenizes a source string completely, turning any invalid input into
+/// [Error][TokenKind::Error] tokens instead of failing.
+pub fn tokenize_tolerant<'a>(source: &'a str) -> Vec<Token<'a>> {
+    Tokenizer::new(source)
+        .tolerant()
+        .map(|token| token.expect("tolerant tokenizers don't return errors"))
+        .collect()
+}
+
+// Tokenizes `source` as Luau, for tests of its syntax, like type annotations.
+#[cfg(test)]
+pub fn tokenize_luau<'a>(source: &'a str) -> Vec<Token<'a>> {
+    Tokenizer::new(source).dialect(Dialect::Luau).collect::<Result<_, _>>().unwrap()
 }
 
 #[cfg(test)]
@@ -553,6 +1520,20 @@ mod tests {
         assert_eq!(kinds, expected);
     }
 
+    fn kinds(dialect: Dialect, input: &'static str) -> Vec<TokenKind<'static>> {
+        Tokenizer::new(input)
+            .dialect(dialect)
+            .map(|token| token.unwrap().kind)
+            .collect()
+    }
+
+    #[test]
+    fn symbol_display() {
+        assert_eq!(Symbol::NotEqual.to_string(), "~=");
+        assert_eq!(Symbol::End.to_string(), "end");
+        assert_eq!(TokenKind::Symbol(Symbol::DoubleSlash).to_string(), "`//`");
+    }
+
     #[test]
     fn keyword_vs_identifier() {
         test_kinds_eq("local", vec![TokenKind::Symbol(Symbol::Local)]);
@@ -562,27 +1543,215 @@ mod tests {
         test_kinds_eq("local _", vec![TokenKind::Symbol(Symbol::Local), TokenKind::Identifier("_".into())]);
     }
 
+    #[test]
+    fn keywords_are_symbols() {
+        assert!(Symbol::End.is_keyword());
+        assert!(Symbol::ElseIf.is_keyword());
+        assert!(!Symbol::Ellipse.is_keyword());
+        assert!(!Symbol::DoubleColon.is_keyword());
+
+        for symbol in SYMBOLS.iter().filter(|v| Dialect::default().has_keyword(**v)) {
+            test_kinds_eq(symbol.to_str(), vec![TokenKind::Symbol(*symbol)]);
+        }
+    }
+
+    #[test]
+    fn dialect_keywords() {
+        assert_eq!(kinds(Dialect::Lua51, "goto continue"), vec![
+            TokenKind::Identifier("goto".into()),
+            TokenKind::Identifier("continue".into()),
+        ]);
+        assert_eq!(kinds(Dialect::Lua52, "goto continue"), vec![
+            TokenKind::Symbol(Symbol::Goto),
+            TokenKind::Identifier("continue".into()),
+        ]);
+        assert_eq!(kinds(Dialect::Lua53, "goto continue"), vec![
+            TokenKind::Symbol(Symbol::Goto),
+            TokenKind::Identifier("continue".into()),
+        ]);
+        assert_eq!(kinds(Dialect::Luau, "goto continue end"), vec![
+            TokenKind::Identifier("goto".into()),
+            TokenKind::Symbol(Symbol::Continue),
+            TokenKind::Symbol(Symbol::End),
+        ]);
+    }
+
+    #[test]
+    fn luau_symbols() {
+        assert_eq!(kinds(Dialect::Luau, "x?->y"), vec![
+            TokenKind::Identifier("x".into()),
+            TokenKind::Symbol(Symbol::QuestionMark),
+            TokenKind::Symbol(Symbol::Arrow),
+            TokenKind::Identifier("y".into()),
+        ]);
+        assert_eq!(kinds(Dialect::Lua53, "x->y"), vec![
+            TokenKind::Identifier("x".into()),
+            TokenKind::Symbol(Symbol::Minus),
+            TokenKind::Symbol(Symbol::GreaterThan),
+            TokenKind::Identifier("y".into()),
+        ]);
+
+        assert!(Tokenizer::new("x?").next().unwrap().is_ok());
+        assert!(Tokenizer::new("x?").nth(1).unwrap().is_err());
+    }
+
+    #[test]
+    fn unicode_identifiers() {
+        let kinds = |input: &'static str| {
+            Tokenizer::new(input)
+                .unicode_identifiers()
+                .map(|token| token.unwrap().kind)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(kinds("local café = π"), vec![
+            TokenKind::Symbol(Symbol::Local),
+            TokenKind::Identifier("café".into()),
+            TokenKind::Symbol(Symbol::Equal),
+            TokenKind::Identifier("π".into()),
+        ]);
+        assert_eq!(kinds("_名前2 end"), vec![
+            TokenKind::Identifier("_名前2".into()),
+            TokenKind::Symbol(Symbol::End),
+        ]);
+
+        assert!(tokenize("local café").is_err());
+    }
+
+    #[test]
+    fn literal_keywords() {
+        test_kinds_eq("nil", vec![TokenKind::Symbol(Symbol::Nil)]);
+        test_kinds_eq("true", vec![TokenKind::Symbol(Symbol::True)]);
+        test_kinds_eq("false", vec![TokenKind::Symbol(Symbol::False)]);
+        test_kinds_eq("nil_", vec![TokenKind::Identifier("nil_".into())]);
+        test_kinds_eq("truely", vec![TokenKind::Identifier("truely".into())]);
+    }
+
+    #[test]
+    fn longest_symbol_wins() {
+        test_kinds_eq("...", vec![TokenKind::Symbol(Symbol::Ellipse)]);
+        test_kinds_eq("..", vec![TokenKind::Symbol(Symbol::TwoDots)]);
+        test_kinds_eq(". ..", vec![TokenKind::Symbol(Symbol::Dot), TokenKind::Symbol(Symbol::TwoDots)]);
+        test_kinds_eq("::", vec![TokenKind::Symbol(Symbol::DoubleColon)]);
+        test_kinds_eq(":", vec![TokenKind::Symbol(Symbol::Colon)]);
+        test_kinds_eq("<=", vec![TokenKind::Symbol(Symbol::LessThanEqual)]);
+        test_kinds_eq("==", vec![TokenKind::Symbol(Symbol::TwoEqual)]);
+        test_kinds_eq("= =", vec![TokenKind::Symbol(Symbol::Equal), TokenKind::Symbol(Symbol::Equal)]);
+        test_kinds_eq("//", vec![TokenKind::Symbol(Symbol::DoubleSlash)]);
+        test_kinds_eq("/ /", vec![TokenKind::Symbol(Symbol::Slash), TokenKind::Symbol(Symbol::Slash)]);
+        test_kinds_eq("<<", vec![TokenKind::Symbol(Symbol::ShiftLeft)]);
+        test_kinds_eq("<<=", vec![TokenKind::Symbol(Symbol::ShiftLeft), TokenKind::Symbol(Symbol::Equal)]);
+        test_kinds_eq("< <", vec![TokenKind::Symbol(Symbol::LessThan), TokenKind::Symbol(Symbol::LessThan)]);
+        test_kinds_eq(">>", vec![TokenKind::Symbol(Symbol::ShiftRight)]);
+        test_kinds_eq(">=", vec![TokenKind::Symbol(Symbol::GreaterThanEqual)]);
+        test_kinds_eq("~=", vec![TokenKind::Symbol(Symbol::NotEqual)]);
+        test_kinds_eq("~ =", vec![TokenKind::Symbol(Symbol::Tilde), TokenKind::Symbol(Symbol::Equal)]);
+        test_kinds_eq("a&b|~c", vec![
+            TokenKind::Identifier("a".into()),
+            TokenKind::Symbol(Symbol::Ampersand),
+            TokenKind::Identifier("b".into()),
+            TokenKind::Symbol(Symbol::Pipe),
+            TokenKind::Symbol(Symbol::Tilde),
+            TokenKind::Identifier("c".into()),
+        ]);
+    }
+
     #[test]
     fn number_literals() {
         test_kinds_eq("6", vec![TokenKind::NumberLiteral("6".into())]);
         test_kinds_eq("0.231e-6", vec![TokenKind::NumberLiteral("0.231e-6".into())]);
-        test_kinds_eq("-123.7", vec![TokenKind::NumberLiteral("-123.7".into())]);
+        test_kinds_eq("123.7", vec![TokenKind::NumberLiteral("123.7".into())]);
         test_kinds_eq("0x12AfEE", vec![TokenKind::NumberLiteral("0x12AfEE".into())]);
-        test_kinds_eq("-0x123FFe", vec![TokenKind::NumberLiteral("-0x123FFe".into())]);
+        test_kinds_eq("0x123FFe", vec![TokenKind::NumberLiteral("0x123FFe".into())]);
         test_kinds_eq("1023.47e126", vec![TokenKind::NumberLiteral("1023.47e126".into())]);
     }
 
+    #[test]
+    fn scientific_number_literals() {
+        test_kinds_eq("1e10", vec![TokenKind::NumberLiteral("1e10".into())]);
+        test_kinds_eq("3.5E-2", vec![TokenKind::NumberLiteral("3.5E-2".into())]);
+        test_kinds_eq("2e+5", vec![TokenKind::NumberLiteral("2e+5".into())]);
+        test_kinds_eq(".5", vec![TokenKind::NumberLiteral(".5".into())]);
+        test_kinds_eq("a.b + .5e1", vec![
+            TokenKind::Identifier("a".into()),
+            TokenKind::Symbol(Symbol::Dot),
+            TokenKind::Identifier("b".into()),
+            TokenKind::Symbol(Symbol::Plus),
+            TokenKind::NumberLiteral(".5e1".into()),
+        ]);
+    }
+
+    #[test]
+    fn trailing_dot_number_literals() {
+        test_kinds_eq("5.", vec![TokenKind::NumberLiteral("5.".into())]);
+        test_kinds_eq("5.e3", vec![TokenKind::NumberLiteral("5.e3".into())]);
+        test_kinds_eq("0x1.", vec![TokenKind::NumberLiteral("0x1.".into())]);
+        test_kinds_eq("x = 10. + 1", vec![
+            TokenKind::Identifier("x".into()),
+            TokenKind::Symbol(Symbol::Equal),
+            TokenKind::NumberLiteral("10.".into()),
+            TokenKind::Symbol(Symbol::Plus),
+            TokenKind::NumberLiteral("1".into()),
+        ]);
+    }
+
+    #[test]
+    fn hex_number_literals() {
+        test_kinds_eq("0xFF", vec![TokenKind::NumberLiteral("0xFF".into())]);
+        test_kinds_eq("0XdeadBEEF", vec![TokenKind::NumberLiteral("0XdeadBEEF".into())]);
+        test_kinds_eq("0x0", vec![TokenKind::NumberLiteral("0x0".into())]);
+        test_kinds_eq("0xff+1", vec![
+            TokenKind::NumberLiteral("0xff".into()),
+            TokenKind::Symbol(Symbol::Plus),
+            TokenKind::NumberLiteral("1".into()),
+        ]);
+    }
+
+    #[test]
+    fn hex_float_literals() {
+        test_kinds_eq("0x1p-4", vec![TokenKind::NumberLiteral("0x1p-4".into())]);
+        test_kinds_eq("0xA.8p2", vec![TokenKind::NumberLiteral("0xA.8p2".into())]);
+        test_kinds_eq("0x.8", vec![TokenKind::NumberLiteral("0x.8".into())]);
+        test_kinds_eq("0x1.", vec![TokenKind::NumberLiteral("0x1.".into())]);
+        test_kinds_eq("0XA.8P+2", vec![TokenKind::NumberLiteral("0XA.8P+2".into())]);
+        test_kinds_eq("0x1.8p2..x", vec![
+            TokenKind::NumberLiteral("0x1.8p2".into()),
+            TokenKind::Symbol(Symbol::TwoDots),
+            TokenKind::Identifier("x".into()),
+        ]);
+    }
+
+    #[test]
+    fn negative_numbers_are_unary_minus() {
+        // Lua has no negative number literals; the minus is always an operator
+        // so that `a-1` and `-2^2` parse correctly.
+        test_kinds_eq("-123.7", vec![TokenKind::Symbol(Symbol::Minus), TokenKind::NumberLiteral("123.7".into())]);
+        test_kinds_eq("-0x123FFe", vec![TokenKind::Symbol(Symbol::Minus), TokenKind::NumberLiteral("0x123FFe".into())]);
+        test_kinds_eq("a-1", vec![
+            TokenKind::Identifier("a".into()),
+            TokenKind::Symbol(Symbol::Minus),
+            TokenKind::NumberLiteral("1".into()),
+        ]);
+    }
+
     #[test]
     fn string_literals() {
-        test_kinds_eq("\"\"", vec![TokenKind::StringLiteral(StringLiteral::DoubleQuote { raw_content: "".into() })]);
-        test_kinds_eq("\"hello\"", vec![TokenKind::StringLiteral(StringLiteral::DoubleQuote { raw_content: "hello".into() })]);
-        test_kinds_eq("\"he\\\"llo\"", vec![TokenKind::StringLiteral(StringLiteral::DoubleQuote { raw_content: "he\\\"llo".into() })]);
-        test_kinds_eq("\"he\\nllo\"", vec![TokenKind::StringLiteral(StringLiteral::DoubleQuote { raw_content: "he\\nllo".into() })]);
+        let double = |raw_content: &'static str, value: &'static [u8]| {
+            TokenKind::StringLiteral(StringLiteral::DoubleQuote { raw_content: raw_content.into(), value: value.into() })
+        };
+        let single = |raw_content: &'static str, value: &'static [u8]| {
+            TokenKind::StringLiteral(StringLiteral::SingleQuote { raw_content: raw_content.into(), value: value.into() })
+        };
+
+        test_kinds_eq("\"\"", vec![double("", b"")]);
+        test_kinds_eq("\"hello\"", vec![double("hello", b"hello")]);
+        test_kinds_eq("\"he\\\"llo\"", vec![double("he\\\"llo", b"he\"llo")]);
+        test_kinds_eq("\"he\\nllo\"", vec![double("he\\nllo", b"he\nllo")]);
 
-        test_kinds_eq("''", vec![TokenKind::StringLiteral(StringLiteral::SingleQuote { raw_content: "".into() })]);
-        test_kinds_eq("'hello'", vec![TokenKind::StringLiteral(StringLiteral::SingleQuote { raw_content: "hello".into() })]);
-        test_kinds_eq("'he\\'llo'", vec![TokenKind::StringLiteral(StringLiteral::SingleQuote { raw_content: "he\\'llo".into() })]);
-        test_kinds_eq("'he\\nllo'", vec![TokenKind::StringLiteral(StringLiteral::SingleQuote { raw_content: "he\\nllo".into() })]);
+        test_kinds_eq("''", vec![single("", b"")]);
+        test_kinds_eq("'hello'", vec![single("hello", b"hello")]);
+        test_kinds_eq("'he\\'llo'", vec![single("he\\'llo", b"he'llo")]);
+        test_kinds_eq("'he\\nllo'", vec![single("he\\nllo", b"he\nllo")]);
 
         assert_eq!(tokenize("\""), Err(TokenizeError::UnclosedString {
             position: SourcePosition {
@@ -609,6 +1778,119 @@ mod tests {
         }));
     }
 
+    #[test]
+    fn error_messages() {
+        assert_eq!(tokenize("x = \"abc").unwrap_err().to_string(), "Unterminated string starting at line 1, column 5");
+        assert_eq!(tokenize("x = 1\n  $").unwrap_err().to_string(), "Unknown sequence of characters at line 2, column 3");
+        assert_eq!(tokenize("x = \"abc").unwrap_err().code(), "P014");
+        assert_eq!(tokenize("x = '\\q'").unwrap_err().code(), "P011");
+    }
+
+    #[test]
+    fn unterminated_comments() {
+        assert_eq!(tokenize("x = 1\n--[[ never\nclosed ]=]\n").unwrap_err().to_string(), "Unterminated comment starting at line 2, column 1");
+        assert_eq!(tokenize("--[==[ ]] ]=]").unwrap_err(), TokenizeError::UnclosedComment {
+            position: SourcePosition {
+                bytes: 0,
+                line: 1,
+                column: 1,
+            },
+        });
+
+        // These aren't multi-line comments at all.
+        assert!(tokenize("--[ x\n--[=x\n-- [[ x").is_ok());
+
+        let tokens = tokenize_tolerant("x = 1 --[[ x\ny()");
+        assert_eq!(tokens.len(), 4);
+        assert_eq!(tokens[3].kind.source_text(), "--[[ x\ny()");
+    }
+
+    // The decoded contents of the string literal `source` starts with.
+    fn value(source: &'static str) -> Vec<u8> {
+        match tokenize(source).unwrap()[0].kind {
+            TokenKind::StringLiteral(ref literal) => literal.value().to_vec(),
+            ref other => panic!("expected a string, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn string_escapes() {
+        assert_eq!(value(r#""\a\b\f\n\r\t\v""#), b"\x07\x08\x0C\n\r\t\x0B");
+        assert_eq!(value(r#""\\ \" \'""#), b"\\ \" '");
+        assert_eq!(value(r#""\65\066\0671""#), b"ABC1");
+        assert_eq!(value(r#""\255""#), b"\xFF");
+        assert_eq!(value(r#""\x41\x6a""#), b"Aj");
+        assert_eq!(value("\"a\\z  \n\t b\""), b"ab");
+        assert_eq!(value("\"a\\z \x0B\x0C\r\nb\""), b"ab");
+        assert_eq!(value("\"a\\\nb\""), b"a\nb");
+        assert_eq!(value("\"a\\\r\nb\""), b"a\nb");
+        assert_eq!(value("'\u{e9}'"), "\u{e9}".as_bytes());
+
+        assert_eq!(tokenize(r#"x = "a\qb""#), Err(TokenizeError::InvalidEscape {
+            position: SourcePosition {
+                bytes: 6,
+                line: 1,
+                column: 7,
+            },
+        }));
+        assert!(tokenize(r#""\256""#).is_err());
+        assert!(tokenize(r#""\x4""#).is_err());
+        assert!(tokenize(r#""\xZZ""#).is_err());
+        assert_eq!(tokenize("\"a\\"), Err(TokenizeError::UnclosedString {
+            position: SourcePosition {
+                bytes: 0,
+                line: 1,
+                column: 1,
+            },
+        }));
+    }
+
+    #[test]
+    fn unicode_escapes() {
+        assert_eq!(value(r#""\u{41}""#), b"A");
+        assert_eq!(value(r#""\u{e9}\u{00E9}""#), "\u{e9}\u{e9}".as_bytes());
+        assert_eq!(value(r#""\u{1F600}""#), "\u{1F600}".as_bytes());
+        assert_eq!(value(r#""\u{10FFFF}""#), "\u{10FFFF}".as_bytes());
+        assert_eq!(value(r#""\u{D800}""#), b"\xED\xA0\x80");
+
+        for source in &[r#""\u{110000}""#, r#""\u{}""#, r#""\u41""#, r#""\u{41""#, r#""\u{4G}""#] {
+            assert_eq!(tokenize(source), Err(TokenizeError::InvalidUnicodeEscape {
+                position: SourcePosition {
+                    bytes: 1,
+                    line: 1,
+                    column: 2,
+                },
+            }), "{}", source);
+        }
+    }
+
+    #[test]
+    fn long_string_literals() {
+        test_kinds_eq("[[]]", vec![TokenKind::StringLiteral(StringLiteral::LongForm { raw_content: "".into(), depth: 0 })]);
+        test_kinds_eq("[[a]b]]", vec![TokenKind::StringLiteral(StringLiteral::LongForm { raw_content: "a]b".into(), depth: 0 })]);
+        test_kinds_eq("[==[a]]b]=]]==]", vec![TokenKind::StringLiteral(StringLiteral::LongForm { raw_content: "a]]b]=]".into(), depth: 2 })]);
+        test_kinds_eq("[[\nhello\n]]", vec![TokenKind::StringLiteral(StringLiteral::LongForm { raw_content: "\nhello\n".into(), depth: 0 })]);
+        test_kinds_eq("[ [", vec![TokenKind::Symbol(Symbol::LeftBracket), TokenKind::Symbol(Symbol::LeftBracket)]);
+
+        assert_eq!(tokenize("x = [=[hello]]"), Err(TokenizeError::UnclosedString {
+            position: SourcePosition {
+                bytes: 4,
+                line: 1,
+                column: 5,
+            },
+        }));
+    }
+
+    #[test]
+    fn long_string_value() {
+        let value = |raw_content: &'static str| StringLiteral::LongForm { raw_content: raw_content.into(), depth: 0 }.value().to_vec();
+
+        assert_eq!(value("hello"), b"hello");
+        assert_eq!(value("\nhello\n"), b"hello\n");
+        assert_eq!(value("\r\nhello"), b"hello");
+        assert_eq!(value("\n\nhello"), b"\nhello");
+    }
+
     #[test]
     fn whitespace() {
         let input = "  local";
@@ -628,6 +1910,136 @@ mod tests {
         assert_eq!(first_token.prefix, &[]);
     }
 
+    #[test]
+    fn comments_are_trivia() {
+        let input = "-- first\r\n--[==[ second ]==]\nlocal -- third";
+        let tokenized = tokenize(input).unwrap();
+
+        assert_eq!(tokenized[0].kind, TokenKind::Symbol(Symbol::Local));
+        assert_eq!(tokenized[0].prefix, vec![
+            TokenPrefix::Comment(Comment::SingleLine { content: " first".into() }),
+            TokenPrefix::Whitespace("\r\n".into()),
+            TokenPrefix::Comment(Comment::MultiLine { content: " second ".into(), depth: 2 }),
+            TokenPrefix::Whitespace("\n".into()),
+        ]);
+        assert_eq!(tokenized[0].comments().count(), 2);
+
+        // Comments at the end of the file are attached to an EndOfFile token
+        assert_eq!(tokenized[1].kind, TokenKind::EndOfFile);
+        assert_eq!(tokenized[1].comments().collect::<Vec<_>>(), vec![&Comment::SingleLine { content: " third".into() }]);
+    }
+
+    #[test]
+    fn trivia_round_trips() {
+        let input = "--!strict\n  --[[ one\ntwo ]] local x -- three\r\n\t--[=[]=]";
+        let tokenized = tokenize(input).unwrap();
+
+        let mut output = String::new();
+        for token in &tokenized {
+            for item in &token.prefix {
+                output += &item.to_string();
+            }
+
+            output += &input[token.span()];
+        }
+
+        assert_eq!(output, input);
+    }
+
+    #[test]
+    fn tokenizer_is_lazy() {
+        let mut tokenizer = Tokenizer::new("local x = @");
+
+        assert_eq!(tokenizer.next().unwrap().unwrap().kind, TokenKind::Symbol(Symbol::Local));
+        assert_eq!(tokenizer.next().unwrap().unwrap().kind, TokenKind::Identifier("x".into()));
+        assert_eq!(tokenizer.next().unwrap().unwrap().kind, TokenKind::Symbol(Symbol::Equal));
+        assert!(tokenizer.next().unwrap().is_err());
+        assert!(tokenizer.next().is_none());
+    }
+
+    #[test]
+    fn tolerant_tokenization() {
+        let input = "local s = @ 'abc\\q\nx = [[never closed\ny";
+        let tokenized = tokenize_tolerant(input);
+        let kinds = tokenized.iter().map(|token| token.kind.clone()).collect::<Vec<_>>();
+
+        assert_eq!(kinds, vec![
+            TokenKind::Symbol(Symbol::Local),
+            TokenKind::Identifier("s".into()),
+            TokenKind::Symbol(Symbol::Equal),
+            TokenKind::Error {
+                error: TokenizeError::UnknownSequence {
+                    position: SourcePosition { bytes: 10, line: 1, column: 11 },
+                },
+                text: "@".into(),
+            },
+            TokenKind::Error {
+                error: TokenizeError::InvalidEscape {
+                    position: SourcePosition { bytes: 16, line: 1, column: 17 },
+                },
+                text: "'abc\\q".into(),
+            },
+            TokenKind::Identifier("x".into()),
+            TokenKind::Symbol(Symbol::Equal),
+            TokenKind::Error {
+                error: TokenizeError::UnclosedString {
+                    position: SourcePosition { bytes: 23, line: 2, column: 5 },
+                },
+                text: "[[never closed\ny".into(),
+            },
+        ]);
+    }
+
+    #[test]
+    fn byte_order_mark() {
+        let input = "\u{FEFF}local x";
+        let tokenized = tokenize(input).unwrap();
+
+        assert_eq!(tokenized[0].kind, TokenKind::Symbol(Symbol::Local));
+        assert_eq!(tokenized[0].prefix, vec![TokenPrefix::ByteOrderMark]);
+        assert_eq!(tokenized[0].start_position, SourcePosition { bytes: 3, line: 1, column: 1 });
+        assert_eq!(&input[tokenized[0].span()], "local");
+
+        assert_eq!(tokenize("\u{FEFF}").unwrap()[0].kind, TokenKind::EndOfFile);
+
+        // Only a mark at the very start of the file is allowed
+        assert!(tokenize("local \u{FEFF}x").is_err());
+    }
+
+    #[test]
+    fn source_text_round_trips() {
+        let input = "local x = { 0x1P4, 1e+2, 'a\\'b', \"\\65\", [==[\n]]]==] } ~= @";
+
+        let mut output = String::new();
+        for token in tokenize_tolerant(input) {
+            for item in &token.prefix {
+                output += &item.to_string();
+            }
+
+            output += &token.kind.source_text();
+        }
+
+        assert_eq!(output, input);
+    }
+
+    #[test]
+    fn read_and_tokenize_reader() {
+        let input = "-- hi\nlocal x = 'y'";
+        let mut source = "old text".to_string();
+        let tokens = read_and_tokenize(input.as_bytes(), &mut source).unwrap();
+        assert_eq!(tokens, tokenize(input).unwrap());
+
+        match read_and_tokenize(&b"local @"[..], &mut String::new()) {
+            Err(ReadError::Tokenize(TokenizeError::UnknownSequence { .. })) => {},
+            other => panic!("expected a tokenize error, got {:?}", other),
+        }
+
+        match read_and_tokenize(&b"local \xFF"[..], &mut String::new()) {
+            Err(ReadError::Io(ref error)) if error.kind() == io::ErrorKind::InvalidData => {},
+            other => panic!("expected an invalid data error, got {:?}", other),
+        }
+    }
+
     #[test]
     fn get_new_line_info() {
         let position = SourcePosition {
@@ -645,6 +2057,21 @@ mod tests {
         assert_eq!(new_position.bytes, 10);
         assert_eq!(new_position.line, 2);
         assert_eq!(new_position.column, 5);
+
+        let new_position = position.next_position("test\n");
+        assert_eq!(new_position.bytes, 5);
+        assert_eq!(new_position.line, 2);
+        assert_eq!(new_position.column, 1);
+    }
+
+    #[test]
+    fn token_spans() {
+        let input = "local s = \"h\\\"i\" -- done\n";
+        let tokenized = tokenize(input).unwrap();
+        let texts = tokenized.iter().map(|token| &input[token.span()]).collect::<Vec<_>>();
+
+        assert_eq!(texts, vec!["local", "s", "=", "\"h\\\"i\"", ""]);
+        assert_eq!(tokenized.last().unwrap().span(), input.len()..input.len());
     }
 
     #[test]
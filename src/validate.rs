@@ -0,0 +1,218 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use ast::*;
+use visit::{self, Visitor};
+
+/// What's wrong with a node found by [validate][validate].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationErrorKind {
+    /// Another node already has this node's ID. Nodes with
+    /// [NodeId::DUMMY][NodeId::DUMMY] are allowed to share it.
+    DuplicateId,
+
+    /// A `break` that isn't inside a loop in the same function.
+    BreakOutsideLoop,
+
+    /// A `...` inside a function that wasn't declared with `...`.
+    VarArgOutsideVarArgFunction,
+
+    /// An assignment to something other than a name, field, or index.
+    InvalidAssignmentTarget,
+
+    /// A function declaration with no name at all.
+    EmptyFunctionName,
+}
+
+/// A problem with a tree, and the ID of the statement or expression it's in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationError {
+    pub kind: ValidationErrorKind,
+    pub id: NodeId,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let message = match self.kind {
+            ValidationErrorKind::DuplicateId => "ID is used by more than one node",
+            ValidationErrorKind::BreakOutsideLoop => "`break` outside of a loop",
+            ValidationErrorKind::VarArgOutsideVarArgFunction => "`...` outside of a function that takes `...`",
+            ValidationErrorKind::InvalidAssignmentTarget => "can only assign to names, fields, and indexes",
+            ValidationErrorKind::EmptyFunctionName => "function declaration has no name",
+        };
+
+        write!(f, "{} (node {})", message, self.id)
+    }
+}
+
+/// Checks the things about a tree that the parser always gets right, but that
+/// hand-built or deserialized trees might not. Errors are listed in the order
+/// their nodes appear in.
+pub fn validate(chunk: &Chunk) -> Vec<ValidationError> {
+    let mut validator = Validator {
+        errors: Vec::new(),
+        seen_ids: HashSet::new(),
+        // The main chunk is always variadic.
+        functions: vec![Function { is_variadic: true, loop_depth: 0 }],
+    };
+
+    validator.visit_chunk(chunk);
+    validator.errors
+}
+
+struct Function {
+    is_variadic: bool,
+    loop_depth: usize,
+}
+
+struct Validator {
+    errors: Vec<ValidationError>,
+    seen_ids: HashSet<NodeId>,
+
+    /// The functions we're inside of, innermost last.
+    functions: Vec<Function>,
+}
+
+impl Validator {
+    fn error(&mut self, kind: ValidationErrorKind, id: NodeId) {
+        self.errors.push(ValidationError { kind, id });
+    }
+
+    fn check_id(&mut self, id: NodeId) {
+        if id != NodeId::DUMMY && !self.seen_ids.insert(id) {
+            self.error(ValidationErrorKind::DuplicateId, id);
+        }
+    }
+
+    fn function(&mut self) -> &mut Function {
+        self.functions.last_mut().expect("the main chunk is always a function")
+    }
+
+    fn in_loop<F: FnOnce(&mut Validator)>(&mut self, walk: F) {
+        self.function().loop_depth += 1;
+        walk(self);
+        self.function().loop_depth -= 1;
+    }
+}
+
+impl<'a> Visitor<'a> for Validator {
+    fn visit_statement(&mut self, statement: &'a Statement<'a>) {
+        self.check_id(statement.id);
+
+        if let StatementKind::Break = statement.kind {
+            if self.function().loop_depth == 0 {
+                self.error(ValidationErrorKind::BreakOutsideLoop, statement.id);
+            }
+        }
+
+        if let StatementKind::Assignment(ref assignment) = statement.kind {
+            let invalid = assignment.targets.iter().any(|target| {
+                !matches!(target.kind, ExpressionKind::Name(_) | ExpressionKind::FieldAccess(_) | ExpressionKind::Index(_))
+            });
+
+            if invalid {
+                self.error(ValidationErrorKind::InvalidAssignmentTarget, statement.id);
+            }
+        }
+
+        if let StatementKind::FunctionDeclaration(ref declaration) = statement.kind {
+            if declaration.name.path.is_empty() {
+                self.error(ValidationErrorKind::EmptyFunctionName, statement.id);
+            }
+        }
+
+        visit::walk_statement(self, statement);
+    }
+
+    fn visit_expression(&mut self, expression: &'a Expression<'a>) {
+        self.check_id(expression.id);
+
+        if let ExpressionKind::VarArg = expression.kind {
+            if !self.function().is_variadic {
+                self.error(ValidationErrorKind::VarArgOutsideVarArgFunction, expression.id);
+            }
+        }
+
+        visit::walk_expression(self, expression);
+    }
+
+    fn visit_function_declaration(&mut self, declaration: &'a FunctionDeclaration<'a>) {
+        self.functions.push(Function {
+            is_variadic: declaration.is_variadic,
+            loop_depth: 0,
+        });
+
+        visit::walk_function_declaration(self, declaration);
+        self.functions.pop();
+    }
+
+    fn visit_numeric_for(&mut self, numeric_for: &'a NumericFor<'a>) {
+        self.in_loop(|this| visit::walk_numeric_for(this, numeric_for));
+    }
+
+    fn visit_generic_for(&mut self, generic_for: &'a GenericFor<'a>) {
+        self.in_loop(|this| visit::walk_generic_for(this, generic_for));
+    }
+
+    fn visit_while_loop(&mut self, while_loop: &'a WhileLoop<'a>) {
+        self.in_loop(|this| visit::walk_while_loop(this, while_loop));
+    }
+
+    fn visit_repeat_loop(&mut self, repeat_loop: &'a RepeatLoop<'a>) {
+        self.in_loop(|this| visit::walk_repeat_loop(this, repeat_loop));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use builder::{chunk, expr, stmt};
+    use tokenizer::tokenize;
+    use parser::parse_from_tokens;
+
+    fn errors(chunk: &Chunk) -> Vec<ValidationErrorKind> {
+        validate(chunk).into_iter().map(|error| error.kind).collect()
+    }
+
+    #[test]
+    fn parsed_trees_are_valid() {
+        let tokens = tokenize("
+            local function f(...) return ... end
+            while x do if y then break end end
+            for i = 1, 2 do repeat break until z end
+            print(...)
+            a.b, c[d] = 1, 2
+        ").unwrap();
+
+        assert_eq!(errors(&parse_from_tokens(&tokens).unwrap()), Vec::new());
+    }
+
+    #[test]
+    fn finds_errors() {
+        use self::ValidationErrorKind::*;
+
+        assert_eq!(errors(&chunk(vec![stmt::brk()])), vec![BreakOutsideLoop]);
+        assert_eq!(errors(&chunk(vec![
+            stmt::while_loop(expr::bool(true), [stmt::local_function("f", Vec::<&str>::new(), [stmt::brk()])]),
+        ])), vec![BreakOutsideLoop]);
+
+        assert_eq!(errors(&chunk(vec![stmt::ret([expr::vararg()])])), Vec::new());
+        assert_eq!(errors(&chunk(vec![
+            stmt::function(["f"], ["x"], [stmt::ret([expr::vararg()])]),
+        ])), vec![VarArgOutsideVarArgFunction]);
+
+        assert_eq!(errors(&chunk(vec![stmt::assign([expr::num("1")], [expr::nil()])])), vec![InvalidAssignmentTarget]);
+        assert_eq!(errors(&chunk(vec![stmt::function(Vec::<&str>::new(), ["x"], vec![])])), vec![EmptyFunctionName]);
+
+        let mut statement = stmt::call(expr::name("f"), [expr::name("x")]);
+        statement.id = NodeId::new(1).unwrap();
+        let mut other = statement.clone();
+        other.id = NodeId::new(2).unwrap();
+        let mut duplicate = statement.clone();
+        duplicate.id = NodeId::new(2).unwrap();
+
+        let errors = validate(&chunk(vec![statement, other, duplicate]));
+        assert_eq!(errors, vec![ValidationError { kind: DuplicateId, id: NodeId::new(2).unwrap() }]);
+        assert_eq!(errors[0].to_string(), "ID is used by more than one node (node 2)");
+    }
+}
@@ -0,0 +1,762 @@
+//! Walking the AST without writing out the whole recursive match each time.
+//!
+//! A [Visitor][Visitor] has a method for every kind of node, each of which
+//! walks into that node's children by default. Override the methods for the
+//! nodes you care about, and call the matching `walk_*` function from them to
+//! keep going into their children:
+//!
+//! ```
+//! use mab::ast::*;
+//! use mab::visit::{self, Visitor};
+//!
+//! #[derive(Default)]
+//! struct CallCounter {
+//!     calls: usize,
+//! }
+//!
+//! impl<'a> Visitor<'a> for CallCounter {
+//!     fn visit_function_call(&mut self, call: &'a FunctionCall<'a>) {
+//!         self.calls += 1;
+//!         visit::walk_function_call(self, call);
+//!     }
+//! }
+//!
+//! let tokens = mab::tokenize("print(f(x))").unwrap();
+//! let chunk = mab::parse_from_tokens(&tokens).unwrap();
+//!
+//! let mut counter = CallCounter::default();
+//! counter.visit_chunk(&chunk);
+//! assert_eq!(counter.calls, 2);
+//! ```
+//!
+//! Nodes are borrowed for `'a`, so visitors can hold on to the nodes they
+//! find.
+//!
+//! [VisitorMut][VisitorMut] is the same, but with mutable access to every node
+//! so that passes can rewrite the tree in place. Its methods and walk functions
+//! end in `_mut`, so one type can implement both traits.
+//!
+//! For simple searches, [Chunk::descendants][::ast::Chunk::descendants] lists
+//! every node without needing a visitor at all.
+
+use std::vec;
+
+use ast::*;
+
+pub trait Visitor<'a> {
+    fn visit_chunk(&mut self, chunk: &'a Chunk<'a>) {
+        walk_chunk(self, chunk);
+    }
+
+    fn visit_statement(&mut self, statement: &'a Statement<'a>) {
+        walk_statement(self, statement);
+    }
+
+    fn visit_expression(&mut self, expression: &'a Expression<'a>) {
+        walk_expression(self, expression);
+    }
+
+    fn visit_assignment(&mut self, assignment: &'a Assignment<'a>) {
+        walk_assignment(self, assignment);
+    }
+
+    fn visit_local_assignment(&mut self, local_assignment: &'a LocalAssignment<'a>) {
+        walk_local_assignment(self, local_assignment);
+    }
+
+    /// Called for calls both as statements and as expressions.
+    fn visit_function_call(&mut self, call: &'a FunctionCall<'a>) {
+        walk_function_call(self, call);
+    }
+
+    fn visit_function_arguments(&mut self, arguments: &'a FunctionArguments<'a>) {
+        walk_function_arguments(self, arguments);
+    }
+
+    fn visit_numeric_for(&mut self, numeric_for: &'a NumericFor<'a>) {
+        walk_numeric_for(self, numeric_for);
+    }
+
+    fn visit_generic_for(&mut self, generic_for: &'a GenericFor<'a>) {
+        walk_generic_for(self, generic_for);
+    }
+
+    fn visit_if_statement(&mut self, if_statement: &'a IfStatement<'a>) {
+        walk_if_statement(self, if_statement);
+    }
+
+    fn visit_do_block(&mut self, do_block: &'a DoBlock<'a>) {
+        walk_do_block(self, do_block);
+    }
+
+    fn visit_while_loop(&mut self, while_loop: &'a WhileLoop<'a>) {
+        walk_while_loop(self, while_loop);
+    }
+
+    fn visit_repeat_loop(&mut self, repeat_loop: &'a RepeatLoop<'a>) {
+        walk_repeat_loop(self, repeat_loop);
+    }
+
+    fn visit_function_declaration(&mut self, function_declaration: &'a FunctionDeclaration<'a>) {
+        walk_function_declaration(self, function_declaration);
+    }
+
+    fn visit_goto(&mut self, _goto: &'a Goto<'a>) {}
+
+    fn visit_label(&mut self, _label: &'a Label<'a>) {}
+
+    fn visit_return(&mut self, return_statement: &'a Return<'a>) {
+        walk_return(self, return_statement);
+    }
+
+    fn visit_table_literal(&mut self, table: &'a TableLiteral<'a>) {
+        walk_table_literal(self, table);
+    }
+
+    fn visit_field_access(&mut self, field_access: &'a FieldAccess<'a>) {
+        walk_field_access(self, field_access);
+    }
+
+    fn visit_index(&mut self, index: &'a Index<'a>) {
+        walk_index(self, index);
+    }
+
+    fn visit_unary_op(&mut self, unary_op: &'a UnaryOp<'a>) {
+        walk_unary_op(self, unary_op);
+    }
+
+    fn visit_binary_op(&mut self, binary_op: &'a BinaryOp<'a>) {
+        walk_binary_op(self, binary_op);
+    }
+}
+
+pub fn walk_chunk<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, chunk: &'a Chunk<'a>) {
+    for statement in &chunk.statements {
+        visitor.visit_statement(statement);
+    }
+}
+
+pub fn walk_statement<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, statement: &'a Statement<'a>) {
+    match statement.kind {
+        StatementKind::Assignment(ref value) => visitor.visit_assignment(value),
+        StatementKind::LocalAssignment(ref value) => visitor.visit_local_assignment(value),
+        StatementKind::FunctionCall(ref value) => visitor.visit_function_call(value),
+        StatementKind::NumericFor(ref value) => visitor.visit_numeric_for(value),
+        StatementKind::GenericFor(ref value) => visitor.visit_generic_for(value),
+        StatementKind::IfStatement(ref value) => visitor.visit_if_statement(value),
+        StatementKind::DoBlock(ref value) => visitor.visit_do_block(value),
+        StatementKind::WhileLoop(ref value) => visitor.visit_while_loop(value),
+        StatementKind::RepeatLoop(ref value) => visitor.visit_repeat_loop(value),
+        StatementKind::FunctionDeclaration(ref value) => visitor.visit_function_declaration(value),
+        StatementKind::Goto(ref value) => visitor.visit_goto(value),
+        StatementKind::Label(ref value) => visitor.visit_label(value),
+        StatementKind::Return(ref value) => visitor.visit_return(value),
+        StatementKind::Break | StatementKind::Empty => {},
+    }
+}
+
+pub fn walk_expression<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, expression: &'a Expression<'a>) {
+    match expression.kind {
+        ExpressionKind::Table(ref value) => visitor.visit_table_literal(value),
+        ExpressionKind::FunctionCall(ref value) => visitor.visit_function_call(value),
+        ExpressionKind::FieldAccess(ref value) => visitor.visit_field_access(value),
+        ExpressionKind::Index(ref value) => visitor.visit_index(value),
+        ExpressionKind::ParenExpression(ref value) => visitor.visit_expression(value),
+        ExpressionKind::UnaryOp(ref value) => visitor.visit_unary_op(value),
+        ExpressionKind::BinaryOp(ref value) => visitor.visit_binary_op(value),
+        ExpressionKind::Nil | ExpressionKind::Bool(_) | ExpressionKind::Number(_) |
+        ExpressionKind::String(_) | ExpressionKind::VarArg | ExpressionKind::Name(_) => {},
+    }
+}
+
+pub fn walk_assignment<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, assignment: &'a Assignment<'a>) {
+    for target in &assignment.targets {
+        visitor.visit_expression(target);
+    }
+
+    for value in &assignment.values {
+        visitor.visit_expression(value);
+    }
+}
+
+pub fn walk_local_assignment<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, local_assignment: &'a LocalAssignment<'a>) {
+    for value in &local_assignment.values {
+        visitor.visit_expression(value);
+    }
+}
+
+pub fn walk_function_call<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, call: &'a FunctionCall<'a>) {
+    visitor.visit_expression(&call.name_expression);
+    visitor.visit_function_arguments(&call.arguments);
+}
+
+pub fn walk_function_arguments<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, arguments: &'a FunctionArguments<'a>) {
+    match *arguments {
+        FunctionArguments::Parenthesized(ref values) => {
+            for value in values {
+                visitor.visit_expression(value);
+            }
+        },
+        FunctionArguments::Table(ref table) => visitor.visit_table_literal(table),
+        FunctionArguments::String(_) => {},
+    }
+}
+
+pub fn walk_numeric_for<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, numeric_for: &'a NumericFor<'a>) {
+    visitor.visit_expression(&numeric_for.start);
+    visitor.visit_expression(&numeric_for.end);
+
+    if let Some(ref step) = numeric_for.step {
+        visitor.visit_expression(step);
+    }
+
+    visitor.visit_chunk(&numeric_for.body);
+}
+
+pub fn walk_generic_for<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, generic_for: &'a GenericFor<'a>) {
+    for item_source in &generic_for.item_source {
+        visitor.visit_expression(item_source);
+    }
+
+    visitor.visit_chunk(&generic_for.body);
+}
+
+pub fn walk_if_statement<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, if_statement: &'a IfStatement<'a>) {
+    visitor.visit_expression(&if_statement.condition);
+    visitor.visit_chunk(&if_statement.body);
+
+    for (condition, body) in &if_statement.else_if_branches {
+        visitor.visit_expression(condition);
+        visitor.visit_chunk(body);
+    }
+
+    if let Some(ref else_branch) = if_statement.else_branch {
+        visitor.visit_chunk(else_branch);
+    }
+}
+
+pub fn walk_do_block<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, do_block: &'a DoBlock<'a>) {
+    visitor.visit_chunk(&do_block.body);
+}
+
+pub fn walk_while_loop<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, while_loop: &'a WhileLoop<'a>) {
+    visitor.visit_expression(&while_loop.condition);
+    visitor.visit_chunk(&while_loop.body);
+}
+
+/// The body comes first, since that's the order they're run in and the
+/// condition can see the body's locals.
+pub fn walk_repeat_loop<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, repeat_loop: &'a RepeatLoop<'a>) {
+    visitor.visit_chunk(&repeat_loop.body);
+    visitor.visit_expression(&repeat_loop.condition);
+}
+
+pub fn walk_function_declaration<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, function_declaration: &'a FunctionDeclaration<'a>) {
+    visitor.visit_chunk(&function_declaration.body);
+}
+
+pub fn walk_return<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, return_statement: &'a Return<'a>) {
+    for value in &return_statement.values {
+        visitor.visit_expression(value);
+    }
+}
+
+pub fn walk_table_literal<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, table: &'a TableLiteral<'a>) {
+    for (key, value) in &table.items {
+        if let Some(TableKey::Expression(ref key)) = *key {
+            visitor.visit_expression(key);
+        }
+
+        visitor.visit_expression(value);
+    }
+}
+
+pub fn walk_field_access<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, field_access: &'a FieldAccess<'a>) {
+    visitor.visit_expression(&field_access.target);
+}
+
+pub fn walk_index<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, index: &'a Index<'a>) {
+    visitor.visit_expression(&index.target);
+    visitor.visit_expression(&index.key);
+}
+
+pub fn walk_unary_op<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, unary_op: &'a UnaryOp<'a>) {
+    visitor.visit_expression(&unary_op.argument);
+}
+
+pub fn walk_binary_op<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, binary_op: &'a BinaryOp<'a>) {
+    visitor.visit_expression(&binary_op.left);
+    visitor.visit_expression(&binary_op.right);
+}
+
+/// Every statement and expression in a chunk, parents before their children,
+/// in the order they appear in the source.
+///
+/// ```
+/// use mab::ast::{ExpressionKind, NodeRef};
+///
+/// let tokens = mab::tokenize("local json = require('json') print(x)").unwrap();
+/// let chunk = mab::parse_from_tokens(&tokens).unwrap();
+///
+/// let requires = chunk.descendants()
+///     .filter(|node| match *node {
+///         NodeRef::Expression(expression) => match expression.kind {
+///             ExpressionKind::FunctionCall(ref call) => match call.name_expression.kind {
+///                 ExpressionKind::Name(ref name) => name == "require",
+///                 _ => false,
+///             },
+///             _ => false,
+///         },
+///         _ => false,
+///     })
+///     .count();
+///
+/// assert_eq!(requires, 1);
+/// ```
+pub struct Descendants<'a> {
+    nodes: vec::IntoIter<NodeRef<'a>>,
+}
+
+impl<'a> Iterator for Descendants<'a> {
+    type Item = NodeRef<'a>;
+
+    fn next(&mut self) -> Option<NodeRef<'a>> {
+        self.nodes.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.nodes.size_hint()
+    }
+}
+
+impl<'a> Chunk<'a> {
+    pub fn descendants(&'a self) -> Descendants<'a> {
+        struct Collector<'a>(Vec<NodeRef<'a>>);
+
+        impl<'a> Visitor<'a> for Collector<'a> {
+            fn visit_statement(&mut self, statement: &'a Statement<'a>) {
+                self.0.push(NodeRef::Statement(statement));
+                walk_statement(self, statement);
+            }
+
+            fn visit_expression(&mut self, expression: &'a Expression<'a>) {
+                self.0.push(NodeRef::Expression(expression));
+                walk_expression(self, expression);
+            }
+        }
+
+        let mut collector = Collector(Vec::new());
+        collector.visit_chunk(self);
+
+        Descendants {
+            nodes: collector.0.into_iter(),
+        }
+    }
+
+    /// Gives every node a new ID, counting up from 1 in the same order as
+    /// [descendants][Chunk::descendants]. The IDs only depend on the shape of
+    /// the tree, which makes them stable enough for snapshot tests. Parsed IDs
+    /// aren't, since they have gaps wherever the parser backed out.
+    pub fn renumber_ids(&mut self) {
+        struct Renumberer(u32);
+
+        impl Renumberer {
+            fn next_id(&mut self) -> NodeId {
+                self.0 += 1;
+                NodeId::new(self.0).expect("IDs start at 1")
+            }
+        }
+
+        impl<'a> VisitorMut<'a> for Renumberer {
+            fn visit_statement_mut(&mut self, statement: &mut Statement<'a>) {
+                statement.id = self.next_id();
+                walk_statement_mut(self, statement);
+            }
+
+            fn visit_expression_mut(&mut self, expression: &mut Expression<'a>) {
+                expression.id = self.next_id();
+                walk_expression_mut(self, expression);
+            }
+        }
+
+        Renumberer(0).visit_chunk_mut(self);
+    }
+}
+
+/// Like [Visitor][Visitor], but with mutable access to each node. Assigning
+/// to a node replaces it, which is how passes rewrite the tree.
+pub trait VisitorMut<'a> {
+    fn visit_chunk_mut(&mut self, chunk: &mut Chunk<'a>) {
+        walk_chunk_mut(self, chunk);
+    }
+
+    fn visit_statement_mut(&mut self, statement: &mut Statement<'a>) {
+        walk_statement_mut(self, statement);
+    }
+
+    fn visit_expression_mut(&mut self, expression: &mut Expression<'a>) {
+        walk_expression_mut(self, expression);
+    }
+
+    fn visit_assignment_mut(&mut self, assignment: &mut Assignment<'a>) {
+        walk_assignment_mut(self, assignment);
+    }
+
+    fn visit_local_assignment_mut(&mut self, local_assignment: &mut LocalAssignment<'a>) {
+        walk_local_assignment_mut(self, local_assignment);
+    }
+
+    /// Called for calls both as statements and as expressions.
+    fn visit_function_call_mut(&mut self, call: &mut FunctionCall<'a>) {
+        walk_function_call_mut(self, call);
+    }
+
+    fn visit_function_arguments_mut(&mut self, arguments: &mut FunctionArguments<'a>) {
+        walk_function_arguments_mut(self, arguments);
+    }
+
+    fn visit_numeric_for_mut(&mut self, numeric_for: &mut NumericFor<'a>) {
+        walk_numeric_for_mut(self, numeric_for);
+    }
+
+    fn visit_generic_for_mut(&mut self, generic_for: &mut GenericFor<'a>) {
+        walk_generic_for_mut(self, generic_for);
+    }
+
+    fn visit_if_statement_mut(&mut self, if_statement: &mut IfStatement<'a>) {
+        walk_if_statement_mut(self, if_statement);
+    }
+
+    fn visit_do_block_mut(&mut self, do_block: &mut DoBlock<'a>) {
+        walk_do_block_mut(self, do_block);
+    }
+
+    fn visit_while_loop_mut(&mut self, while_loop: &mut WhileLoop<'a>) {
+        walk_while_loop_mut(self, while_loop);
+    }
+
+    fn visit_repeat_loop_mut(&mut self, repeat_loop: &mut RepeatLoop<'a>) {
+        walk_repeat_loop_mut(self, repeat_loop);
+    }
+
+    fn visit_function_declaration_mut(&mut self, function_declaration: &mut FunctionDeclaration<'a>) {
+        walk_function_declaration_mut(self, function_declaration);
+    }
+
+    fn visit_goto_mut(&mut self, _goto: &mut Goto<'a>) {}
+
+    fn visit_label_mut(&mut self, _label: &mut Label<'a>) {}
+
+    fn visit_return_mut(&mut self, return_statement: &mut Return<'a>) {
+        walk_return_mut(self, return_statement);
+    }
+
+    fn visit_table_literal_mut(&mut self, table: &mut TableLiteral<'a>) {
+        walk_table_literal_mut(self, table);
+    }
+
+    fn visit_field_access_mut(&mut self, field_access: &mut FieldAccess<'a>) {
+        walk_field_access_mut(self, field_access);
+    }
+
+    fn visit_index_mut(&mut self, index: &mut Index<'a>) {
+        walk_index_mut(self, index);
+    }
+
+    fn visit_unary_op_mut(&mut self, unary_op: &mut UnaryOp<'a>) {
+        walk_unary_op_mut(self, unary_op);
+    }
+
+    fn visit_binary_op_mut(&mut self, binary_op: &mut BinaryOp<'a>) {
+        walk_binary_op_mut(self, binary_op);
+    }
+}
+
+pub fn walk_chunk_mut<'a, V: VisitorMut<'a> + ?Sized>(visitor: &mut V, chunk: &mut Chunk<'a>) {
+    for statement in &mut chunk.statements {
+        visitor.visit_statement_mut(statement);
+    }
+}
+
+pub fn walk_statement_mut<'a, V: VisitorMut<'a> + ?Sized>(visitor: &mut V, statement: &mut Statement<'a>) {
+    match statement.kind {
+        StatementKind::Assignment(ref mut value) => visitor.visit_assignment_mut(value),
+        StatementKind::LocalAssignment(ref mut value) => visitor.visit_local_assignment_mut(value),
+        StatementKind::FunctionCall(ref mut value) => visitor.visit_function_call_mut(value),
+        StatementKind::NumericFor(ref mut value) => visitor.visit_numeric_for_mut(value),
+        StatementKind::GenericFor(ref mut value) => visitor.visit_generic_for_mut(value),
+        StatementKind::IfStatement(ref mut value) => visitor.visit_if_statement_mut(value),
+        StatementKind::DoBlock(ref mut value) => visitor.visit_do_block_mut(value),
+        StatementKind::WhileLoop(ref mut value) => visitor.visit_while_loop_mut(value),
+        StatementKind::RepeatLoop(ref mut value) => visitor.visit_repeat_loop_mut(value),
+        StatementKind::FunctionDeclaration(ref mut value) => visitor.visit_function_declaration_mut(value),
+        StatementKind::Goto(ref mut value) => visitor.visit_goto_mut(value),
+        StatementKind::Label(ref mut value) => visitor.visit_label_mut(value),
+        StatementKind::Return(ref mut value) => visitor.visit_return_mut(value),
+        StatementKind::Break | StatementKind::Empty => {},
+    }
+}
+
+pub fn walk_expression_mut<'a, V: VisitorMut<'a> + ?Sized>(visitor: &mut V, expression: &mut Expression<'a>) {
+    match expression.kind {
+        ExpressionKind::Table(ref mut value) => visitor.visit_table_literal_mut(value),
+        ExpressionKind::FunctionCall(ref mut value) => visitor.visit_function_call_mut(value),
+        ExpressionKind::FieldAccess(ref mut value) => visitor.visit_field_access_mut(value),
+        ExpressionKind::Index(ref mut value) => visitor.visit_index_mut(value),
+        ExpressionKind::ParenExpression(ref mut value) => visitor.visit_expression_mut(value),
+        ExpressionKind::UnaryOp(ref mut value) => visitor.visit_unary_op_mut(value),
+        ExpressionKind::BinaryOp(ref mut value) => visitor.visit_binary_op_mut(value),
+        ExpressionKind::Nil | ExpressionKind::Bool(_) | ExpressionKind::Number(_) |
+        ExpressionKind::String(_) | ExpressionKind::VarArg | ExpressionKind::Name(_) => {},
+    }
+}
+
+pub fn walk_assignment_mut<'a, V: VisitorMut<'a> + ?Sized>(visitor: &mut V, assignment: &mut Assignment<'a>) {
+    for target in &mut assignment.targets {
+        visitor.visit_expression_mut(target);
+    }
+
+    for value in &mut assignment.values {
+        visitor.visit_expression_mut(value);
+    }
+}
+
+pub fn walk_local_assignment_mut<'a, V: VisitorMut<'a> + ?Sized>(visitor: &mut V, local_assignment: &mut LocalAssignment<'a>) {
+    for value in &mut local_assignment.values {
+        visitor.visit_expression_mut(value);
+    }
+}
+
+pub fn walk_function_call_mut<'a, V: VisitorMut<'a> + ?Sized>(visitor: &mut V, call: &mut FunctionCall<'a>) {
+    visitor.visit_expression_mut(&mut call.name_expression);
+    visitor.visit_function_arguments_mut(&mut call.arguments);
+}
+
+pub fn walk_function_arguments_mut<'a, V: VisitorMut<'a> + ?Sized>(visitor: &mut V, arguments: &mut FunctionArguments<'a>) {
+    match *arguments {
+        FunctionArguments::Parenthesized(ref mut values) => {
+            for value in values {
+                visitor.visit_expression_mut(value);
+            }
+        },
+        FunctionArguments::Table(ref mut table) => visitor.visit_table_literal_mut(table),
+        FunctionArguments::String(_) => {},
+    }
+}
+
+pub fn walk_numeric_for_mut<'a, V: VisitorMut<'a> + ?Sized>(visitor: &mut V, numeric_for: &mut NumericFor<'a>) {
+    visitor.visit_expression_mut(&mut numeric_for.start);
+    visitor.visit_expression_mut(&mut numeric_for.end);
+
+    if let Some(ref mut step) = numeric_for.step {
+        visitor.visit_expression_mut(step);
+    }
+
+    visitor.visit_chunk_mut(&mut numeric_for.body);
+}
+
+pub fn walk_generic_for_mut<'a, V: VisitorMut<'a> + ?Sized>(visitor: &mut V, generic_for: &mut GenericFor<'a>) {
+    for item_source in &mut generic_for.item_source {
+        visitor.visit_expression_mut(item_source);
+    }
+
+    visitor.visit_chunk_mut(&mut generic_for.body);
+}
+
+pub fn walk_if_statement_mut<'a, V: VisitorMut<'a> + ?Sized>(visitor: &mut V, if_statement: &mut IfStatement<'a>) {
+    visitor.visit_expression_mut(&mut if_statement.condition);
+    visitor.visit_chunk_mut(&mut if_statement.body);
+
+    for (condition, body) in &mut if_statement.else_if_branches {
+        visitor.visit_expression_mut(condition);
+        visitor.visit_chunk_mut(body);
+    }
+
+    if let Some(ref mut else_branch) = if_statement.else_branch {
+        visitor.visit_chunk_mut(else_branch);
+    }
+}
+
+pub fn walk_do_block_mut<'a, V: VisitorMut<'a> + ?Sized>(visitor: &mut V, do_block: &mut DoBlock<'a>) {
+    visitor.visit_chunk_mut(&mut do_block.body);
+}
+
+pub fn walk_while_loop_mut<'a, V: VisitorMut<'a> + ?Sized>(visitor: &mut V, while_loop: &mut WhileLoop<'a>) {
+    visitor.visit_expression_mut(&mut while_loop.condition);
+    visitor.visit_chunk_mut(&mut while_loop.body);
+}
+
+pub fn walk_repeat_loop_mut<'a, V: VisitorMut<'a> + ?Sized>(visitor: &mut V, repeat_loop: &mut RepeatLoop<'a>) {
+    visitor.visit_chunk_mut(&mut repeat_loop.body);
+    visitor.visit_expression_mut(&mut repeat_loop.condition);
+}
+
+pub fn walk_function_declaration_mut<'a, V: VisitorMut<'a> + ?Sized>(visitor: &mut V, function_declaration: &mut FunctionDeclaration<'a>) {
+    visitor.visit_chunk_mut(&mut function_declaration.body);
+}
+
+pub fn walk_return_mut<'a, V: VisitorMut<'a> + ?Sized>(visitor: &mut V, return_statement: &mut Return<'a>) {
+    for value in &mut return_statement.values {
+        visitor.visit_expression_mut(value);
+    }
+}
+
+pub fn walk_table_literal_mut<'a, V: VisitorMut<'a> + ?Sized>(visitor: &mut V, table: &mut TableLiteral<'a>) {
+    for (key, value) in &mut table.items {
+        if let Some(TableKey::Expression(ref mut key)) = *key {
+            visitor.visit_expression_mut(key);
+        }
+
+        visitor.visit_expression_mut(value);
+    }
+}
+
+pub fn walk_field_access_mut<'a, V: VisitorMut<'a> + ?Sized>(visitor: &mut V, field_access: &mut FieldAccess<'a>) {
+    visitor.visit_expression_mut(&mut field_access.target);
+}
+
+pub fn walk_index_mut<'a, V: VisitorMut<'a> + ?Sized>(visitor: &mut V, index: &mut Index<'a>) {
+    visitor.visit_expression_mut(&mut index.target);
+    visitor.visit_expression_mut(&mut index.key);
+}
+
+pub fn walk_unary_op_mut<'a, V: VisitorMut<'a> + ?Sized>(visitor: &mut V, unary_op: &mut UnaryOp<'a>) {
+    visitor.visit_expression_mut(&mut unary_op.argument);
+}
+
+pub fn walk_binary_op_mut<'a, V: VisitorMut<'a> + ?Sized>(visitor: &mut V, binary_op: &mut BinaryOp<'a>) {
+    visitor.visit_expression_mut(&mut binary_op.left);
+    visitor.visit_expression_mut(&mut binary_op.right);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokenizer::tokenize;
+    use parser::parse_from_tokens;
+
+    // Records every name it sees, in the order it sees them.
+    #[derive(Default)]
+    struct NameCollector<'a> {
+        names: Vec<&'a str>,
+    }
+
+    impl<'a> Visitor<'a> for NameCollector<'a> {
+        fn visit_expression(&mut self, expression: &'a Expression<'a>) {
+            if let ExpressionKind::Name(ref name) = expression.kind {
+                self.names.push(name);
+            }
+
+            walk_expression(self, expression);
+        }
+    }
+
+    fn names(source: &str) -> Vec<String> {
+        let tokens = tokenize(source).unwrap();
+        let chunk = parse_from_tokens(&tokens).unwrap();
+
+        let mut collector = NameCollector::default();
+        collector.visit_chunk(&chunk);
+        collector.names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn visits_every_expression() {
+        assert_eq!(names("a = b + -c"), vec!["a", "b", "c"]);
+        assert_eq!(names("local x = { a, [b] = c, d = e }"), vec!["a", "b", "c", "e"]);
+        assert_eq!(names("f(a)[b].c:d { e }"), vec!["f", "a", "b", "e"]);
+        assert_eq!(names("if a then b() elseif c then d() else e() end"), vec!["a", "b", "c", "d", "e"]);
+        assert_eq!(names("for i = a, b, c do d() end for k, v in e do f() end"), vec!["a", "b", "c", "d", "e", "f"]);
+        assert_eq!(names("while a do b() end repeat c() until d"), vec!["a", "b", "c", "d"]);
+        assert_eq!(names("local function f(x) return (x) end"), vec!["x"]);
+        assert_eq!(names("do goto a ::b:: end"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn overriding_stops_the_walk() {
+        struct SkipFunctions(usize);
+
+        impl<'a> Visitor<'a> for SkipFunctions {
+            fn visit_function_call(&mut self, _call: &'a FunctionCall<'a>) {
+                self.0 += 1;
+            }
+        }
+
+        let tokens = tokenize("f(g(h()))\nlocal x = f(1) + g(2)").unwrap();
+        let chunk = parse_from_tokens(&tokens).unwrap();
+
+        let mut visitor = SkipFunctions(0);
+        visitor.visit_chunk(&chunk);
+        assert_eq!(visitor.0, 3);
+    }
+
+    // Replaces `x` with `nil`, uppercases other names, and drops empty
+    // statements.
+    struct Rewriter;
+
+    impl<'a> VisitorMut<'a> for Rewriter {
+        fn visit_chunk_mut(&mut self, chunk: &mut Chunk<'a>) {
+            chunk.statements.retain(|statement| !matches!(statement.kind, StatementKind::Empty));
+            walk_chunk_mut(self, chunk);
+        }
+
+        fn visit_expression_mut(&mut self, expression: &mut Expression<'a>) {
+            let replacement = match expression.kind {
+                ExpressionKind::Name(ref name) if name == "x" => Some(ExpressionKind::Nil),
+                ExpressionKind::Name(ref name) => Some(ExpressionKind::Name(name.to_uppercase().into())),
+                _ => None,
+            };
+
+            match replacement {
+                Some(replacement) => expression.kind = replacement,
+                None => walk_expression_mut(self, expression),
+            }
+        }
+    }
+
+    #[test]
+    fn rewrites_in_place() {
+        let tokens = tokenize("a = x;; while b(x) do c.d[x] = { x, e = -f } end;").unwrap();
+        let mut chunk = parse_from_tokens(&tokens).unwrap();
+        Rewriter.visit_chunk_mut(&mut chunk);
+
+        let expected_tokens = tokenize("A = nil while B(nil) do C.d[nil] = { nil, e = -F } end").unwrap();
+        let expected = parse_from_tokens(&expected_tokens).unwrap();
+
+        assert!(chunk.structural_eq(&expected));
+    }
+
+    #[test]
+    fn descendants_in_source_order() {
+        let tokens = tokenize("a = f(b) repeat c() until d").unwrap();
+        let chunk = parse_from_tokens(&tokens).unwrap();
+
+        let kinds = chunk.descendants()
+            .map(|node| match node {
+                NodeRef::Statement(statement) => match statement.kind {
+                    StatementKind::Assignment(_) => "assignment".to_string(),
+                    StatementKind::RepeatLoop(_) => "repeat".to_string(),
+                    StatementKind::FunctionCall(_) => "call statement".to_string(),
+                    _ => "other statement".to_string(),
+                },
+                NodeRef::Expression(expression) => match expression.kind {
+                    ExpressionKind::Name(ref name) => name.to_string(),
+                    ExpressionKind::FunctionCall(_) => "call".to_string(),
+                    _ => "other expression".to_string(),
+                },
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(kinds, vec!["assignment", "a", "call", "f", "b", "repeat", "call statement", "c", "d"]);
+    }
+
+    #[test]
+    fn renumbers_in_source_order() {
+        let tokens = tokenize("a = f(b) repeat c() until d").unwrap();
+        let mut chunk = parse_from_tokens(&tokens).unwrap();
+        chunk.renumber_ids();
+
+        let ids = chunk.descendants().map(|node| node.id().get()).collect::<Vec<_>>();
+        assert_eq!(ids, (1..10).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,104 @@
+extern crate mab;
+
+use std::fs::{read_dir, read_to_string};
+
+use mab::{tokenize, parse_from_tokens, Dialect, cst::Cst};
+use mab::format::{verify_roundtrip, FormatConfig, IndentStyle, QuoteStyle, RoundtripError};
+use mab::minify::minify_chunk;
+use mab::obfuscate::obfuscate_chunk;
+
+#[test]
+fn emit_by_example() {
+    for entry in read_dir("parse_examples/source").unwrap() {
+        let entry_path = entry.unwrap().path();
+        let contents = read_to_string(&entry_path)
+            .expect("Unable to read from file!");
+
+        // Some examples are there to show errors.
+        let tokens = match tokenize(&contents) {
+            Ok(tokens) => tokens,
+            Err(_) => continue,
+        };
+
+        let chunk = match parse_from_tokens(&tokens) {
+            Ok(chunk) => chunk,
+            Err(_) => continue,
+        };
+
+        // Narrow lines get everything that can be split up split up. Quotes
+        // are kept because changing them counts as changing the tree.
+        let narrow = FormatConfig {
+            quote_style: QuoteStyle::Preserve,
+            max_line_width: 10,
+            ..FormatConfig::default()
+        };
+
+        verify_roundtrip(&contents, &narrow, Dialect::Lua53)
+            .unwrap_or_else(|error| panic!("{} didn't round-trip: {:?}", entry_path.display(), error));
+
+        // Minifying changes strings, numbers, and names, so it only has to
+        // parse.
+        for minified in &[minify_chunk(&chunk, Dialect::Lua53).unwrap(), obfuscate_chunk(&chunk, Dialect::Lua53).unwrap()] {
+            let tokens = tokenize(minified)
+                .unwrap_or_else(|error| panic!("Minified {} didn't tokenize: {:?}\n{}", entry_path.display(), error, minified));
+            let reparsed = parse_from_tokens(&tokens)
+                .unwrap_or_else(|error| panic!("Minified {} didn't parse: {:?}\n{}", entry_path.display(), error, minified));
+
+            assert_eq!(reparsed.statements.len(), chunk.statements.len(), "Minified {} parsed differently:\n{}", entry_path.display(), minified);
+        }
+    }
+}
+
+#[test]
+fn print_exact_by_example() {
+    for entry in read_dir("parse_examples/source").unwrap() {
+        let entry_path = entry.unwrap().path();
+        let contents = read_to_string(&entry_path)
+            .expect("Unable to read from file!");
+
+        let tokens = match tokenize(&contents) {
+            Ok(tokens) => tokens,
+            Err(_) => continue,
+        };
+
+        if let Ok(cst) = Cst::parse(&tokens) {
+            assert_eq!(cst.print_exact(), contents, "{} didn't print exactly", entry_path.display());
+        }
+    }
+}
+
+#[test]
+fn roundtrip_by_example() {
+    let configs = [
+        FormatConfig::default(),
+        FormatConfig {
+            indent_style: IndentStyle::Spaces,
+            indent_width: 2,
+            quote_style: QuoteStyle::Preserve,
+            trailing_table_separator: false,
+            max_line_width: 10,
+        },
+        FormatConfig {
+            quote_style: QuoteStyle::PreferSingle,
+            max_line_width: 40,
+            ..FormatConfig::default()
+        },
+    ];
+
+    for entry in read_dir("parse_examples/source").unwrap() {
+        let entry_path = entry.unwrap().path();
+        let contents = read_to_string(&entry_path)
+            .expect("Unable to read from file!");
+
+        for config in &configs {
+            match verify_roundtrip(&contents, config, Dialect::Lua53) {
+                Ok(()) => {},
+
+                // Some examples are there to show errors.
+                Err(RoundtripError::Tokenize(_)) | Err(RoundtripError::Parse(_)) => break,
+
+                Err(error) => panic!("{} didn't round-trip with {:?}: {:?}", entry_path.display(), config, error),
+            }
+        }
+    }
+}
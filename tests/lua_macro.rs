@@ -0,0 +1,50 @@
+#![cfg(feature = "macros")]
+
+extern crate mab;
+
+use mab::ast::*;
+use mab::builder::expr;
+use mab::lua;
+
+fn parse(source: &str) -> Chunk<'static> {
+    let tokens = mab::tokenize(source).unwrap();
+    mab::parse_from_tokens(&tokens).unwrap().into_owned()
+}
+
+#[test]
+fn lua_macro() {
+    let chunk = lua! {
+        local t = { 1, [2] = "two"; three = 3 }
+        for i = 1, #t do
+            if t[i] ~= nil and i >= 2 then
+                print(t[i] .. "!", ...)
+            end
+        end
+        ::done::
+    };
+
+    assert!(chunk.structural_eq(&parse("
+        local t = { 1, [2] = \"two\"; three = 3 }
+        for i = 1, #t do
+            if t[i] ~= nil and i >= 2 then
+                print(t[i] .. \"!\", ...)
+            end
+        end
+        ::done::
+    ")));
+
+    assert!(chunk.descendants().all(|node| node.id() == NodeId::DUMMY));
+}
+
+#[test]
+fn lua_macro_splices() {
+    let value = expr::num("2");
+    let mut name = expr::name("y");
+    name.id = NodeId::new(42).unwrap();
+
+    let chunk = lua! { local x = $value + 1 f($name, $(expr::string("a"))) };
+    assert!(chunk.structural_eq(&parse("local x = 2 + 1 f(y, \"a\")")));
+
+    // Spliced nodes keep their IDs.
+    assert!(chunk.descendants().any(|node| node.id().get() == 42));
+}
@@ -4,7 +4,7 @@ extern crate serde_json;
 use std::fs::{File, read_dir};
 use std::io::{Read, Write};
 
-use mab::{tokenize, parse_from_tokens, Token, ast::Chunk};
+use mab::{tokenize, parse_from_tokens, parse_from_tokens_with_recovery, Token, ast::Chunk};
 
 #[test]
 fn parse_by_example() {
@@ -105,10 +105,18 @@ fn parse_by_example() {
         let ast = match parse_from_tokens(&tokens) {
             Ok(ast) => ast,
             Err(err) => {
-                panic!("Failed to parse file {}: {:?}", entry_path.display(), err);
+                panic!("Failed to parse file {}: {}", entry_path.display(), err);
             },
         };
 
+        if parse_from_tokens_with_recovery(&tokens) != (ast.clone(), Vec::new()) {
+            panic!("Recovering parse gave a different result for file {}", entry_path.display());
+        }
+
+        if ast.clone().into_owned() != ast {
+            panic!("Owned copy of the AST is different for file {}", entry_path.display());
+        }
+
         match expected_ast {
             Some(expected_ast) => {
                 if ast != expected_ast {
@@ -3,7 +3,7 @@ extern crate mab;
 use std::fs::{File, read_dir};
 use std::io::Read;
 
-use mab::{tokenize, parse_from_tokens};
+use mab::{tokenize, parse_from_tokens, parse_from_tokens_with_recovery};
 
 #[test]
 fn should_not_parse() {
@@ -30,6 +30,12 @@ fn should_not_parse() {
 
         println!("Parsing file {}", entry.path().display());
 
+        // Recovering from the same errors should find at least one of them.
+        let (_, errors) = parse_from_tokens_with_recovery(&tokens);
+        if errors.is_empty() {
+            panic!("Recovering parse found no errors in file {}", entry.path().display());
+        }
+
         let ast = match parse_from_tokens(&tokens) {
             Ok(ast) => ast,
             Err(_) => continue,